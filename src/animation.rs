@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::flame::{FlameGraph, StackIdentifier, ROOT_ID};
+
+/// In-between frames drawn from the layout before a zoom to the layout after it
+pub const ZOOM_TRANSITION_STEPS: u16 = 3;
+/// Delay between the frames of a zoom transition
+pub const ZOOM_TRANSITION_INTERVAL: Duration = Duration::from_millis(40);
+/// Children of frames narrower than this many columns are left out of the layouts
+const MIN_LAYOUT_WIDTH: f64 = 0.5;
+
+/// Column and width (in columns) of the frames wide enough to be seen
+pub type Layout = HashMap<StackIdentifier, (f64, f64)>;
+
+/// Layout of the flamegraph `width` columns wide when zoomed on `zoom`, as rendered: the zoomed
+/// frame and its ancestors take the whole width and the other frames share the width of their
+/// parent by their number of samples.
+pub fn get_layout(flamegraph: &FlameGraph, zoom: Option<StackIdentifier>, width: f64) -> Layout {
+    let zoom_chain = zoom
+        .map(|zoom| flamegraph.get_ancestors(&zoom))
+        .unwrap_or_default();
+    let mut layout = Layout::new();
+    add_to_layout(flamegraph, ROOT_ID, 0.0, width, &zoom_chain, &mut layout);
+    layout
+}
+
+fn add_to_layout(
+    flamegraph: &FlameGraph,
+    stack_id: StackIdentifier,
+    x: f64,
+    width: f64,
+    zoom_chain: &[StackIdentifier],
+    layout: &mut Layout,
+) {
+    layout.insert(stack_id, (x, width));
+    let Some(stack) = flamegraph.get_stack(&stack_id) else {
+        return;
+    };
+    if stack.total_count == 0 {
+        return;
+    }
+    let zoomed_child = stack
+        .children
+        .iter()
+        .find(|child_id| zoom_chain.contains(child_id));
+    let mut offset = x;
+    for child_id in stack.children.iter() {
+        let child_width = match zoomed_child {
+            Some(zoomed_child) if zoomed_child == child_id => width,
+            Some(_) => 0.0,
+            None => flamegraph.get_stack(child_id).map_or(0.0, |child| {
+                width * child.total_count as f64 / stack.total_count.max(1) as f64
+            }),
+        };
+        // Narrow children are kept where they are so that they grow from there, not their children
+        if child_width < MIN_LAYOUT_WIDTH {
+            layout.insert(*child_id, (offset, child_width));
+        } else {
+            add_to_layout(
+                flamegraph,
+                *child_id,
+                offset,
+                child_width,
+                zoom_chain,
+                layout,
+            );
+        }
+        offset += child_width;
+    }
+}
+
+/// Position of a frame in `layout`, or a zero width at its closest ancestor that is in it
+fn get_position(flamegraph: &FlameGraph, layout: &Layout, stack_id: StackIdentifier) -> (f64, f64) {
+    if let Some(position) = layout.get(&stack_id) {
+        return *position;
+    }
+    flamegraph
+        .get_ancestors(&stack_id)
+        .iter()
+        .find_map(|id| layout.get(id))
+        .map_or((0.0, 0.0), |(x, _)| (*x, 0.0))
+}
+
+/// In-between frames from the layout of the flamegraph before a zoom to the layout after it.
+#[derive(Debug, Clone)]
+pub struct ZoomTransition {
+    from: Layout,
+    to: Layout,
+    /// Frames drawn so far
+    step: u16,
+}
+
+impl ZoomTransition {
+    /// Position and width of the frames at the current step, interpolated between the layouts
+    pub fn get_frames(&self, flamegraph: &FlameGraph) -> Vec<(StackIdentifier, f64, f64)> {
+        let t = (self.step + 1) as f64 / (ZOOM_TRANSITION_STEPS + 1) as f64;
+        let mut stack_ids = self
+            .from
+            .keys()
+            .chain(self.to.keys().filter(|id| !self.from.contains_key(id)))
+            .copied()
+            .collect::<Vec<_>>();
+        stack_ids.sort();
+        stack_ids
+            .into_iter()
+            .filter_map(|stack_id| {
+                let (from_x, from_width) = get_position(flamegraph, &self.from, stack_id);
+                let (to_x, to_width) = get_position(flamegraph, &self.to, stack_id);
+                let width = from_width + (to_width - from_width) * t;
+                (width >= MIN_LAYOUT_WIDTH).then_some((
+                    stack_id,
+                    from_x + (to_x - from_x) * t,
+                    width,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Zoom transitions of the flamegraph, started when the zoom changed since the last render.
+#[derive(Debug, Default)]
+pub struct ZoomAnimation {
+    pub enabled: bool,
+    /// Zoomed frame of the last render and when its flamegraph was updated
+    rendered: Option<(Option<StackIdentifier>, Instant)>,
+    /// The running transition, kept for one more render after its last in-between frame so that
+    /// the flamegraph is drawn as usual
+    transition: Option<ZoomTransition>,
+}
+
+impl ZoomAnimation {
+    /// Start a transition if the zoom changed since the last render of the same flamegraph
+    pub fn update(
+        &mut self,
+        flamegraph: &FlameGraph,
+        zoom: Option<StackIdentifier>,
+        updated_at: Instant,
+        width: u16,
+    ) {
+        let previous = self.rendered.replace((zoom, updated_at));
+        if !self.enabled || width == 0 {
+            return;
+        }
+        let Some((previous_zoom, previous_updated_at)) = previous else {
+            return;
+        };
+        if previous_zoom == zoom || previous_updated_at != updated_at {
+            return;
+        }
+        self.transition = Some(ZoomTransition {
+            from: get_layout(flamegraph, previous_zoom, width as f64),
+            to: get_layout(flamegraph, zoom, width as f64),
+            step: 0,
+        });
+    }
+
+    /// In-between frame to draw instead of the flamegraph, if any
+    pub fn get_transition(&self) -> Option<&ZoomTransition> {
+        self.transition
+            .as_ref()
+            .filter(|transition| transition.step < ZOOM_TRANSITION_STEPS)
+    }
+
+    /// Move to the next frame of the transition, ending it after the flamegraph was drawn as usual
+    pub fn advance(&mut self) {
+        if let Some(transition) = self.transition.as_mut() {
+            transition.step += 1;
+            if transition.step > ZOOM_TRANSITION_STEPS {
+                self.transition = None;
+            }
+        }
+    }
+
+    pub fn skip(&mut self) {
+        self.transition = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.transition.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zoom_transition() {
+        let fg = FlameGraph::from_string("main;a 3\nmain;b 1".to_string(), true);
+        let main = fg.find_heaviest_stack_by_name("main").unwrap();
+        let a = fg.find_heaviest_stack_by_name("main;a").unwrap();
+        let b = fg.find_heaviest_stack_by_name("main;b").unwrap();
+        let layout = get_layout(&fg, None, 8.0);
+        assert_eq!(layout[&a], (0.0, 6.0));
+        assert_eq!(layout[&b], (6.0, 2.0));
+        let zoomed = get_layout(&fg, Some(b), 8.0);
+        assert_eq!(zoomed[&main], (0.0, 8.0));
+        assert_eq!(zoomed[&b], (0.0, 8.0));
+        assert_eq!(zoomed[&a], (0.0, 0.0));
+
+        let mut animation = ZoomAnimation {
+            enabled: true,
+            ..Default::default()
+        };
+        let updated_at = Instant::now();
+        animation.update(&fg, None, updated_at, 8);
+        assert!(!animation.is_running());
+        animation.update(&fg, Some(b), updated_at, 8);
+        let transition = animation.get_transition().unwrap();
+        let frames = transition.get_frames(&fg);
+        assert!(frames.contains(&(a, 0.0, 4.5)));
+        assert!(frames.contains(&(b, 4.5, 3.5)));
+        for _ in 0..ZOOM_TRANSITION_STEPS {
+            assert!(animation.get_transition().is_some());
+            animation.advance();
+        }
+        assert!(animation.get_transition().is_none());
+        assert!(animation.is_running());
+        animation.advance();
+        assert!(!animation.is_running());
+
+        // No transition across flamegraph updates
+        animation.update(&fg, None, Instant::now(), 8);
+        assert!(!animation.is_running());
+    }
+}
@@ -1,16 +1,56 @@
-use crate::flame::{FlameGraph, SearchPattern};
+use crate::animation::ZoomAnimation;
+use crate::baseline::Baseline;
+use crate::budget::RenderBudget;
+use crate::command::ControlCommand;
+use crate::config::Config;
+use crate::editor::EditorRequest;
+use crate::flame::ROOT_ID;
+use crate::flame::{
+    parse_frame_location, CountFormat, CountUnit, FlameGraph, ParseOptions, SearchModes,
+    SearchPattern, SortColumn, StackIdentifier, TopColumn,
+};
+use crate::flamechart::{FlameChart, FlameChartView};
+use crate::history::SearchHistory;
+use crate::keys::{Action, Keymap};
+use crate::macros::{Macros, PendingRegister};
+use crate::palette::ColorDepth;
+use crate::parsers;
 #[cfg(feature = "python")]
-use crate::py_spy::{record_samples, ProfilerOutput, SamplerState, SamplerStatus};
-use crate::state::FlameGraphState;
-use crate::view::FlameGraphView;
+use crate::py_spy::{
+    config_from_options, dump_stacks, next_window, record_samples, LiveOptions, ProfilerOutput,
+    SamplerSettings, SamplerState, SamplerStatus, ThreadStack,
+};
+#[cfg(feature = "python")]
+use crate::snapshot::{format_timestamp, Snapshotter};
+use crate::source::SourceCache;
+#[cfg(feature = "python")]
+use crate::spawn::SpawnedCommand;
+use crate::state::{FlameGraphState, ViewKind, MAX_KEPT_SEARCHES};
+use crate::stream::StreamAggregator;
+use crate::text::Truncation;
+use crate::theme::Theme;
+use crate::timeline::Timeline;
+use crate::tutorial::Tutorial;
+use crate::view::{FlameGraphView, Lane, ProcessNode};
+use crate::watch::WatchList;
+use ratatui::layout::Rect;
 #[cfg(feature = "python")]
 use remoteprocess;
 use std::collections::HashMap;
 use std::error;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-#[cfg(feature = "python")]
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+/// How often a watched input file is checked for changes
+const FILE_WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often the flamegraph of a streamed input is rebuilt
+const STREAM_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Annotation key telling the device of the stacks when not given with `--lanes`
+const DEFAULT_LANE_KEY: &str = "device";
 
 /// Application result type.
 pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
@@ -21,16 +61,236 @@ pub enum FlameGraphInput {
     Pid(u64, Option<String>),
 }
 
+/// What is shown at the end of wide frames: the count of the Top view's sorted column (total or
+/// own), or its share of all samples (of the parent frame if enabled)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameLabels {
+    #[default]
+    None,
+    Count,
+    Percent,
+}
+
+impl FrameLabels {
+    pub fn next(&self) -> FrameLabels {
+        match self {
+            FrameLabels::None => FrameLabels::Count,
+            FrameLabels::Count => FrameLabels::Percent,
+            FrameLabels::Percent => FrameLabels::None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ParsedFlameGraph {
     pub flamegraph: FlameGraph,
+    /// Flame chart of the same samples, if their format has timestamps
+    pub flame_chart: Option<FlameChart>,
     pub elapsed: Duration,
+    /// No flamegraphs come after this one, as the sampling is complete
+    pub last: bool,
+}
+
+/// Start of a search pattern leaving out the stacks of the matching functions, as in `/!gc`
+pub const EXCLUDE_PREFIX: &str = "!";
+/// Flamegraphs with more frames than this only preview the search once typing pauses
+const SEARCH_PREVIEW_DEBOUNCE_FRAMES: usize = 50_000;
+/// Pause in typing after which the search is previewed on large flamegraphs
+const SEARCH_PREVIEW_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// What the text typed in the prompt is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prompt {
+    Search,
+    /// Name of a function to jump to
+    Goto,
 }
 
 #[derive(Debug)]
 pub struct InputBuffer {
     pub buffer: tui_input::Input,
     pub cursor: Option<(u16, u16)>,
+    pub prompt: Prompt,
+    /// Why the text typed isn't a valid regex
+    pub error: Option<String>,
+    /// When to preview the search typed so far, on large flamegraphs
+    pub preview_due: Option<Instant>,
+    /// Search pattern before the prompt was opened, brought back when it is cancelled
+    pub previous_search: Option<SearchPattern>,
+    /// Entry of the search history recalled in the prompt
+    pub history_index: Option<usize>,
+    /// Text typed before recalling the search history
+    pub draft: String,
+}
+
+impl InputBuffer {
+    pub fn new(prompt: Prompt) -> Self {
+        Self {
+            buffer: tui_input::Input::new("".to_string()),
+            cursor: None,
+            prompt,
+            error: None,
+            preview_due: None,
+            previous_search: None,
+            history_index: None,
+            draft: String::new(),
+        }
+    }
+}
+
+/// Frames matching the search pattern, listed to jump to one of them
+#[derive(Debug)]
+pub struct MatchList {
+    /// Matching frames, widest first
+    pub ids: Vec<StackIdentifier>,
+    pub selected: usize,
+}
+
+impl MatchList {
+    /// Rows moved by a page up or down
+    const PAGE_SIZE: usize = 10;
+
+    pub fn to_next_row(&mut self) {
+        self.selected = (self.selected + 1).min(self.ids.len().saturating_sub(1));
+    }
+
+    pub fn to_previous_row(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn page_down(&mut self) {
+        self.selected = (self.selected + Self::PAGE_SIZE).min(self.ids.len().saturating_sub(1));
+    }
+
+    pub fn page_up(&mut self) {
+        self.selected = self.selected.saturating_sub(Self::PAGE_SIZE);
+    }
+}
+
+/// Thread frames of the profile, listed to show or hide the stacks of each thread
+#[derive(Debug)]
+pub struct ThreadList {
+    /// Names of the thread frames, most samples first when the list was opened, then the threads
+    /// seen since
+    pub names: Vec<String>,
+    pub selected: usize,
+}
+
+impl ThreadList {
+    pub fn to_next_row(&mut self) {
+        self.selected = (self.selected + 1).min(self.names.len().saturating_sub(1));
+    }
+
+    pub fn to_previous_row(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+/// Processes of the profile, listed as a tree to show or hide their stacks or zoom in on them
+#[derive(Debug)]
+pub struct ProcessList {
+    /// Processes, each one followed by its child processes
+    pub processes: Vec<ProcessNode>,
+    pub selected: usize,
+}
+
+impl ProcessList {
+    pub fn to_next_row(&mut self) {
+        self.selected = (self.selected + 1).min(self.processes.len().saturating_sub(1));
+    }
+
+    pub fn to_previous_row(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+/// Samples of the live session captured to show the ones collected since then against them
+#[derive(Debug)]
+pub struct LiveBaseline {
+    /// Own count of every stack when captured, subtracted from the new flamegraphs
+    counts: HashMap<String, u64>,
+    /// Baseline given with `--baseline`, restored once the capture is released
+    previous: Option<Baseline>,
+}
+
+/// Summary of a live session whose sampling is complete, offering to write the samples to a file
+#[cfg(feature = "python")]
+#[derive(Debug)]
+pub struct CompletionBanner {
+    pub num_samples: u64,
+    pub duration: Duration,
+}
+
+/// Stacks of the threads of the live process at one point in time, scrolled through in a popup
+#[cfg(feature = "python")]
+#[derive(Debug)]
+pub struct ThreadDump {
+    pub threads: Vec<ThreadStack>,
+    /// First line shown
+    pub offset: usize,
+}
+
+#[cfg(feature = "python")]
+impl ThreadDump {
+    /// Rows moved by a page up or down
+    const PAGE_SIZE: usize = 10;
+
+    /// Header and frames of every thread, with an empty line between threads
+    pub fn num_lines(&self) -> usize {
+        self.threads
+            .iter()
+            .map(|thread| thread.frames.len() + 2)
+            .sum::<usize>()
+            .saturating_sub(1)
+    }
+
+    pub fn scroll(&mut self, delta: isize) {
+        let max_offset = self.num_lines().saturating_sub(1);
+        self.offset = self.offset.saturating_add_signed(delta).min(max_offset);
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll(Self::PAGE_SIZE as isize);
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll(-(Self::PAGE_SIZE as isize));
+    }
+}
+
+/// Sampler settings being edited before restarting the sampler with them
+#[cfg(feature = "python")]
+#[derive(Debug)]
+pub struct SettingsPopup {
+    pub settings: SamplerSettings,
+    /// Selected row, see [`SettingsPopup::NUM_ROWS`]
+    pub selected: usize,
+}
+
+#[cfg(feature = "python")]
+impl SettingsPopup {
+    /// Sampling rate, GIL only, idle, thread ids and native
+    pub const NUM_ROWS: usize = 5;
+
+    pub fn to_next_row(&mut self) {
+        self.selected = (self.selected + 1).min(Self::NUM_ROWS - 1);
+    }
+
+    pub fn to_previous_row(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Toggle the selected option, or step the sampling rate up or down
+    pub fn change_selected(&mut self, up: bool) {
+        let settings = &mut self.settings;
+        match self.selected {
+            0 => settings.step_sampling_rate(up),
+            1 => settings.gil_only = !settings.gil_only,
+            2 => settings.include_idle = !settings.include_idle,
+            3 => settings.include_thread_ids = !settings.include_thread_ids,
+            _ => settings.native = !settings.native,
+        }
+    }
 }
 
 /// Application.
@@ -44,16 +304,108 @@ pub struct App {
     pub flamegraph_input: FlameGraphInput,
     /// User input buffer
     pub input_buffer: Option<InputBuffer>,
+    /// How the search prompt turns the typed text into a regex
+    pub search_modes: SearchModes,
+    /// Text submitted in the search prompt
+    pub search_history: SearchHistory,
+    /// Where the view preferences of the profile are saved on quit, if they are
+    pub prefs_path: Option<PathBuf>,
     /// Timing information for debugging
     pub elapsed: HashMap<String, Duration>,
     /// Transient message
     pub transient_message: Option<String>,
     /// Debug mode
     pub debug: bool,
+    /// Show the session statistics overlay
+    pub show_stats: bool,
+    /// Show the details panel of the selected frame
+    pub show_details: bool,
+    /// Show a ruler with the share of the samples above the flamegraph
+    pub show_ruler: bool,
+    /// Show the source around the line of the selected frame below the flamegraph
+    pub show_source: bool,
+    /// Source files read for the source panel
+    pub source_cache: SourceCache,
+    /// Zoom transitions of the flamegraph
+    pub zoom_animation: ZoomAnimation,
+    /// Time allowed for rendering the flamegraph before degrading it
+    pub render_budget: RenderBudget,
+    /// Colors of the terminal, the RGB colors being mapped to the nearest ones it has
+    pub color_depth: ColorDepth,
+    /// Colors of the interface around the frames
+    pub theme: Theme,
+    /// Which part of the names too long for their frame is left out
+    pub truncation: Truncation,
+    /// Counts or percentages shown at the end of wide frames
+    pub frame_labels: FrameLabels,
+    /// Optional columns of the Top view
+    pub top_columns: Vec<TopColumn>,
+    /// Samples per second of the profiler that recorded the input, if known
+    pub sample_rate: Option<u64>,
+    /// Show the samples as the time they stand for instead of their number
+    pub show_durations: bool,
+    /// The samples of the live session were taken at different rates and have no single duration
+    pub mixed_sample_rates: bool,
+    /// Shares of the selected frame and counts in the frames relative to the parent frame
+    pub show_parent_share: bool,
+    /// Key bindings
+    pub keymap: Keymap,
+    /// Samples per function over time (live mode)
+    pub timeline: Option<Arc<Mutex<Timeline>>>,
+    /// Time-ordered view of the samples, for inputs with timestamps
+    pub flame_chart: Option<FlameChartView>,
+    /// Functions whose counts are always shown
+    pub watch_list: WatchList,
+    /// Screen areas of the zoom breadcrumbs, as rendered last
+    pub breadcrumbs: Vec<(Rect, StackIdentifier)>,
+    /// Keyboard macros
+    pub macros: Macros,
+    /// Annotation key telling the device of the stacks, for the lanes
+    pub lane_key: String,
+    /// Source file to open in the editor, with the TUI suspended, before the next draw
+    pub editor_request: Option<EditorRequest>,
+    /// Popup listing the frames matching the search pattern
+    pub match_list: Option<MatchList>,
+    pub thread_list: Option<ThreadList>,
+    pub process_list: Option<ProcessList>,
+    /// Guided tour shown over the flamegraph
+    pub tutorial: Option<Tutorial>,
+    /// Samples keep coming in, from a process or a stream
+    pub live: bool,
+    /// Captured samples the live flamegraph is diffed against
+    pub live_baseline: Option<LiveBaseline>,
     /// Next flamegraph to swap in
     next_flamegraph: Arc<Mutex<Option<ParsedFlameGraph>>>,
     #[cfg(feature = "python")]
     sampler_state: Option<Arc<Mutex<SamplerState>>>,
+    /// Sampler settings popup (live mode)
+    #[cfg(feature = "python")]
+    pub settings_popup: Option<SettingsPopup>,
+    /// Banner shown once the sampling is complete (live mode)
+    #[cfg(feature = "python")]
+    pub completion_banner: Option<CompletionBanner>,
+    /// Stacks of the threads dumped on demand (live mode)
+    #[cfg(feature = "python")]
+    pub thread_dump: Option<ThreadDump>,
+    /// Dump taken in the background, to show in the thread dump popup
+    #[cfg(feature = "python")]
+    pending_thread_dump: Arc<Mutex<Option<Vec<ThreadStack>>>>,
+    /// py-spy configuration of the live session, to attach to the process for dumps
+    #[cfg(feature = "python")]
+    py_spy_config: Option<py_spy::Config>,
+    /// Command launched to profile it (live mode)
+    #[cfg(feature = "python")]
+    pub spawned_command: Option<SpawnedCommand>,
+    #[cfg(feature = "python")]
+    pub show_command_output: bool,
+    /// Message from a background thread to show as the transient message
+    background_message: Arc<Mutex<Option<String>>>,
+    /// Folded stacks of all the samples of the live session since they were last cleared
+    #[cfg(feature = "python")]
+    live_data: Arc<Mutex<String>>,
+    /// File to save the live samples to
+    #[cfg(feature = "python")]
+    live_output: Option<PathBuf>,
 }
 
 impl App {
@@ -64,34 +416,131 @@ impl App {
             flamegraph_view: FlameGraphView::new(flamegraph),
             flamegraph_input: FlameGraphInput::File(filename.to_string()),
             input_buffer: None,
+            search_modes: SearchModes::default(),
+            search_history: SearchHistory::default(),
+            prefs_path: None,
             elapsed: HashMap::new(),
             transient_message: None,
             debug: false,
+            show_stats: false,
+            show_details: false,
+            show_ruler: false,
+            show_source: false,
+            source_cache: SourceCache::default(),
+            frame_labels: FrameLabels::None,
+            top_columns: vec![],
+            sample_rate: None,
+            show_durations: false,
+            mixed_sample_rates: false,
+            show_parent_share: false,
+            zoom_animation: ZoomAnimation::default(),
+            render_budget: RenderBudget::default(),
+            color_depth: ColorDepth::Truecolor,
+            theme: Theme::default(),
+            truncation: Truncation::End,
+            keymap: Keymap::default(),
+            timeline: None,
+            flame_chart: None,
+            watch_list: WatchList::default(),
+            breadcrumbs: vec![],
+            macros: Macros::default(),
+            lane_key: DEFAULT_LANE_KEY.to_string(),
+            editor_request: None,
+            match_list: None,
+            thread_list: None,
+            process_list: None,
+            tutorial: None,
+            live: false,
+            live_baseline: None,
             next_flamegraph: Arc::new(Mutex::new(None)),
             #[cfg(feature = "python")]
             sampler_state: None,
+            #[cfg(feature = "python")]
+            settings_popup: None,
+            #[cfg(feature = "python")]
+            completion_banner: None,
+            #[cfg(feature = "python")]
+            thread_dump: None,
+            #[cfg(feature = "python")]
+            pending_thread_dump: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "python")]
+            py_spy_config: None,
+            #[cfg(feature = "python")]
+            spawned_command: None,
+            #[cfg(feature = "python")]
+            show_command_output: false,
+            background_message: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "python")]
+            live_data: Arc::new(Mutex::new(String::new())),
+            #[cfg(feature = "python")]
+            live_output: None,
         }
     }
 
     #[cfg(feature = "python")]
-    pub fn with_pid(pid: u64, py_spy_args: Option<String>) -> Self {
+    pub fn with_pid(
+        pid: u64,
+        py_spy_args: Option<String>,
+        options: LiveOptions,
+    ) -> AppResult<Self> {
+        let config = config_from_options(pid, &options.py_spy, py_spy_args.as_deref())?;
         let next_flamegraph: Arc<Mutex<Option<ParsedFlameGraph>>> = Arc::new(Mutex::new(None));
         let pyspy_data: Arc<Mutex<Option<ProfilerOutput>>> = Arc::new(Mutex::new(None));
         let sampler_state = Arc::new(Mutex::new(SamplerState::default()));
+        let timeline = Arc::new(Mutex::new(Timeline::default()));
+        let background_message = Arc::new(Mutex::new(None));
+        let live_data = Arc::new(Mutex::new(String::new()));
+        let live_output = options.output.clone();
 
         // Thread to poll data from pyspy and construct the next flamegraph
         {
             let next_flamegraph = next_flamegraph.clone();
             let pyspy_data = pyspy_data.clone();
+            let timeline = timeline.clone();
+            let background_message = background_message.clone();
+            let live_data = live_data.clone();
+            let mut resets = 0;
+            let mut snapshotter = options
+                .snapshots
+                .clone()
+                .map(|o| Snapshotter::new(o, &format!("flamelens-{}", pid)));
             let _handle = thread::spawn(move || loop {
                 if let Some(output) = pyspy_data.lock().unwrap().take() {
                     let tic = std::time::Instant::now();
+                    let windowed = output.cumulative.is_some();
+                    let all_data = output.cumulative.unwrap_or_else(|| output.data.clone());
                     let flamegraph = FlameGraph::from_string(output.data, true);
+                    // The timeline and snapshots need counts that only grow
+                    let cumulative =
+                        windowed.then(|| FlameGraph::from_string(all_data.clone(), true));
+                    let cumulative = cumulative.as_ref().unwrap_or(&flamegraph);
+                    if output.resets != resets {
+                        resets = output.resets;
+                        timeline.lock().unwrap().rebase();
+                        if let Some(snapshotter) = snapshotter.as_mut() {
+                            snapshotter.rebase();
+                        }
+                    }
+                    // Recorded here rather than on replacement so that freezing doesn't skew it
+                    timeline.lock().unwrap().record(cumulative, tic);
+                    if let Some(snapshotter) = snapshotter.as_mut() {
+                        let message = match snapshotter.maybe_write(cumulative, tic) {
+                            Ok(Some(path)) => Some(format!("Saved snapshot {}", path.display())),
+                            Ok(None) => None,
+                            Err(e) => Some(format!("Could not write snapshot: {}", e)),
+                        };
+                        if message.is_some() {
+                            *background_message.lock().unwrap() = message;
+                        }
+                    }
                     let parsed = ParsedFlameGraph {
                         flamegraph,
+                        flame_chart: None,
                         elapsed: tic.elapsed(),
+                        last: output.last,
                     };
                     *next_flamegraph.lock().unwrap() = Some(parsed);
+                    *live_data.lock().unwrap() = all_data;
                 }
                 thread::sleep(std::time::Duration::from_millis(250));
             });
@@ -101,26 +550,10 @@ impl App {
         {
             let pyspy_data = pyspy_data.clone();
             let sampler_state = sampler_state.clone();
+            let config = config.clone();
             let _handle = thread::spawn(move || {
-                // Note: mimic a record command's invocation vs simply getting default Config as
-                // from_args does a lot of heavy lifting
-                let mut args = [
-                    "py-spy",
-                    "record",
-                    "--pid",
-                    pid.to_string().as_str(),
-                    "--format",
-                    "raw",
-                ]
-                .iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>();
-                if let Some(py_spy_args) = py_spy_args {
-                    args.extend(py_spy_args.split_whitespace().map(|s| s.to_string()));
-                }
-                let config = py_spy::Config::from_args(&args).unwrap();
                 let pid = pid as remoteprocess::Pid;
-                record_samples(pid, &config, pyspy_data, sampler_state);
+                record_samples(pid, &config, &options, pyspy_data, sampler_state);
             });
         }
 
@@ -129,33 +562,255 @@ impl App {
             .and_then(|p| p.cmdline())
             .ok()
             .map(|c| c.join(" "));
-        Self {
+        Ok(Self {
             running: true,
             flamegraph_view: FlameGraphView::new(flamegraph),
             flamegraph_input: FlameGraphInput::Pid(pid, process_info),
             next_flamegraph: next_flamegraph.clone(),
             input_buffer: None,
+            search_modes: SearchModes::default(),
+            search_history: SearchHistory::default(),
+            prefs_path: None,
             elapsed: HashMap::new(),
             transient_message: None,
             debug: false,
+            show_stats: false,
+            show_details: false,
+            show_ruler: false,
+            show_source: false,
+            source_cache: SourceCache::default(),
+            frame_labels: FrameLabels::None,
+            top_columns: vec![],
+            sample_rate: None,
+            show_durations: false,
+            mixed_sample_rates: false,
+            show_parent_share: false,
+            zoom_animation: ZoomAnimation::default(),
+            render_budget: RenderBudget::default(),
+            color_depth: ColorDepth::Truecolor,
+            theme: Theme::default(),
+            truncation: Truncation::End,
+            keymap: Keymap::default(),
+            timeline: Some(timeline),
+            flame_chart: None,
+            watch_list: WatchList::default(),
+            breadcrumbs: vec![],
+            macros: Macros::default(),
+            lane_key: DEFAULT_LANE_KEY.to_string(),
+            editor_request: None,
+            match_list: None,
+            thread_list: None,
+            process_list: None,
+            tutorial: None,
+            live: true,
+            live_baseline: None,
             sampler_state: Some(sampler_state),
+            settings_popup: None,
+            completion_banner: None,
+            thread_dump: None,
+            pending_thread_dump: Arc::new(Mutex::new(None)),
+            py_spy_config: Some(config.clone()),
+            spawned_command: None,
+            show_command_output: false,
+            background_message,
+            live_data,
+            live_output,
+        })
+    }
+
+    /// Reparse the input file whenever it changes on disk, e.g. when a profiler rewrites or appends
+    /// to it. The selection, zoom and search are kept on reload.
+    pub fn watch_file(&mut self, path: PathBuf, options: ParseOptions) {
+        let next_flamegraph = self.next_flamegraph.clone();
+        thread::spawn(move || {
+            let version = |path: &PathBuf| {
+                std::fs::metadata(path)
+                    .and_then(|m| Ok((m.modified()?, m.len())))
+                    .ok()
+            };
+            let mut last_version = version(&path);
+            loop {
+                thread::sleep(FILE_WATCH_INTERVAL);
+                let current_version = version(&path);
+                if current_version.is_none() || current_version == last_version {
+                    continue;
+                }
+                last_version = current_version;
+                let tic = std::time::Instant::now();
+                // Keep showing the previous version if the file can't be read or parsed
+                let Ok(content) = std::fs::read(&path) else {
+                    continue;
+                };
+                let Ok((flamegraph, samples)) =
+                    FlameGraph::from_bytes_with_timed_samples(content, &options)
+                else {
+                    continue;
+                };
+                *next_flamegraph.lock().unwrap() = Some(ParsedFlameGraph {
+                    flamegraph,
+                    flame_chart: samples.and_then(|samples| FlameChart::from_samples(&samples)),
+                    elapsed: tic.elapsed(),
+                    last: false,
+                });
+            }
+        });
+    }
+
+    /// Keep reading folded stacks from the file (such as a named pipe) or stdin until the end of
+    /// the stream, refreshing the flamegraph periodically with all the stacks read so far.
+    pub fn stream_input(&mut self, path: Option<PathBuf>, options: ParseOptions) {
+        self.live = true;
+        let aggregator = Arc::new(Mutex::new(StreamAggregator::default()));
+        {
+            let aggregator = aggregator.clone();
+            let background_message = self.background_message.clone();
+            thread::spawn(move || {
+                // Opening a named pipe blocks until there is a writer
+                let result = match &path {
+                    Some(path) => std::fs::File::open(path).and_then(|f| {
+                        StreamAggregator::read_lines(&aggregator, std::io::BufReader::new(f))
+                    }),
+                    None => StreamAggregator::read_lines(&aggregator, std::io::stdin().lock()),
+                };
+                let message = match result {
+                    Ok(_) => "End of stream".to_string(),
+                    Err(e) => format!("Could not read the stream: {}", e),
+                };
+                *background_message.lock().unwrap() = Some(message);
+            });
         }
+        let next_flamegraph = self.next_flamegraph.clone();
+        thread::spawn(move || loop {
+            thread::sleep(STREAM_REFRESH_INTERVAL);
+            let Some(folded) = aggregator.lock().unwrap().take_folded() else {
+                continue;
+            };
+            let tic = std::time::Instant::now();
+            let folded = match &options.delimiter {
+                Some(delimiter) => parsers::folded::with_delimiter(&folded, delimiter),
+                None => folded,
+            };
+            let flamegraph = FlameGraph::from_string_with_options(folded, &options);
+            *next_flamegraph.lock().unwrap() = Some(ParsedFlameGraph {
+                flamegraph,
+                flame_chart: None,
+                elapsed: tic.elapsed(),
+                last: false,
+            });
+        });
     }
 
     /// Handles the tick event of the terminal.
     pub fn tick(&mut self) {
+        if let Some(due) = self
+            .input_buffer
+            .as_ref()
+            .and_then(|input| input.preview_due)
+        {
+            if due <= Instant::now() {
+                self.preview_search();
+            }
+        }
         // Replace flamegraph
         if !self.flamegraph_view.state.freeze {
-            if let Some(parsed) = self.next_flamegraph.lock().unwrap().take() {
+            let next_flamegraph = self.next_flamegraph.lock().unwrap().take();
+            if let Some(parsed) = next_flamegraph {
                 self.elapsed
                     .insert("flamegraph".to_string(), parsed.elapsed);
                 let tic = std::time::Instant::now();
-                self.flamegraph_view.replace_flamegraph(parsed.flamegraph);
+                // The frame selected in the match list, found by full name in the new flamegraph
+                let selected_match = self.match_list.as_ref().map(|match_list| {
+                    match_list
+                        .ids
+                        .get(match_list.selected)
+                        .and_then(|id| self.flamegraph().get_stack_full_name(id))
+                        .map(|full_name| full_name.to_string())
+                });
+                #[cfg(feature = "python")]
+                let last = parsed.last;
+                let flamegraph = match &self.live_baseline {
+                    Some(live_baseline) => parsed.flamegraph.subtract_counts(&live_baseline.counts),
+                    None => parsed.flamegraph,
+                };
+                self.flamegraph_view.replace_flamegraph(flamegraph);
+                self.flame_chart = match (self.flame_chart.take(), parsed.flame_chart) {
+                    (Some(mut view), Some(chart)) => {
+                        view.replace_chart(chart);
+                        Some(view)
+                    }
+                    (_, chart) => chart.map(FlameChartView::new),
+                };
+                let state = &mut self.flamegraph_view.state;
+                if state.view_kind == ViewKind::FlameChart && self.flame_chart.is_none() {
+                    state.view_kind = ViewKind::FlameGraph;
+                }
+                self.watch_list
+                    .update(&self.flamegraph_view.flamegraph, tic);
+                if let Some(full_name) = selected_match {
+                    let selected_id = full_name.and_then(|full_name| {
+                        self.flamegraph()
+                            .get_stack_by_full_name(&full_name)
+                            .map(|stack| stack.id)
+                    });
+                    self.match_list = self.build_match_list(selected_id);
+                }
+                // Threads started since the list was opened go at its end, keeping the selection
+                if let Some(thread_list) = self.thread_list.as_mut() {
+                    for (name, _) in self.flamegraph_view.get_threads() {
+                        if !thread_list.names.contains(&name) {
+                            thread_list.names.push(name);
+                        }
+                    }
+                }
+                // The tree is rebuilt with the new counts, keeping the selected process
+                if let Some(process_list) = self.process_list.as_mut() {
+                    let selected_name = process_list
+                        .processes
+                        .get(process_list.selected)
+                        .map(|process| process.full_name.clone());
+                    process_list.processes = self.flamegraph_view.get_processes();
+                    process_list.selected = selected_name
+                        .and_then(|name| {
+                            process_list
+                                .processes
+                                .iter()
+                                .position(|process| process.full_name == name)
+                        })
+                        .unwrap_or(0);
+                }
                 self.elapsed
                     .insert("replacement".to_string(), tic.elapsed());
+                #[cfg(feature = "python")]
+                if last {
+                    self.complete_sampling();
+                }
             }
         }
 
+        #[cfg(feature = "python")]
+        if let Some(status) = self
+            .spawned_command
+            .as_mut()
+            .and_then(|command| command.check_exit())
+        {
+            self.set_transient_message(&format!("The command exited with {}", status));
+        }
+        #[cfg(feature = "python")]
+        if let Some(threads) = self.pending_thread_dump.lock().unwrap().take() {
+            self.thread_dump = Some(ThreadDump { threads, offset: 0 });
+            self.transient_message = None;
+        }
+
+        #[allow(unused_mut)]
+        let mut message = self.background_message.lock().unwrap().take();
+        #[cfg(feature = "python")]
+        if let Some(state) = &self.sampler_state {
+            message = state.lock().unwrap().message.take().or(message);
+        }
+        if let Some(message) = message {
+            self.set_transient_message(&message);
+        }
+
         // Exit if fatal error in sampler
         #[cfg(feature = "python")]
         if let Some(SamplerStatus::Error(s)) = self
@@ -170,6 +825,11 @@ impl App {
     /// Set running to false to quit the application.
     pub fn quit(&mut self) {
         self.running = false;
+        // Also ends the wait for a process that is not there yet
+        #[cfg(feature = "python")]
+        if let Some(state) = &self.sampler_state {
+            state.lock().unwrap().stop_requested = true;
+        }
     }
 
     pub fn flamegraph(&self) -> &FlameGraph {
@@ -187,6 +847,213 @@ impl App {
             .map(|s| s.lock().unwrap().clone())
     }
 
+    /// Switch to the next sliding window of the live flamegraph
+    #[cfg(feature = "python")]
+    pub fn cycle_window(&mut self) {
+        let Some(state) = &self.sampler_state else {
+            return;
+        };
+        if self.live_baseline.is_some() {
+            self.set_transient_message("Release the captured samples to use a sliding window");
+            return;
+        }
+        let window = {
+            let mut state = state.lock().unwrap();
+            state.window = next_window(state.window);
+            state.window
+        };
+        let message = match window {
+            Some(window) => format!("Showing the samples of the last {}s", window.as_secs()),
+            None => "Showing all samples".to_string(),
+        };
+        self.set_transient_message(&message);
+    }
+
+    /// Freeze the view on the final samples and show a summary of the session
+    #[cfg(feature = "python")]
+    fn complete_sampling(&mut self) {
+        self.flamegraph_view.state.freeze = true;
+        let duration = self
+            .sampler_state()
+            .map_or(Duration::ZERO, |state| state.total_sampled_duration);
+        self.completion_banner = Some(CompletionBanner {
+            num_samples: self.flamegraph_view.unfiltered_flamegraph().total_count(),
+            duration,
+        });
+    }
+
+    #[cfg(feature = "python")]
+    pub fn close_completion_banner(&mut self) {
+        self.completion_banner = None;
+    }
+
+    /// Dump the current stacks of all the threads of the live process in the background, the
+    /// sampler carrying on meanwhile
+    #[cfg(feature = "python")]
+    pub fn dump_threads(&mut self) {
+        let (FlameGraphInput::Pid(pid, _), Some(config)) =
+            (&self.flamegraph_input, &self.py_spy_config)
+        else {
+            return;
+        };
+        let pid = *pid as remoteprocess::Pid;
+        let config = config.clone();
+        let pending_thread_dump = self.pending_thread_dump.clone();
+        let background_message = self.background_message.clone();
+        thread::spawn(move || match dump_stacks(pid, &config) {
+            Ok(threads) => *pending_thread_dump.lock().unwrap() = Some(threads),
+            Err(e) => {
+                let message = format!("Could not dump the stacks: {}", e);
+                *background_message.lock().unwrap() = Some(message);
+            }
+        });
+        self.set_transient_message("Dumping the stacks of the threads...");
+    }
+
+    #[cfg(feature = "python")]
+    pub fn close_thread_dump(&mut self) {
+        self.thread_dump = None;
+    }
+
+    /// Capture the live samples to show the ones collected since then, colored by how their share
+    /// changed compared to the capture, or release the capture to show all the samples again
+    pub fn toggle_live_baseline(&mut self) {
+        if !self.live {
+            self.set_transient_message("Only live flamegraphs can be diffed against a capture");
+            return;
+        }
+        if let Some(live_baseline) = self.live_baseline.take() {
+            self.flamegraph_view.diff = None;
+            if let Some(baseline) = live_baseline.previous {
+                self.flamegraph_view.set_baseline(baseline);
+            } else {
+                self.flamegraph_view.baseline = None;
+            }
+            self.set_transient_message(
+                "Released the capture, all the samples come back on refresh",
+            );
+            return;
+        }
+        // Subtracting the captured counts takes all the samples
+        #[cfg(feature = "python")]
+        if let Some(state) = &self.sampler_state {
+            state.lock().unwrap().window = None;
+        }
+        let flamegraph = self.flamegraph_view.unfiltered_flamegraph();
+        let num_samples = flamegraph.total_count();
+        let counts = flamegraph.get_stack_counts();
+        let baseline = Baseline::new(&format!("{} captured samples", num_samples), flamegraph);
+        let previous = self.flamegraph_view.baseline.take();
+        self.flamegraph_view.set_baseline(baseline);
+        self.live_baseline = Some(LiveBaseline { counts, previous });
+        self.set_transient_message("Captured the samples, showing the ones collected since then");
+    }
+
+    /// Show or hide the output of the profiled command, if it is kept rather than written to a file
+    #[cfg(feature = "python")]
+    pub fn toggle_command_output(&mut self) {
+        match &self.spawned_command {
+            Some(command) if command.output.is_some() => {
+                self.show_command_output = !self.show_command_output;
+            }
+            Some(_) => self.set_transient_message("The output of the command goes to a file"),
+            None => self.set_transient_message("No command launched by flamelens"),
+        }
+    }
+
+    #[cfg(feature = "python")]
+    pub fn open_settings_popup(&mut self) {
+        let settings = self
+            .sampler_state
+            .as_ref()
+            .and_then(|s| s.lock().unwrap().settings);
+        self.settings_popup = settings.map(|settings| SettingsPopup {
+            settings,
+            selected: 0,
+        });
+    }
+
+    #[cfg(feature = "python")]
+    pub fn close_settings_popup(&mut self) {
+        self.settings_popup = None;
+    }
+
+    /// Restart the sampler with the settings of the popup if they changed
+    #[cfg(feature = "python")]
+    pub fn apply_settings_popup(&mut self) {
+        let (Some(popup), Some(state)) = (self.settings_popup.take(), &self.sampler_state) else {
+            return;
+        };
+        let (changed, rate_changed) = {
+            let mut state = state.lock().unwrap();
+            let changed = state.settings != Some(popup.settings);
+            let rate_changed = state
+                .settings
+                .is_some_and(|s| s.sampling_rate != popup.settings.sampling_rate);
+            state.settings = Some(popup.settings);
+            (changed, rate_changed)
+        };
+        if rate_changed {
+            // The samples so far stand for another duration than the next ones
+            self.mixed_sample_rates = true;
+            self.show_durations = false;
+            self.set_transient_message(
+                "Restarting the sampler with the new settings, durations are off until cleared (x)",
+            );
+        } else if changed {
+            self.set_transient_message("Restarting the sampler with the new settings");
+        }
+    }
+
+    /// Write all the samples of the live session to the output file, or to a timestamped file in
+    /// the current directory if there is none.
+    #[cfg(feature = "python")]
+    pub fn save_live_data(&self) -> std::io::Result<PathBuf> {
+        let path = match (&self.live_output, &self.flamegraph_input) {
+            (Some(path), _) => path.clone(),
+            (None, FlameGraphInput::Pid(pid, _)) => PathBuf::from(format!(
+                "flamelens-{}-{}.txt",
+                pid,
+                format_timestamp(std::time::SystemTime::now())
+            )),
+            (None, FlameGraphInput::File(_)) => {
+                return Err(std::io::Error::other("not a live session"));
+            }
+        };
+        let mut data = self.live_data.lock().unwrap().clone();
+        data.push('\n');
+        std::fs::write(&path, data)?;
+        Ok(path)
+    }
+
+    #[cfg(feature = "python")]
+    pub fn save(&mut self) {
+        if self.sampler_state.is_none() {
+            return;
+        }
+        let message = match self.save_live_data() {
+            Ok(path) => format!("Saved all samples to {}", path.display()),
+            Err(e) => format!("Could not save samples: {}", e),
+        };
+        self.set_transient_message(&message);
+    }
+
+    /// Discard the samples collected so far, keeping the sampler attached
+    #[cfg(feature = "python")]
+    pub fn clear_samples(&mut self) {
+        let Some(state) = &self.sampler_state else {
+            return;
+        };
+        state.lock().unwrap().clear_requested = true;
+        // The new samples are all taken at the current rate
+        self.mixed_sample_rates = false;
+        // The new samples all come after the capture
+        if let Some(live_baseline) = self.live_baseline.as_mut() {
+            live_baseline.counts.clear();
+        }
+        self.set_transient_message("Cleared the samples collected so far");
+    }
+
     pub fn add_elapsed(&mut self, name: &str, elapsed: Duration) {
         self.elapsed.insert(name.to_string(), elapsed);
     }
@@ -205,6 +1072,20 @@ impl App {
         }
     }
 
+    /// Select the heaviest frame of the function best matching `query` in the flamegraph
+    pub fn goto(&mut self, query: &str) {
+        let stack_id = self
+            .flamegraph()
+            .find_function_by_fuzzy_name(query)
+            .and_then(|name| self.flamegraph().find_heaviest_stack_by_name(name));
+        let Some(stack_id) = stack_id else {
+            self.set_transient_message(&format!("No function matching \"{}\"", query));
+            return;
+        };
+        self.flamegraph_view.state.view_kind = ViewKind::FlameGraph;
+        self.flamegraph_view.jump_to(stack_id);
+    }
+
     pub fn search_selected_row(&mut self) {
         let short_name = self
             .flamegraph_view
@@ -216,6 +1097,54 @@ impl App {
         self.flamegraph_view.state.toggle_view_kind();
     }
 
+    /// Go from the Top view to the flamegraph zoomed on the heaviest frame of the function of the
+    /// selected row, searching for the function to highlight where else it is called from
+    pub fn zoom_selected_row(&mut self) {
+        let Some(short_name) = self
+            .flamegraph_view
+            .get_selected_row_name()
+            .map(|s| s.to_string())
+        else {
+            return;
+        };
+        let Some(stack_id) = self.flamegraph().find_heaviest_stack_by_name(&short_name) else {
+            // Rows counting the samples by file or module aren't frames
+            self.set_transient_message(&format!("No frame named \"{}\"", short_name));
+            return;
+        };
+        self.set_manual_search_pattern(short_name.as_str(), false);
+        self.flamegraph_view.state.view_kind = ViewKind::FlameGraph;
+        self.zoom_to(stack_id);
+    }
+
+    /// Select a frame and zoom in on it, unless it is already the zoomed frame
+    fn zoom_to(&mut self, stack_id: StackIdentifier) {
+        self.flamegraph_view.select_id(&stack_id);
+        let already_zoomed = self
+            .flamegraph_state()
+            .get_zoom()
+            .is_some_and(|z| z.stack_id == stack_id);
+        if !already_zoomed {
+            self.flamegraph_view.set_zoom_for_id(stack_id);
+            self.flamegraph_view.record_zoom();
+        }
+        self.flamegraph_view.scroll_to_selected();
+    }
+
+    /// Cycle through the flamegraph, the table and, if available, the flame chart
+    pub fn switch_view(&mut self) {
+        let state = &mut self.flamegraph_view.state;
+        state.view_kind = match state.view_kind {
+            ViewKind::FlameGraph => ViewKind::Table,
+            ViewKind::Table => ViewKind::CallTree,
+            ViewKind::CallTree if self.flame_chart.is_some() => ViewKind::FlameChart,
+            ViewKind::CallTree | ViewKind::FlameChart => ViewKind::FlameGraph,
+        };
+        if state.view_kind == ViewKind::CallTree {
+            self.flamegraph_view.reveal_selected_in_call_tree();
+        }
+    }
+
     pub fn set_manual_search_pattern(&mut self, pattern: &str, is_regex: bool) {
         match SearchPattern::new(pattern, is_regex, true) {
             Ok(p) => self.flamegraph_view.set_search_pattern(p),
@@ -225,6 +1154,651 @@ impl App {
         }
     }
 
+    /// Leave out the stacks going through a function matching the regex `pattern`, or bring back
+    /// all the stacks when it is empty
+    pub fn set_exclude_pattern(&mut self, pattern: &str) {
+        let excluded = (!pattern.is_empty()).then(|| pattern.to_string());
+        let message = match self.flamegraph_view.set_excluded(excluded) {
+            Ok(()) if pattern.is_empty() => "Showing all stacks".to_string(),
+            Ok(()) => format!("Left out the stacks going through \"{}\"", pattern),
+            Err(_) => format!("Invalid regex: {}", pattern),
+        };
+        self.set_transient_message(&message);
+    }
+
+    pub fn open_search_prompt(&mut self) {
+        let mut input_buffer = InputBuffer::new(Prompt::Search);
+        input_buffer.previous_search = self.flamegraph_state().search_pattern.clone();
+        self.input_buffer = Some(input_buffer);
+    }
+
+    /// Replace the text of the search prompt with an older (or newer) search of the history, the
+    /// text typed coming back after the newest one
+    pub fn recall_search(&mut self, older: bool) {
+        let num_entries = self.search_history.len();
+        let Some(input_buffer) = self.input_buffer.as_mut() else {
+            return;
+        };
+        if input_buffer.prompt != Prompt::Search || num_entries == 0 {
+            return;
+        }
+        let index = match (input_buffer.history_index, older) {
+            (None, true) => {
+                input_buffer.draft = input_buffer.buffer.value().to_string();
+                Some(num_entries - 1)
+            }
+            (None, false) => return,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < num_entries => Some(index + 1),
+            (Some(_), false) => None,
+        };
+        let text = match index {
+            Some(index) => self.search_history.get(index).unwrap_or_default(),
+            None => &input_buffer.draft,
+        };
+        input_buffer.buffer = tui_input::Input::new(text.to_string());
+        input_buffer.history_index = index;
+        self.search_input_changed();
+    }
+
+    /// Remember a search submitted in the prompt
+    pub fn add_search_history(&mut self, text: &str) {
+        if let Err(e) = self.search_history.push(text) {
+            self.set_transient_message(&format!("{:#}", e));
+        }
+    }
+
+    /// Regex searched for when `text` is submitted in the search prompt, keeping the exclude
+    /// prefix
+    pub fn search_text_to_regex(&self, text: &str) -> String {
+        match text.strip_prefix(EXCLUDE_PREFIX) {
+            Some("") => EXCLUDE_PREFIX.to_string(),
+            Some(text) => format!("{}{}", EXCLUDE_PREFIX, self.search_modes.to_regex(text)),
+            None if text.is_empty() => String::new(),
+            None => self.search_modes.to_regex(text),
+        }
+    }
+
+    /// Check the regex typed in the search prompt and highlight its matches, right away or once
+    /// typing pauses on large flamegraphs
+    pub fn search_input_changed(&mut self) {
+        let Some(input_buffer) = &self.input_buffer else {
+            return;
+        };
+        if input_buffer.prompt != Prompt::Search {
+            return;
+        }
+        let re_pattern = self.search_text_to_regex(input_buffer.buffer.value());
+        let re_pattern = re_pattern
+            .strip_prefix(EXCLUDE_PREFIX)
+            .unwrap_or(&re_pattern);
+        let error = regex::Regex::new(re_pattern).err().map(|e| match e {
+            // The last line of syntax errors tells what is wrong, the others show where
+            regex::Error::Syntax(message) => message
+                .lines()
+                .last()
+                .unwrap_or_default()
+                .trim_start_matches("error: ")
+                .to_string(),
+            e => e.to_string(),
+        });
+        let is_large = self.flamegraph().stacks().len() > SEARCH_PREVIEW_DEBOUNCE_FRAMES;
+        let input_buffer = self.input_buffer.as_mut().unwrap();
+        input_buffer.error = error;
+        if is_large {
+            input_buffer.preview_due = Some(Instant::now() + SEARCH_PREVIEW_DEBOUNCE);
+        } else {
+            self.preview_search();
+        }
+    }
+
+    /// Highlight the frames matching the regex typed in the search prompt, as if it was submitted.
+    /// Invalid regexes keep the last preview, and exclude patterns only apply once submitted.
+    pub fn preview_search(&mut self) {
+        let Some(input_buffer) = self.input_buffer.as_mut() else {
+            return;
+        };
+        input_buffer.preview_due = None;
+        if input_buffer.error.is_some() {
+            return;
+        }
+        let previous_search = input_buffer.previous_search.clone();
+        let text = input_buffer.buffer.value().to_string();
+        let re_pattern = self.search_text_to_regex(&text);
+        if re_pattern.is_empty() || re_pattern.starts_with(EXCLUDE_PREFIX) {
+            self.restore_search(previous_search);
+        } else if let Ok(p) = SearchPattern::new(&re_pattern, true, true) {
+            self.flamegraph_view.set_search_pattern(p);
+        }
+    }
+
+    /// Bring back the search pattern from before the search prompt was opened
+    pub fn cancel_search_preview(&mut self) {
+        if let Some(input_buffer) = &self.input_buffer {
+            if input_buffer.prompt == Prompt::Search {
+                self.restore_search(input_buffer.previous_search.clone());
+            }
+        }
+    }
+
+    fn restore_search(&mut self, search_pattern: Option<SearchPattern>) {
+        match search_pattern {
+            Some(p) => self.flamegraph_view.set_search_pattern(p),
+            None => self.flamegraph_view.unset_search_pattern(),
+        }
+    }
+
+    /// Move the guided tour to its next step if `action` completes the current one
+    pub fn advance_tutorial(&mut self, action: Action) {
+        let Some(tutorial) = self.tutorial.as_mut() else {
+            return;
+        };
+        if tutorial.advance(action) {
+            self.end_tutorial();
+        }
+    }
+
+    pub fn end_tutorial(&mut self) {
+        if self.tutorial.take().is_some() {
+            self.set_transient_message("Tour over, run \"flamelens tutorial\" to take it again");
+        }
+    }
+
+    /// Turn a search mode on or off, `literal` or else ignoring the case
+    pub fn toggle_search_mode(&mut self, literal: bool) {
+        let mode = if literal {
+            &mut self.search_modes.literal
+        } else {
+            &mut self.search_modes.ignore_case
+        };
+        *mode = !*mode;
+    }
+
+    /// Keep the manual search pattern highlighted in its own color, to search for another one
+    pub fn keep_search(&mut self) {
+        let pattern = match &self.flamegraph_state().search_pattern {
+            Some(p) if p.is_manual => p.pattern.clone(),
+            _ => {
+                self.set_transient_message("Search for a pattern to keep first");
+                return;
+            }
+        };
+        let message = if self.flamegraph_view.keep_search_pattern() {
+            format!(
+                "Keeping \"{}\" highlighted, press {} to search for another pattern",
+                pattern,
+                self.keymap.label(&[Action::Search], "/")
+            )
+        } else {
+            format!("Up to {} search patterns can be kept", MAX_KEPT_SEARCHES)
+        };
+        self.set_transient_message(&message);
+    }
+
+    pub fn set_watch_list(&mut self, watch_list: WatchList) {
+        self.watch_list = watch_list;
+        self.watch_list
+            .update(&self.flamegraph_view.flamegraph, std::time::Instant::now());
+    }
+
+    /// Watch the manually searched pattern, or the selected function if there is none
+    pub fn toggle_watch(&mut self) {
+        let short_name = match self.flamegraph_state().view_kind {
+            ViewKind::Table => self
+                .flamegraph_view
+                .get_selected_row_name()
+                .map(|s| s.to_string()),
+            ViewKind::FlameChart => self
+                .flame_chart
+                .as_ref()
+                .and_then(|view| view.get_selected_frame())
+                .map(|frame| frame.name.clone()),
+            _ if self.flamegraph_view.is_root_selected() => None,
+            _ => self.flamegraph_view.get_selected_stack().map(|s| {
+                self.flamegraph()
+                    .get_stack_short_name_from_info(s)
+                    .to_string()
+            }),
+        };
+        let pattern = match &self.flamegraph_state().search_pattern {
+            Some(p) if p.is_manual => Some(p.clone()),
+            _ => short_name.and_then(|name| SearchPattern::new(&name, false, false).ok()),
+        };
+        let Some(pattern) = pattern else {
+            return;
+        };
+        let message = if self.watch_list.toggle(pattern.clone()) {
+            format!("Watching \"{}\"", pattern.pattern)
+        } else {
+            format!("Stopped watching \"{}\"", pattern.pattern)
+        };
+        self.watch_list
+            .update(&self.flamegraph_view.flamegraph, std::time::Instant::now());
+        self.set_transient_message(&message);
+    }
+
+    /// Jump to the next (or previous) frame matching the search pattern, telling when the search
+    /// wraps around
+    pub fn to_next_search_result(&mut self, forward: bool) {
+        let wrapped = if forward {
+            self.flamegraph_view.to_next_search_result()
+        } else {
+            self.flamegraph_view.to_previous_search_result()
+        };
+        if wrapped {
+            let message = if forward {
+                "Search wrapped around to the first match"
+            } else {
+                "Search wrapped around to the last match"
+            };
+            self.set_transient_message(message);
+        }
+    }
+
+    /// List the frames matching the search pattern, widest first, to jump to one of them
+    pub fn open_match_list(&mut self) {
+        self.match_list = self.build_match_list(Some(self.flamegraph_state().selected));
+        if self.match_list.is_none() {
+            self.set_transient_message("No frames match the search");
+        }
+    }
+
+    /// Match list of the current flamegraph with `selected_id` selected if it matches
+    fn build_match_list(&self, selected_id: Option<StackIdentifier>) -> Option<MatchList> {
+        let flamegraph = self.flamegraph();
+        let mut ids = flamegraph.hit_ids().filter(|ids| !ids.is_empty())?.clone();
+        ids.sort_by_key(|id| {
+            std::cmp::Reverse(
+                flamegraph
+                    .get_stack(id)
+                    .map_or(0, |stack| stack.total_count),
+            )
+        });
+        let selected = selected_id
+            .and_then(|selected_id| ids.iter().position(|id| *id == selected_id))
+            .unwrap_or(0);
+        Some(MatchList { ids, selected })
+    }
+
+    pub fn close_match_list(&mut self) {
+        self.match_list = None;
+    }
+
+    /// List the thread frames with their samples, to show or hide the stacks of each thread
+    pub fn open_thread_list(&mut self) {
+        let names = self
+            .flamegraph_view
+            .get_threads()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            self.set_transient_message(
+                "No thread frames: enable thread ids in the sampler settings",
+            );
+            return;
+        }
+        self.thread_list = Some(ThreadList { names, selected: 0 });
+    }
+
+    pub fn close_thread_list(&mut self) {
+        self.thread_list = None;
+    }
+
+    /// Hide the stacks of the thread selected in the thread list, or show them again
+    pub fn toggle_selected_thread(&mut self) {
+        let Some(name) = self
+            .thread_list
+            .as_ref()
+            .and_then(|thread_list| thread_list.names.get(thread_list.selected))
+            .cloned()
+        else {
+            return;
+        };
+        self.flamegraph_view.toggle_thread(&name);
+    }
+
+    /// List the processes as a tree with their samples, to show or hide their stacks or zoom in on
+    /// one of them
+    pub fn open_process_list(&mut self) {
+        let processes = self.flamegraph_view.get_processes();
+        if processes.is_empty() {
+            self.set_transient_message("No process frames: profile with --subprocesses");
+            return;
+        }
+        self.process_list = Some(ProcessList {
+            processes,
+            selected: 0,
+        });
+    }
+
+    pub fn close_process_list(&mut self) {
+        self.process_list = None;
+    }
+
+    /// Hide the stacks of the process selected in the process list and of its child processes, or
+    /// show them again
+    pub fn toggle_selected_process(&mut self) {
+        let Some(name) = self
+            .process_list
+            .as_ref()
+            .and_then(|process_list| process_list.processes.get(process_list.selected))
+            .map(|process| process.name.clone())
+        else {
+            return;
+        };
+        self.flamegraph_view.toggle_process(&name);
+    }
+
+    /// Zoom in on the process selected in the process list and close it
+    pub fn zoom_to_selected_process(&mut self) {
+        let Some(process) = self
+            .process_list
+            .as_ref()
+            .and_then(|process_list| process_list.processes.get(process_list.selected))
+            .cloned()
+        else {
+            return;
+        };
+        if self.flamegraph_view.zoom_to_process(&process) {
+            self.close_process_list();
+        } else {
+            self.set_transient_message("The process is hidden by the filters");
+        }
+    }
+
+    /// Select the frame chosen in the match list and close it
+    pub fn jump_to_selected_match(&mut self) {
+        let Some(match_list) = self.match_list.take() else {
+            return;
+        };
+        if let Some(stack_id) = match_list.ids.get(match_list.selected) {
+            if self.flamegraph().get_stack(stack_id).is_some() {
+                self.flamegraph_view.jump_to(*stack_id);
+            }
+        }
+    }
+
+    /// Jump to the next (or previous) frame of the selected function
+    pub fn to_next_occurrence(&mut self, forward: bool) {
+        if let Some((index, len)) = self.flamegraph_view.to_next_occurrence(forward) {
+            let message = format!("Occurrence {} of {}", index + 1, len);
+            self.set_transient_message(&message);
+        }
+    }
+
+    /// Only keep the stacks going through the function of the selected frame, or bring back all
+    /// the stacks
+    pub fn toggle_focus(&mut self) {
+        let message = if self.flamegraph_view.filters.focus.is_some() {
+            self.flamegraph_view.unset_focus();
+            "Showing all stacks".to_string()
+        } else {
+            if self.flamegraph_view.is_root_selected() {
+                return;
+            }
+            let Some(name) = self.flamegraph_view.get_selected_stack().map(|s| {
+                self.flamegraph()
+                    .get_stack_short_name_from_info(s)
+                    .to_string()
+            }) else {
+                return;
+            };
+            self.flamegraph_view.set_focus(&name);
+            format!("Only showing the stacks going through {}", name)
+        };
+        self.set_transient_message(&message);
+    }
+
+    /// Merge the frames of a function that only differ by their line, or bring them back
+    pub fn toggle_merge_lines(&mut self) {
+        let merge_lines = !self.flamegraph_view.filters.merge_lines;
+        self.flamegraph_view.set_merge_lines(merge_lines);
+        let message = if merge_lines {
+            "Merged the frames differing only by their line"
+        } else {
+            "Showing a frame per line"
+        };
+        self.set_transient_message(message);
+    }
+
+    /// Group the stacks by the next annotation key, or stop grouping after the last key
+    pub fn cycle_group_by(&mut self) {
+        let keys = self
+            .flamegraph_view
+            .unfiltered_flamegraph()
+            .get_annotation_keys();
+        if keys.is_empty() {
+            self.set_transient_message("No annotations to group by; pass --annotations");
+            return;
+        }
+        let next = match &self.flamegraph_view.filters.group_by {
+            Some(key) => keys
+                .iter()
+                .position(|k| k == key)
+                .and_then(|i| keys.get(i + 1)),
+            None => keys.first(),
+        }
+        .map(|key| key.to_string());
+        let message = match &next {
+            Some(key) => format!("Grouped by {}", key),
+            None => "Not grouped".to_string(),
+        };
+        self.flamegraph_view.set_group_by(next);
+        self.set_transient_message(&message);
+    }
+
+    /// Only show the stacks of the next device: the CPU (stacks without a device), then every
+    /// device in turn, then all of them again
+    pub fn cycle_lane(&mut self) {
+        let flamegraph = self.flamegraph_view.unfiltered_flamegraph();
+        let values = flamegraph.get_annotation_values(&self.lane_key);
+        if values.is_empty() {
+            let message = format!(
+                "No lanes: no frames annotated with #{}=...; pass --annotations",
+                self.lane_key
+            );
+            self.set_transient_message(&message);
+            return;
+        }
+        let mut lanes = vec![];
+        if flamegraph.has_stacks_without_annotation(&self.lane_key) {
+            lanes.push(None);
+        }
+        lanes.extend(values.into_iter().map(|value| Some(value.to_string())));
+        let position = match &self.flamegraph_view.filters.lane {
+            Some(lane) => lanes
+                .iter()
+                .position(|value| *value == lane.value)
+                .map(|i| i + 1),
+            None => Some(0),
+        };
+        let num_lanes = lanes.len();
+        let next = position.filter(|i| *i < num_lanes).map(|i| Lane {
+            key: self.lane_key.clone(),
+            value: lanes.swap_remove(i),
+        });
+        let message = match (&next, position) {
+            (Some(lane), Some(i)) => format!("Lane {} ({}/{})", lane.label(), i + 1, num_lanes),
+            _ => "All lanes".to_string(),
+        };
+        self.flamegraph_view.set_lane(next);
+        self.set_transient_message(&message);
+    }
+
+    /// Remove the function of the selected frame from every stack
+    pub fn prune_selected(&mut self) {
+        if self.flamegraph_view.is_root_selected() {
+            return;
+        }
+        let Some(name) = self.flamegraph_view.get_selected_stack().map(|s| {
+            self.flamegraph()
+                .get_stack_short_name_from_info(s)
+                .to_string()
+        }) else {
+            return;
+        };
+        self.flamegraph_view.prune(&name);
+        let message = format!(
+            "Pruned {}; press {} to undo",
+            name,
+            self.keymap.label(&[Action::UndoPrune], "/")
+        );
+        self.set_transient_message(&message);
+    }
+
+    pub fn undo_prune(&mut self) {
+        let message = match self.flamegraph_view.undo_prune() {
+            Some(name) => format!("Restored {}", name),
+            None => "Nothing to undo".to_string(),
+        };
+        self.set_transient_message(&message);
+    }
+
+    pub fn toggle_pin(&mut self) {
+        let message = if self.flamegraph_view.toggle_pin() {
+            let name = self
+                .flamegraph_view
+                .get_selected_stack()
+                .map_or("", |s| self.flamegraph().get_stack_short_name_from_info(s));
+            format!("Pinned {}; select another frame to compare", name)
+        } else {
+            "Unpinned".to_string()
+        };
+        self.set_transient_message(&message);
+    }
+
+    /// Ask for the source file of the selected frame (or row of the Top view) to be opened in the
+    /// editor, at its line if known
+    pub fn open_in_editor(&mut self) {
+        let location = match self.flamegraph_state().view_kind {
+            ViewKind::Table => self
+                .flamegraph_view
+                .get_selected_row_name()
+                .and_then(parse_frame_location)
+                .map(|(file, line)| (file.to_string(), line)),
+            _ => self.flamegraph_view.get_selected_stack().and_then(|stack| {
+                self.flamegraph()
+                    .get_location(stack)
+                    .map(|(file, line)| (file.to_string(), line))
+            }),
+        };
+        match location {
+            Some((file, line)) => self.editor_request = Some(EditorRequest { file, line }),
+            None => self.set_transient_message("No source location for this frame"),
+        }
+    }
+
+    pub fn toggle_single_child_prefix(&mut self) {
+        let message = if self.flamegraph_view.toggle_single_child_prefix() {
+            format!(
+                "Skipped {} levels of frames with a single child",
+                self.flamegraph_state().level_offset
+            )
+        } else {
+            "Showing all levels".to_string()
+        };
+        self.set_transient_message(&message);
+    }
+
+    /// Wait for the register to record a macro into, or stop recording and save the macros to
+    /// the config file
+    pub fn toggle_macro_recording(&mut self) {
+        let Some((register, len)) = self.macros.stop_recording() else {
+            self.macros.pending = Some(PendingRegister::Record);
+            return;
+        };
+        let message = match Config::persist("macros", &self.macros.to_config()) {
+            Ok(_) => format!("Recorded {} steps into @{}", len, register),
+            Err(e) => format!(
+                "Recorded {} steps into @{} (could not save to config: {})",
+                len, register, e
+            ),
+        };
+        self.set_transient_message(&message);
+    }
+
+    /// Switch to the next color palette and remember the choice in the config file
+    pub fn cycle_palette(&mut self) {
+        self.flamegraph_view.state.cycle_palette();
+        let palette = self.flamegraph_state().palette;
+        let message = match Config::persist("palette", &palette) {
+            Ok(_) => format!("Palette: {}", palette.name()),
+            Err(e) => format!(
+                "Palette: {} (could not save to config: {})",
+                palette.name(),
+                e
+            ),
+        };
+        self.set_transient_message(&message);
+    }
+
+    /// Switch to the next way of separating sibling frames and remember it in the config file
+    pub fn cycle_separators(&mut self) {
+        self.flamegraph_view.state.cycle_separators();
+        let separators = self.flamegraph_state().separators;
+        let message = match Config::persist("separators", &separators) {
+            Ok(_) => format!("Separators: {}", separators.name()),
+            Err(e) => format!(
+                "Separators: {} (could not save to config: {})",
+                separators.name(),
+                e
+            ),
+        };
+        self.set_transient_message(&message);
+    }
+
+    /// Run a command received on the control socket or from a batch file and return the reply
+    pub fn handle_control_command(&mut self, command: ControlCommand) -> Result<String, String> {
+        match command {
+            ControlCommand::Search(pattern) => {
+                if pattern.is_empty() {
+                    self.flamegraph_view.unset_search_pattern();
+                    return Ok(String::new());
+                }
+                let p = SearchPattern::new(&pattern, true, true)
+                    .map_err(|_| format!("invalid regex: {}", pattern))?;
+                self.flamegraph_view.set_search_pattern(p);
+                self.flamegraph_view.to_heaviest_search_result();
+                let total_count = self.flamegraph().total_count();
+                let hit_count = self.flamegraph().hit_coverage_count().unwrap_or(0);
+                Ok(format!(
+                    "{}/{} samples ({:.2}%)",
+                    hit_count,
+                    total_count,
+                    100.0 * hit_count as f64 / total_count.max(1) as f64
+                ))
+            }
+            ControlCommand::Zoom(name) => {
+                let stack_id = self
+                    .flamegraph()
+                    .find_heaviest_stack_by_name(&name)
+                    .ok_or_else(|| format!("no such frame: {}", name))?;
+                self.zoom_to(stack_id);
+                Ok(String::new())
+            }
+            ControlCommand::Reset => {
+                self.flamegraph_view.reset();
+                Ok(String::new())
+            }
+            ControlCommand::Export(path) => {
+                let stack_id = self
+                    .flamegraph_state()
+                    .get_zoom()
+                    .map(|z| z.stack_id)
+                    .unwrap_or(ROOT_ID);
+                let folded = self.flamegraph().to_folded(&stack_id);
+                std::fs::write(&path, folded + "\n")
+                    .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+                Ok(path.display().to_string())
+            }
+            ControlCommand::Quit => {
+                self.quit();
+                Ok(String::new())
+            }
+        }
+    }
+
     pub fn set_transient_message(&mut self, message: &str) {
         self.transient_message = Some(message.to_string());
     }
@@ -236,4 +1810,105 @@ impl App {
     pub fn toggle_debug(&mut self) {
         self.debug = !self.debug;
     }
+
+    pub fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+    }
+
+    pub fn toggle_details(&mut self) {
+        self.show_details = !self.show_details;
+    }
+
+    pub fn toggle_source(&mut self) {
+        self.show_source = !self.show_source;
+    }
+
+    pub fn toggle_ruler(&mut self) {
+        self.show_ruler = !self.show_ruler;
+    }
+
+    /// Samples per second: the current one of the sampler in live mode, unless the rate changed
+    /// during the session, or the one given for the input file
+    pub fn get_sample_rate(&self) -> Option<u64> {
+        if self.mixed_sample_rates {
+            return None;
+        }
+        #[cfg(feature = "python")]
+        if let Some(settings) = self
+            .sampler_state
+            .as_ref()
+            .and_then(|s| s.lock().unwrap().settings)
+        {
+            return Some(settings.sampling_rate);
+        }
+        self.sample_rate
+    }
+
+    /// How counts are shown: as durations if enabled and the sample rate is known
+    pub fn count_format(&self) -> CountFormat {
+        CountFormat {
+            unit: self.flamegraph().count_unit,
+            sample_rate: self.get_sample_rate().filter(|_| self.show_durations),
+        }
+    }
+
+    pub fn toggle_durations(&mut self) {
+        if self.flamegraph().count_unit != CountUnit::Samples {
+            self.set_transient_message("Only samples can be shown as durations");
+        } else if self.mixed_sample_rates {
+            self.set_transient_message(
+                "The samples were taken at different rates, clear them (x) to show durations",
+            );
+        } else if self.get_sample_rate().is_none() {
+            self.set_transient_message("Durations need the sampling rate, given with --rate");
+        } else {
+            self.show_durations = !self.show_durations;
+        }
+    }
+
+    pub fn toggle_parent_share(&mut self) {
+        self.show_parent_share = !self.show_parent_share;
+        let message = if self.show_parent_share {
+            "Shares relative to the parent frame"
+        } else {
+            "Shares relative to all samples"
+        };
+        self.set_transient_message(message);
+    }
+
+    pub fn toggle_sideways(&mut self) {
+        let state = &mut self.flamegraph_view.state;
+        state.sideways = !state.sideways;
+        // The number of levels in view is only known once rendered
+        state.frame_height = None;
+    }
+
+    pub fn cycle_frame_labels(&mut self) {
+        self.frame_labels = self.frame_labels.next();
+        let message = match self.frame_labels {
+            FrameLabels::None => "Frames show no counts",
+            FrameLabels::Count => "Frames show their samples",
+            FrameLabels::Percent => "Frames show their share of the samples",
+        };
+        self.set_transient_message(message);
+    }
+
+    pub fn cycle_aggregation(&mut self) {
+        let aggregation = self.flamegraph_view.cycle_aggregation();
+        let message = format!("Top view by {}", aggregation.label().to_lowercase());
+        self.set_transient_message(&message);
+    }
+
+    /// Sort the Top view by `column`, or the other way around if it is already sorted by it. The
+    /// count shown in the frames is the own one when sorted by it, the total one otherwise.
+    pub fn set_sort_column(&mut self, column: SortColumn) {
+        self.flamegraph_view.set_sort_column(column);
+        if self.frame_labels != FrameLabels::None {
+            let message = match column {
+                SortColumn::Total | SortColumn::Name => "Frames show their total samples",
+                SortColumn::Own => "Frames show their own samples",
+            };
+            self.set_transient_message(message);
+        }
+    }
 }
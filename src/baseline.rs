@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use crate::flame::{FlameGraph, StackIdentifier, ROOT_ID};
+
+/// Color of the stacks whose share didn't change, a gray set apart from the near white of the
+/// selected frame
+const UNCHANGED_COLOR: (u8, u8, u8) = (170, 170, 170);
+
+/// Share of the full color given to the smallest changes, so that they don't look unchanged
+const MIN_INTENSITY: f64 = 0.25;
+
+/// Share of all samples of every stack of a baseline profile, keyed by full name.
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    pub name: String,
+    shares: HashMap<String, f64>,
+}
+
+/// Change in the share of all samples of every stack of a flamegraph compared to a baseline.
+#[derive(Debug, Clone, Default)]
+pub struct Diff {
+    /// Indexed by stack id
+    deltas: Vec<f64>,
+    max_abs_delta: f64,
+}
+
+impl Baseline {
+    pub fn new(name: &str, flamegraph: &FlameGraph) -> Self {
+        let total = flamegraph.total_count().max(1) as f64;
+        let shares = flamegraph
+            .stacks()
+            .iter()
+            .map(|stack| {
+                (
+                    flamegraph.get_stack_full_name_from_info(stack).to_string(),
+                    stack.total_count as f64 / total,
+                )
+            })
+            .collect();
+        Self {
+            name: name.to_string(),
+            shares,
+        }
+    }
+
+    /// Share of all samples of a stack in the baseline, 0 if it doesn't appear in it.
+    pub fn share(&self, full_name: &str) -> f64 {
+        self.shares.get(full_name).copied().unwrap_or(0.0)
+    }
+
+    pub fn compare(&self, flamegraph: &FlameGraph) -> Diff {
+        let total = flamegraph.total_count().max(1) as f64;
+        let deltas = flamegraph
+            .stacks()
+            .iter()
+            .map(|stack| {
+                if stack.id == ROOT_ID {
+                    return 0.0;
+                }
+                let full_name = flamegraph.get_stack_full_name_from_info(stack);
+                stack.total_count as f64 / total - self.share(full_name)
+            })
+            .collect::<Vec<_>>();
+        let max_abs_delta = deltas
+            .iter()
+            .fold(0.0f64, |max, delta| max.max(delta.abs()));
+        Diff {
+            deltas,
+            max_abs_delta,
+        }
+    }
+}
+
+impl Diff {
+    pub fn delta(&self, stack_id: &StackIdentifier) -> f64 {
+        self.deltas.get(*stack_id).copied().unwrap_or(0.0)
+    }
+
+    /// Color of a stack in the style of differential flamegraphs: red when it got heavier than
+    /// in the baseline, blue when it got lighter, and more saturated the larger the change, from
+    /// the gray of the unchanged stacks. With `colorblind`, heavier stacks are orange instead of
+    /// red.
+    pub fn color(&self, stack_id: &StackIdentifier, colorblind: bool) -> (u8, u8, u8) {
+        let delta = self.delta(stack_id);
+        if delta == 0.0 || self.max_abs_delta == 0.0 {
+            return UNCHANGED_COLOR;
+        }
+        let intensity = MIN_INTENSITY + (1.0 - MIN_INTENSITY) * delta.abs() / self.max_abs_delta;
+        // Orange and blue are apart from each other with any color vision
+        let (r, g, b) = match (delta > 0.0, colorblind) {
+            (true, false) => (255, 0, 0),
+            (false, false) => (0, 0, 255),
+            (true, true) => (230, 159, 0),
+            (false, true) => (0, 114, 178),
+        };
+        let fade = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * intensity) as u8;
+        (
+            fade(UNCHANGED_COLOR.0, r),
+            fade(UNCHANGED_COLOR.1, g),
+            fade(UNCHANGED_COLOR.2, b),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let baseline = FlameGraph::from_string("main;a 2\nmain;b 2".to_string(), true);
+        let baseline = Baseline::new("baseline.txt", &baseline);
+        assert_eq!(baseline.share("all"), 1.0);
+        assert_eq!(baseline.share("main;a"), 0.5);
+        assert_eq!(baseline.share("main;c"), 0.0);
+
+        let fg = FlameGraph::from_string("main;a 3\nmain;b 1\nmain;c 4".to_string(), true);
+        let diff = baseline.compare(&fg);
+        let id = |full_name| fg.get_stack_id_by_full_name(full_name).unwrap();
+        assert_eq!(diff.delta(&id("main")), 0.0);
+        assert_eq!(diff.delta(&id("main;a")), 3.0 / 8.0 - 0.5);
+        assert_eq!(diff.delta(&id("main;b")), 1.0 / 8.0 - 0.5);
+        assert_eq!(diff.delta(&id("main;c")), 0.5);
+
+        // Unchanged stacks are gray, unlike the near white selected frame
+        assert_eq!(diff.color(&id("main"), false), UNCHANGED_COLOR);
+        assert_eq!(diff.color(&id("main"), true), UNCHANGED_COLOR);
+        assert_ne!(UNCHANGED_COLOR, (250, 250, 250));
+        assert_eq!(diff.color(&id("main;c"), false), (255, 0, 0));
+        assert_eq!(diff.color(&id("main;b"), false), (31, 31, 239));
+        assert_eq!(diff.color(&id("main;c"), true), (230, 159, 0));
+        // The smallest changes are tinted nonetheless
+        let fg = FlameGraph::from_string("main;a 101\nmain;b 99\nmain;c 1".to_string(), true);
+        let diff = baseline.compare(&fg);
+        let a = fg.get_stack_id_by_full_name("main;a").unwrap();
+        let (r, g, b) = diff.color(&a, false);
+        assert!(r > g + 20 && g == b);
+    }
+}
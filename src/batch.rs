@@ -0,0 +1,147 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::app::App;
+use crate::command::ControlCommand;
+use crate::flame::{FlameGraph, ParseOptions};
+
+/// Operations applied to every profile of a batch, read from a TOML file such as:
+///
+/// ```toml
+/// commands = ["search json\\.(loads|dumps)", "zoom handle_request"]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchOps {
+    /// Control socket commands run in order on each profile
+    pub commands: Vec<String>,
+}
+
+impl BatchOps {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Could not parse {}", path.display()))
+    }
+
+    fn parse_commands(&self) -> Result<Vec<ControlCommand>> {
+        self.commands
+            .iter()
+            .map(|line| match ControlCommand::parse(line) {
+                Ok(ControlCommand::Quit) => bail!("quit can't be used in batch mode"),
+                Ok(command) => Ok(command),
+                Err(e) => bail!("{}: {}", line, e),
+            })
+            .collect()
+    }
+}
+
+/// Load a profile of the batch, failing if its export would overwrite it.
+fn load(file: &Path, export_path: &Path, options: &ParseOptions) -> Result<FlameGraph> {
+    let same_file = export_path
+        .canonicalize()
+        .is_ok_and(|p| file.canonicalize().is_ok_and(|f| f == p));
+    if same_file {
+        bail!("Refusing to overwrite the input {}", file.display());
+    }
+    let content =
+        std::fs::read(file).with_context(|| format!("Could not read {}", file.display()))?;
+    FlameGraph::from_bytes_with_options(content, options)
+}
+
+/// Run the commands on each profile and export the folded stacks of the resulting zoom to a file
+/// of the same name in `export_dir`. The replies of the commands are written to `out`, prefixed
+/// with the profile, as are the profiles that can't be loaded, which are skipped. Returns whether
+/// all the profiles were loaded and all their commands succeeded.
+pub fn run(
+    ops: &BatchOps,
+    files: &[PathBuf],
+    export_dir: &Path,
+    options: &ParseOptions,
+    out: &mut dyn Write,
+) -> Result<bool> {
+    let commands = ops.parse_commands()?;
+    std::fs::create_dir_all(export_dir)
+        .with_context(|| format!("Could not create {}", export_dir.display()))?;
+    let mut success = true;
+    for file in files {
+        let Some(file_name) = file.file_name() else {
+            success = false;
+            writeln!(out, "{}: error: not a file", file.display())?;
+            continue;
+        };
+        let export_path = export_dir.join(file_name);
+        let flamegraph = match load(file, &export_path, options) {
+            Ok(flamegraph) => flamegraph,
+            Err(e) => {
+                success = false;
+                writeln!(out, "{}: error: {:#}", file.display(), e)?;
+                continue;
+            }
+        };
+        let mut app = App::with_flamegraph(&file.display().to_string(), flamegraph);
+        for (line, command) in ops.commands.iter().zip(commands.iter()) {
+            match app.handle_control_command(command.clone()) {
+                Ok(reply) if reply.is_empty() => {}
+                Ok(reply) => writeln!(out, "{}: {}: {}", file.display(), line, reply)?,
+                Err(e) => {
+                    success = false;
+                    writeln!(out, "{}: {}: error: {}", file.display(), line, e)?;
+                }
+            }
+        }
+        if let Err(e) = app.handle_control_command(ControlCommand::Export(export_path)) {
+            success = false;
+            writeln!(out, "{}: error: {}", file.display(), e)?;
+        }
+    }
+    Ok(success)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch() {
+        let dir = std::env::temp_dir().join(format!("flamelens-batch-{}", std::process::id()));
+        let ops: BatchOps = toml::from_str(
+            "commands = [\"search work\", \"zoom work (long_running.py:8)\", \"zoom nope\"]",
+        )
+        .unwrap();
+        let files = [PathBuf::from("tests/data/py-spy-simple.txt")];
+        let mut out = vec![];
+        let success = run(&ops, &files, &dir, &ParseOptions::default(), &mut out).unwrap();
+        assert!(!success);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "tests/data/py-spy-simple.txt: search work: 656/657 samples (99.85%)\n\
+             tests/data/py-spy-simple.txt: zoom nope: error: no such frame: nope\n"
+        );
+        let exported = FlameGraph::from_string(
+            std::fs::read_to_string(dir.join("py-spy-simple.txt")).unwrap(),
+            true,
+        );
+        assert_eq!(exported.total_count(), 421);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // A missing profile is reported and the next one is still processed
+        let files = [
+            PathBuf::from("tests/data/missing.txt"),
+            PathBuf::from("tests/data/py-spy-simple.txt"),
+        ];
+        let mut out = vec![];
+        let success = run(&ops, &files, &dir, &ParseOptions::default(), &mut out).unwrap();
+        assert!(!success);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("tests/data/missing.txt: error: Could not read"));
+        assert!(dir.join("py-spy-simple.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let ops: BatchOps = toml::from_str("commands = [\"quit\"]").unwrap();
+        assert!(run(&ops, &files, &dir, &ParseOptions::default(), &mut vec![]).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+/// Width (in columns) under which frames are left blank when degraded
+pub const DEGRADED_MIN_LABEL_WIDTH: u16 = 8;
+/// Percent of the flamegraph width under which frames are collapsed when degraded, unless
+/// `min_frame_width` is larger
+pub const DEGRADED_MIN_FRAME_WIDTH: f64 = 0.5;
+
+/// Time allowed for rendering the flamegraph. Once a render takes longer, the next ones are
+/// degraded: search terms aren't highlighted inside frames, narrow frames are left blank and tiny
+/// frames are collapsed.
+#[derive(Debug, Clone, Default)]
+pub struct RenderBudget {
+    pub budget: Option<Duration>,
+    pub degraded: bool,
+    /// Time of the full render that went over the budget
+    full_render_time: Option<Duration>,
+    /// How many times longer a full render takes than a degraded one, measured on the first
+    /// degraded render
+    full_cost_ratio: Option<f64>,
+}
+
+impl RenderBudget {
+    pub fn new(budget: Option<Duration>) -> Self {
+        Self {
+            budget,
+            ..Default::default()
+        }
+    }
+
+    /// Degrade the next renders if this one took too long, or go back to full fidelity once a
+    /// full render would fit in the budget again, estimated from the time of the degraded render
+    pub fn update(&mut self, render_time: Duration) {
+        let Some(budget) = self.budget else {
+            return;
+        };
+        if !self.degraded {
+            if render_time > budget {
+                self.degraded = true;
+                self.full_render_time = Some(render_time);
+                self.full_cost_ratio = None;
+            }
+            return;
+        }
+        let ratio = match (self.full_cost_ratio, self.full_render_time) {
+            (Some(ratio), _) => ratio,
+            (None, Some(full_render_time)) => {
+                let ratio = full_render_time.as_secs_f64() / render_time.as_secs_f64().max(1e-6);
+                // A degraded render is never slower than a full one
+                let ratio = ratio.max(1.0);
+                self.full_cost_ratio = Some(ratio);
+                ratio
+            }
+            (None, None) => 1.0,
+        };
+        if render_time.mul_f64(ratio) <= budget {
+            self.degraded = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let mut budget = RenderBudget::new(Some(Duration::from_millis(40)));
+        budget.update(Duration::from_millis(30));
+        assert!(!budget.degraded);
+        budget.update(Duration::from_millis(50));
+        assert!(budget.degraded);
+        // A full render takes 5 times as long as this degraded one
+        budget.update(Duration::from_millis(10));
+        assert!(budget.degraded);
+        budget.update(Duration::from_millis(9));
+        assert!(budget.degraded);
+        // A full render would now take 40ms
+        budget.update(Duration::from_millis(8));
+        assert!(!budget.degraded);
+
+        let mut budget = RenderBudget::default();
+        budget.update(Duration::from_secs(1));
+        assert!(!budget.degraded);
+    }
+
+    #[test]
+    fn test_update_does_not_flip() {
+        // A full render always over the budget while a degraded one is well within it
+        let mut budget = RenderBudget::new(Some(Duration::from_millis(50)));
+        budget.update(Duration::from_millis(60));
+        for _ in 0..10 {
+            budget.update(Duration::from_millis(10));
+            assert!(budget.degraded);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::flame::{decode_input, split_frames};
+
+/// Frames that stack collapsers and profilers put in place of the frames they dropped
+const TRUNCATION_MARKERS: &[&str] = &["[truncated]", "[...]", "...", "…"];
+
+/// A kind of problem with the number of lines it affects and the first of them (1-based).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Occurrences {
+    pub count: u64,
+    pub first_line: Option<usize>,
+}
+
+impl Occurrences {
+    fn add(&mut self, line_number: usize) {
+        self.count += 1;
+        self.first_line.get_or_insert(line_number);
+    }
+}
+
+/// Statistics and problems of a folded stacks file, as found by [`check`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckReport {
+    pub lines: u64,
+    pub comment_lines: u64,
+    pub blank_lines: u64,
+    /// Distinct stacks among the valid lines
+    pub distinct_stacks: u64,
+    /// Valid lines repeating the stack of a previous line, which could be merged into it
+    pub duplicate_lines: u64,
+    pub total_samples: u64,
+    pub max_depth: usize,
+    pub invalid_utf8_sequences: u64,
+    pub nul_bytes: u64,
+    /// Lines without a stack followed by a count
+    pub invalid_lines: Occurrences,
+    /// Counts that don't fit in 64 bits, or make the total overflow
+    pub count_overflows: Occurrences,
+    pub empty_frames: Occurrences,
+    /// Stacks with the same frame twice in a row, which recursion makes as well as broken
+    /// collapsers, so only noted rather than counted as a problem
+    pub repeated_adjacent_frames: Occurrences,
+    /// Frame names with control characters, replacement characters or surrounding whitespace
+    pub suspicious_frame_names: Occurrences,
+    pub truncation_markers: Occurrences,
+}
+
+impl CheckReport {
+    /// Problems that make the file misrepresent the profile, by name
+    pub fn problems(&self) -> Vec<(&'static str, Occurrences)> {
+        [
+            ("invalid lines", self.invalid_lines),
+            ("count overflows", self.count_overflows),
+            ("stacks with empty frames", self.empty_frames),
+            (
+                "stacks with suspicious frame names",
+                self.suspicious_frame_names,
+            ),
+            ("truncated stacks", self.truncation_markers),
+        ]
+        .into_iter()
+        .filter(|(_, occurrences)| occurrences.count > 0)
+        .collect()
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.problems().is_empty() && self.invalid_utf8_sequences == 0 && self.nul_bytes == 0
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Lines: {} ({} comments, {} blank)",
+            self.lines, self.comment_lines, self.blank_lines
+        )?;
+        writeln!(
+            f,
+            "Stacks: {} distinct, {} duplicate lines could be merged",
+            self.distinct_stacks, self.duplicate_lines
+        )?;
+        writeln!(f, "Samples: {}", self.total_samples)?;
+        writeln!(f, "Max depth: {}", self.max_depth)?;
+        if self.invalid_utf8_sequences > 0 {
+            writeln!(f, "{} invalid UTF-8 sequences", self.invalid_utf8_sequences)?;
+        }
+        if self.nul_bytes > 0 {
+            writeln!(f, "{} NUL bytes", self.nul_bytes)?;
+        }
+        if self.repeated_adjacent_frames.count > 0 {
+            writeln!(
+                f,
+                "{} stacks with repeated adjacent frames, as recursive calls make \
+                them (first at line {})",
+                self.repeated_adjacent_frames.count,
+                self.repeated_adjacent_frames.first_line.unwrap_or_default()
+            )?;
+        }
+        for (name, occurrences) in self.problems() {
+            write!(f, "{} {}", occurrences.count, name)?;
+            match occurrences.first_line {
+                Some(line) => writeln!(f, " (first at line {})", line)?,
+                None => writeln!(f)?,
+            }
+        }
+        if self.is_ok() {
+            writeln!(f, "No problems found")?;
+        }
+        Ok(())
+    }
+}
+
+fn is_suspicious_frame_name(frame: &str) -> bool {
+    frame != frame.trim()
+        || frame
+            .chars()
+            .any(|c| (c.is_control() && c != '\t') || c == char::REPLACEMENT_CHARACTER)
+}
+
+fn is_truncation_marker(frame: &str) -> bool {
+    TRUNCATION_MARKERS.contains(&frame)
+}
+
+/// Validate folded stacks, checking more than the parser of the flamegraph does.
+pub fn check(buf: Vec<u8>) -> CheckReport {
+    let (content, diagnostics) = decode_input(buf);
+    let mut report = CheckReport {
+        invalid_utf8_sequences: diagnostics.invalid_utf8_sequences,
+        nul_bytes: diagnostics.nul_bytes,
+        ..Default::default()
+    };
+    let mut stacks = HashSet::new();
+    for (i, line) in content.lines().enumerate() {
+        let line_number = i + 1;
+        report.lines += 1;
+        if line.starts_with('#') {
+            report.comment_lines += 1;
+            continue;
+        }
+        if line.trim().is_empty() {
+            report.blank_lines += 1;
+            continue;
+        }
+        let Some((stack, count)) = line.rsplit_once(' ').filter(|(stack, count)| {
+            !stack.is_empty() && !count.is_empty() && count.bytes().all(|b| b.is_ascii_digit())
+        }) else {
+            report.invalid_lines.add(line_number);
+            continue;
+        };
+        match count
+            .parse::<u64>()
+            .ok()
+            .and_then(|count| report.total_samples.checked_add(count))
+        {
+            Some(total) => report.total_samples = total,
+            None => report.count_overflows.add(line_number),
+        }
+        if !stacks.insert(stack) {
+            report.duplicate_lines += 1;
+        }
+        let frames = split_frames(stack).collect::<Vec<_>>();
+        report.max_depth = report.max_depth.max(frames.len());
+        if frames.iter().any(|frame| frame.is_empty()) {
+            report.empty_frames.add(line_number);
+        }
+        if frames.windows(2).any(|w| !w[0].is_empty() && w[0] == w[1]) {
+            report.repeated_adjacent_frames.add(line_number);
+        }
+        if frames.iter().any(|frame| is_suspicious_frame_name(frame)) {
+            report.suspicious_frame_names.add(line_number);
+        }
+        if frames.iter().any(|frame| is_truncation_marker(frame)) {
+            report.truncation_markers.add(line_number);
+        }
+    }
+    report.distinct_stacks = stacks.len() as u64;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let report = check(std::fs::read("tests/data/py-spy-simple.txt").unwrap());
+        assert!(report.is_ok());
+        assert_eq!(report.total_samples, 657);
+
+        // Recursion is not a problem
+        let report = check(std::fs::read("tests/data/recursive.txt").unwrap());
+        assert!(report.repeated_adjacent_frames.count > 0);
+        assert!(report.is_ok());
+
+        let content = "# comment\n\
+            main;a 1\n\
+            main;a 2\n\
+            main;;b 1\n\
+            main;c;c 1\n\
+            main;[truncated];d 1\n\
+            main; e 1\n\
+            main;f 99999999999999999999\n\
+            no count\n\
+            \n";
+        let report = check(content.as_bytes().to_vec());
+        let at = |count, first_line| Occurrences {
+            count,
+            first_line: Some(first_line),
+        };
+        assert_eq!(
+            report,
+            CheckReport {
+                lines: 10,
+                comment_lines: 1,
+                blank_lines: 1,
+                distinct_stacks: 6,
+                duplicate_lines: 1,
+                total_samples: 7,
+                max_depth: 3,
+                invalid_lines: at(1, 9),
+                count_overflows: at(1, 8),
+                empty_frames: at(1, 4),
+                repeated_adjacent_frames: at(1, 5),
+                suspicious_frame_names: at(1, 7),
+                truncation_markers: at(1, 6),
+                ..Default::default()
+            }
+        );
+        assert!(!report.is_ok());
+        assert!(report
+            .to_string()
+            .contains("1 count overflows (first at line 8)"));
+    }
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+/// Commands accepted one per line on the control socket and in batch files.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlCommand {
+    /// Highlight frames matching a regex; an empty pattern clears the search
+    Search(String),
+    /// Zoom in on the heaviest frame with the given name (or full stack if it contains ';')
+    Zoom(String),
+    /// Reset selection, zoom and search
+    Reset,
+    /// Write the folded stacks of the current zoom to a file
+    Export(PathBuf),
+    /// Exit flamelens
+    Quit,
+}
+
+impl ControlCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+        let require_argument = |argument: &str| {
+            if argument.is_empty() {
+                Err(format!("{} requires an argument", command))
+            } else {
+                Ok(argument.to_string())
+            }
+        };
+        match command {
+            "search" => Ok(ControlCommand::Search(argument.to_string())),
+            "zoom" => require_argument(argument).map(ControlCommand::Zoom),
+            "reset" => Ok(ControlCommand::Reset),
+            "export" => require_argument(argument).map(|a| ControlCommand::Export(a.into())),
+            "quit" => Ok(ControlCommand::Quit),
+            "" => Err("empty command".to_string()),
+            _ => Err(format!("unknown command: {}", command)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ControlCommand::parse("search  foo.*bar "),
+            Ok(ControlCommand::Search("foo.*bar".to_string()))
+        );
+        assert_eq!(
+            ControlCommand::parse("search"),
+            Ok(ControlCommand::Search("".to_string()))
+        );
+        assert_eq!(
+            ControlCommand::parse("zoom work (long_running.py:8)"),
+            Ok(ControlCommand::Zoom("work (long_running.py:8)".to_string()))
+        );
+        assert_eq!(
+            ControlCommand::parse("export /tmp/out.txt"),
+            Ok(ControlCommand::Export("/tmp/out.txt".into()))
+        );
+        assert_eq!(ControlCommand::parse("quit"), Ok(ControlCommand::Quit));
+        assert!(ControlCommand::parse("zoom").is_err());
+        assert!(ControlCommand::parse("dance").is_err());
+    }
+}
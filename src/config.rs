@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use toml_edit::DocumentMut;
+
+use crate::flame::TopColumn;
+use crate::keys::{Action, KeyList};
+use crate::macros::MacroStep;
+use crate::palette::{ColorDepth, Palette, Separators};
+use crate::rewrite::{RewritePreset, RewriteRule};
+use crate::text::Truncation;
+use crate::theme::{ThemeColors, ThemeName};
+
+/// User configuration read from `~/.config/flamelens/config.toml`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Color palette of the flamegraph
+    pub palette: Option<Palette>,
+    /// How sibling frames are told apart
+    pub separators: Option<Separators>,
+    /// Percent of the flamegraph width under which frames are collapsed into a placeholder
+    pub min_frame_width: Option<f64>,
+    /// Animate zooming in and out with a few frames interpolating the widths of the frames
+    pub zoom_animation: Option<bool>,
+    /// Most frames drawn per second
+    pub max_fps: Option<u32>,
+    /// Milliseconds the flamegraph can take to render before the rendering is degraded
+    pub render_budget: Option<u64>,
+    /// Colors of the terminal: truecolor, 256 or 16
+    pub colors: Option<ColorDepth>,
+    /// Interface colors for dark or light terminals
+    pub theme: Option<ThemeName>,
+    /// Interface colors replacing the ones of the theme
+    pub theme_colors: ThemeColors,
+    /// Part of the names too long for their frame left out: end or middle
+    pub truncate: Option<Truncation>,
+    /// Columns of the Top view shown after the total and own counts
+    pub top_columns: Vec<TopColumn>,
+    /// Keys bound to actions, replacing the default keys of those actions
+    pub keys: HashMap<Action, KeyList>,
+    /// Regexes of functions to always show the counts of
+    pub watch: Vec<String>,
+    /// Rules rewriting frame names while parsing, such as `s/<.*>//`
+    pub rewrite: Vec<RewriteRule>,
+    /// Presets of rewrite rules, applied before `rewrite`
+    pub rewrite_presets: Vec<RewritePreset>,
+    /// Recorded macros by register
+    pub macros: HashMap<String, Vec<MacroStep>>,
+    /// Keep the searches in a file next to the config file, to recall them in later sessions
+    pub save_search_history: bool,
+    /// Save the zoom, palette, pruned functions and sort of each profile on quit, to restore them
+    /// when the same profile is opened again
+    pub save_profile_prefs: bool,
+    /// Whether the guided tour was shown, which happens on the first run
+    pub tutorial_seen: bool,
+}
+
+impl Config {
+    /// Location of the config file, respecting `XDG_CONFIG_HOME`.
+    pub fn path() -> Option<PathBuf> {
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_dir.join("flamelens").join("config.toml"))
+    }
+
+    /// Load the config file. A missing file results in the default config.
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read config file {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Could not parse config file {}", path.display()))
+    }
+
+    /// Update a single top-level key in the config file, keeping the other keys, the comments and
+    /// the formatting as they are.
+    pub fn persist<T: Serialize>(key: &str, value: &T) -> Result<()> {
+        let path = Self::path().context("Could not determine config file location")?;
+        let content = if path.exists() {
+            std::fs::read_to_string(&path)?
+        } else {
+            String::new()
+        };
+        let content = set_key(&content, key, value)
+            .with_context(|| format!("Could not update config file {}", path.display()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, content)
+            .with_context(|| format!("Could not write config file {}", path.display()))
+    }
+}
+
+/// Set the top-level `key` of the TOML document `content` to `value`
+fn set_key<T: Serialize>(content: &str, key: &str, value: &T) -> Result<String> {
+    let mut document = content.parse::<DocumentMut>()?;
+    // Serialized as if it were alone in the file, so that maps become tables
+    let mut table = toml::Table::new();
+    table.insert(key.to_string(), toml::Value::try_from(value)?);
+    let mut serialized = toml::to_string(&table)?.parse::<DocumentMut>()?;
+    let mut item = serialized
+        .remove(key)
+        .context("Could not serialize the value")?;
+    if let Some(table) = item.as_table_mut() {
+        match document.get(key).and_then(|item| item.as_table()) {
+            // Keep the table where it was, with the comments above it
+            Some(existing) => {
+                if let Some(position) = existing.position() {
+                    table.set_position(position);
+                }
+                *table.decor_mut() = existing.decor().clone();
+            }
+            // Put it after the other tables
+            None => {
+                let position = document
+                    .iter()
+                    .filter_map(|(_, item)| item.as_table()?.position())
+                    .max()
+                    .map_or(0, |position| position + 1);
+                table.set_position(position);
+                if !document.is_empty() {
+                    table.decor_mut().set_prefix("\n");
+                }
+            }
+        }
+    }
+    match document.get_mut(key) {
+        Some(existing) if existing.is_value() && item.is_value() => {
+            // Keep the comments and spacing around the value
+            let decor = existing.as_value().unwrap().decor().clone();
+            let value = item.as_value_mut().unwrap();
+            *value.decor_mut() = decor;
+            *existing = item;
+        }
+        _ => {
+            document.insert(key, item);
+        }
+    }
+    Ok(document.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_key() {
+        let content = "\
+# Colors
+palette = \"hot\"  # the default one is too bright
+
+[keys]
+quit = [\"q\"]
+
+[theme_colors]
+accent = \"#ff8800\"
+";
+        let content = set_key(content, "palette", &Palette::Module).unwrap();
+        assert_eq!(
+            content,
+            "\
+# Colors
+palette = \"module\"  # the default one is too bright
+
+[keys]
+quit = [\"q\"]
+
+[theme_colors]
+accent = \"#ff8800\"
+"
+        );
+        let macros = HashMap::from([("a".to_string(), vec!["quit".to_string()])]);
+        let content = set_key(&content, "macros", &macros).unwrap();
+        assert!(content.ends_with("accent = \"#ff8800\"\n\n[macros]\na = [\"quit\"]\n"));
+        let macros: HashMap<String, Vec<String>> = HashMap::from([("b".to_string(), vec![])]);
+        let content = set_key(&content, "macros", &macros).unwrap();
+        assert!(content.ends_with("accent = \"#ff8800\"\n\n[macros]\nb = []\n"));
+        assert!(content.starts_with("# Colors\n"));
+
+        assert_eq!(
+            set_key("", "tutorial_seen", &true).unwrap(),
+            "tutorial_seen = true\n"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+use anyhow::{bail, Context};
+
+use crate::app::AppResult;
+use crate::command::ControlCommand;
+use crate::event::Event;
+
+/// A command together with the channel to send the reply to.
+#[derive(Debug, Clone)]
+pub struct ControlRequest {
+    pub command: ControlCommand,
+    pub reply: mpsc::Sender<Result<String, String>>,
+}
+
+/// Listens on a Unix socket and forwards commands to the main loop as [`Event::Command`].
+#[derive(Debug)]
+pub struct ControlSocket {
+    path: PathBuf,
+}
+
+impl ControlSocket {
+    pub fn bind(path: &Path, sender: mpsc::Sender<Event>) -> AppResult<Self> {
+        // Remove a stale socket left behind by a previous instance
+        if path.exists() && UnixStream::connect(path).is_err() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                thread::spawn(move || Self::handle_client(stream, sender));
+            }
+        });
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn handle_client(stream: UnixStream, sender: mpsc::Sender<Event>) {
+        let Ok(mut writer) = stream.try_clone() else {
+            return;
+        };
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match ControlCommand::parse(&line) {
+                Ok(command) => {
+                    let (reply_sender, reply_receiver) = mpsc::channel();
+                    let request = ControlRequest {
+                        command,
+                        reply: reply_sender,
+                    };
+                    if sender.send(Event::Command(request)).is_err() {
+                        break;
+                    }
+                    reply_receiver
+                        .recv()
+                        .unwrap_or_else(|_| Err("flamelens exited".to_string()))
+                }
+                Err(e) => Err(e),
+            };
+            let response = match reply {
+                Ok(message) if message.is_empty() => "ok\n".to_string(),
+                Ok(message) => format!("ok {}\n", message),
+                Err(message) => format!("error {}\n", message),
+            };
+            if writer.write_all(response.as_bytes()).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Send a single command to a running instance and return the message of its reply.
+pub fn send(path: &Path, command: &str) -> anyhow::Result<String> {
+    let stream = UnixStream::connect(path)
+        .with_context(|| format!("Could not connect to {}", path.display()))?;
+    let mut writer = stream.try_clone()?;
+    writer.write_all(format!("{}\n", command.trim()).as_bytes())?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    let reply = reply.trim_end();
+    match reply.split_once(' ').unwrap_or((reply, "")) {
+        ("ok", message) => Ok(message.to_string()),
+        ("error", message) => bail!("{}", message),
+        _ => bail!("Unexpected reply: {}", reply),
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
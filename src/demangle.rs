@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use crate::flame::{split_frames, FRAME_DELIMITER};
+
+/// Whether `c` can be part of a mangled symbol. Rust legacy symbols use `$` and `.` for escapes
+/// and suffixes.
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '.'
+}
+
+/// Demangle a Rust (legacy `_ZN...E` or v0 `_R...`) or C++ (`_Z...`) symbol, without the hashes
+/// of Rust symbols
+pub fn demangle_symbol(symbol: &str) -> Option<String> {
+    if let Ok(demangled) = rustc_demangle::try_demangle(symbol) {
+        return Some(format!("{:#}", demangled));
+    }
+    if !symbol.trim_start_matches('_').starts_with('Z') {
+        return None;
+    }
+    cpp_demangle::Symbol::new(symbol)
+        .ok()?
+        .demangle(&cpp_demangle::DemangleOptions::default())
+        .ok()
+}
+
+/// Frame with the mangled symbol at its start demangled, keeping what follows it such as an
+/// offset (`+0x1f`) or a module (` [libfoo.so]`)
+pub fn demangle_frame(frame: &str) -> Option<String> {
+    let end = frame
+        .find(|c: char| !is_symbol_char(c))
+        .unwrap_or(frame.len());
+    let demangled = demangle_symbol(&frame[..end])?;
+    Some(format!("{}{}", demangled, &frame[end..]))
+}
+
+/// Demangle the frames of "frame;frame count" lines. Returns the new lines and the mangled name of
+/// each demangled frame, by demangled name.
+pub fn demangle_frames(content: &str) -> (String, HashMap<String, String>) {
+    let mut out = String::with_capacity(content.len());
+    let mut mangled_names = HashMap::new();
+    for line in content.split_inclusive('\n') {
+        let stack = line
+            .trim_end_matches(['\n', '\r'])
+            .rsplit_once(' ')
+            .filter(|_| !line.starts_with('#'))
+            .map(|(stack, _count)| stack);
+        let Some(stack) = stack else {
+            out.push_str(line);
+            continue;
+        };
+        for (i, frame) in split_frames(stack).enumerate() {
+            if i > 0 {
+                out.push(FRAME_DELIMITER);
+            }
+            match demangle_frame(frame) {
+                Some(demangled) => {
+                    out.push_str(&demangled);
+                    mangled_names
+                        .entry(demangled)
+                        .or_insert_with(|| frame.to_string());
+                }
+                None => out.push_str(frame),
+            }
+        }
+        out.push_str(&line[stack.len()..]);
+    }
+    (out, mangled_names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demangle_frames() {
+        let content = "main;_ZN4core3ptr13drop_in_place17h0123456789abcdefE+0x1f 2\n\
+            _RNvCs1234_7mycrate3foo;_ZN3foo3barEi [libfoo.so];not_mangled 1\n";
+        let (demangled, mangled_names) = demangle_frames(content);
+        assert_eq!(
+            demangled,
+            "main;core::ptr::drop_in_place+0x1f 2\n\
+             mycrate::foo;foo::bar(int) [libfoo.so];not_mangled 1\n"
+        );
+        assert_eq!(
+            mangled_names["foo::bar(int) [libfoo.so]"],
+            "_ZN3foo3barEi [libfoo.so]"
+        );
+        assert_eq!(mangled_names.len(), 3);
+        assert_eq!(demangle_symbol("_ZN3foo"), None);
+    }
+}
@@ -0,0 +1,6 @@
+/// Name of the demo profile, shown in place of a filename
+pub const DEMO_NAME: &str = "demo";
+
+/// Folded stacks of a small Python web server, compiled in so that there is always a profile to
+/// try flamelens on (`flamelens --demo`) and to refer to in bug reports
+pub const DEMO_PROFILE: &str = include_str!("demo.txt");
@@ -0,0 +1,72 @@
+use std::io;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+/// Editor used when neither `VISUAL` nor `EDITOR` is set
+const DEFAULT_EDITOR: &str = "vi";
+
+/// A source file to open in the user's editor, at a line if known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditorRequest {
+    pub file: String,
+    pub line: Option<usize>,
+}
+
+impl EditorRequest {
+    /// Command opening the file with `editor`, which can have arguments (`code -w`), as in
+    /// `$EDITOR +<line> <file>`
+    pub fn command(&self, editor: &str) -> Command {
+        let mut words = editor.split_whitespace();
+        let mut command = Command::new(words.next().unwrap_or(DEFAULT_EDITOR));
+        command.args(words);
+        if let Some(line) = self.line {
+            command.arg(format!("+{}", line));
+        }
+        command.arg(&self.file);
+        command
+    }
+
+    /// Open the file with `$VISUAL` or `$EDITOR` and wait for the editor to exit. The terminal is
+    /// expected to be out of the TUI.
+    pub fn run(&self) -> io::Result<ExitStatus> {
+        if !Path::new(&self.file).exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found", self.file),
+            ));
+        }
+        let editor = ["VISUAL", "EDITOR"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|editor| !editor.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+        self.command(&editor).status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let request = EditorRequest {
+            file: "long_running.py".to_string(),
+            line: Some(8),
+        };
+        let command = request.command("code -w");
+        assert_eq!(command.get_program(), "code");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["-w", "+8", "long_running.py"]
+        );
+        let request = EditorRequest {
+            line: None,
+            ..request
+        };
+        assert_eq!(
+            request.command("vim").get_args().collect::<Vec<_>>(),
+            ["long_running.py"]
+        );
+    }
+}
@@ -1,11 +1,17 @@
 use crate::app::AppResult;
+#[cfg(unix)]
+use crate::control::ControlRequest;
 use crossterm::event::{self, Event as CrosstermEvent, KeyEvent, KeyEventKind, MouseEvent};
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::{Duration, Instant};
 
+/// How often a paused event handler checks whether to resume
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 /// Terminal events.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum Event {
     /// Terminal tick.
     Tick,
@@ -15,6 +21,9 @@ pub enum Event {
     Mouse(MouseEvent),
     /// Terminal resize.
     Resize(u16, u16),
+    /// Command received on the control socket.
+    #[cfg(unix)]
+    Command(ControlRequest),
 }
 
 /// Terminal event handler.
@@ -27,6 +36,8 @@ pub struct EventHandler {
     receiver: mpsc::Receiver<Event>,
     /// Event handler thread.
     handler: thread::JoinHandle<()>,
+    /// Whether the thread should stop reading the terminal, and whether it did
+    paused: Arc<(AtomicBool, AtomicBool)>,
 }
 
 impl EventHandler {
@@ -34,11 +45,20 @@ impl EventHandler {
     pub fn new(tick_rate: u64) -> Self {
         let tick_rate = Duration::from_millis(tick_rate);
         let (sender, receiver) = mpsc::channel();
+        let paused = Arc::new((AtomicBool::new(false), AtomicBool::new(false)));
         let handler = {
             let sender = sender.clone();
+            let paused = paused.clone();
             thread::spawn(move || {
                 let mut last_tick = Instant::now();
                 loop {
+                    let (pause_requested, is_paused) = &*paused;
+                    if pause_requested.load(Ordering::SeqCst) {
+                        is_paused.store(true, Ordering::SeqCst);
+                        thread::sleep(PAUSED_POLL_INTERVAL);
+                        continue;
+                    }
+                    is_paused.store(false, Ordering::SeqCst);
                     let timeout = tick_rate
                         .checked_sub(last_tick.elapsed())
                         .unwrap_or(tick_rate);
@@ -72,9 +92,29 @@ impl EventHandler {
             sender,
             receiver,
             handler,
+            paused,
+        }
+    }
+
+    /// Stop reading the terminal, so that another program can, and wait until the thread did.
+    pub fn pause(&self) {
+        let (pause_requested, is_paused) = &*self.paused;
+        pause_requested.store(true, Ordering::SeqCst);
+        while !is_paused.load(Ordering::SeqCst) {
+            thread::sleep(PAUSED_POLL_INTERVAL);
         }
     }
 
+    /// Read the terminal again after [`EventHandler::pause`].
+    pub fn resume(&self) {
+        self.paused.0.store(false, Ordering::SeqCst);
+    }
+
+    /// Sender that other threads can use to inject events.
+    pub fn sender(&self) -> mpsc::Sender<Event> {
+        self.sender.clone()
+    }
+
     /// Receive the next event from the handler thread.
     ///
     /// This function will always block the current thread if
@@ -82,4 +122,13 @@ impl EventHandler {
     pub fn next(&self) -> AppResult<Event> {
         Ok(self.receiver.recv()?)
     }
+
+    /// Receive the next event if one comes within `timeout`.
+    pub fn next_timeout(&self, timeout: Duration) -> AppResult<Option<Event>> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(event) => Ok(Some(event)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
 }
@@ -1,10 +1,38 @@
-use std::collections::{HashMap, HashSet};
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 
-use serde::Serialize;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::demangle::demangle_frames;
+use crate::flamechart::format_duration;
+use crate::palette::module_of;
+use crate::parsers::{
+    self, cpuprofile::CpuProfileOptions, ghc_prof::GhcProfOptions, jfr::JfrOptions,
+    massif::MassifOptions, perf_script::PerfScriptOptions, InputFormat, TimedSample,
+};
+use crate::rewrite::{rewrite_frames, RewriteRule};
 
 pub type StackIdentifier = usize;
 pub static ROOT: &str = "all";
 pub static ROOT_ID: usize = 0;
+/// Default cap on the length (in characters) of a frame name
+pub const DEFAULT_MAX_FRAME_NAME_LENGTH: usize = 1024;
+/// Limit (in bytes) of the full text kept for frame names that were capped
+pub const FULL_FRAME_NAME_LIMIT: usize = 64 * 1024;
+/// Separator of the frames of folded stacks
+pub const FRAME_DELIMITER: char = ';';
+/// Character making the delimiter following it part of a frame name
+pub const FRAME_DELIMITER_ESCAPE: char = '\\';
+/// A delimiter that is part of a frame name
+const ESCAPED_FRAME_DELIMITER: &str = "\\;";
+/// Separator of the `key=value` annotations following a frame name, as in `func#cpu=3#numa=1`
+pub const ANNOTATION_DELIMITER: char = '#';
+
+/// Number of samples of each value of each annotation key of a frame
+pub type FrameAnnotations = BTreeMap<String, BTreeMap<String, u64>>;
 
 #[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct StackInfo {
@@ -19,6 +47,32 @@ pub struct StackInfo {
     pub level: usize,
     pub width_factor: f64,
     pub hit: bool,
+    /// Source location of the frame, if its name has one
+    pub location: Option<FrameLocation>,
+}
+
+/// Source location of a frame named like `work (long_running.py:8)`: where the file is in the data
+/// of the flamegraph, and the line if there is one.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct FrameLocation {
+    pub file_start: usize,
+    pub file_end: usize,
+    pub line: Option<usize>,
+}
+
+impl FrameLocation {
+    /// Location of the frame named `data[start_index..end_index]`
+    fn parse(data: &str, start_index: usize, end_index: usize) -> Option<Self> {
+        let name = &data[start_index..end_index];
+        let (file, line) = parse_frame_location(name)?;
+        // The file always follows the " (" found by parse_frame_location
+        let file_start = start_index + name.rfind(" (")? + 2;
+        Some(Self {
+            file_start,
+            file_end: file_start + file.len(),
+            line,
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -46,6 +100,41 @@ impl SearchPattern {
     }
 }
 
+/// How the text typed in the search prompt is turned into a regex
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchModes {
+    /// Match regardless of the case of the letters
+    pub ignore_case: bool,
+    /// Match the text as it is typed, for names full of regex characters such as `ns::f<T>()`
+    pub literal: bool,
+}
+
+impl SearchModes {
+    /// Regex searching for `text` in these modes
+    pub fn to_regex(self, text: &str) -> String {
+        let pattern = if self.literal {
+            regex::escape(text)
+        } else {
+            text.to_string()
+        };
+        if self.ignore_case {
+            format!("(?i){}", pattern)
+        } else {
+            pattern
+        }
+    }
+
+    /// Short description of the modes turned on, if any
+    pub fn label(self) -> Option<String> {
+        let labels = [(self.ignore_case, "ignore case"), (self.literal, "literal")]
+            .iter()
+            .filter(|(on, _)| *on)
+            .map(|(_, label)| *label)
+            .collect::<Vec<_>>();
+        (!labels.is_empty()).then(|| labels.join(", "))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Hits {
     coverage_count: u64,
@@ -56,6 +145,29 @@ pub struct Hits {
 pub struct Count {
     pub total: u64,
     pub own: u64,
+    /// Frames of the entry, one per distinct stack leading to it
+    #[serde(skip)]
+    pub frames: u64,
+    /// Depth of the frames of the entry times their samples, for the average depth
+    #[serde(skip)]
+    depth_samples: u64,
+    /// Samples of the frames of the entry, counting nested frames again
+    #[serde(skip)]
+    frame_samples: u64,
+}
+
+impl Count {
+    /// Add a frame at `level` with `count` samples to the depth of the entry
+    fn add_depth(&mut self, level: usize, count: u64) {
+        self.depth_samples += level as u64 * count;
+        self.frame_samples += count;
+    }
+
+    /// Depth of the frames of the entry, averaged over their samples: low for the functions
+    /// called from near the root, high for the utility functions called from deep stacks
+    pub fn average_depth(&self) -> f64 {
+        self.depth_samples as f64 / self.frame_samples.max(1) as f64
+    }
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -65,10 +177,61 @@ pub struct CountEntry {
     pub visible: bool,
 }
 
-#[derive(Serialize, Debug, Clone, Eq, PartialEq, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Copy)]
 pub enum SortColumn {
     Total,
     Own,
+    Name,
+}
+
+/// Optional column of the Top view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TopColumn {
+    /// Number of distinct stacks the entry appears in
+    Stacks,
+    /// Average depth of the frames of the entry
+    Depth,
+}
+
+/// What the entries of the Top view count the samples of
+#[derive(Debug, Clone, Eq, PartialEq, Copy, Default)]
+pub enum Aggregation {
+    #[default]
+    Function,
+    /// Source file of the frames, or their name when they have none
+    File,
+    /// Top-level module, package or crate of the frames (see [`module_of`])
+    Module,
+}
+
+impl Aggregation {
+    pub fn next(&self) -> Self {
+        match self {
+            Aggregation::Function => Aggregation::File,
+            Aggregation::File => Aggregation::Module,
+            Aggregation::Module => Aggregation::Function,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Aggregation::Function => "Function",
+            Aggregation::File => "File",
+            Aggregation::Module => "Module",
+        }
+    }
+
+    /// Name of the entry counting the samples of the frame `short_name`
+    pub fn key<'a>(&self, short_name: &'a str) -> &'a str {
+        match self {
+            Aggregation::Function => short_name,
+            Aggregation::File => {
+                parse_frame_location(short_name).map_or(short_name, |(file, _)| file)
+            }
+            Aggregation::Module => module_of(short_name),
+        }
+    }
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -76,14 +239,20 @@ pub struct Ordered {
     pub entries: Vec<CountEntry>,
     pub num_rows: usize,
     pub sorted_column: SortColumn,
+    /// Smallest counts or first names first
+    #[serde(skip)]
+    pub ascending: bool,
+    #[serde(skip)]
+    pub aggregation: Aggregation,
     pub search_pattern_ignored_because_of_no_match: bool,
 }
 
 impl Ordered {
-    pub fn set_search_pattern(&mut self, p: &SearchPattern) {
+    /// Only show the entries whose name `is_match` if the pattern was typed
+    pub fn set_search_pattern(&mut self, p: &SearchPattern, is_match: impl Fn(&str) -> bool) {
         if p.is_manual {
             self.entries.iter_mut().for_each(|entry| {
-                entry.visible = p.re.is_match(&entry.name);
+                entry.visible = is_match(&entry.name);
             });
             self.num_rows = self.entries.iter().filter(|entry| entry.visible).count();
             if self.num_rows == 0 {
@@ -103,11 +272,25 @@ impl Ordered {
         self.search_pattern_ignored_because_of_no_match = false;
     }
 
+    /// Sort by `column` in its natural order: the largest counts first, or the names in
+    /// alphabetical order
     pub fn set_sort_column(&mut self, column: SortColumn) {
-        if column == self.sorted_column {
+        if column != self.sorted_column {
+            self.sort(column, column == SortColumn::Name);
+        }
+    }
+
+    /// Sort by the same column the other way around
+    pub fn toggle_sort_direction(&mut self) {
+        self.sort(self.sorted_column, !self.ascending);
+    }
+
+    pub fn sort(&mut self, column: SortColumn, ascending: bool) {
+        if column == self.sorted_column && ascending == self.ascending {
             return;
         }
         self.sorted_column = column;
+        self.ascending = ascending;
         match column {
             SortColumn::Total => {
                 self.entries
@@ -117,8 +300,13 @@ impl Ordered {
                 self.entries
                     .sort_by_key(|entry| (entry.count.own, entry.name.clone()));
             }
+            SortColumn::Name => {
+                self.entries.sort_by(|a, b| a.name.cmp(&b.name));
+            }
+        }
+        if !ascending {
+            self.entries.reverse();
         }
-        self.entries.reverse();
     }
 }
 
@@ -130,10 +318,524 @@ pub struct FlameGraph {
     pub ordered_stacks: Ordered,
     hits: Option<Hits>,
     sorted: bool,
+    diagnostics: InputDiagnostics,
+    full_frame_names: HashMap<String, String>,
+    /// Annotations of the frames, by full name of the stack
+    annotations: HashMap<String, FrameAnnotations>,
+    /// Stacks as written with their annotations and their counts, by full name of the stack
+    annotated_stacks: HashMap<String, Vec<(String, u64)>>,
+    /// Mangled names of the frames that were demangled, by demangled name
+    mangled_names: HashMap<String, String>,
+    /// Names of the frames written with escaped delimiters, by name as written
+    unescaped_names: HashMap<String, String>,
+    /// What the counts are
+    pub count_unit: CountUnit,
+}
+
+/// Size statistics of a parsed flamegraph.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct FlameGraphStats {
+    pub num_stacks: usize,
+    pub num_levels: usize,
+    pub num_symbols: usize,
+    pub num_input_bytes: usize,
+    pub memory_bytes: usize,
+}
+
+/// Every occurrence of a function in the flamegraph with its immediate callers and callees.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionCalls {
+    /// Stacks ending with the function, i.e. its call sites
+    pub stack_ids: Vec<StackIdentifier>,
+    /// Samples spent in the function, counting recursive calls once
+    pub total: u64,
+    /// Callers with the samples spent in the function when called from them, heaviest first
+    pub callers: Vec<CallEntry>,
+    /// Callees with the samples spent in them when called from the function, heaviest first
+    pub callees: Vec<CallEntry>,
+}
+
+/// A caller or callee of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallEntry {
+    pub name: String,
+    pub count: u64,
+    /// Heaviest stack of the call: the function under the caller, or the callee under the function
+    pub stack_id: StackIdentifier,
+}
+
+/// Source location of a frame name such as `work (long_running.py:8)`, as the file and the line
+/// if present.
+pub fn parse_frame_location(name: &str) -> Option<(&str, Option<usize>)> {
+    let start = name.rfind(" (")?;
+    let location = name[start + 2..].strip_suffix(')')?;
+    match location.rsplit_once(':') {
+        Some((file, line)) if line.parse::<usize>().is_ok() => Some((file, line.parse().ok())),
+        _ if location.contains(['/', '.']) => Some((location, None)),
+        _ => None,
+    }
+}
+
+/// Frame name without the line of its source location, such as `work (long_running.py)` for
+/// `work (long_running.py:8)`. Annotations following the name are kept.
+pub fn strip_line_number(frame: &str) -> Cow<'_, str> {
+    let (name, _) = split_frame_annotations(frame);
+    let Some((file, Some(_))) = parse_frame_location(name) else {
+        return Cow::Borrowed(frame);
+    };
+    let start = name.rfind(" (").unwrap_or_default();
+    Cow::Owned(format!(
+        "{} ({}){}",
+        &name[..start],
+        file,
+        &frame[name.len()..]
+    ))
+}
+
+/// Options controlling how folded stacks are parsed.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Whether to sort the stacks by time spent
+    pub sorted: bool,
+    /// Drop empty frame names
+    pub repair_frames: bool,
+    /// Format of the input to convert from
+    pub format: InputFormat,
+    /// Options for the `perf script` input format
+    pub perf: PerfScriptOptions,
+    /// Options for the `.cpuprofile` input format
+    pub cpuprofile: CpuProfileOptions,
+    /// Options for the JFR input format
+    pub jfr: JfrOptions,
+    /// Options for the GHC `.prof` input format
+    pub ghc_prof: GhcProfOptions,
+    /// Options for the massif input format
+    pub massif: MassifOptions,
+    /// What the counts are, when it isn't the unit of the input format
+    pub count_unit: Option<CountUnit>,
+    /// Cap frame names longer than this many characters, keeping a hash for identity
+    pub max_frame_name_length: Option<usize>,
+    /// Keep the full text (up to [`FULL_FRAME_NAME_LIMIT`]) of capped frame names
+    pub keep_full_frame_names: bool,
+    /// Separator of the frames of folded stacks, when it isn't [`FRAME_DELIMITER`]
+    pub delimiter: Option<String>,
+    /// Read the `#key=value` annotations at the end of frame names (`func#cpu=3#numa=1`)
+    pub annotations: bool,
+    /// Keep only the stacks with a frame annotated with this key and value
+    pub annotation_filter: Option<(String, String)>,
+    /// Rules applied in order to every frame name, before the annotations are read
+    pub rewrite: Vec<RewriteRule>,
+    /// Demangle Rust and C++ symbols in frame names, before the rewrite rules are applied
+    pub demangle: bool,
+}
+
+/// What the counts of the stacks are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum CountUnit {
+    /// Samples, or whatever the profiler counts (calls, microseconds)
+    #[default]
+    Samples,
+    /// Bytes of memory
+    Bytes,
+}
+
+impl CountUnit {
+    /// A count as a number, or as a size for bytes ("1.5 MiB")
+    pub fn format(&self, count: u64) -> String {
+        match self {
+            CountUnit::Samples => count.to_string(),
+            CountUnit::Bytes => format_bytes(count),
+        }
+    }
+
+    /// A count with its unit, as in "42 samples" or "1.5 MiB"
+    pub fn format_with_unit(&self, count: u64) -> String {
+        match self {
+            CountUnit::Samples => format!("{} samples", count),
+            CountUnit::Bytes => format_bytes(count),
+        }
+    }
+}
+
+/// How counts are written: in their unit, or as the time the samples stand for when they were
+/// taken at `sample_rate` samples per second
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountFormat {
+    pub unit: CountUnit,
+    pub sample_rate: Option<u64>,
+}
+
+impl CountFormat {
+    fn duration(&self, count: u64) -> Option<String> {
+        match (self.unit, self.sample_rate) {
+            (CountUnit::Samples, Some(rate)) if rate > 0 => {
+                Some(format_duration(count.saturating_mul(1_000_000) / rate))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn format(&self, count: u64) -> String {
+        self.duration(count)
+            .unwrap_or_else(|| self.unit.format(count))
+    }
+
+    pub fn format_with_unit(&self, count: u64) -> String {
+        self.duration(count)
+            .unwrap_or_else(|| self.unit.format_with_unit(count))
+    }
+
+    /// Header of a column of counts
+    pub fn label(&self) -> &'static str {
+        match (self.unit, self.sample_rate) {
+            (CountUnit::Samples, Some(_)) => "Time",
+            (CountUnit::Samples, None) => "Samples",
+            (CountUnit::Bytes, _) => "Bytes",
+        }
+    }
+}
+
+/// A size in bytes with a binary unit, as in "1.5 MiB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Problems detected in the input while parsing.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct InputDiagnostics {
+    pub lines_with_empty_frames: u64,
+    pub dropped_lines: u64,
+    pub invalid_utf8_sequences: u64,
+    pub nul_bytes: u64,
+    pub capped_frame_names: u64,
+}
+
+impl InputDiagnostics {
+    pub fn has_frame_issues(&self) -> bool {
+        self.lines_with_empty_frames > 0
+    }
+
+    pub fn has_encoding_issues(&self) -> bool {
+        self.invalid_utf8_sequences > 0 || self.nul_bytes > 0
+    }
+
+    pub fn has_issues(&self) -> bool {
+        self.has_frame_issues() || self.has_encoding_issues() || self.capped_frame_names > 0
+    }
+
+    /// Human readable summary of the detected problems, if any.
+    pub fn summary(&self, repaired: bool) -> Option<String> {
+        if !self.has_issues() {
+            return None;
+        }
+        let mut parts = vec![];
+        if self.invalid_utf8_sequences > 0 {
+            parts.push(format!(
+                "{} invalid UTF-8 sequences",
+                self.invalid_utf8_sequences
+            ));
+        }
+        if self.nul_bytes > 0 {
+            parts.push(format!("{} NUL bytes", self.nul_bytes));
+        }
+        if self.lines_with_empty_frames > 0 {
+            parts.push(format!(
+                "{} stacks with empty frame names",
+                self.lines_with_empty_frames
+            ));
+        }
+        let mut out = if parts.is_empty() {
+            String::new()
+        } else {
+            format!("Input has {}", parts.join(" and "))
+        };
+        if self.has_encoding_issues() {
+            out += " (replaced with U+FFFD)";
+        }
+        if self.has_frame_issues() {
+            if repaired {
+                out += " (repaired";
+                if self.dropped_lines > 0 {
+                    out += format!(", {} empty stacks dropped", self.dropped_lines).as_str();
+                }
+                out += ")";
+            } else {
+                out += "; rerun with --repair-frames to fix";
+            }
+        }
+        if self.capped_frame_names > 0 {
+            if !out.is_empty() {
+                out += ". ";
+            }
+            out += format!(
+                "{} overly long frame names were truncated",
+                self.capped_frame_names
+            )
+            .as_str();
+        }
+        Some(out)
+    }
+}
+
+/// Byte offsets of the delimiters between the frames of a folded stack, skipping the escaped ones
+pub fn frame_delimiters(stack: &str) -> impl Iterator<Item = usize> + '_ {
+    let bytes = stack.as_bytes();
+    bytes.iter().enumerate().filter_map(move |(i, b)| {
+        let escaped = i > 0 && bytes[i - 1] == FRAME_DELIMITER_ESCAPE as u8;
+        (*b == FRAME_DELIMITER as u8 && !escaped).then_some(i)
+    })
+}
+
+/// Frames of a folded stack, as written (with the escaped delimiters)
+pub fn split_frames(stack: &str) -> impl Iterator<Item = &str> {
+    let mut start = 0;
+    frame_delimiters(stack)
+        .chain(std::iter::once(stack.len()))
+        .map(move |end| {
+            let frame = &stack[start..end];
+            start = end + 1;
+            frame
+        })
+}
+
+/// Frame name as written in folded stacks, with the delimiters it contains escaped
+pub fn escape_frame(name: &str) -> Cow<'_, str> {
+    if name.contains(FRAME_DELIMITER) {
+        Cow::Owned(name.replace(FRAME_DELIMITER, ESCAPED_FRAME_DELIMITER))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Name of a frame as written in folded stacks (the reverse of [`escape_frame`])
+pub fn unescape_frame(frame: &str) -> Cow<'_, str> {
+    if frame.contains(ESCAPED_FRAME_DELIMITER) {
+        Cow::Owned(frame.replace(ESCAPED_FRAME_DELIMITER, &FRAME_DELIMITER.to_string()))
+    } else {
+        Cow::Borrowed(frame)
+    }
+}
+
+/// Folded stack of the frame names `names`, from the root
+pub fn join_frames<S: AsRef<str>>(names: impl IntoIterator<Item = S>) -> String {
+    let mut stack = String::new();
+    for (i, name) in names.into_iter().enumerate() {
+        if i > 0 {
+            stack.push(FRAME_DELIMITER);
+        }
+        stack.push_str(&escape_frame(name.as_ref()));
+    }
+    stack
+}
+
+/// Name and `key=value` annotations of a frame of the extended folded format, such as
+/// `func#cpu=3#numa=1`. A frame with a `#` that isn't followed by annotations is only a name.
+pub fn split_frame_annotations(frame: &str) -> (&str, Vec<(&str, &str)>) {
+    let mut parts = frame.split(ANNOTATION_DELIMITER);
+    let name = parts.next().unwrap_or_default();
+    let annotations = parts
+        .map(|part| part.split_once('=').filter(|(key, _)| !key.is_empty()))
+        .collect::<Option<Vec<_>>>();
+    match annotations {
+        Some(annotations) if !annotations.is_empty() => (name, annotations),
+        _ => (frame, vec![]),
+    }
+}
+
+/// Value of the annotation `key` of the outermost frame of the stack `name` annotated with it
+fn get_annotation_value<'a>(name: &'a str, key: &str) -> Option<&'a str> {
+    split_frames(name).find_map(|frame| {
+        let (_, annotations) = split_frame_annotations(frame);
+        annotations
+            .into_iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value)
+    })
+}
+
+/// Decode raw input bytes into a string without failing on invalid UTF-8. Invalid sequences and
+/// NUL bytes are replaced with U+FFFD and counted in the returned diagnostics.
+pub fn decode_input(buf: Vec<u8>) -> (String, InputDiagnostics) {
+    let mut diagnostics = InputDiagnostics::default();
+    let mut content = match String::from_utf8(buf) {
+        Ok(content) => content,
+        Err(e) => {
+            let buf = e.into_bytes();
+            let mut content = String::with_capacity(buf.len());
+            for chunk in buf.utf8_chunks() {
+                content.push_str(chunk.valid());
+                if !chunk.invalid().is_empty() {
+                    content.push(char::REPLACEMENT_CHARACTER);
+                    diagnostics.invalid_utf8_sequences += 1;
+                }
+            }
+            content
+        }
+    };
+    diagnostics.nul_bytes = content.bytes().filter(|b| *b == 0).count() as u64;
+    if diagnostics.nul_bytes > 0 {
+        content = content.replace('\0', "\u{FFFD}");
+    }
+    (content, diagnostics)
+}
+
+fn is_name_match(
+    re: &regex::Regex,
+    short_name: &str,
+    mangled_names: &HashMap<String, String>,
+) -> bool {
+    re.is_match(short_name)
+        || mangled_names
+            .get(short_name)
+            .is_some_and(|mangled| re.is_match(mangled))
+}
+
+/// Replace frame names longer than `max_length` characters with a truncated prefix followed by an
+/// ellipsis and a hash of the full name, so that distinct long names remain distinct.
+fn cap_frame_names(
+    content: String,
+    max_length: usize,
+    mut full_frame_names: Option<&mut HashMap<String, String>>,
+    diagnostics: &mut InputDiagnostics,
+) -> String {
+    // Fast path: nothing to do unless some line is long enough to contain a long frame name
+    if !content.lines().any(|line| line.len() > max_length) {
+        return content;
+    }
+    let mut out = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        if line.len() <= max_length || line.starts_with('#') {
+            out.push_str(line);
+            continue;
+        }
+        let (stack, rest) = match line.trim_end_matches('\n').rsplit_once(' ') {
+            Some((stack, _count)) => (stack, &line[stack.len()..]),
+            None => (line, ""),
+        };
+        for (i, frame) in split_frames(stack).enumerate() {
+            if i > 0 {
+                out.push(FRAME_DELIMITER);
+            }
+            if frame.chars().count() <= max_length {
+                out.push_str(frame);
+                continue;
+            }
+            let mut hasher = DefaultHasher::new();
+            frame.hash(&mut hasher);
+            let prefix = frame.chars().take(max_length).collect::<String>();
+            let capped = format!("{}…#{:08x}", prefix, hasher.finish() as u32);
+            if let Some(full_frame_names) = full_frame_names.as_mut() {
+                let mut end = FULL_FRAME_NAME_LIMIT.min(frame.len());
+                while !frame.is_char_boundary(end) {
+                    end -= 1;
+                }
+                full_frame_names
+                    .entry(capped.clone())
+                    .or_insert_with(|| frame[..end].to_string());
+            }
+            out.push_str(&capped);
+            diagnostics.capped_frame_names += 1;
+        }
+        out.push_str(rest);
+    }
+    out
 }
 
 impl FlameGraph {
-    pub fn from_string(mut content: String, sorted: bool) -> Self {
+    pub fn from_string(content: String, sorted: bool) -> Self {
+        Self::from_string_with_options(
+            content,
+            &ParseOptions {
+                sorted,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Parse raw bytes that may not be valid UTF-8 (see [`decode_input`]), converting from the
+    /// input format given in `options` to folded stacks first.
+    pub fn from_bytes_with_options(buf: Vec<u8>, options: &ParseOptions) -> anyhow::Result<Self> {
+        if let Some(content) = parsers::binary_to_folded(&buf, options)? {
+            return Ok(Self::from_string_with_options(content, options));
+        }
+        let (content, decode_diagnostics) = decode_input(buf);
+        let format = parsers::resolve_format(&content, options);
+        let content = parsers::to_folded(content, format, options)?;
+        Ok(Self::from_decoded(
+            content,
+            format,
+            decode_diagnostics,
+            options,
+        ))
+    }
+
+    /// Like [`FlameGraph::from_bytes_with_options`], also returning the samples in the order they
+    /// were taken for the formats that carry timestamps, read in the same pass as the stacks.
+    pub fn from_bytes_with_timed_samples(
+        buf: Vec<u8>,
+        options: &ParseOptions,
+    ) -> anyhow::Result<(Self, Option<Vec<TimedSample>>)> {
+        if let Some(content) = parsers::binary_to_folded(&buf, options)? {
+            return Ok((Self::from_string_with_options(content, options), None));
+        }
+        let (content, decode_diagnostics) = decode_input(buf);
+        let format = parsers::resolve_format(&content, options);
+        let (content, samples) = parsers::to_folded_with_timed_samples(content, format, options)?;
+        let out = Self::from_decoded(content, format, decode_diagnostics, options);
+        Ok((out, samples))
+    }
+
+    fn from_decoded(
+        content: String,
+        format: InputFormat,
+        decode_diagnostics: InputDiagnostics,
+        options: &ParseOptions,
+    ) -> Self {
+        let mut out = Self::from_string_with_options(content, options);
+        out.count_unit = options.count_unit.unwrap_or(format.count_unit());
+        out.diagnostics.invalid_utf8_sequences = decode_diagnostics.invalid_utf8_sequences;
+        out.diagnostics.nul_bytes = decode_diagnostics.nul_bytes;
+        out
+    }
+
+    pub fn from_string_with_options(mut content: String, options: &ParseOptions) -> Self {
+        let mut diagnostics = InputDiagnostics::default();
+        let mut full_frame_names = HashMap::new();
+        let mut mangled_names = HashMap::new();
+        if options.demangle {
+            (content, mangled_names) = demangle_frames(&content);
+        }
+        if !options.rewrite.is_empty() {
+            content = rewrite_frames(&content, &options.rewrite);
+        }
+        let mut annotated = parsers::folded::Annotated::default();
+        if options.annotations || options.annotation_filter.is_some() {
+            annotated =
+                parsers::folded::strip_annotations(&content, options.annotation_filter.as_ref());
+            content = std::mem::take(&mut annotated.content);
+        }
+        if let Some(max_length) = options.max_frame_name_length {
+            content = cap_frame_names(
+                content,
+                max_length,
+                options
+                    .keep_full_frame_names
+                    .then_some(&mut full_frame_names),
+                &mut diagnostics,
+            );
+        }
         // Make sure content ends with newline to simplify parsing
         if !content.ends_with('\n') {
             content.push('\n');
@@ -151,6 +853,7 @@ impl FlameGraph {
             children: Vec::<StackIdentifier>::new(),
             level: 0,
             hit: false,
+            location: None,
         });
         let mut last_line_index = 0;
         let mut counts: HashMap<String, Count> = HashMap::new();
@@ -178,49 +881,70 @@ impl FlameGraph {
             }
             let (line, count) = line_and_count.unwrap();
 
+            // Collect the frames of this line as (start, end) offsets into the line
+            let mut frames = Vec::<(usize, usize)>::new();
+            let mut last_delim_index = 0;
+            for delim_index in frame_delimiters(line) {
+                frames.push((last_delim_index, delim_index));
+                last_delim_index = delim_index + 1;
+            }
+            frames.push((last_delim_index, line.len()));
+
+            // Detect (and optionally repair) frames emitted by broken stack collapsers
+            let has_empty_frame = frames.iter().any(|(start, end)| start == end);
+            if has_empty_frame {
+                diagnostics.lines_with_empty_frames += 1;
+            }
+            if options.repair_frames && has_empty_frame {
+                frames.retain(|(start, end)| start != end);
+                if frames.is_empty() {
+                    diagnostics.dropped_lines += 1;
+                    last_line_index = line_index + 1;
+                    continue;
+                }
+            }
+
             stacks[ROOT_ID].total_count += count;
             let mut parent_id = ROOT_ID;
-            let mut level = 1;
-            let mut last_delim_index = 0;
             let mut counted_names = HashSet::<String>::new();
-            for delim_index in line
-                .char_indices()
-                .filter(|(_, c)| *c == ';')
-                .map(|(i, _)| i)
-            {
-                let stack_id = FlameGraph::update_one(
+            let num_frames = frames.len();
+            for (i, (start, end)) in frames.into_iter().enumerate() {
+                parent_id = FlameGraph::update_one(
                     &mut stacks,
                     &mut counts,
                     &mut counted_names,
                     &content,
                     count,
                     last_line_index,
-                    last_line_index + last_delim_index,
-                    last_line_index + delim_index,
+                    last_line_index + start,
+                    last_line_index + end,
                     parent_id,
-                    level,
-                    false,
+                    i + 1,
+                    i + 1 == num_frames,
                 );
-                parent_id = stack_id;
-                level += 1;
-                last_delim_index = delim_index + 1;
             }
-            FlameGraph::update_one(
-                &mut stacks,
-                &mut counts,
-                &mut counted_names,
-                &content,
-                count,
-                last_line_index,
-                last_line_index + last_delim_index,
-                last_line_index + line.len(),
-                parent_id,
-                level,
-                true,
-            );
             last_line_index = line_index + 1;
         }
 
+        // Frames are told apart by their names as written, but shown and searched without the
+        // escapes of the delimiters they contain
+        let mut unescaped_names = HashMap::new();
+        if content.contains(ESCAPED_FRAME_DELIMITER) {
+            for stack in stacks.iter().filter(|stack| stack.id != ROOT_ID) {
+                let name = &content[stack.start_index..stack.end_index];
+                if let Cow::Owned(unescaped) = unescape_frame(name) {
+                    unescaped_names.insert(name.to_string(), unescaped);
+                }
+            }
+            counts = counts
+                .into_iter()
+                .map(|(name, count)| match unescaped_names.get(&name) {
+                    Some(unescaped) => (unescaped.clone(), count),
+                    None => (name, count),
+                })
+                .collect();
+        }
+
         let ordered = FlameGraph::get_ordered_stacks(&counts);
         let mut out = Self {
             data: content,
@@ -228,7 +952,14 @@ impl FlameGraph {
             levels: vec![],
             ordered_stacks: ordered,
             hits: None,
-            sorted,
+            sorted: options.sorted,
+            diagnostics,
+            full_frame_names,
+            annotations: annotated.frames,
+            annotated_stacks: annotated.stacks,
+            mangled_names,
+            unescaped_names,
+            count_unit: options.count_unit.unwrap_or_default(),
         };
         out.populate_levels(&ROOT_ID, 0, None);
         out
@@ -251,10 +982,63 @@ impl FlameGraph {
             entries: ordered_by_self_count,
             num_rows,
             sorted_column: SortColumn::Own,
+            ascending: false,
+            aggregation: Aggregation::Function,
             search_pattern_ignored_because_of_no_match: false,
         }
     }
 
+    /// Count the samples of the Top view by `aggregation` instead, keeping the sort column. A
+    /// frame only adds to the total of its entry if none of its ancestors has the same entry.
+    pub fn set_aggregation(&mut self, aggregation: Aggregation) {
+        if aggregation == self.ordered_stacks.aggregation {
+            return;
+        }
+        let mut counts = HashMap::new();
+        let mut active = HashMap::new();
+        self.count_by(ROOT_ID, aggregation, &mut counts, &mut active);
+        let sorted_column = self.ordered_stacks.sorted_column;
+        let ascending = self.ordered_stacks.ascending;
+        self.ordered_stacks = FlameGraph::get_ordered_stacks(&counts);
+        self.ordered_stacks.sort(sorted_column, ascending);
+        self.ordered_stacks.aggregation = aggregation;
+    }
+
+    /// Add the counts of the subtree of `stack_id` to the entries of `aggregation`, `active`
+    /// holding how many of the ancestors of `stack_id` count towards each entry
+    fn count_by<'a>(
+        &'a self,
+        stack_id: StackIdentifier,
+        aggregation: Aggregation,
+        counts: &mut HashMap<String, Count>,
+        active: &mut HashMap<&'a str, usize>,
+    ) {
+        let Some(stack) = self.get_stack(&stack_id) else {
+            return;
+        };
+        let key = (stack_id != ROOT_ID)
+            .then(|| aggregation.key(self.get_stack_short_name_from_info(stack)));
+        if let Some(key) = key {
+            let count = counts.entry(key.to_string()).or_default();
+            count.own += stack.self_count;
+            count.frames += 1;
+            count.add_depth(stack.level, stack.total_count);
+            let depth = active.entry(key).or_default();
+            if *depth == 0 {
+                count.total += stack.total_count;
+            }
+            *depth += 1;
+        }
+        for child_id in stack.children.iter() {
+            self.count_by(*child_id, aggregation, counts, active);
+        }
+        if let Some(key) = key {
+            if let Some(depth) = active.get_mut(key) {
+                *depth -= 1;
+            }
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn update_one(
         stacks: &mut Vec<StackInfo>,
@@ -300,6 +1084,7 @@ impl FlameGraph {
                 children: Vec::<StackIdentifier>::new(),
                 level,
                 hit: false,
+                location: FrameLocation::parse(content, start_index, end_index),
             });
             let stack_id = stacks.len() - 1;
             stacks.get_mut(parent_id).unwrap().children.push(stack_id);
@@ -313,6 +1098,10 @@ impl FlameGraph {
 
         // Update summarized counts
         let summarized_count = counts.entry(short_name.to_string()).or_default();
+        if current_stack_id_if_exists.is_none() {
+            summarized_count.frames += 1;
+        }
+        summarized_count.add_depth(level, count);
         if !counted_names.contains(short_name) {
             counted_names.insert(short_name.to_string());
             summarized_count.total += count;
@@ -342,7 +1131,7 @@ impl FlameGraph {
         let width_factor = if let Some((parent_total_count, parent_width_factor)) =
             parent_total_count_and_width_factor
         {
-            parent_width_factor * (total_count as f64 / parent_total_count as f64)
+            parent_width_factor * (total_count as f64 / parent_total_count.max(1) as f64)
         } else {
             1.0
         };
@@ -375,6 +1164,10 @@ impl FlameGraph {
         }
     }
 
+    pub fn stacks(&self) -> &[StackInfo] {
+        &self.stacks
+    }
+
     pub fn get_stack(&self, stack_id: &StackIdentifier) -> Option<&StackInfo> {
         self.stacks.get(*stack_id)
     }
@@ -389,11 +1182,80 @@ impl FlameGraph {
             .map(|stack| self.get_stack_full_name_from_info(stack))
     }
 
+    /// Name of the frame of a stack, without the escapes of the delimiters it contains
     pub fn get_stack_short_name_from_info(&self, stack: &StackInfo) -> &str {
         if stack.id == ROOT_ID {
-            ROOT
+            return ROOT;
+        }
+        let name = &self.data[stack.start_index..stack.end_index];
+        if self.unescaped_names.is_empty() {
+            name
         } else {
-            &self.data[stack.start_index..stack.end_index]
+            self.unescaped_names.get(name).map_or(name, |s| s.as_str())
+        }
+    }
+
+    /// File and line of the source location of a frame, if its name has one
+    pub fn get_location(&self, stack: &StackInfo) -> Option<(&str, Option<usize>)> {
+        let location = stack.location?;
+        Some((
+            &self.data[location.file_start..location.file_end],
+            location.line,
+        ))
+    }
+
+    /// Name of a frame before it was demangled, if it was.
+    pub fn get_mangled_name(&self, short_name: &str) -> Option<&str> {
+        self.mangled_names.get(short_name).map(|s| s.as_str())
+    }
+
+    /// Whether `re` matches the name of a frame or its mangled name
+    pub fn is_name_match(&self, re: &regex::Regex, short_name: &str) -> bool {
+        is_name_match(re, short_name, &self.mangled_names)
+    }
+
+    /// Full text of a frame name that was capped while parsing, if it was kept.
+    pub fn get_full_frame_name(&self, short_name: &str) -> Option<&str> {
+        self.full_frame_names.get(short_name).map(|s| s.as_str())
+    }
+
+    /// Annotations of the frame of a stack, if the input had any
+    pub fn get_annotations(&self, stack_id: &StackIdentifier) -> Option<&FrameAnnotations> {
+        self.annotations.get(self.get_stack_full_name(stack_id)?)
+    }
+
+    /// Whether the stacks were read with the annotations of their frames
+    pub fn has_annotations(&self) -> bool {
+        !self.annotated_stacks.is_empty()
+    }
+
+    /// Keys of the annotations of all the frames, sorted
+    pub fn get_annotation_keys(&self) -> Vec<&str> {
+        let keys = self
+            .annotations
+            .values()
+            .flat_map(|annotations| annotations.keys())
+            .map(|key| key.as_str())
+            .collect::<BTreeSet<_>>();
+        keys.into_iter().collect()
+    }
+
+    /// The stack `full_name` with `count` samples as written in the input, split by the
+    /// annotations of its frames if there were any
+    fn get_annotated_counts(&self, full_name: &str, count: u64) -> Vec<(String, u64)> {
+        match self.annotated_stacks.get(full_name) {
+            Some(annotated) => annotated.clone(),
+            None => vec![(full_name.to_string(), count)],
+        }
+    }
+
+    /// Parse options to read folded stacks made from this flamegraph
+    fn get_reparse_options(&self) -> ParseOptions {
+        ParseOptions {
+            sorted: self.sorted,
+            annotations: self.has_annotations(),
+            count_unit: Some(self.count_unit),
+            ..Default::default()
         }
     }
 
@@ -415,36 +1277,497 @@ impl FlameGraph {
         self.get_stack_by_full_name(full_name).map(|stack| stack.id)
     }
 
-    pub fn get_stacks_at_level(&self, level: usize) -> Option<&Vec<StackIdentifier>> {
-        self.levels.get(level)
-    }
-
-    pub fn root(&self) -> &StackInfo {
-        self.get_stack(&ROOT_ID).unwrap()
+    /// Find the stack with the largest total count whose short name matches `name`. A name
+    /// containing ';' is looked up as a full stack name instead.
+    pub fn find_heaviest_stack_by_name(&self, name: &str) -> Option<StackIdentifier> {
+        if name.contains(';') {
+            return self.get_stack_id_by_full_name(name);
+        }
+        self.stacks
+            .iter()
+            .filter(|stack| {
+                stack.id != ROOT_ID && self.get_stack_short_name_from_info(stack) == name
+            })
+            .max_by_key(|stack| stack.total_count)
+            .map(|stack| stack.id)
     }
 
-    pub fn total_count(&self) -> u64 {
-        self.root().total_count
+    /// Function name best matching `query`: an exact match, then the names containing it the
+    /// earliest, then the names containing its characters in order over the shortest span,
+    /// case-insensitively. Ties go to the function with the most samples.
+    pub fn find_function_by_fuzzy_name(&self, query: &str) -> Option<&str> {
+        let query = query.to_lowercase();
+        if query.is_empty() {
+            return None;
+        }
+        self.ordered_stacks
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let score = fuzzy_score(&query, &entry.name.to_lowercase())?;
+                Some((score, entry.count.total, entry.name.as_str()))
+            })
+            .max_by_key(|(score, total, _)| (*score, *total))
+            .map(|(_, _, name)| name)
     }
 
-    pub fn get_num_levels(&self) -> usize {
-        self.levels.len()
+    /// Stacks whose short name is `short_name`, level by level from the top and left to right
+    pub fn get_stacks_with_short_name(&self, short_name: &str) -> Vec<StackIdentifier> {
+        self.levels
+            .iter()
+            .flatten()
+            .filter(|id| **id != ROOT_ID && self.get_stack_short_name(id) == Some(short_name))
+            .copied()
+            .collect()
     }
 
-    pub fn get_ancestors(&self, stack_id: &StackIdentifier) -> Vec<StackIdentifier> {
-        let mut ancestors = vec![];
-        let mut current_id = *stack_id;
-        while let Some(stack) = self.get_stack(&current_id) {
-            ancestors.push(current_id);
-            if let Some(parent_id) = stack.parent {
-                current_id = parent_id;
-            } else {
-                break;
+    /// Aggregate the callers and callees of every stack whose short name is `short_name`.
+    pub fn get_function_calls(&self, short_name: &str) -> FunctionCalls {
+        let mut calls = FunctionCalls {
+            total: self
+                .ordered_stacks
+                .entries
+                .iter()
+                .find(|entry| entry.name == short_name)
+                .map(|entry| entry.count.total)
+                .unwrap_or(0),
+            ..Default::default()
+        };
+        let mut callers: HashMap<&str, (u64, &StackInfo)> = HashMap::new();
+        let mut callees: HashMap<&str, (u64, &StackInfo)> = HashMap::new();
+        fn add<'a>(
+            entries: &mut HashMap<&'a str, (u64, &'a StackInfo)>,
+            name: &'a str,
+            stack: &'a StackInfo,
+        ) {
+            let entry = entries.entry(name).or_insert((0, stack));
+            entry.0 += stack.total_count;
+            if stack.total_count > entry.1.total_count {
+                entry.1 = stack;
+            }
+        }
+        for stack in self.stacks.iter() {
+            if stack.id == ROOT_ID || self.get_stack_short_name_from_info(stack) != short_name {
+                continue;
+            }
+            calls.stack_ids.push(stack.id);
+            if let Some(parent) = stack.parent.and_then(|id| self.get_stack(&id)) {
+                add(
+                    &mut callers,
+                    self.get_stack_short_name_from_info(parent),
+                    stack,
+                );
+            }
+            for child in stack.children.iter().filter_map(|id| self.get_stack(id)) {
+                add(
+                    &mut callees,
+                    self.get_stack_short_name_from_info(child),
+                    child,
+                );
+            }
+        }
+        let sorted = |entries: HashMap<&str, (u64, &StackInfo)>| {
+            let mut entries = entries
+                .into_iter()
+                .map(|(name, (count, stack))| CallEntry {
+                    name: name.to_string(),
+                    count,
+                    stack_id: stack.id,
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+            entries
+        };
+        calls.callers = sorted(callers);
+        calls.callees = sorted(callees);
+        calls
+    }
+
+    /// Full name and own count of the stacks with samples in the subtree rooted at `stack_id`,
+    /// sorted by name.
+    pub fn get_folded_counts(&self, stack_id: &StackIdentifier) -> Vec<(String, u64)> {
+        let mut counts = self
+            .get_descendants(stack_id)
+            .iter()
+            .filter_map(|id| self.get_stack(id))
+            .filter(|stack| stack.id != ROOT_ID && stack.self_count > 0)
+            .map(|stack| {
+                (
+                    self.get_stack_full_name_from_info(stack).to_string(),
+                    stack.self_count,
+                )
+            })
+            .collect::<Vec<_>>();
+        counts.sort();
+        counts
+    }
+
+    /// Folded stacks ("frame;frame count" lines) of the subtree rooted at `stack_id`, with the
+    /// annotations of their frames.
+    pub fn to_folded(&self, stack_id: &StackIdentifier) -> String {
+        self.get_folded_counts(stack_id)
+            .iter()
+            .flat_map(|(name, count)| self.get_annotated_counts(name, *count))
+            .map(|(name, count)| format!("{} {}", name, count))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// New flamegraph with only the stacks going through a frame of the function `short_name`
+    pub fn filter_by_function(&self, short_name: &str) -> FlameGraph {
+        // Parents are created before their children, so they are always visited first
+        let mut passes = vec![false; self.stacks.len()];
+        let mut lines = vec![];
+        for stack in self.stacks.iter().filter(|stack| stack.id != ROOT_ID) {
+            passes[stack.id] = stack.parent.is_some_and(|parent| passes[parent])
+                || self.get_stack_short_name_from_info(stack) == short_name;
+            if passes[stack.id] && stack.self_count > 0 {
+                let full_name = self.get_stack_full_name_from_info(stack);
+                for (name, count) in self.get_annotated_counts(full_name, stack.self_count) {
+                    lines.push(format!("{} {}", name, count));
+                }
+            }
+        }
+        let mut out =
+            FlameGraph::from_string_with_options(lines.join("\n"), &self.get_reparse_options());
+        out.full_frame_names = self.full_frame_names.clone();
+        out.mangled_names = self.mangled_names.clone();
+        out
+    }
+
+    /// New flamegraph without the stacks going through a frame whose function matches `re`
+    pub fn exclude_by_function(&self, re: &regex::Regex) -> FlameGraph {
+        // Parents are created before their children, so they are always visited first
+        let mut excluded = vec![false; self.stacks.len()];
+        let mut lines = vec![];
+        for stack in self.stacks.iter().filter(|stack| stack.id != ROOT_ID) {
+            excluded[stack.id] = stack.parent.is_some_and(|parent| excluded[parent])
+                || re.is_match(self.get_stack_short_name_from_info(stack));
+            if !excluded[stack.id] && stack.self_count > 0 {
+                let full_name = self.get_stack_full_name_from_info(stack);
+                for (name, count) in self.get_annotated_counts(full_name, stack.self_count) {
+                    lines.push(format!("{} {}", name, count));
+                }
+            }
+        }
+        let mut out =
+            FlameGraph::from_string_with_options(lines.join("\n"), &self.get_reparse_options());
+        out.full_frame_names = self.full_frame_names.clone();
+        out.mangled_names = self.mangled_names.clone();
+        out
+    }
+
+    /// New flamegraph without the frames of the functions `short_names`, their children taking
+    /// their place. Samples of stacks made only of such frames are dropped.
+    pub fn prune_functions(&self, short_names: &[String]) -> FlameGraph {
+        let mut lines = vec![];
+        for stack in self.stacks.iter() {
+            if stack.id == ROOT_ID || stack.self_count == 0 {
+                continue;
+            }
+            let full_name = self.get_stack_full_name_from_info(stack);
+            for (name, count) in self.get_annotated_counts(full_name, stack.self_count) {
+                let frames = split_frames(&name)
+                    .filter(|frame| {
+                        let (frame, _) = split_frame_annotations(frame);
+                        let frame = unescape_frame(frame);
+                        !short_names.iter().any(|name| *name == frame)
+                    })
+                    .collect::<Vec<_>>();
+                if !frames.is_empty() {
+                    lines.push(format!(
+                        "{} {}",
+                        frames.join(&FRAME_DELIMITER.to_string()),
+                        count
+                    ));
+                }
+            }
+        }
+        let mut out =
+            FlameGraph::from_string_with_options(lines.join("\n"), &self.get_reparse_options());
+        out.full_frame_names = self.full_frame_names.clone();
+        out.mangled_names = self.mangled_names.clone();
+        out
+    }
+
+    /// Own count of every stack with samples, keyed by its folded name with the annotations of
+    /// its frames, to subtract from a later flamegraph with [`Self::subtract_counts`]
+    pub fn get_stack_counts(&self) -> HashMap<String, u64> {
+        let mut counts = HashMap::new();
+        for stack in self.stacks.iter() {
+            if stack.id == ROOT_ID || stack.self_count == 0 {
+                continue;
+            }
+            let full_name = self.get_stack_full_name_from_info(stack);
+            counts.extend(self.get_annotated_counts(full_name, stack.self_count));
+        }
+        counts
+    }
+
+    /// New flamegraph with the samples of this one minus `counts`, such as the samples collected
+    /// since `counts` were taken from an earlier flamegraph of the same live session
+    pub fn subtract_counts(&self, counts: &HashMap<String, u64>) -> FlameGraph {
+        let mut lines = vec![];
+        for stack in self.stacks.iter() {
+            if stack.id == ROOT_ID || stack.self_count == 0 {
+                continue;
+            }
+            let full_name = self.get_stack_full_name_from_info(stack);
+            for (name, count) in self.get_annotated_counts(full_name, stack.self_count) {
+                let count = count.saturating_sub(counts.get(&name).copied().unwrap_or(0));
+                if count > 0 {
+                    lines.push(format!("{} {}", name, count));
+                }
+            }
+        }
+        let mut out =
+            FlameGraph::from_string_with_options(lines.join("\n"), &self.get_reparse_options());
+        out.full_frame_names = self.full_frame_names.clone();
+        out.mangled_names = self.mangled_names.clone();
+        out
+    }
+
+    /// New flamegraph with a top-level `key=value` frame for each value of the annotation `key`,
+    /// holding the stacks with a frame annotated with that value (the outermost one when there
+    /// are several). The stacks without the annotation are under `key=(none)`.
+    pub fn group_by_annotation(&self, key: &str) -> FlameGraph {
+        let mut lines = vec![];
+        for stack in self.stacks.iter() {
+            if stack.id == ROOT_ID || stack.self_count == 0 {
+                continue;
+            }
+            let full_name = self.get_stack_full_name_from_info(stack);
+            for (name, count) in self.get_annotated_counts(full_name, stack.self_count) {
+                lines.push(format!(
+                    "{}={}{}{} {}",
+                    key,
+                    get_annotation_value(&name, key).unwrap_or("(none)"),
+                    FRAME_DELIMITER,
+                    name,
+                    count
+                ));
+            }
+        }
+        let mut out =
+            FlameGraph::from_string_with_options(lines.join("\n"), &self.get_reparse_options());
+        out.full_frame_names = self.full_frame_names.clone();
+        out.mangled_names = self.mangled_names.clone();
+        out
+    }
+
+    /// New flamegraph with the stacks of all the flamegraphs, such as the profiles of several
+    /// hosts, with their annotations
+    pub fn merge(flamegraphs: &[&FlameGraph]) -> FlameGraph {
+        let folded = flamegraphs
+            .iter()
+            .map(|flamegraph| flamegraph.to_folded(&ROOT_ID))
+            .filter(|folded| !folded.is_empty())
+            .collect::<Vec<_>>();
+        let options = ParseOptions {
+            annotations: flamegraphs
+                .iter()
+                .any(|flamegraph| flamegraph.has_annotations()),
+            ..flamegraphs
+                .first()
+                .map(|flamegraph| flamegraph.get_reparse_options())
+                .unwrap_or_default()
+        };
+        let mut out = FlameGraph::from_string_with_options(folded.join("\n"), &options);
+        for flamegraph in flamegraphs {
+            out.full_frame_names
+                .extend(flamegraph.full_frame_names.clone());
+            out.mangled_names.extend(flamegraph.mangled_names.clone());
+        }
+        out
+    }
+
+    /// Values of the annotation `key`, sorted
+    pub fn get_annotation_values(&self, key: &str) -> Vec<&str> {
+        let values = self
+            .annotations
+            .values()
+            .filter_map(|annotations| annotations.get(key))
+            .flat_map(|values| values.keys())
+            .map(|value| value.as_str())
+            .collect::<BTreeSet<_>>();
+        values.into_iter().collect()
+    }
+
+    /// Whether some stacks have no frame annotated with `key`
+    pub fn has_stacks_without_annotation(&self, key: &str) -> bool {
+        self.stacks.iter().any(|stack| {
+            stack.id != ROOT_ID
+                && stack.self_count > 0
+                && self
+                    .get_annotated_counts(self.get_stack_full_name_from_info(stack), 0)
+                    .iter()
+                    .any(|(name, _)| get_annotation_value(name, key).is_none())
+        })
+    }
+
+    /// New flamegraph with only the stacks whose outermost frame annotated with `key` has the
+    /// annotation `value`, or without any frame annotated with `key` when `value` is `None`
+    pub fn filter_by_annotation(&self, key: &str, value: Option<&str>) -> FlameGraph {
+        let mut lines = vec![];
+        for stack in self.stacks.iter() {
+            if stack.id == ROOT_ID || stack.self_count == 0 {
+                continue;
+            }
+            let full_name = self.get_stack_full_name_from_info(stack);
+            for (name, count) in self.get_annotated_counts(full_name, stack.self_count) {
+                if get_annotation_value(&name, key) == value {
+                    lines.push(format!("{} {}", name, count));
+                }
+            }
+        }
+        let mut out =
+            FlameGraph::from_string_with_options(lines.join("\n"), &self.get_reparse_options());
+        out.full_frame_names = self.full_frame_names.clone();
+        out.mangled_names = self.mangled_names.clone();
+        out
+    }
+
+    /// New flamegraph where the frames of a function that only differ by the line of their source
+    /// location (see [`strip_line_number`]) are merged
+    pub fn merge_line_numbers(&self) -> FlameGraph {
+        let mut lines = vec![];
+        for stack in self.stacks.iter() {
+            if stack.id == ROOT_ID || stack.self_count == 0 {
+                continue;
+            }
+            let full_name = self.get_stack_full_name_from_info(stack);
+            for (name, count) in self.get_annotated_counts(full_name, stack.self_count) {
+                let frames = split_frames(&name)
+                    .map(strip_line_number)
+                    .collect::<Vec<_>>();
+                lines.push(format!(
+                    "{} {}",
+                    frames.join(&FRAME_DELIMITER.to_string()),
+                    count
+                ));
+            }
+        }
+        let mut out =
+            FlameGraph::from_string_with_options(lines.join("\n"), &self.get_reparse_options());
+        out.full_frame_names = self.full_frame_names.clone();
+        out.mangled_names = self.mangled_names.clone();
+        out
+    }
+
+    /// Shallowest stack with the same frames as the stack `full_name` once the frames of the
+    /// function `short_name` are left out of both
+    pub fn find_stack_ignoring_function(
+        &self,
+        full_name: &str,
+        short_name: &str,
+    ) -> Option<StackIdentifier> {
+        let frames = split_frames(full_name)
+            .filter(|frame| unescape_frame(frame) != short_name)
+            .collect::<Vec<_>>();
+        // Parents are created before their children, so the first match is the shallowest
+        self.stacks
+            .iter()
+            .find(|stack| {
+                stack.id != ROOT_ID
+                    && split_frames(self.get_stack_full_name_from_info(stack))
+                        .filter(|frame| unescape_frame(frame) != short_name)
+                        .eq(frames.iter().copied())
+            })
+            .map(|stack| stack.id)
+    }
+
+    /// First stack that doesn't have exactly one child, following the only child from the root.
+    pub fn get_single_child_chain_end(&self) -> StackIdentifier {
+        let mut stack = self.root();
+        while let [child_id] = stack.children.as_slice() {
+            match self.get_stack(child_id) {
+                Some(child) => stack = child,
+                None => break,
+            }
+        }
+        stack.id
+    }
+
+    pub fn get_stacks_at_level(&self, level: usize) -> Option<&Vec<StackIdentifier>> {
+        self.levels.get(level)
+    }
+
+    pub fn root(&self) -> &StackInfo {
+        self.get_stack(&ROOT_ID).unwrap()
+    }
+
+    pub fn total_count(&self) -> u64 {
+        self.root().total_count
+    }
+
+    /// Size of the flamegraph and a rough estimate of the memory it uses.
+    pub fn stats(&self) -> FlameGraphStats {
+        let stacks_bytes = self.stacks.capacity() * std::mem::size_of::<StackInfo>()
+            + self
+                .stacks
+                .iter()
+                .map(|s| s.children.capacity() * std::mem::size_of::<StackIdentifier>())
+                .sum::<usize>();
+        let levels_bytes = self
+            .levels
+            .iter()
+            .map(|l| l.capacity() * std::mem::size_of::<StackIdentifier>())
+            .sum::<usize>();
+        let ordered_bytes = self
+            .ordered_stacks
+            .entries
+            .iter()
+            .map(|e| std::mem::size_of::<CountEntry>() + e.name.capacity())
+            .sum::<usize>();
+        let full_frame_names_bytes = self
+            .full_frame_names
+            .iter()
+            .map(|(k, v)| k.capacity() + v.capacity())
+            .sum::<usize>();
+        FlameGraphStats {
+            num_stacks: self.stacks.len(),
+            num_levels: self.levels.len(),
+            num_symbols: self.ordered_stacks.entries.len(),
+            num_input_bytes: self.data.len(),
+            memory_bytes: self.data.capacity()
+                + stacks_bytes
+                + levels_bytes
+                + ordered_bytes
+                + full_frame_names_bytes,
+        }
+    }
+
+    pub fn diagnostics(&self) -> &InputDiagnostics {
+        &self.diagnostics
+    }
+
+    pub fn get_num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn get_ancestors(&self, stack_id: &StackIdentifier) -> Vec<StackIdentifier> {
+        let mut ancestors = vec![];
+        let mut current_id = *stack_id;
+        while let Some(stack) = self.get_stack(&current_id) {
+            ancestors.push(current_id);
+            if let Some(parent_id) = stack.parent {
+                current_id = parent_id;
+            } else {
+                break;
             }
         }
         ancestors
     }
 
+    /// Deepest frame that is an ancestor of both frames, or one of them if it contains the other
+    pub fn get_common_ancestor(&self, a: &StackIdentifier, b: &StackIdentifier) -> StackIdentifier {
+        let a_ancestors = self.get_ancestors(a);
+        self.get_ancestors(b)
+            .into_iter()
+            .find(|id| a_ancestors.contains(id))
+            .unwrap_or(ROOT_ID)
+    }
+
     pub fn get_descendants(&self, stack_id: &StackIdentifier) -> Vec<StackIdentifier> {
         let mut descendants = vec![];
         let mut stack_ids = vec![*stack_id];
@@ -457,16 +1780,30 @@ impl FlameGraph {
         descendants
     }
 
-    pub fn set_hits(&mut self, p: &SearchPattern) {
-        self.stacks.iter_mut().for_each(|stack| {
-            stack.hit =
-                p.re.is_match(&self.data[stack.start_index..stack.end_index]);
-        });
+    /// Flag the frames matching `p`. The rows of the Top view matching `p` or one of the `kept`
+    /// patterns stay visible.
+    pub fn set_hits(&mut self, p: &SearchPattern, kept: &[&regex::Regex]) {
+        let hits = self
+            .stacks
+            .iter()
+            .map(|stack| {
+                let name = &self.data[stack.start_index..stack.end_index];
+                let name = self.unescaped_names.get(name).map_or(name, |s| s.as_str());
+                self.is_name_match(&p.re, name)
+            })
+            .collect::<Vec<_>>();
+        for (stack, hit) in self.stacks.iter_mut().zip(hits) {
+            stack.hit = hit;
+        }
         self.hits = Some(Hits {
-            coverage_count: self._count_hit_coverage(ROOT_ID),
+            coverage_count: self._count_coverage(ROOT_ID, &|stack| stack.hit),
             ids: self._collect_hit_ids(),
         });
-        self.ordered_stacks.set_search_pattern(p);
+        let mangled_names = &self.mangled_names;
+        self.ordered_stacks.set_search_pattern(p, |name| {
+            is_name_match(&p.re, name, mangled_names)
+                || kept.iter().any(|re| is_name_match(re, name, mangled_names))
+        });
     }
 
     pub fn clear_hits(&mut self) {
@@ -483,14 +1820,39 @@ impl FlameGraph {
         self.hits.as_ref().map(|h| &h.ids)
     }
 
-    fn _count_hit_coverage(&self, stack_id: StackIdentifier) -> u64 {
+    /// Samples in frames whose short name (or mangled name) matches `re`, not counting nested
+    /// matches twice.
+    pub fn count_coverage(&self, re: &regex::Regex) -> u64 {
+        self._count_coverage(ROOT_ID, &|stack| {
+            stack.id != ROOT_ID
+                && self.is_name_match(re, self.get_stack_short_name_from_info(stack))
+        })
+    }
+
+    /// Own samples of all the frames whose function matches `re`
+    pub fn count_own(&self, re: &regex::Regex) -> u64 {
+        self.stacks
+            .iter()
+            .filter(|stack| {
+                stack.id != ROOT_ID
+                    && self.is_name_match(re, self.get_stack_short_name_from_info(stack))
+            })
+            .map(|stack| stack.self_count)
+            .sum()
+    }
+
+    fn _count_coverage(
+        &self,
+        stack_id: StackIdentifier,
+        is_match: &dyn Fn(&StackInfo) -> bool,
+    ) -> u64 {
         let stack = self.get_stack(&stack_id).unwrap();
-        if stack.hit {
+        if is_match(stack) {
             return stack.total_count;
         }
         let mut count = 0;
         for child_id in stack.children.iter() {
-            count += self._count_hit_coverage(*child_id);
+            count += self._count_coverage(*child_id, is_match);
         }
         count
     }
@@ -510,6 +1872,34 @@ impl FlameGraph {
     }
 }
 
+/// How well `candidate` matches `query`, both lowercase: higher is better, `None` if the
+/// characters of `query` don't all appear in order in `candidate`.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<(u8, std::cmp::Reverse<usize>)> {
+    if candidate == query {
+        return Some((2, std::cmp::Reverse(0)));
+    }
+    if let Some(position) = candidate.find(query) {
+        return Some((1, std::cmp::Reverse(position)));
+    }
+    // Shortest span containing the characters of the query in order, starting from every
+    // occurrence of its first character
+    let first = query.chars().next()?;
+    candidate
+        .char_indices()
+        .filter(|(_, c)| *c == first)
+        .filter_map(|(start, _)| {
+            let mut rest = candidate[start..].char_indices();
+            let mut end = start;
+            for q in query.chars() {
+                let (i, c) = rest.find(|(_, c)| *c == q)?;
+                end = start + i + c.len_utf8();
+            }
+            Some(end - start)
+        })
+        .min()
+        .map(|span| (0, std::cmp::Reverse(span)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -534,7 +1924,7 @@ mod tests {
     }
 
     impl FlameGraph {
-        pub fn to_readable_stacks(&self) -> Vec<StackInfoReadable> {
+        pub fn to_readable_stacks(&self) -> Vec<StackInfoReadable<'_>> {
             self.stacks
                 .iter()
                 .map(|stack| StackInfoReadable {
@@ -556,6 +1946,7 @@ mod tests {
         }
     }
 
+    #[allow(clippy::bool_assert_comparison)]
     fn check_result<P: AsRef<std::path::Path>>(data_filename: P) -> FlameGraph {
         let content = std::fs::read_to_string(&data_filename).unwrap();
         let fg = FlameGraph::from_string(content, true);
@@ -611,6 +2002,7 @@ mod tests {
                 children: vec![3, 1, 5],
                 level: 0,
                 hit: false,
+                location: None,
             }
         );
     }
@@ -626,8 +2018,569 @@ mod tests {
         check_result("tests/data/ignore-metadata-lines.txt");
     }
 
+    #[test]
+    fn test_escape_frame() {
+        assert_eq!(escape_frame("main"), "main");
+        assert_eq!(escape_frame("a;b"), "a\\;b");
+        assert_eq!(unescape_frame("a\\;b"), "a;b");
+        assert_eq!(join_frames(["main", "a;b", "c"]), "main;a\\;b;c");
+        let stack = join_frames(["x;y", "z"]);
+        assert_eq!(
+            split_frames(&stack).map(unescape_frame).collect::<Vec<_>>(),
+            vec!["x;y", "z"]
+        );
+    }
+
+    #[test]
+    fn test_delimiter() {
+        let fg = FlameGraph::from_bytes_with_options(
+            b"main|a;b|c 2\nmain|a;b 1\nmain|x\\|y 4".to_vec(),
+            &ParseOptions {
+                delimiter: Some("|".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(fg.total_count(), 7);
+        let id = fg.get_stack_id_by_full_name("main;a\\;b").unwrap();
+        assert_eq!(fg.get_stack_short_name(&id), Some("a;b"));
+        assert!(fg
+            .ordered_stacks
+            .entries
+            .iter()
+            .any(|entry| entry.name == "a;b"));
+        assert_eq!(fg.get_stacks_with_short_name("a;b"), vec![id]);
+        assert_eq!(fg.get_stack(&id).unwrap().total_count, 3);
+        assert_eq!(fg.get_stack(&id).unwrap().children.len(), 1);
+        let id = fg.get_stack_id_by_full_name("main;x|y").unwrap();
+        assert_eq!(fg.get_stack(&id).unwrap().level, 2);
+    }
+
     #[test]
     fn test_recursive() {
         check_result("tests/data/recursive.txt");
     }
+
+    #[test]
+    fn test_from_bytes_with_timed_samples() {
+        let options = ParseOptions::default();
+        let buf = std::fs::read("tests/data/simple.cpuprofile").unwrap();
+        let (fg, samples) =
+            FlameGraph::from_bytes_with_timed_samples(buf.clone(), &options).unwrap();
+        assert_eq!(samples.unwrap().len(), 5);
+        let expected = FlameGraph::from_bytes_with_options(buf, &options).unwrap();
+        assert_eq!(fg.to_folded(&ROOT_ID), expected.to_folded(&ROOT_ID));
+
+        // Folded stacks have no timestamps
+        let buf = b"main;a 2\nmain;b 1".to_vec();
+        let (fg, samples) = FlameGraph::from_bytes_with_timed_samples(buf, &options).unwrap();
+        assert_eq!(fg.total_count(), 3);
+        assert_eq!(samples, None);
+    }
+
+    #[test]
+    fn test_find_function_by_fuzzy_name() {
+        let fg = FlameGraph::from_string(
+            "main;parse_json 1\nmain;json_parse 3\nmain;parse 1\nmain;process_json_file 5"
+                .to_string(),
+            true,
+        );
+        assert_eq!(fg.find_function_by_fuzzy_name("Parse"), Some("parse"));
+        // Substrings rank above scattered characters, the earlier the better
+        assert_eq!(fg.find_function_by_fuzzy_name("json"), Some("json_parse"));
+        assert_eq!(fg.find_function_by_fuzzy_name("_json"), Some("parse_json"));
+        assert_eq!(fg.find_function_by_fuzzy_name("pjs"), Some("parse_json"));
+        assert_eq!(fg.find_function_by_fuzzy_name("pj"), Some("parse_json"));
+        assert_eq!(fg.find_function_by_fuzzy_name("xyz"), None);
+        assert_eq!(fg.find_function_by_fuzzy_name(""), None);
+    }
+
+    #[test]
+    fn test_common_ancestor() {
+        let fg = FlameGraph::from_string("main;a;b 1\nmain;a;c 2\nmain;d 3".to_string(), true);
+        let id = |name| fg.find_heaviest_stack_by_name(name).unwrap();
+        assert_eq!(
+            fg.get_common_ancestor(&id("main;a;b"), &id("main;a;c")),
+            id("main;a")
+        );
+        assert_eq!(
+            fg.get_common_ancestor(&id("main;a;b"), &id("main;d")),
+            id("main")
+        );
+        assert_eq!(
+            fg.get_common_ancestor(&id("main;a"), &id("main;a;c")),
+            id("main;a")
+        );
+    }
+
+    #[test]
+    fn test_filter_by_function() {
+        let fg = FlameGraph::from_string(
+            "main;a;log 2\nmain;b;log;write 3\nmain;b 4\nmain;c 5\nlog 1".to_string(),
+            true,
+        );
+        let filtered = fg.filter_by_function("log");
+        assert_eq!(filtered.total_count(), 6);
+        assert_eq!(
+            filtered.to_folded(&ROOT_ID),
+            "log 1\nmain;a;log 2\nmain;b;log;write 3"
+        );
+        assert_eq!(fg.filter_by_function("missing").total_count(), 0);
+    }
+
+    #[test]
+    fn test_prune_functions() {
+        let fg = FlameGraph::from_string(
+            "main;wrap;a 2\nmain;wrap;b;wrap;c 3\nmain;wrap 1\nwrap 4".to_string(),
+            true,
+        );
+        let pruned = fg.prune_functions(&["wrap".to_string()]);
+        assert_eq!(pruned.total_count(), 6);
+        assert_eq!(pruned.to_folded(&ROOT_ID), "main 1\nmain;a 2\nmain;b;c 3");
+    }
+
+    #[test]
+    fn test_subtract_counts() {
+        let before = FlameGraph::from_string("main;a 2\nmain;b 3".to_string(), true);
+        let after = FlameGraph::from_string("main;a 5\nmain;b 3\nmain;c 1".to_string(), true);
+        let since = after.subtract_counts(&before.get_stack_counts());
+        assert_eq!(since.total_count(), 4);
+        assert_eq!(since.to_folded(&ROOT_ID), "main;a 3\nmain;c 1");
+        // Samples cleared since then
+        let cleared = FlameGraph::from_string("main;a 1".to_string(), true);
+        assert_eq!(
+            cleared
+                .subtract_counts(&before.get_stack_counts())
+                .total_count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_annotations() {
+        let content = "main;wrap#cpu=1;a#cpu=1 2\nmain;wrap#cpu=2;a 1\nmain;Foo#bar 3";
+        let options = ParseOptions {
+            annotations: true,
+            ..Default::default()
+        };
+        let fg = FlameGraph::from_string_with_options(content.to_string(), &options);
+        assert!(fg.has_annotations());
+        let wrap = fg.find_heaviest_stack_by_name("main;wrap").unwrap();
+        let annotations = fg.get_annotations(&wrap).unwrap();
+        assert_eq!(annotations["cpu"]["1"], 2);
+        assert_eq!(annotations["cpu"]["2"], 1);
+        assert!(fg.find_heaviest_stack_by_name("main;Foo#bar").is_some());
+        assert_eq!(
+            fg.to_folded(&ROOT_ID),
+            "main;Foo#bar 3\nmain;wrap#cpu=1;a#cpu=1 2\nmain;wrap#cpu=2;a 1"
+        );
+
+        let pruned = fg.prune_functions(&["wrap".to_string()]);
+        let a = pruned.find_heaviest_stack_by_name("main;a").unwrap();
+        assert_eq!(pruned.get_annotations(&a).unwrap()["cpu"]["1"], 2);
+        let filtered = fg.filter_by_function("a");
+        let wrap = filtered.find_heaviest_stack_by_name("main;wrap").unwrap();
+        assert_eq!(filtered.get_annotations(&wrap).unwrap()["cpu"].len(), 2);
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut fg = FlameGraph::from_string("main;b 3\nmain;c 1\nmain;a 2".to_string(), true);
+        let names = |fg: &FlameGraph| {
+            fg.ordered_stacks
+                .entries
+                .iter()
+                .map(|e| e.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&fg), ["b", "a", "c", "main"]);
+        fg.ordered_stacks.set_sort_column(SortColumn::Name);
+        assert!(fg.ordered_stacks.ascending);
+        assert_eq!(names(&fg), ["a", "b", "c", "main"]);
+        fg.ordered_stacks.toggle_sort_direction();
+        assert_eq!(names(&fg), ["main", "c", "b", "a"]);
+        fg.ordered_stacks.set_sort_column(SortColumn::Total);
+        assert!(!fg.ordered_stacks.ascending);
+        assert_eq!(names(&fg), ["main", "b", "a", "c"]);
+        fg.ordered_stacks.toggle_sort_direction();
+        assert_eq!(names(&fg), ["c", "a", "b", "main"]);
+        // The order is kept when counting by file
+        fg.set_aggregation(Aggregation::File);
+        assert!(fg.ordered_stacks.ascending);
+        assert_eq!(fg.ordered_stacks.sorted_column, SortColumn::Total);
+    }
+
+    #[test]
+    fn test_frames_and_depth() {
+        let fg = FlameGraph::from_string(
+            "main;a;log 2\nmain;b;c;log 2\nmain;log 2\nmain;a;log 1".to_string(),
+            true,
+        );
+        let count = |name: &str| {
+            let entry = fg.ordered_stacks.entries.iter().find(|e| e.name == name);
+            entry.unwrap().count.clone()
+        };
+        assert_eq!(count("log").frames, 3);
+        assert_eq!(count("log").average_depth(), 3.0);
+        assert_eq!(count("main").frames, 1);
+        assert_eq!(count("main").average_depth(), 1.0);
+    }
+
+    #[test]
+    fn test_aggregation() {
+        let mut fg = FlameGraph::from_string(
+            "main (app.py:1);load (json/decoder.py:3);scan (json/scanner.py:5) 2\n\
+             main (app.py:1);load (json/decoder.py:3);decode (json/decoder.py:9) 3\n\
+             main (app.py:1);run (app.py:7) 1"
+                .to_string(),
+            true,
+        );
+        fg.ordered_stacks.set_sort_column(SortColumn::Total);
+        fg.set_aggregation(Aggregation::File);
+        assert_eq!(fg.ordered_stacks.sorted_column, SortColumn::Total);
+        let count = |fg: &FlameGraph, name: &str| {
+            let entry = fg.ordered_stacks.entries.iter().find(|e| e.name == name);
+            entry.map(|e| (e.count.total, e.count.own))
+        };
+        assert_eq!(count(&fg, "app.py"), Some((6, 1)));
+        // Nested frames of the same file are only counted once in the total
+        assert_eq!(count(&fg, "json/decoder.py"), Some((5, 3)));
+        assert_eq!(count(&fg, "json/scanner.py"), Some((2, 2)));
+        fg.set_aggregation(Aggregation::Module);
+        assert_eq!(count(&fg, "json"), Some((5, 5)));
+        assert_eq!(fg.ordered_stacks.entries[0].name, "app");
+        fg.set_aggregation(Aggregation::Function);
+        assert_eq!(count(&fg, "run (app.py:7)"), Some((1, 1)));
+    }
+
+    #[test]
+    fn test_merge_line_numbers() {
+        assert_eq!(strip_line_number("work (a.py:8)"), "work (a.py)");
+        assert_eq!(
+            strip_line_number("work (a.py:8)#cpu=1"),
+            "work (a.py)#cpu=1"
+        );
+        assert_eq!(strip_line_number("work (a.py)"), "work (a.py)");
+        assert_eq!(strip_line_number("main"), "main");
+        let fg = FlameGraph::from_string(
+            "main (a.py:1);work (a.py:8) 2\nmain (a.py:1);work (a.py:9) 3\nmain (a.py:2) 1"
+                .to_string(),
+            true,
+        );
+        let merged = fg.merge_line_numbers();
+        assert_eq!(
+            merged.to_folded(&ROOT_ID),
+            "main (a.py) 1\nmain (a.py);work (a.py) 5"
+        );
+        assert_eq!(merged.ordered_stacks.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_demangle() {
+        let content = "main;_ZN3foo3barEi 2\nmain;_ZN3foo3bazEv 1";
+        let options = ParseOptions {
+            demangle: true,
+            ..Default::default()
+        };
+        let mut fg = FlameGraph::from_string_with_options(content.to_string(), &options);
+        assert!(fg
+            .find_heaviest_stack_by_name("main;foo::bar(int)")
+            .is_some());
+        assert_eq!(fg.get_mangled_name("foo::baz()"), Some("_ZN3foo3bazEv"));
+        fg.set_hits(&SearchPattern::new("3barE", true, true).unwrap(), &[]);
+        assert_eq!(fg.hit_coverage_count(), Some(2));
+        assert_eq!(fg.ordered_stacks.num_rows, 1);
+    }
+
+    #[test]
+    fn test_count_format() {
+        let format = CountFormat {
+            unit: CountUnit::Samples,
+            sample_rate: Some(100),
+        };
+        assert_eq!(format.format(124), "1.24s");
+        assert_eq!(format.format_with_unit(31), "310.00ms");
+        assert_eq!(format.label(), "Time");
+        let format = CountFormat {
+            unit: CountUnit::Bytes,
+            sample_rate: Some(100),
+        };
+        assert_eq!(format.format(2048), "2.0 KiB");
+        let format = CountFormat {
+            unit: CountUnit::Samples,
+            sample_rate: None,
+        };
+        assert_eq!(format.format_with_unit(3), "3 samples");
+    }
+
+    #[test]
+    fn test_search_modes() {
+        let fg = FlameGraph::from_string(
+            "std::vector<int>::push_back 2\nPushBack 1".to_string(),
+            true,
+        );
+        let count_hits = |modes: SearchModes, text: &str| {
+            let mut fg = fg.clone();
+            let pattern = SearchPattern::new(&modes.to_regex(text), true, true).unwrap();
+            fg.set_hits(&pattern, &[]);
+            fg.hit_coverage_count()
+        };
+        let literal = SearchModes {
+            literal: true,
+            ..Default::default()
+        };
+        let ignore_case = SearchModes {
+            ignore_case: true,
+            ..Default::default()
+        };
+        assert!(SearchPattern::new(
+            &SearchModes::default().to_regex("vector<int>::push_back("),
+            true,
+            true
+        )
+        .is_err());
+        assert_eq!(count_hits(literal, "vector<int>::push_back"), Some(2));
+        assert_eq!(count_hits(SearchModes::default(), "push_?back"), Some(2));
+        assert_eq!(count_hits(ignore_case, "push_?back"), Some(3));
+        assert_eq!(literal.label(), Some("literal".to_string()));
+        assert_eq!(SearchModes::default().label(), None);
+    }
+
+    #[test]
+    fn test_group_by_annotation() {
+        let content = "main;wrap#cpu=1;a#cpu=2 2\nmain;wrap#cpu=2;a 1\nmain#numa=0 3";
+        let options = ParseOptions {
+            annotations: true,
+            ..Default::default()
+        };
+        let fg = FlameGraph::from_string_with_options(content.to_string(), &options);
+        assert_eq!(fg.get_annotation_keys(), vec!["cpu", "numa"]);
+        let grouped = fg.group_by_annotation("cpu");
+        assert_eq!(grouped.total_count(), 6);
+        let values = grouped
+            .get_stacks_at_level(1)
+            .unwrap()
+            .iter()
+            .map(|id| grouped.get_stack_short_name(id).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec!["cpu=(none)", "cpu=1", "cpu=2"]);
+        let a = grouped
+            .find_heaviest_stack_by_name("cpu=1;main;wrap;a")
+            .unwrap();
+        assert_eq!(grouped.get_annotations(&a).unwrap()["cpu"]["2"], 2);
+        assert_eq!(
+            grouped.to_folded(&ROOT_ID),
+            "cpu=(none);main#numa=0 3\ncpu=1;main;wrap#cpu=1;a#cpu=2 2\ncpu=2;main;wrap#cpu=2;a 1"
+        );
+    }
+
+    #[test]
+    fn test_filter_by_annotation() {
+        let content = "main;launch;kernel#device=gpu0 4\nmain;launch 2\nmain;copy#device=gpu1 1";
+        let options = ParseOptions {
+            annotations: true,
+            ..Default::default()
+        };
+        let fg = FlameGraph::from_string_with_options(content.to_string(), &options);
+        assert_eq!(fg.get_annotation_values("device"), vec!["gpu0", "gpu1"]);
+        assert!(fg.has_stacks_without_annotation("device"));
+        let gpu0 = fg.filter_by_annotation("device", Some("gpu0"));
+        assert_eq!(gpu0.total_count(), 4);
+        assert_eq!(gpu0.to_folded(&ROOT_ID), "main;launch;kernel#device=gpu0 4");
+        let cpu = fg.filter_by_annotation("device", None);
+        assert_eq!(cpu.total_count(), 2);
+        assert!(!gpu0.has_stacks_without_annotation("device"));
+    }
+
+    #[test]
+    fn test_find_and_export_subtree() {
+        let content = std::fs::read_to_string("tests/data/py-spy-simple.txt").unwrap();
+        let fg = FlameGraph::from_string(content, true);
+        let id = fg
+            .find_heaviest_stack_by_name("work (long_running.py:8)")
+            .unwrap();
+        assert_eq!(
+            fg.get_stack_full_name(&id),
+            Some("<module> (long_running.py:25);work (long_running.py:8)")
+        );
+        assert_eq!(
+            fg.find_heaviest_stack_by_name(
+                "<module> (long_running.py:25);work (long_running.py:8)"
+            ),
+            Some(id)
+        );
+        assert_eq!(fg.find_heaviest_stack_by_name("missing"), None);
+        assert_eq!(
+            fg.to_folded(&id),
+            "<module> (long_running.py:25);work (long_running.py:8) 421"
+        );
+        let exported = FlameGraph::from_string(fg.to_folded(&ROOT_ID), true);
+        assert_eq!(exported.total_count(), fg.total_count());
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let content = std::fs::read_to_string("tests/data/py-spy-simple.txt").unwrap();
+        let fg = FlameGraph::from_string(content, true);
+        let calls = fg.get_function_calls("<module> (long_running.py:25)");
+        let module_id = fg
+            .get_stack_id_by_full_name("<module> (long_running.py:25)")
+            .unwrap();
+        let entry = |name: &str, count, full_name: &str| CallEntry {
+            name: name.to_string(),
+            count,
+            stack_id: fg.get_stack_id_by_full_name(full_name).unwrap(),
+        };
+        assert_eq!(calls.stack_ids, vec![module_id]);
+        assert_eq!(calls.total, 639);
+        assert_eq!(
+            calls.callers,
+            vec![entry("all", 639, "<module> (long_running.py:25)")]
+        );
+        assert_eq!(
+            calls.callees,
+            vec![
+                entry(
+                    "work (long_running.py:8)",
+                    421,
+                    "<module> (long_running.py:25);work (long_running.py:8)"
+                ),
+                entry(
+                    "work (long_running.py:7)",
+                    218,
+                    "<module> (long_running.py:25);work (long_running.py:7)"
+                ),
+            ]
+        );
+        assert_eq!(fg.get_function_calls("missing"), FunctionCalls::default());
+    }
+
+    #[test]
+    fn test_parse_frame_location() {
+        assert_eq!(
+            parse_frame_location("work (long_running.py:8)"),
+            Some(("long_running.py", Some(8)))
+        );
+        assert_eq!(
+            parse_frame_location("main (/usr/lib/app.js)"),
+            Some(("/usr/lib/app.js", None))
+        );
+        assert_eq!(parse_frame_location("(garbage collector)"), None);
+        assert_eq!(parse_frame_location("main"), None);
+
+        let fg = FlameGraph::from_string("main;work (long_running.py:8) 1".to_string(), true);
+        let work = fg.find_heaviest_stack_by_name("main;work (long_running.py:8)");
+        let work = fg.get_stack(&work.unwrap()).unwrap();
+        assert_eq!(fg.get_location(work), Some(("long_running.py", Some(8))));
+        assert_eq!(fg.get_location(fg.root()), None);
+    }
+
+    #[test]
+    fn test_detect_broken_frames() {
+        let content = std::fs::read_to_string("tests/data/broken-frames.txt").unwrap();
+        let fg = FlameGraph::from_string(content, true);
+        assert_eq!(
+            *fg.diagnostics(),
+            InputDiagnostics {
+                lines_with_empty_frames: 2,
+                dropped_lines: 0,
+                ..Default::default()
+            }
+        );
+        assert_eq!(fg.total_count(), 14);
+        assert!(fg.get_stack_by_full_name("main;a;;b").is_some());
+        assert!(fg.get_stack_by_full_name("main;main;a;b").is_some());
+        // Repeated frames are as likely to be recursion, so they are not reported
+        let summary = fg.diagnostics().summary(false).unwrap();
+        assert!(summary.contains("2 stacks with empty frame names"));
+        assert!(!summary.contains("repeated"));
+    }
+
+    #[test]
+    fn test_recursion_is_not_reported() {
+        let content = std::fs::read_to_string("tests/data/recursive.txt").unwrap();
+        let fg = FlameGraph::from_string(content, true);
+        assert_eq!(*fg.diagnostics(), InputDiagnostics::default());
+        assert_eq!(fg.diagnostics().summary(false), None);
+    }
+
+    #[test]
+    fn test_repair_broken_frames() {
+        let content = std::fs::read_to_string("tests/data/broken-frames.txt").unwrap();
+        let fg = FlameGraph::from_string_with_options(
+            content,
+            &ParseOptions {
+                sorted: true,
+                repair_frames: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(fg.diagnostics().dropped_lines, 1);
+        assert!(fg
+            .diagnostics()
+            .summary(true)
+            .unwrap()
+            .contains("2 stacks with empty frame names (repaired, 1 empty stacks dropped)"));
+        assert_eq!(fg.total_count(), 10);
+        let main = fg.get_stack_by_full_name("main").unwrap();
+        assert_eq!(main.total_count, 10);
+        assert_eq!(main.children.len(), 2);
+        let child = |stack: &StackInfo, name: &str| {
+            stack
+                .children
+                .iter()
+                .map(|id| fg.get_stack(id).unwrap())
+                .find(|child| fg.get_stack_short_name_from_info(child) == name)
+                .unwrap()
+        };
+        assert_eq!(child(child(main, "a"), "b").self_count, 7);
+        // Recursive calls are kept as is
+        assert_eq!(
+            fg.get_stack_by_full_name("main;main;a;b")
+                .unwrap()
+                .self_count,
+            3
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid_utf8_and_nul_bytes() {
+        let buf = b"main;f\xff\xfeoo 3\nmain;b\0ar 2\n".to_vec();
+        let fg = FlameGraph::from_bytes_with_options(buf, &ParseOptions::default()).unwrap();
+        assert_eq!(fg.total_count(), 5);
+        assert_eq!(fg.diagnostics().invalid_utf8_sequences, 2);
+        assert_eq!(fg.diagnostics().nul_bytes, 1);
+        assert!(fg
+            .get_stack_by_full_name("main;f\u{FFFD}\u{FFFD}oo")
+            .is_some());
+        assert!(fg.get_stack_by_full_name("main;b\u{FFFD}ar").is_some());
+    }
+
+    #[test]
+    fn test_cap_frame_names() {
+        let long_a = "a".repeat(100);
+        let long_b = format!("{}b", "a".repeat(99));
+        let content = format!(
+            "main;{} 1\nmain;{} 2\nmain;{};leaf 3\n",
+            long_a, long_b, long_a
+        );
+        let fg = FlameGraph::from_string_with_options(
+            content,
+            &ParseOptions {
+                max_frame_name_length: Some(10),
+                keep_full_frame_names: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(fg.total_count(), 6);
+        assert_eq!(fg.diagnostics().capped_frame_names, 3);
+        let main = fg.get_stack_by_full_name("main").unwrap();
+        assert_eq!(main.children.len(), 2);
+        for child_id in main.children.iter() {
+            let short_name = fg.get_stack_short_name(child_id).unwrap();
+            assert!(short_name.starts_with("aaaaaaaaaa…#"));
+            let full_name = fg.get_full_frame_name(short_name).unwrap();
+            assert!(full_name == long_a || full_name == long_b);
+        }
+    }
 }
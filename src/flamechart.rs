@@ -0,0 +1,348 @@
+use crate::flame::{split_frames, unescape_frame};
+use crate::parsers::TimedSample;
+
+/// Shortest time window (in microseconds) the flame chart can be zoomed to
+const MIN_WINDOW_DURATION: u64 = 10;
+
+/// A call that stayed on the stack between two times.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartFrame {
+    pub name: String,
+    pub level: usize,
+    /// Start time in microseconds
+    pub start: u64,
+    /// End time in microseconds
+    pub end: u64,
+}
+
+impl ChartFrame {
+    pub fn duration(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+/// Frames of a profile laid out on a time axis, without merging identical stacks.
+#[derive(Debug, Clone)]
+pub struct FlameChart {
+    frames: Vec<ChartFrame>,
+    /// Indexes of the frames of each level, ordered by start time
+    levels: Vec<Vec<usize>>,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl FlameChart {
+    /// Build a flame chart from samples sorted by time. Each sample lasts until the next one and
+    /// the last one for the average interval between samples.
+    pub fn from_samples(samples: &[TimedSample]) -> Option<Self> {
+        let (first, last) = (samples.first()?, samples.last()?);
+        let average_interval = (last.time - first.time) / (samples.len() as u64 - 1).max(1);
+        let end = last.time + average_interval.max(1);
+        let mut frames: Vec<ChartFrame> = vec![];
+        // Index of the frame currently open at each level
+        let mut open: Vec<usize> = vec![];
+        for sample in samples.iter() {
+            let names = split_frames(&sample.stack)
+                .filter(|name| !name.is_empty())
+                .map(unescape_frame)
+                .collect::<Vec<_>>();
+            let common = open
+                .iter()
+                .zip(names.iter())
+                .take_while(|(idx, name)| frames[**idx].name == **name)
+                .count();
+            for idx in open.drain(common..) {
+                frames[idx].end = sample.time;
+            }
+            for (level, name) in names.iter().enumerate().skip(common) {
+                open.push(frames.len());
+                frames.push(ChartFrame {
+                    name: name.to_string(),
+                    level,
+                    start: sample.time,
+                    end: sample.time,
+                });
+            }
+        }
+        for idx in open {
+            frames[idx].end = end;
+        }
+        let mut levels: Vec<Vec<usize>> = vec![];
+        for (idx, frame) in frames.iter().enumerate() {
+            if levels.len() <= frame.level {
+                levels.resize(frame.level + 1, vec![]);
+            }
+            levels[frame.level].push(idx);
+        }
+        Some(Self {
+            frames,
+            levels,
+            start: first.time,
+            end,
+        })
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Frames of a level that overlap the time range, ordered by start time.
+    pub fn frames_in(
+        &self,
+        level: usize,
+        start: u64,
+        end: u64,
+    ) -> impl Iterator<Item = &ChartFrame> + '_ {
+        let ids = self.levels.get(level).map(|l| l.as_slice()).unwrap_or(&[]);
+        let first = ids.partition_point(|idx| self.frames[*idx].end <= start);
+        ids[first..]
+            .iter()
+            .map(|idx| &self.frames[*idx])
+            .take_while(move |frame| frame.start < end)
+    }
+
+    /// Frame of a level that is on the stack at the given time.
+    pub fn frame_at(&self, level: usize, time: u64) -> Option<&ChartFrame> {
+        self.frames_in(level, time, time + 1).next()
+    }
+}
+
+/// Flame chart with the visible time window and the selected level.
+#[derive(Debug)]
+pub struct FlameChartView {
+    pub chart: FlameChart,
+    pub window_start: u64,
+    pub window_end: u64,
+    /// Level of the selected frame. The selected frame is the one at the center of the window,
+    /// or the deepest one above it if there is none at that level.
+    pub level: usize,
+}
+
+impl FlameChartView {
+    pub fn new(chart: FlameChart) -> Self {
+        let (window_start, window_end) = (chart.start, chart.end);
+        Self {
+            chart,
+            window_start,
+            window_end,
+            level: 0,
+        }
+    }
+
+    /// Show a new version of the chart, such as the one of a reloaded file, keeping the window
+    /// and the level where they still fit
+    pub fn replace_chart(&mut self, chart: FlameChart) {
+        let (start, duration) = (self.window_start, self.window_duration());
+        self.chart = chart;
+        self.set_window(start, duration);
+        self.level = self.level.min(self.chart.num_levels().saturating_sub(1));
+    }
+
+    pub fn reset(&mut self) {
+        self.window_start = self.chart.start;
+        self.window_end = self.chart.end;
+        self.level = 0;
+    }
+
+    pub fn window_duration(&self) -> u64 {
+        self.window_end - self.window_start
+    }
+
+    /// Time at the center of the window, where frames are selected
+    pub fn cursor_time(&self) -> u64 {
+        self.window_start + self.window_duration() / 2
+    }
+
+    pub fn get_selected_frame(&self) -> Option<&ChartFrame> {
+        let time = self.cursor_time();
+        (0..=self.level)
+            .rev()
+            .find_map(|level| self.chart.frame_at(level, time))
+    }
+
+    /// Move the window by a fraction of its duration, to the right if positive
+    pub fn pan(&mut self, fraction: f64) {
+        let duration = self.window_duration();
+        let delta = (duration as f64 * fraction.abs()) as u64;
+        let start = if fraction < 0.0 {
+            self.window_start.saturating_sub(delta)
+        } else {
+            self.window_start.saturating_add(delta)
+        };
+        self.set_window(start, duration);
+    }
+
+    /// Scale the duration of the window around its center, zooming in if `factor` < 1
+    pub fn zoom_by(&mut self, factor: f64) {
+        let center = self.cursor_time();
+        let duration = (self.window_duration() as f64 * factor) as u64;
+        self.set_window(center.saturating_sub(duration / 2), duration);
+    }
+
+    pub fn zoom_to_selected(&mut self) {
+        if let Some(frame) = self.get_selected_frame() {
+            let (start, duration, level) = (frame.start, frame.duration(), frame.level);
+            self.set_window(start, duration);
+            self.level = level;
+        }
+    }
+
+    pub fn to_child(&mut self) {
+        let time = self.cursor_time();
+        let level = self.get_selected_frame().map(|f| f.level + 1).unwrap_or(0);
+        if self.chart.frame_at(level, time).is_some() {
+            self.level = level;
+        }
+    }
+
+    pub fn to_parent(&mut self) {
+        if let Some(frame) = self.get_selected_frame() {
+            self.level = frame.level.saturating_sub(1);
+        }
+    }
+
+    fn set_window(&mut self, start: u64, duration: u64) {
+        let duration = duration.clamp(
+            MIN_WINDOW_DURATION.min(self.chart.duration()),
+            self.chart.duration(),
+        );
+        let start = start.clamp(self.chart.start, self.chart.end - duration);
+        self.window_start = start;
+        self.window_end = start + duration;
+    }
+}
+
+/// Human readable duration of a number of microseconds.
+pub fn format_duration(micros: u64) -> String {
+    if micros < 1000 {
+        format!("{}µs", micros)
+    } else if micros < 1_000_000 {
+        format!("{:.2}ms", micros as f64 / 1000.0)
+    } else {
+        format!("{:.2}s", micros as f64 / 1_000_000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_chart() -> FlameChart {
+        let sample = |time, stack: &str| TimedSample {
+            time,
+            stack: stack.to_string(),
+        };
+        let samples = vec![
+            sample(100, "main;a"),
+            sample(200, "main;a;x"),
+            sample(300, "main;b"),
+            sample(400, ""),
+            sample(500, "main;a"),
+        ];
+        FlameChart::from_samples(&samples).unwrap()
+    }
+
+    #[test]
+    fn test_from_samples() {
+        let chart = get_chart();
+        assert_eq!((chart.start, chart.end), (100, 600));
+        assert_eq!(chart.num_levels(), 3);
+        let spans = |level| {
+            chart
+                .frames_in(level, chart.start, chart.end)
+                .map(|f| (f.name.as_str(), f.start, f.end))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(spans(0), vec![("main", 100, 400), ("main", 500, 600)]);
+        assert_eq!(
+            spans(1),
+            vec![("a", 100, 300), ("b", 300, 400), ("a", 500, 600)]
+        );
+        assert_eq!(spans(2), vec![("x", 200, 300)]);
+        assert_eq!(chart.frame_at(1, 299).unwrap().name, "a");
+        assert_eq!(chart.frame_at(1, 300).unwrap().name, "b");
+        assert_eq!(chart.frame_at(0, 450), None);
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut view = FlameChartView::new(get_chart());
+        // The cursor is at 350
+        assert_eq!(view.get_selected_frame().unwrap().name, "main");
+        view.to_child();
+        assert_eq!(view.get_selected_frame().unwrap().name, "b");
+        // No frame below b
+        view.to_child();
+        assert_eq!(view.level, 1);
+
+        view.zoom_to_selected();
+        assert_eq!((view.window_start, view.window_end), (300, 400));
+        view.zoom_by(4.0);
+        assert_eq!((view.window_start, view.window_end), (150, 550));
+        view.pan(-0.5);
+        assert_eq!((view.window_start, view.window_end), (100, 500));
+        view.pan(-0.5);
+        assert_eq!((view.window_start, view.window_end), (100, 500));
+        view.to_parent();
+        assert_eq!(view.get_selected_frame().unwrap().name, "main");
+
+        view.reset();
+        assert_eq!((view.window_start, view.window_end), (100, 600));
+    }
+
+    #[test]
+    fn test_replace_chart() {
+        let mut view = FlameChartView::new(get_chart());
+        view.to_child();
+        view.zoom_to_selected();
+        assert_eq!(
+            (view.window_start, view.window_end, view.level),
+            (300, 400, 1)
+        );
+        // A longer recording keeps the window
+        let longer = FlameChart::from_samples(&[
+            TimedSample {
+                time: 100,
+                stack: "main;b".to_string(),
+            },
+            TimedSample {
+                time: 1000,
+                stack: "main;b".to_string(),
+            },
+        ])
+        .unwrap();
+        view.replace_chart(longer);
+        assert_eq!(
+            (view.window_start, view.window_end, view.level),
+            (300, 400, 1)
+        );
+        // A shorter and shallower one clamps it
+        let shorter = FlameChart::from_samples(&[
+            TimedSample {
+                time: 100,
+                stack: "main".to_string(),
+            },
+            TimedSample {
+                time: 200,
+                stack: "main".to_string(),
+            },
+        ])
+        .unwrap();
+        view.replace_chart(shorter);
+        assert_eq!(
+            (view.window_start, view.window_end, view.level),
+            (200, 300, 0)
+        );
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(999), "999µs");
+        assert_eq!(format_duration(12_345), "12.35ms");
+        assert_eq!(format_duration(1_500_000), "1.50s");
+    }
+}
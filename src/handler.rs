@@ -1,15 +1,37 @@
 use std::time::Instant;
 
 use crate::{
-    app::{App, AppResult, InputBuffer},
+    app::{App, AppResult, InputBuffer, Prompt, EXCLUDE_PREFIX},
+    flame::SortColumn,
+    keys::Action,
+    macros::{MacroStep, Macros, PendingRegister},
     state::ViewKind,
 };
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 use tui_input::backend::crossterm::EventHandler;
 
+/// Actions on the selected frame or row, which have nothing to act on without samples
+const FRAME_ACTIONS: &[Action] = &[
+    Action::Zoom,
+    Action::ZoomSelected,
+    Action::SearchSelected,
+    Action::NextOccurrence,
+    Action::PreviousOccurrence,
+    Action::ToggleFocus,
+    Action::PruneFunction,
+    Action::TogglePin,
+    Action::OpenInEditor,
+    Action::ListMatches,
+];
+
 /// Handles the key events and updates the state of [`App`].
 pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
-    if app.input_buffer.is_none() {
+    app.zoom_animation.skip();
+    if let Some(pending) = app.macros.pending.take() {
+        handle_macro_register(pending, key_event, app)
+    } else if app.input_buffer.is_none() {
         let tic = Instant::now();
         handle_command(key_event, app)?;
         app.add_elapsed("handle_key_events", tic.elapsed());
@@ -19,50 +41,172 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
     }
 }
 
+/// Handles the mouse events: a click on a zoom breadcrumb zooms out to it.
+pub fn handle_mouse_events(mouse_event: MouseEvent, app: &mut App) -> AppResult<()> {
+    if mouse_event.kind != MouseEventKind::Down(MouseButton::Left) {
+        return Ok(());
+    }
+    let clicked = app.breadcrumbs.iter().find(|(area, _)| {
+        area.contains(ratatui::layout::Position {
+            x: mouse_event.column,
+            y: mouse_event.row,
+        })
+    });
+    if let Some((_, stack_id)) = clicked {
+        let stack_id = *stack_id;
+        app.flamegraph_view.zoom_out_to(stack_id);
+    }
+    Ok(())
+}
+
 /// Handle key events as commands
 pub fn handle_command(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
-    let mut key_handled = handle_command_generic(key_event, app)?;
-    if !key_handled {
-        if app.flamegraph_state().view_kind == ViewKind::FlameGraph {
-            key_handled = handle_command_flamegraph(key_event, app)?;
-        } else {
-            key_handled = handle_command_table(key_event, app)?;
+    let Some(action) = app.keymap.action(&key_event) else {
+        return Ok(());
+    };
+    // Quitting ends the guided tour first
+    if action == Action::Quit && app.tutorial.is_some() {
+        app.end_tutorial();
+        return Ok(());
+    }
+    match action {
+        Action::RecordMacro => {
+            app.toggle_macro_recording();
         }
+        Action::ReplayMacro => {
+            app.macros.pending = Some(PendingRegister::Replay);
+        }
+        _ => handle_action(action, app)?,
+    }
+    // The search step is done once the search is submitted
+    if action != Action::Search {
+        app.advance_tutorial(action);
+    }
+    Ok(())
+}
+
+/// Run an action, adding it to the macro being recorded
+pub fn handle_action(action: Action, app: &mut App) -> AppResult<()> {
+    // The prompts are recorded as the text submitted from them
+    if !matches!(action, Action::Quit | Action::Search | Action::Goto) {
+        app.macros.record(MacroStep::Action(action));
+    }
+    #[cfg(feature = "python")]
+    if app.settings_popup.is_some() {
+        return handle_command_settings_popup(action, app);
+    }
+    #[cfg(feature = "python")]
+    if app.completion_banner.is_some() {
+        return handle_command_completion_banner(action, app);
+    }
+    #[cfg(feature = "python")]
+    if app.thread_dump.is_some() {
+        return handle_command_thread_dump(action, app);
+    }
+    if app.match_list.is_some() {
+        return handle_command_match_list(action, app);
+    }
+    if app.thread_list.is_some() {
+        return handle_command_thread_list(action, app);
+    }
+    if app.process_list.is_some() {
+        return handle_command_process_list(action, app);
+    }
+    let has_frames = app.flamegraph_state().view_kind == ViewKind::FlameChart
+        || app.flamegraph().total_count() > 0;
+    if !has_frames && FRAME_ACTIONS.contains(&action) {
+        app.set_transient_message("No samples to act on");
+        return Ok(());
     }
-    if key_handled && app.transient_message.is_some() {
-        app.clear_transient_message();
+    // The message of the previous action is replaced by the one of this action, if any
+    let previous_message = app.transient_message.take();
+    let mut key_handled = handle_command_generic(action, app)?;
+    if !key_handled {
+        key_handled = match app.flamegraph_state().view_kind {
+            ViewKind::FlameGraph => handle_command_flamegraph(action, app)?,
+            ViewKind::Table if app.flamegraph_state().drill_down.is_some() => {
+                handle_command_drill_down(action, app)?
+            }
+            ViewKind::Table => handle_command_table(action, app)?,
+            ViewKind::CallTree => handle_command_call_tree(action, app)?,
+            ViewKind::FlameChart => handle_command_flame_chart(action, app)?,
+        };
+    }
+    if !key_handled && app.transient_message.is_none() {
+        app.transient_message = previous_message;
     }
     Ok(())
 }
 
-pub fn handle_command_generic(key_event: KeyEvent, app: &mut App) -> AppResult<bool> {
+pub fn handle_command_generic(action: Action, app: &mut App) -> AppResult<bool> {
     let mut key_handled = true;
-    match key_event.code {
-        // Exit application on `q`
-        KeyCode::Char('q') => {
+    match action {
+        Action::Quit => {
             app.quit();
         }
-        // Exit application on `Ctrl-C`
-        KeyCode::Char('c') | KeyCode::Char('C') => {
-            if key_event.modifiers == KeyModifiers::CONTROL {
-                app.quit();
-            }
-        }
-        KeyCode::Char('z') => {
+        Action::ToggleFreeze => {
             app.flamegraph_view.state.toggle_freeze();
         }
-        KeyCode::Tab => {
-            app.flamegraph_view.state.toggle_view_kind();
+        #[cfg(feature = "python")]
+        Action::CycleWindow => {
+            app.cycle_window();
+        }
+        #[cfg(feature = "python")]
+        Action::SamplerSettings => {
+            app.open_settings_popup();
+        }
+        #[cfg(feature = "python")]
+        Action::Save => {
+            app.save();
+        }
+        #[cfg(feature = "python")]
+        Action::ClearSamples => {
+            app.clear_samples();
+        }
+        #[cfg(feature = "python")]
+        Action::DumpThreads => {
+            app.dump_threads();
+        }
+        Action::CaptureBaseline => {
+            app.toggle_live_baseline();
+        }
+        #[cfg(feature = "python")]
+        Action::ToggleCommandOutput => {
+            app.toggle_command_output();
         }
-        KeyCode::Char('/') => {
-            app.input_buffer = Some(InputBuffer {
-                buffer: tui_input::Input::new("".to_string()),
-                cursor: None,
-            });
+        Action::SwitchView => {
+            app.switch_view();
         }
-        KeyCode::Char('?') => {
+        Action::Search => {
+            app.open_search_prompt();
+        }
+        Action::Goto => {
+            app.input_buffer = Some(InputBuffer::new(Prompt::Goto));
+        }
+        Action::KeepSearch => {
+            app.keep_search();
+        }
+        Action::ToggleDebug => {
             app.toggle_debug();
         }
+        Action::ToggleStats => {
+            app.toggle_stats();
+        }
+        Action::ToggleWatch => {
+            app.toggle_watch();
+        }
+        Action::ToggleDurations => {
+            app.toggle_durations();
+        }
+        Action::ToggleParentShare => {
+            app.toggle_parent_share();
+        }
+        Action::ListThreads => {
+            app.open_thread_list();
+        }
+        Action::ListProcesses => {
+            app.open_process_list();
+        }
         _ => {
             key_handled = false;
         }
@@ -70,51 +214,134 @@ pub fn handle_command_generic(key_event: KeyEvent, app: &mut App) -> AppResult<b
     Ok(key_handled)
 }
 
-fn handle_command_flamegraph(key_event: KeyEvent, app: &mut App) -> AppResult<bool> {
+fn handle_command_flamegraph(action: Action, app: &mut App) -> AppResult<bool> {
+    // Sideways, moving right goes deeper and moving down goes to the next sibling
+    let action = match (app.flamegraph_state().sideways, action) {
+        (true, Action::MoveRight) => Action::MoveDown,
+        (true, Action::MoveLeft) => Action::MoveUp,
+        (true, Action::MoveDown) => Action::MoveRight,
+        (true, Action::MoveUp) => Action::MoveLeft,
+        _ => action,
+    };
     let mut key_handled = true;
-    match key_event.code {
-        KeyCode::Right | KeyCode::Char('l') => {
+    match action {
+        Action::MoveRight => {
             app.flamegraph_view.to_next_sibling();
         }
-        KeyCode::Left | KeyCode::Char('h') => {
+        Action::MoveLeft => {
             app.flamegraph_view.to_previous_sibling();
         }
-        KeyCode::Down | KeyCode::Char('j') => {
+        Action::MoveDown => {
             app.flamegraph_view.to_child_stack();
         }
-        KeyCode::Up | KeyCode::Char('k') => {
+        Action::MoveUp => {
             app.flamegraph_view.to_parent_stack();
         }
-        KeyCode::Char('G') => {
+        Action::ScrollBottom => {
             app.flamegraph_view.scroll_bottom();
         }
-        KeyCode::Char('g') => {
+        Action::ScrollTop => {
             app.flamegraph_view.scroll_top();
         }
-        KeyCode::Char('f') => {
+        Action::ScrollDown => {
             app.flamegraph_view.page_down();
         }
-        KeyCode::Char('b') => {
+        Action::ScrollUp => {
             app.flamegraph_view.page_up();
         }
-        KeyCode::Char('n') => {
-            app.flamegraph_view.to_next_search_result();
+        Action::NextMatch => {
+            app.to_next_search_result(true);
+        }
+        Action::PreviousMatch => {
+            app.to_next_search_result(false);
+        }
+        Action::ListMatches => {
+            app.open_match_list();
         }
-        KeyCode::Char('N') => {
-            app.flamegraph_view.to_previous_search_result();
+        Action::NextOccurrence => {
+            app.to_next_occurrence(true);
         }
-        KeyCode::Enter => {
+        Action::PreviousOccurrence => {
+            app.to_next_occurrence(false);
+        }
+        Action::Zoom => {
             app.flamegraph_view.set_zoom();
         }
-        KeyCode::Esc => {
+        Action::Unzoom => {
             app.flamegraph_view.unset_zoom();
         }
-        KeyCode::Char('r') => {
+        Action::ZoomParent => {
+            app.flamegraph_view.zoom_to_parent();
+        }
+        Action::ZoomBack => {
+            app.flamegraph_view.step_zoom_history(false);
+        }
+        Action::ZoomForward => {
+            app.flamegraph_view.step_zoom_history(true);
+        }
+        Action::Reset => {
             app.flamegraph_view.reset();
         }
-        KeyCode::Char('#') => {
+        Action::SearchSelected => {
             app.search_selected();
         }
+        Action::CyclePalette => {
+            app.cycle_palette();
+        }
+        Action::CycleSeparators => {
+            app.cycle_separators();
+        }
+        Action::ToggleDetails => {
+            app.toggle_details();
+        }
+        Action::ToggleSource => {
+            app.toggle_source();
+        }
+        Action::ToggleRuler => {
+            app.toggle_ruler();
+        }
+        Action::ToggleSideways => {
+            app.toggle_sideways();
+        }
+        Action::ToggleFrameCounts => {
+            app.cycle_frame_labels();
+        }
+        Action::SortByTotal => {
+            app.set_sort_column(SortColumn::Total);
+        }
+        Action::SortByOwn => {
+            app.set_sort_column(SortColumn::Own);
+        }
+        Action::SortByName => {
+            app.set_sort_column(SortColumn::Name);
+        }
+        Action::ToggleFocus => {
+            app.toggle_focus();
+        }
+        Action::PruneFunction => {
+            app.prune_selected();
+        }
+        Action::UndoPrune => {
+            app.undo_prune();
+        }
+        Action::ToggleMergeLines => {
+            app.toggle_merge_lines();
+        }
+        Action::CycleGroupBy => {
+            app.cycle_group_by();
+        }
+        Action::CycleLane => {
+            app.cycle_lane();
+        }
+        Action::TogglePin => {
+            app.toggle_pin();
+        }
+        Action::TogglePrefix => {
+            app.toggle_single_child_prefix();
+        }
+        Action::OpenInEditor => {
+            app.open_in_editor();
+        }
         _ => {
             key_handled = false;
         }
@@ -122,33 +349,176 @@ fn handle_command_flamegraph(key_event: KeyEvent, app: &mut App) -> AppResult<bo
     Ok(key_handled)
 }
 
-fn handle_command_table(key_event: KeyEvent, app: &mut App) -> AppResult<bool> {
+fn handle_command_table(action: Action, app: &mut App) -> AppResult<bool> {
     let mut key_handled = true;
-    match key_event.code {
-        KeyCode::Down | KeyCode::Char('j') => {
+    match action {
+        Action::MoveDown => {
             app.flamegraph_view.to_next_row();
         }
-        KeyCode::Up | KeyCode::Char('k') => {
+        Action::MoveUp => {
             app.flamegraph_view.to_previous_row();
         }
-        KeyCode::Char('f') => {
+        Action::ScrollDown => {
             app.flamegraph_view.scroll_next_rows();
         }
-        KeyCode::Char('b') => {
+        Action::ScrollUp => {
             app.flamegraph_view.scroll_previous_rows();
         }
-        KeyCode::Char('1') => {
-            app.flamegraph_view.set_sort_by_total();
+        Action::SortByTotal => {
+            app.set_sort_column(SortColumn::Total);
+        }
+        Action::SortByOwn => {
+            app.set_sort_column(SortColumn::Own);
+        }
+        Action::SortByName => {
+            app.set_sort_column(SortColumn::Name);
         }
-        KeyCode::Char('2') => {
-            app.flamegraph_view.set_sort_by_own();
+        Action::CycleAggregation => {
+            app.cycle_aggregation();
         }
-        KeyCode::Char('r') => {
+        Action::Reset => {
             app.flamegraph_view.reset();
         }
-        KeyCode::Enter => {
+        Action::Zoom => {
+            app.flamegraph_view.open_drill_down();
+        }
+        Action::SearchSelected => {
             app.search_selected_row();
         }
+        Action::ZoomSelected => {
+            app.zoom_selected_row();
+        }
+        Action::OpenInEditor => {
+            app.open_in_editor();
+        }
+        _ => {
+            key_handled = false;
+        }
+    }
+    Ok(key_handled)
+}
+
+fn handle_command_call_tree(action: Action, app: &mut App) -> AppResult<bool> {
+    let mut key_handled = true;
+    match action {
+        Action::MoveDown => {
+            app.flamegraph_view.to_next_call_tree_row(1);
+        }
+        Action::MoveUp => {
+            app.flamegraph_view.to_previous_call_tree_row(1);
+        }
+        Action::MoveRight => {
+            app.flamegraph_view.expand_or_enter_call_tree_row();
+        }
+        Action::MoveLeft => {
+            app.flamegraph_view.collapse_or_leave_call_tree_row();
+        }
+        Action::ScrollDown => {
+            app.flamegraph_view.scroll_call_tree_rows(true);
+        }
+        Action::ScrollUp => {
+            app.flamegraph_view.scroll_call_tree_rows(false);
+        }
+        Action::ScrollTop => {
+            app.flamegraph_view.to_call_tree_top();
+        }
+        Action::ScrollBottom => {
+            app.flamegraph_view.to_call_tree_bottom();
+        }
+        Action::Zoom => {
+            app.flamegraph_view.toggle_call_tree_row();
+        }
+        Action::NextMatch => {
+            app.to_next_search_result(true);
+        }
+        Action::PreviousMatch => {
+            app.to_next_search_result(false);
+        }
+        Action::NextOccurrence => {
+            app.to_next_occurrence(true);
+        }
+        Action::PreviousOccurrence => {
+            app.to_next_occurrence(false);
+        }
+        Action::SearchSelected => {
+            app.search_selected();
+        }
+        Action::OpenInEditor => {
+            app.open_in_editor();
+        }
+        _ => {
+            key_handled = false;
+        }
+    }
+    // Frames selected by a search may sit under folded ones
+    app.flamegraph_view.reveal_selected_in_call_tree();
+    Ok(key_handled)
+}
+
+fn handle_command_drill_down(action: Action, app: &mut App) -> AppResult<bool> {
+    let mut key_handled = true;
+    match action {
+        Action::MoveDown => {
+            app.flamegraph_view.to_next_drill_down_entry();
+        }
+        Action::MoveUp => {
+            app.flamegraph_view.to_previous_drill_down_entry();
+        }
+        Action::Zoom => {
+            app.flamegraph_view.jump_to_drill_down_entry();
+        }
+        Action::Unzoom => {
+            app.flamegraph_view.close_drill_down();
+        }
+        Action::Reset => {
+            app.flamegraph_view.reset();
+        }
+        _ => {
+            key_handled = false;
+        }
+    }
+    Ok(key_handled)
+}
+
+fn handle_command_flame_chart(action: Action, app: &mut App) -> AppResult<bool> {
+    let Some(chart) = app.flame_chart.as_mut() else {
+        return Ok(false);
+    };
+    let mut key_handled = true;
+    match action {
+        Action::MoveLeft => {
+            chart.pan(-0.1);
+        }
+        Action::MoveRight => {
+            chart.pan(0.1);
+        }
+        Action::ScrollUp => {
+            chart.pan(-1.0);
+        }
+        Action::ScrollDown => {
+            chart.pan(1.0);
+        }
+        Action::MoveDown => {
+            chart.to_child();
+        }
+        Action::MoveUp => {
+            chart.to_parent();
+        }
+        Action::ZoomIn => {
+            chart.zoom_by(0.5);
+        }
+        Action::ZoomOut => {
+            chart.zoom_by(2.0);
+        }
+        Action::Zoom => {
+            chart.zoom_to_selected();
+        }
+        Action::Unzoom | Action::Reset => {
+            chart.reset();
+        }
+        Action::CyclePalette => {
+            app.cycle_palette();
+        }
         _ => {
             key_handled = false;
         }
@@ -160,21 +530,205 @@ pub fn handle_input_buffer(key_event: KeyEvent, app: &mut App) -> AppResult<()>
     if let Some(input) = app.input_buffer.as_mut() {
         match key_event.code {
             KeyCode::Esc => {
+                app.cancel_search_preview();
                 app.input_buffer = None;
             }
+            KeyCode::Char(c @ ('c' | 'r'))
+                if key_event.modifiers.contains(KeyModifiers::ALT)
+                    && input.prompt == Prompt::Search =>
+            {
+                app.toggle_search_mode(c == 'r');
+                app.search_input_changed();
+            }
+            KeyCode::Up | KeyCode::Down if input.prompt == Prompt::Search => {
+                app.recall_search(key_event.code == KeyCode::Up);
+            }
             KeyCode::Enter => {
-                if input.buffer.value().is_empty() {
-                    app.flamegraph_view.unset_manual_search_pattern();
-                } else {
-                    let re_pattern = input.buffer.value().to_string();
-                    app.set_manual_search_pattern(re_pattern.as_str(), true);
+                if input.error.is_some() {
+                    app.cancel_search_preview();
                 }
+                let Some(input) = app.input_buffer.as_ref() else {
+                    return Ok(());
+                };
+                let value = input.buffer.value().to_string();
+                let prompt = input.prompt;
                 app.input_buffer = None;
+                match prompt {
+                    Prompt::Search => submit_search(&value, app),
+                    Prompt::Goto => submit_goto(&value, app),
+                }
             }
             _ => {
-                input.buffer.handle_event(&Event::Key(key_event));
+                let changed = input.buffer.handle_event(&Event::Key(key_event));
+                if changed.is_some_and(|changed| changed.value) {
+                    app.search_input_changed();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn submit_search(text: &str, app: &mut App) {
+    app.add_search_history(text);
+    // The modes are applied here, so that the recorded regex replays the same search
+    let search = app.search_text_to_regex(text);
+    search_regex(&search, app);
+    app.advance_tutorial(Action::Search);
+}
+
+fn search_regex(re_pattern: &str, app: &mut App) {
+    app.macros.record(MacroStep::Search {
+        search: re_pattern.to_string(),
+    });
+    if let Some(pattern) = re_pattern.strip_prefix(EXCLUDE_PREFIX) {
+        app.set_exclude_pattern(pattern);
+    } else if re_pattern.is_empty() {
+        app.flamegraph_view.unset_manual_search_pattern();
+    } else {
+        app.set_manual_search_pattern(re_pattern, true);
+    }
+}
+
+fn submit_goto(query: &str, app: &mut App) {
+    app.macros.record(MacroStep::Goto {
+        goto: query.to_string(),
+    });
+    if !query.is_empty() {
+        app.goto(query);
+    }
+}
+
+/// Take the key pressed after the record or replay keys as the register. Any other key cancels.
+fn handle_macro_register(
+    pending: PendingRegister,
+    key_event: KeyEvent,
+    app: &mut App,
+) -> AppResult<()> {
+    let KeyCode::Char(register) = key_event.code else {
+        return Ok(());
+    };
+    if !Macros::is_valid_register(register) {
+        return Ok(());
+    }
+    match pending {
+        PendingRegister::Record => {
+            app.macros.start_recording(register);
+        }
+        PendingRegister::Replay => {
+            let Some(steps) = app.macros.get(register).map(|steps| steps.to_vec()) else {
+                app.set_transient_message(&format!("Nothing recorded in @{}", register));
+                return Ok(());
+            };
+            for step in steps {
+                match step {
+                    MacroStep::Action(action) => handle_action(action, app)?,
+                    MacroStep::Search { search } => search_regex(&search, app),
+                    MacroStep::Goto { goto } => submit_goto(&goto, app),
+                }
             }
         }
     }
     Ok(())
 }
+
+fn handle_command_match_list(action: Action, app: &mut App) -> AppResult<()> {
+    let Some(match_list) = app.match_list.as_mut() else {
+        return Ok(());
+    };
+    match action {
+        Action::MoveDown => match_list.to_next_row(),
+        Action::MoveUp => match_list.to_previous_row(),
+        Action::ScrollDown => match_list.page_down(),
+        Action::ScrollUp => match_list.page_up(),
+        Action::Zoom => app.jump_to_selected_match(),
+        Action::Unzoom | Action::ListMatches => app.close_match_list(),
+        Action::Quit => app.quit(),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_command_thread_list(action: Action, app: &mut App) -> AppResult<()> {
+    let Some(thread_list) = app.thread_list.as_mut() else {
+        return Ok(());
+    };
+    match action {
+        Action::MoveDown => thread_list.to_next_row(),
+        Action::MoveUp => thread_list.to_previous_row(),
+        Action::Zoom => app.toggle_selected_thread(),
+        Action::Unzoom | Action::ListThreads => app.close_thread_list(),
+        Action::Quit => app.quit(),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_command_process_list(action: Action, app: &mut App) -> AppResult<()> {
+    let Some(process_list) = app.process_list.as_mut() else {
+        return Ok(());
+    };
+    match action {
+        Action::MoveDown => process_list.to_next_row(),
+        Action::MoveUp => process_list.to_previous_row(),
+        Action::Zoom => app.toggle_selected_process(),
+        Action::ZoomSelected => app.zoom_to_selected_process(),
+        Action::Unzoom | Action::ListProcesses => app.close_process_list(),
+        Action::Quit => app.quit(),
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(feature = "python")]
+fn handle_command_settings_popup(action: Action, app: &mut App) -> AppResult<()> {
+    let Some(popup) = app.settings_popup.as_mut() else {
+        return Ok(());
+    };
+    match action {
+        Action::MoveDown => popup.to_next_row(),
+        Action::MoveUp => popup.to_previous_row(),
+        Action::MoveRight => popup.change_selected(true),
+        Action::MoveLeft => popup.change_selected(false),
+        Action::Zoom => app.apply_settings_popup(),
+        Action::Unzoom | Action::SamplerSettings => app.close_settings_popup(),
+        Action::Quit => app.quit(),
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(feature = "python")]
+fn handle_command_thread_dump(action: Action, app: &mut App) -> AppResult<()> {
+    let Some(thread_dump) = app.thread_dump.as_mut() else {
+        return Ok(());
+    };
+    match action {
+        Action::MoveDown => thread_dump.scroll(1),
+        Action::MoveUp => thread_dump.scroll(-1),
+        Action::ScrollDown => thread_dump.page_down(),
+        Action::ScrollUp => thread_dump.page_up(),
+        Action::ScrollTop => thread_dump.offset = 0,
+        Action::ScrollBottom => thread_dump.scroll(isize::MAX),
+        // Take a new dump in place of this one
+        Action::DumpThreads => app.dump_threads(),
+        Action::Unzoom => app.close_thread_dump(),
+        Action::Quit => app.quit(),
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(feature = "python")]
+fn handle_command_completion_banner(action: Action, app: &mut App) -> AppResult<()> {
+    match action {
+        Action::Save => {
+            app.close_completion_banner();
+            app.save();
+        }
+        Action::Zoom | Action::Unzoom => app.close_completion_banner(),
+        Action::Quit => app.quit(),
+        _ => {}
+    }
+    Ok(())
+}
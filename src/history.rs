@@ -0,0 +1,115 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::config::Config;
+
+/// Searches remembered, the oldest ones being forgotten first
+const MAX_SEARCH_HISTORY: usize = 100;
+
+/// Text submitted in the search prompt, oldest first and without duplicates, to recall it with
+/// the arrow keys. It can be saved to a file next to the config file to be kept across sessions.
+#[derive(Debug, Default)]
+pub struct SearchHistory {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl SearchHistory {
+    /// Location of the saved history, next to the config file
+    pub fn default_path() -> Option<PathBuf> {
+        Some(Config::path()?.with_file_name("search_history"))
+    }
+
+    /// History read from `path` (a missing file being an empty history), saved back to it on
+    /// every search
+    pub fn load(path: &Path) -> Result<Self> {
+        let entries = if path.exists() {
+            std::fs::read_to_string(path)
+                .with_context(|| format!("Could not read search history {}", path.display()))?
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(|line| line.to_string())
+                .collect()
+        } else {
+            vec![]
+        };
+        let mut history = Self {
+            entries: vec![],
+            path: Some(path.to_path_buf()),
+        };
+        for entry in entries {
+            history.remember(entry);
+        }
+        Ok(history)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|entry| entry.as_str())
+    }
+
+    /// Add a search as the most recent one, saving the history if it has a file
+    pub fn push(&mut self, text: &str) -> Result<()> {
+        if text.is_empty() || text.contains('\n') {
+            return Ok(());
+        }
+        self.remember(text.to_string());
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.entries.join("\n") + "\n")
+            .with_context(|| format!("Could not write search history {}", path.display()))
+    }
+
+    fn remember(&mut self, text: String) {
+        self.entries.retain(|entry| *entry != text);
+        self.entries.push(text);
+        if self.entries.len() > MAX_SEARCH_HISTORY {
+            self.entries.remove(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push() {
+        let mut history = SearchHistory::default();
+        for text in ["json", "gc_", "", "json"] {
+            history.push(text).unwrap();
+        }
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0), Some("gc_"));
+        assert_eq!(history.get(1), Some("json"));
+        for i in 0..MAX_SEARCH_HISTORY {
+            history.push(&i.to_string()).unwrap();
+        }
+        assert_eq!(history.len(), MAX_SEARCH_HISTORY);
+        assert_eq!(history.get(0), Some("0"));
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("flamelens-history-{}", std::process::id()));
+        let mut history = SearchHistory::load(&path).unwrap();
+        assert!(history.is_empty());
+        history.push("json\\.loads").unwrap();
+        history.push("!gc").unwrap();
+        let history = SearchHistory::load(&path).unwrap();
+        assert_eq!(history.get(1), Some("!gc"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
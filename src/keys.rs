@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+/// Actions that can be bound to keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Quit,
+    ToggleFreeze,
+    /// Cycle the sliding window of the live flamegraph
+    CycleWindow,
+    /// Change the py-spy sampling options of the live flamegraph
+    SamplerSettings,
+    /// Write all the samples of the live session to a file
+    Save,
+    /// Discard the samples of the live session collected so far
+    ClearSamples,
+    /// Show the current stacks of all the threads of the live process
+    DumpThreads,
+    /// Capture the live samples to diff the ones collected since then against them
+    CaptureBaseline,
+    /// Show or hide the last lines written by the profiled command
+    ToggleCommandOutput,
+    SwitchView,
+    Search,
+    /// Jump to the heaviest frame of a function typed in a prompt
+    Goto,
+    ToggleDebug,
+    ToggleStats,
+    /// Previous sibling frame
+    MoveLeft,
+    /// Next sibling frame
+    MoveRight,
+    /// Child frame or next row of the table
+    MoveDown,
+    /// Parent frame or previous row of the table
+    MoveUp,
+    ScrollDown,
+    ScrollUp,
+    ScrollBottom,
+    ScrollTop,
+    NextMatch,
+    PreviousMatch,
+    /// List the frames matching the search to jump to one of them
+    ListMatches,
+    /// Keep highlighting the searched pattern in its own color, to search for another one
+    KeepSearch,
+    /// Next frame of the same function as the selected frame
+    NextOccurrence,
+    /// Previous frame of the same function as the selected frame
+    PreviousOccurrence,
+    /// Zoom in on the selected frame or show the callers and callees of the selected row
+    Zoom,
+    Unzoom,
+    /// Zoom out to the parent of the zoomed frame
+    ZoomParent,
+    /// Go back to the previous zoom
+    ZoomBack,
+    /// Go forward to the next zoom after going back
+    ZoomForward,
+    Reset,
+    SearchSelected,
+    /// Switch to the flamegraph zoomed on the heaviest frame of the function of the selected row,
+    /// its other frames being highlighted
+    ZoomSelected,
+    CyclePalette,
+    /// Cycle how sibling frames are told apart: no separators, blank cells or alternating shades
+    CycleSeparators,
+    ToggleDetails,
+    /// Show or hide the source around the line of the selected frame
+    ToggleSource,
+    /// Show or hide the ruler marking quarters of the flamegraph width
+    ToggleRuler,
+    /// Draw the flamegraph from left to right instead of from top to bottom, or back
+    ToggleSideways,
+    /// Show the count of the sorted column of the Top view at the end of wide frames, then its
+    /// share of the samples, then nothing
+    ToggleFrameCounts,
+    /// Show the samples as the time they stand for, when the sampling rate is known, or back
+    ToggleDurations,
+    /// Give the shares of the selected frame and the counts in the frames relative to the parent
+    /// frame, or back to all samples
+    ToggleParentShare,
+    /// Only keep the stacks going through the function of the selected frame, or all of them again
+    ToggleFocus,
+    /// Remove the function of the selected frame from every stack, splicing its children up
+    PruneFunction,
+    /// Bring back the last function pruned
+    UndoPrune,
+    /// Merge the frames of a function that only differ by their line, or bring them back
+    ToggleMergeLines,
+    /// Group the stacks by the values of the next annotation key, or stop grouping
+    CycleGroupBy,
+    /// Only show the stacks of the next device lane (the CPU, then every GPU), or all of them
+    CycleLane,
+    /// List the threads to show or hide the stacks of each of them
+    ListThreads,
+    /// List the processes as a tree to show or hide their stacks or zoom in on one of them
+    ListProcesses,
+    /// Pin the selected frame to compare the selected frames with it, or unpin it
+    TogglePin,
+    /// Open the source file of the selected frame in `$EDITOR`, at its line
+    OpenInEditor,
+    /// Reveal or skip the chain of frames with a single child at the top of the flamegraph
+    TogglePrefix,
+    /// Watch the searched or selected function, or stop watching it
+    ToggleWatch,
+    SortByTotal,
+    SortByOwn,
+    SortByName,
+    /// Count the samples of the Top view by function, file or module, in turn
+    CycleAggregation,
+    /// Narrow the time window of the flame chart
+    ZoomIn,
+    /// Widen the time window of the flame chart
+    ZoomOut,
+    /// Start recording a macro into the register given by the next key, or stop recording
+    RecordMacro,
+    /// Replay the macro of the register given by the next key
+    ReplayMacro,
+}
+
+const DEFAULT_BINDINGS: &[(Action, &[&str])] = &[
+    (Action::Quit, &["q", "ctrl-c"]),
+    (Action::ToggleFreeze, &["z"]),
+    (Action::CycleWindow, &["w"]),
+    (Action::Save, &["ctrl-s"]),
+    (Action::ClearSamples, &["x"]),
+    (Action::DumpThreads, &["D"]),
+    (Action::CaptureBaseline, &["B"]),
+    (Action::ToggleCommandOutput, &["I"]),
+    (Action::SamplerSettings, &["S"]),
+    (Action::SwitchView, &["tab"]),
+    (Action::Search, &["/"]),
+    (Action::Goto, &["o"]),
+    (Action::ToggleDebug, &["?"]),
+    (Action::ToggleStats, &["i"]),
+    (Action::MoveLeft, &["h", "left"]),
+    (Action::MoveRight, &["l", "right"]),
+    (Action::MoveDown, &["j", "down"]),
+    (Action::MoveUp, &["k", "up"]),
+    (Action::ScrollDown, &["f"]),
+    (Action::ScrollUp, &["b"]),
+    (Action::ScrollBottom, &["G"]),
+    (Action::ScrollTop, &["g"]),
+    (Action::NextMatch, &["n"]),
+    (Action::PreviousMatch, &["N"]),
+    (Action::ListMatches, &["M"]),
+    (Action::KeepSearch, &["&"]),
+    (Action::NextOccurrence, &[">"]),
+    (Action::PreviousOccurrence, &["<"]),
+    (Action::Zoom, &["enter"]),
+    (Action::Unzoom, &["esc"]),
+    (Action::ZoomParent, &["u"]),
+    (Action::ZoomBack, &["ctrl-o", "["]),
+    (Action::ZoomForward, &["]"]),
+    (Action::Reset, &["r"]),
+    (Action::SearchSelected, &["#"]),
+    (Action::ZoomSelected, &["Z"]),
+    (Action::CyclePalette, &["c"]),
+    (Action::CycleSeparators, &["s"]),
+    (Action::ToggleDetails, &["d"]),
+    (Action::ToggleSource, &["P"]),
+    (Action::ToggleRuler, &["R"]),
+    (Action::ToggleSideways, &["O"]),
+    (Action::ToggleFrameCounts, &["C"]),
+    (Action::ToggleDurations, &["T"]),
+    (Action::ToggleParentShare, &["%"]),
+    (Action::ToggleFocus, &["F"]),
+    (Action::PruneFunction, &["H"]),
+    (Action::UndoPrune, &["U"]),
+    (Action::ToggleMergeLines, &["L"]),
+    (Action::CycleGroupBy, &["A"]),
+    (Action::CycleLane, &["v"]),
+    (Action::ListThreads, &["y"]),
+    (Action::ListProcesses, &["Y"]),
+    (Action::TogglePin, &["m"]),
+    (Action::OpenInEditor, &["e"]),
+    (Action::TogglePrefix, &["p"]),
+    (Action::ToggleWatch, &["W"]),
+    (Action::SortByTotal, &["1"]),
+    (Action::SortByOwn, &["2"]),
+    (Action::SortByName, &["3"]),
+    (Action::CycleAggregation, &["a"]),
+    (Action::ZoomIn, &["+", "="]),
+    (Action::ZoomOut, &["-"]),
+    (Action::RecordMacro, &["Q"]),
+    (Action::ReplayMacro, &["@"]),
+];
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("space", KeyCode::Char(' ')),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pgup", KeyCode::PageUp),
+    ("pgdown", KeyCode::PageDown),
+];
+
+/// A key with its modifiers, written as e.g. `q`, `G`, `ctrl-c`, `alt-enter` or `f5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        // Split off modifiers, taking care of bindings such as "-" or "ctrl--"
+        while let Some((modifier, key)) = rest.split_once('-') {
+            if key.is_empty() {
+                break;
+            }
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier in key: {}", s)),
+            };
+            rest = key;
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let name = rest.to_lowercase();
+                if let Some((_, code)) = NAMED_KEYS.iter().find(|(n, _)| *n == name) {
+                    *code
+                } else if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    KeyCode::F(n)
+                } else {
+                    return Err(format!("unknown key: {}", s));
+                }
+            }
+        };
+        Ok(Self { code, modifiers })
+    }
+
+    /// Whether a key event triggers this binding. Shift is implied by the case of characters.
+    pub fn matches(&self, key_event: &KeyEvent) -> bool {
+        let ignored = match key_event.code {
+            KeyCode::Char(_) => KeyModifiers::SHIFT,
+            _ => KeyModifiers::NONE,
+        };
+        self.code == key_event.code
+            && self.modifiers.difference(ignored) == key_event.modifiers.difference(ignored)
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s)
+    }
+}
+
+impl From<KeyBinding> for String {
+    fn from(binding: KeyBinding) -> Self {
+        binding.to_string()
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl-"),
+            (KeyModifiers::ALT, "alt-"),
+            (KeyModifiers::SHIFT, "shift-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}", name)?;
+            }
+        }
+        if let Some((name, _)) = NAMED_KEYS.iter().find(|(_, code)| *code == self.code) {
+            write!(f, "{}", name)
+        } else {
+            match self.code {
+                KeyCode::Char(c) => write!(f, "{}", c),
+                KeyCode::F(n) => write!(f, "f{}", n),
+                code => write!(f, "{:?}", code),
+            }
+        }
+    }
+}
+
+/// One or more keys bound to an action in the config file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(KeyBinding),
+    Many(Vec<KeyBinding>),
+}
+
+impl KeyList {
+    fn into_vec(self) -> Vec<KeyBinding> {
+        match self {
+            KeyList::One(binding) => vec![binding],
+            KeyList::Many(bindings) => bindings,
+        }
+    }
+}
+
+/// Mapping from actions to the keys that trigger them.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<KeyBinding>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .map(|(action, keys)| {
+                let keys = keys
+                    .iter()
+                    .map(|k| KeyBinding::parse(k).expect("invalid default key binding"))
+                    .collect();
+                (*action, keys)
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// Default keymap with the keys of the given actions replaced.
+    pub fn with_overrides(overrides: &HashMap<Action, KeyList>) -> Self {
+        let mut keymap = Self::default();
+        for (action, keys) in keymap.bindings.iter_mut() {
+            if let Some(key_list) = overrides.get(action) {
+                *keys = key_list.clone().into_vec();
+            }
+        }
+        keymap
+    }
+
+    pub fn action(&self, key_event: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|k| k.matches(key_event)))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyBinding] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| keys.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Label of the first key of each action for the help bar, e.g. "hjkl" or "enter/esc".
+    /// Single character keys are concatenated when `separator` is empty.
+    pub fn label(&self, actions: &[Action], separator: &str) -> String {
+        let labels = actions
+            .iter()
+            .map(|action| {
+                self.keys(*action)
+                    .first()
+                    .map(|k| k.to_string())
+                    .unwrap_or_else(|| "-".to_string())
+            })
+            .collect::<Vec<_>>();
+        if separator.is_empty() && labels.iter().any(|l| l.chars().count() > 1) {
+            labels.join("/")
+        } else {
+            labels.join(separator)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        for s in [
+            "q",
+            "G",
+            "ctrl-c",
+            "alt-enter",
+            "f5",
+            "-",
+            "ctrl--",
+            "space",
+            "pgdown",
+        ] {
+            assert_eq!(KeyBinding::parse(s).unwrap().to_string(), s);
+        }
+        assert_eq!(
+            KeyBinding::parse("Ctrl-C").unwrap(),
+            KeyBinding {
+                code: KeyCode::Char('C'),
+                modifiers: KeyModifiers::CONTROL
+            }
+        );
+        assert!(KeyBinding::parse("hyper-x").is_err());
+        assert!(KeyBinding::parse("nope").is_err());
+    }
+
+    #[test]
+    fn test_keymap() {
+        let overrides: HashMap<Action, KeyList> =
+            toml::from_str("zoom = \"space\"\nmove-left = [\"a\", \"left\"]").unwrap();
+        let keymap = Keymap::with_overrides(&overrides);
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        assert_eq!(
+            keymap.action(&key(KeyCode::Char(' '), KeyModifiers::NONE)),
+            Some(Action::Zoom)
+        );
+        assert_eq!(
+            keymap.action(&key(KeyCode::Enter, KeyModifiers::NONE)),
+            None
+        );
+        assert_eq!(
+            keymap.action(&key(KeyCode::Char('G'), KeyModifiers::SHIFT)),
+            Some(Action::ScrollBottom)
+        );
+        assert_eq!(
+            keymap.action(&key(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Some(Action::Quit)
+        );
+        assert_eq!(
+            keymap.action(&key(KeyCode::Char('c'), KeyModifiers::NONE)),
+            Some(Action::CyclePalette)
+        );
+        let moves = [
+            Action::MoveLeft,
+            Action::MoveDown,
+            Action::MoveUp,
+            Action::MoveRight,
+        ];
+        assert_eq!(keymap.label(&moves, ""), "ajkl");
+        assert_eq!(
+            keymap.label(&[Action::Zoom, Action::Unzoom], "/"),
+            "space/esc"
+        );
+    }
+}
@@ -1,6 +1,18 @@
+/// Zoom transitions of the flamegraph.
+pub mod animation;
+
 /// Application.
 pub mod app;
 
+/// Comparison against a baseline profile.
+pub mod baseline;
+
+/// Render time budget and the degraded rendering past it.
+pub mod budget;
+
+/// Applying the same commands to many profiles without the TUI.
+pub mod batch;
+
 /// Terminal events handler.
 pub mod event;
 
@@ -10,15 +22,91 @@ pub mod ui;
 /// Terminal user interface.
 pub mod tui;
 
+/// Demangling of Rust and C++ symbols in frame names.
+pub mod demangle;
+
+/// Compiled-in profile to try the viewer on.
+pub mod demo;
+
 /// Event handler.
 pub mod handler;
 
+/// Opening source files in the user's editor.
+pub mod editor;
+
+/// Validation of folded stacks files.
+pub mod check;
+
+/// Commands of the control socket and batch files.
+pub mod command;
+
+/// User configuration file.
+pub mod config;
+
+/// Remote control over a Unix socket.
+#[cfg(unix)]
+pub mod control;
+
 pub mod flame;
 
+/// Time-ordered flame charts of timestamped profiles.
+pub mod flamechart;
+
+/// Searches recalled in the search prompt.
+pub mod history;
+
+/// Key bindings.
+pub mod keys;
+
+/// Keyboard macros.
+pub mod macros;
+
+/// Flamegraph color palettes.
+pub mod palette;
+
+/// Converters from other profile formats to folded stacks.
+pub mod parsers;
+
+/// View preferences saved for each profile.
+pub mod prefs;
+
+/// Periodic snapshots of live sessions.
+pub mod snapshot;
+
+/// Rules rewriting frame names while parsing.
+pub mod rewrite;
+
+/// Source files shown next to the flamegraph.
+pub mod source;
+
+/// Profiles of several hosts merged into one flamegraph.
+pub mod sources;
+
+/// Commands launched to be profiled from their start.
+pub mod spawn;
+
 pub mod state;
 
+/// Folded stacks read continuously from a stream.
+pub mod stream;
+
+/// Interface colors for dark or light terminals.
+pub mod theme;
+
+/// Measuring and cutting frame names by their width on screen.
+pub mod text;
+
+/// Samples per function over time in live sessions.
+pub mod timeline;
+
+/// Guided tour of the key bindings.
+pub mod tutorial;
+
 pub mod view;
 
+/// Watched functions with live counters.
+pub mod watch;
+
 #[cfg(feature = "python")]
 pub mod py_spy;
 
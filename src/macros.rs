@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::keys::Action;
+
+/// A recorded step of a macro: an action, or the text submitted from the search or goto prompt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MacroStep {
+    Action(Action),
+    Search { search: String },
+    Goto { goto: String },
+}
+
+/// What the next key press is taken as the register of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingRegister {
+    Record,
+    Replay,
+}
+
+/// Macros recorded into registers, named by a letter or a digit.
+#[derive(Debug, Clone, Default)]
+pub struct Macros {
+    registers: HashMap<char, Vec<MacroStep>>,
+    /// Register being recorded into with the steps so far
+    recording: Option<(char, Vec<MacroStep>)>,
+    pub pending: Option<PendingRegister>,
+}
+
+impl Macros {
+    /// Macros saved in the config file, keyed by register. Invalid registers are ignored.
+    pub fn new(registers: &HashMap<String, Vec<MacroStep>>) -> Self {
+        let registers = registers
+            .iter()
+            .filter_map(|(name, steps)| {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if Self::is_valid_register(c) => Some((c, steps.clone())),
+                    _ => None,
+                }
+            })
+            .collect();
+        Self {
+            registers,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_valid_register(register: char) -> bool {
+        register.is_ascii_alphanumeric()
+    }
+
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    pub fn start_recording(&mut self, register: char) {
+        self.recording = Some((register, vec![]));
+    }
+
+    /// Stop recording and store the steps in the register. Returns the register and the number
+    /// of steps recorded.
+    pub fn stop_recording(&mut self) -> Option<(char, usize)> {
+        let (register, steps) = self.recording.take()?;
+        let len = steps.len();
+        self.registers.insert(register, steps);
+        Some((register, len))
+    }
+
+    /// Add a step to the macro being recorded, if any
+    pub fn record(&mut self, step: MacroStep) {
+        if let Some((_, steps)) = self.recording.as_mut() {
+            steps.push(step);
+        }
+    }
+
+    pub fn get(&self, register: char) -> Option<&[MacroStep]> {
+        self.registers.get(&register).map(|steps| steps.as_slice())
+    }
+
+    /// All the registers in the form saved in the config file
+    pub fn to_config(&self) -> HashMap<String, Vec<MacroStep>> {
+        self.registers
+            .iter()
+            .map(|(register, steps)| (register.to_string(), steps.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_macros() {
+        let config: HashMap<String, Vec<MacroStep>> = toml::from_str(
+            "a = [\"move-down\", { search = \"foo\" }, \"zoom\", { goto = \"bar\" }]\n\"%\" = []",
+        )
+        .unwrap();
+        let mut macros = Macros::new(&config);
+        assert_eq!(
+            macros.get('a').unwrap(),
+            &[
+                MacroStep::Action(Action::MoveDown),
+                MacroStep::Search {
+                    search: "foo".to_string()
+                },
+                MacroStep::Action(Action::Zoom),
+                MacroStep::Goto {
+                    goto: "bar".to_string()
+                },
+            ]
+        );
+        assert!(macros.get('%').is_none());
+
+        // Steps are only kept while recording
+        macros.record(MacroStep::Action(Action::Reset));
+        macros.start_recording('b');
+        assert_eq!(macros.recording(), Some('b'));
+        macros.record(MacroStep::Action(Action::ZoomParent));
+        assert_eq!(macros.stop_recording(), Some(('b', 1)));
+        assert_eq!(macros.recording(), None);
+        assert_eq!(macros.stop_recording(), None);
+        assert_eq!(
+            macros.get('b').unwrap(),
+            &[MacroStep::Action(Action::ZoomParent)]
+        );
+
+        let saved = toml::to_string(&macros.to_config()).unwrap();
+        let reloaded = Macros::new(&toml::from_str(&saved).unwrap());
+        assert_eq!(reloaded.get('a'), macros.get('a'));
+        assert_eq!(reloaded.get('b'), macros.get('b'));
+    }
+}
@@ -1,23 +1,164 @@
-use clap::{command, Parser};
+use clap::{Parser, Subcommand};
+use flamelens::animation::ZOOM_TRANSITION_INTERVAL;
 use flamelens::app::{App, AppResult};
+use flamelens::baseline::Baseline;
+use flamelens::batch::{self, BatchOps};
+use flamelens::budget::RenderBudget;
+use flamelens::check;
+use flamelens::config::Config;
+#[cfg(unix)]
+use flamelens::control::{self, ControlSocket};
+use flamelens::demo::{DEMO_NAME, DEMO_PROFILE};
 use flamelens::event::{Event, EventHandler};
-use flamelens::flame::FlameGraph;
-use flamelens::handler::handle_key_events;
+use flamelens::flame::{CountUnit, FlameGraph, ParseOptions, DEFAULT_MAX_FRAME_NAME_LENGTH};
+use flamelens::flamechart::{FlameChart, FlameChartView};
+use flamelens::handler::{handle_key_events, handle_mouse_events};
+use flamelens::history::SearchHistory;
+use flamelens::keys::Keymap;
+use flamelens::macros::Macros;
+use flamelens::palette::{ColorDepth, Palette, Separators};
+use flamelens::parsers::cpuprofile::{CpuProfileOptions, CpuProfileWeight};
+use flamelens::parsers::ghc_prof::{GhcProfOptions, GhcProfWeight};
+use flamelens::parsers::jfr::JfrOptions;
+use flamelens::parsers::massif::MassifOptions;
+use flamelens::parsers::perf_script::{PerfGroupBy, PerfScriptOptions};
+use flamelens::parsers::InputFormat;
+use flamelens::prefs::ProfilePrefs;
+#[cfg(feature = "python")]
+use flamelens::py_spy::{LiveOptions, PySpyOptions};
+use flamelens::rewrite::{RewritePreset, RewriteRule};
+#[cfg(feature = "python")]
+use flamelens::snapshot::{format_timestamp, SnapshotOptions};
+use flamelens::sources::Sources;
+#[cfg(feature = "python")]
+use flamelens::spawn::{OutputTarget, SpawnedCommand};
+use flamelens::text::Truncation;
+use flamelens::theme::{Theme, ThemeName};
 use flamelens::tui::Tui;
+use flamelens::tutorial::Tutorial;
+use flamelens::watch::WatchList;
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 
 #[derive(Parser, Debug)]
-#[command(version)]
+#[command(version, args_conflicts_with_subcommands = true)]
+#[cfg_attr(
+    feature = "python",
+    command(group(clap::ArgGroup::new("live").args(["pid", "process_name", "target_command"])))
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Profile data filename
     filename: Option<String>,
 
     /// Whether to sort the stacks by time spent
-    #[clap(long, action, value_name = "sorted")]
+    #[clap(long, global = true, action, value_name = "sorted")]
     sorted: bool,
 
+    /// Drop empty frame names emitted by broken stack collapsers
+    #[clap(long, global = true, action, value_name = "repair-frames")]
+    repair_frames: bool,
+
+    /// Format of the profile data
+    #[clap(long, global = true, value_enum, default_value_t = InputFormat::Auto)]
+    format: InputFormat,
+
+    /// For "perf script" input: how to group samples at the root of the stacks
+    #[clap(long, global = true, value_enum, default_value_t = PerfGroupBy::Process)]
+    perf_group_by: PerfGroupBy,
+
+    /// For "perf script" input: drop kernel frames
+    #[clap(long, global = true, action)]
+    perf_drop_kernel: bool,
+
+    /// For ".cpuprofile" input: what each sample contributes to the counts
+    #[clap(long, global = true, value_enum, default_value_t = CpuProfileWeight::Samples)]
+    cpuprofile_weight: CpuProfileWeight,
+
+    /// For JFR input: add the sampled thread as the root frame of each stack
+    #[clap(long, global = true, action)]
+    jfr_split_by_thread: bool,
+
+    /// For GHC ".prof" input: what each cost centre contributes to the counts
+    #[clap(long, global = true, value_enum, default_value_t = GhcProfWeight::Time)]
+    ghc_prof_weight: GhcProfWeight,
+
+    /// For massif input: number of the snapshot to show instead of the peak one
+    #[clap(long, global = true, value_name = "number")]
+    massif_snapshot: Option<usize>,
+
+    /// What the counts of the stacks are, e.g. bytes for the folded allocations of heaptrack_print
+    /// --print-flamegraph. Massif input is in bytes unless told otherwise
+    #[clap(long, global = true, value_enum)]
+    count_unit: Option<CountUnit>,
+
+    /// For folded stacks input: separator of the frames instead of ';'. A separator preceded by a
+    /// backslash is part of the frame name
+    #[clap(long, global = true, value_name = "delimiter", value_parser = parse_delimiter)]
+    delimiter: Option<String>,
+
+    /// For folded stacks input: read the "#key=value" annotations at the end of frame names, as in
+    /// "func#cpu=3#numa=1"
+    #[clap(long, global = true, action)]
+    annotations: bool,
+
+    /// Keep only the stacks with a frame annotated with this key and value (implies --annotations)
+    #[clap(long, global = true, value_name = "key=value", value_parser = parse_annotation)]
+    annotation_filter: Option<(String, String)>,
+
+    /// Demangle Rust and C++ symbols in frame names. Searches match the mangled names too
+    #[clap(long, global = true, action)]
+    demangle: bool,
+
+    /// Start with the frames of a function that only differ by the line of their source location
+    /// merged, as in "work (long_running.py)" for "work (long_running.py:8)"
+    #[clap(long, action)]
+    merge_lines: bool,
+
+    /// Rewrite frame names with a regex while parsing, as in "s/<regex>/<replacement>/". Can be
+    /// repeated; rules are applied in order after those of the config file
+    #[clap(long, global = true, value_name = "rule")]
+    rewrite: Vec<RewriteRule>,
+
+    /// Rewrite frame names with the rules of a preset, before the other rules. Can be repeated
+    #[clap(long, value_enum, value_name = "preset")]
+    rewrite_preset: Vec<RewritePreset>,
+
+    /// Start with one top-level frame per value of this annotation key (implies --annotations)
+    #[clap(long, value_name = "key")]
+    group_by: Option<String>,
+
+    /// Show the stacks of one device at a time, starting with the CPU, telling the device of a
+    /// stack by this annotation key as in "kernel#device=gpu0" (implies --annotations)
+    #[clap(long, value_name = "key")]
+    lanes: Option<String>,
+
+    /// Truncate frame names longer than this many characters (0 to disable)
+    #[clap(long, global = true, value_name = "chars", default_value_t = DEFAULT_MAX_FRAME_NAME_LENGTH)]
+    max_frame_name_length: usize,
+
+    /// Keep the full text of truncated frame names in memory (up to 64 KiB each)
+    #[clap(long, global = true, action)]
+    keep_full_frame_names: bool,
+
+    /// Reload the file whenever it changes on disk, keeping the selection, zoom and search
+    #[clap(long, requires = "filename")]
+    watch_file: bool,
+
+    /// Keep reading folded stacks from stdin or the file (e.g. a named pipe) until the end of the
+    /// stream, refreshing the flamegraph as they come in
+    #[clap(long, conflicts_with_all = ["watch_file", "echo"])]
+    stream: bool,
+
+    /// Open the compiled-in profile of a small web server instead of a file, to try flamelens out
+    /// or to refer to in bug reports
+    #[clap(long, conflicts_with_all = ["filename", "stream", "watch_file", "merge", "echo"])]
+    #[cfg_attr(feature = "python", clap(conflicts_with = "live"))]
+    demo: bool,
+
     /// Print data to stdout on exit. Useful when piping to other tools
     #[clap(long, action, value_name = "echo")]
     echo: bool,
@@ -27,81 +168,859 @@ struct Args {
     #[clap(long, value_name = "pid")]
     pid: Option<String>,
 
+    /// Name of the program or Python script to profile instead of a pid, as in "job.py" for
+    /// "python /srv/job.py" (Linux only). The most recent process with that name is profiled
+    #[cfg(feature = "python")]
+    #[clap(long = "name", value_name = "name")]
+    process_name: Option<String>,
+
+    /// Samples per second of py-spy in live mode (defaults to 100), or of the profiler that
+    /// recorded the input, to show the samples as durations (T)
+    #[clap(long, value_name = "rate")]
+    rate: Option<u64>,
+
+    /// Stop sampling after this long (e.g. 30s or 5m)
+    #[cfg(feature = "python")]
+    #[clap(long, value_name = "duration", value_parser = parse_duration, requires = "live")]
+    duration: Option<std::time::Duration>,
+
+    /// Collect stack traces from native extensions written in Cython, C or C++
+    #[cfg(feature = "python")]
+    #[clap(long, requires = "live", conflicts_with = "nonblocking")]
+    native: bool,
+
+    /// Profile subprocesses of the original process
+    #[cfg(feature = "python")]
+    #[clap(long, requires = "live")]
+    subprocesses: bool,
+
+    /// Only include traces that are holding on to the GIL
+    #[cfg(feature = "python")]
+    #[clap(long, requires = "live")]
+    gil: bool,
+
+    /// Include stack traces for idle threads
+    #[cfg(feature = "python")]
+    #[clap(long, requires = "live")]
+    idle: bool,
+
+    /// Don't pause the python process when collecting samples. This reduces the performance
+    /// impact of sampling, but may lead to inaccurate results
+    #[cfg(feature = "python")]
+    #[clap(long, requires = "live")]
+    nonblocking: bool,
+
+    /// File to write all the samples of the live session to when pressing ctrl-s (defaults to
+    /// a timestamped file in the current directory)
+    #[cfg(feature = "python")]
+    #[clap(long, value_name = "filename", requires = "live")]
+    output: Option<std::path::PathBuf>,
+
+    /// Command to launch and profile from its start, as in "flamelens -- python script.py"
+    #[cfg(feature = "python")]
+    #[clap(
+        last = true,
+        value_name = "command",
+        conflicts_with_all = ["pid", "filename", "stream", "demo"]
+    )]
+    target_command: Vec<String>,
+
+    /// File to write the stdout and stderr of the launched command to, instead of keeping their
+    /// last lines to show with 'I'
+    #[cfg(feature = "python")]
+    #[clap(long, value_name = "filename", requires = "target_command")]
+    command_output: Option<std::path::PathBuf>,
+
+    /// Wait for the process to exist and run Python, retrying until the sampler can attach to it
+    /// (or for a process with the --name to start), e.g. for a job about to be started by a
+    /// scheduler
+    #[cfg(feature = "python")]
+    #[clap(long, requires = "live")]
+    wait: bool,
+
+    /// Also write all the samples of the live session when quitting
+    #[cfg(feature = "python")]
+    #[clap(long, requires = "live")]
+    save_on_quit: bool,
+
     /// Additional arguments to pass to "py-spy record" command
     #[cfg(feature = "python")]
     #[clap(long, value_name = "py-spy-args")]
     py_spy_args: Option<String>,
 
+    /// Memory budget for the accumulated stacks in live mode (e.g. 512M or 2G). When exceeded,
+    /// the smallest stacks are folded into a "(pruned)" frame
+    #[cfg(feature = "python")]
+    #[clap(long, value_name = "size", value_parser = parse_size)]
+    max_memory: Option<usize>,
+
+    /// In live mode, write the samples collected during each interval (e.g. 30s, 5m or 1h) to a
+    /// timestamped folded stacks file
+    #[cfg(feature = "python")]
+    #[clap(long, value_name = "interval", value_parser = parse_duration)]
+    snapshot_every: Option<std::time::Duration>,
+
+    /// In live mode, only show the samples of the last interval (e.g. 10s, 30s or 1m) instead of all
+    /// the samples since attaching. Cycle through windows with 't'
+    #[cfg(feature = "python")]
+    #[clap(long, value_name = "interval", value_parser = parse_duration)]
+    window: Option<std::time::Duration>,
+
+    /// Directory of the snapshots written with --snapshot-every
+    #[cfg(feature = "python")]
+    #[clap(
+        long,
+        value_name = "dir",
+        default_value = ".",
+        requires = "snapshot_every"
+    )]
+    snapshot_dir: std::path::PathBuf,
+
+    /// Profile to compare against: frames are colored red when they take a larger share of the
+    /// samples than in it and blue when they take a smaller one
+    #[clap(long, value_name = "filename")]
+    baseline: Option<String>,
+
+    /// Profiles to merge with the main one, such as those of the other hosts of a fleet: frames
+    /// are colored gray when they show up in every profile and up to orange when they show up in
+    /// a single one. Can be repeated
+    #[clap(
+        long,
+        value_name = "filename",
+        requires = "filename",
+        conflicts_with_all = ["baseline", "watch_file", "stream"]
+    )]
+    merge: Vec<String>,
+
+    /// Start scrolled past the chain of frames with a single child at the top of the flamegraph,
+    /// such as interpreter startup and main wrappers. Press 'p' to reveal them
+    #[clap(long)]
+    auto_skip: bool,
+
+    /// Show a ruler marking every quarter of the flamegraph width with its number of samples.
+    /// Toggle it with 'R'
+    #[clap(long)]
+    ruler: bool,
+
+    /// Regex of functions whose counts are always shown with a trend arrow, in addition to the
+    /// ones in the config file. Can be repeated
+    #[clap(long, value_name = "regex")]
+    watch: Vec<String>,
+
+    /// Color palette of the flamegraph (defaults to the one in the config file)
+    #[clap(long, value_enum)]
+    palette: Option<Palette>,
+
+    /// How to tell adjacent sibling frames apart (defaults to the one in the config file)
+    #[clap(long, value_enum)]
+    separators: Option<Separators>,
+
+    /// Collapse runs of sibling frames narrower than this percent of the flamegraph width (e.g.
+    /// 0.5) into a placeholder showing how many frames it hides (defaults to the config file)
+    #[clap(long, value_name = "percent", value_parser = parse_percent)]
+    min_frame_width: Option<f64>,
+
+    /// Animate zooming in and out with a few frames interpolating the widths of the frames. Any
+    /// key skips the animation (defaults to the config file, off otherwise)
+    #[clap(long)]
+    zoom_animation: bool,
+
+    /// Draw at most this many frames per second, handling the keys pressed in between at once
+    /// (defaults to the config file, unlimited otherwise)
+    #[clap(long, value_name = "fps", value_parser = clap::value_parser!(u32).range(1..))]
+    max_fps: Option<u32>,
+
+    /// Milliseconds the flamegraph can take to render. Past it, search terms aren't highlighted
+    /// inside frames, narrow frames lose their label and tiny frames are collapsed until a full
+    /// render would fit in the budget again (defaults to the config file, no budget otherwise)
+    #[clap(long, value_name = "ms", value_parser = clap::value_parser!(u64).range(1..))]
+    render_budget: Option<u64>,
+
+    /// Colors of the terminal: the RGB colors are mapped to the nearest ones of the 256 or 16
+    /// colors (defaults to the config file, detected from COLORTERM and TERM otherwise)
+    #[clap(long, value_enum)]
+    colors: Option<ColorDepth>,
+
+    /// Interface colors for dark or light terminals (defaults to the config file, dark otherwise)
+    #[clap(long, value_enum)]
+    theme: Option<ThemeName>,
+
+    /// Cut the names too long for their frame at the end, or in the middle to keep the function
+    /// at the end of long paths (defaults to the config file, end otherwise)
+    #[clap(long, value_enum)]
+    truncate: Option<Truncation>,
+
+    /// Accept newline-delimited commands (search, zoom, reset, export, quit) on this Unix socket
+    #[cfg(unix)]
+    #[clap(long, value_name = "path")]
+    control_socket: Option<std::path::PathBuf>,
+
     /// Show debug info
     #[clap(long)]
     debug: bool,
 }
 
-fn get_app_from_filename_or_stdin(args: &Args, echo: bool) -> App {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send a command (search, zoom, reset, export or quit) to an instance started with
+    /// --control-socket, e.g. "flamelens remote search foo"
+    #[cfg(unix)]
+    Remote {
+        /// Path of the control socket
+        #[clap(long, env = "FLAMELENS_SOCKET")]
+        socket: std::path::PathBuf,
+
+        /// Command and its arguments
+        #[clap(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Run the commands of a TOML file (e.g. commands = ["search foo", "zoom bar"]) on each
+    /// profile without the TUI and export the folded stacks of the resulting zoom
+    Batch {
+        /// TOML file listing the commands to run
+        #[clap(long, value_name = "filename")]
+        apply: std::path::PathBuf,
+
+        /// Directory to write the exported profiles to, under their original file names
+        #[clap(long, value_name = "dir")]
+        export_dir: std::path::PathBuf,
+
+        /// Profile data filenames
+        #[clap(required = true)]
+        files: Vec<std::path::PathBuf>,
+    },
+    /// Sample a Python process or command without the TUI until it exits, --duration elapses or
+    /// ctrl-c is pressed, write the folded stacks to a file and optionally open them
+    #[cfg(feature = "python")]
+    Record {
+        /// Pid of the process to sample
+        #[clap(long, value_name = "pid", required_unless_present_any = ["command", "name"])]
+        pid: Option<u64>,
+
+        /// Name of the program or Python script to sample instead of a pid (Linux only)
+        #[clap(long, value_name = "name", conflicts_with = "pid")]
+        name: Option<String>,
+
+        /// File to write the folded stacks to (defaults to flamelens-<pid>-<timestamp>.txt in the
+        /// current directory)
+        #[clap(long, short, value_name = "filename")]
+        output: Option<std::path::PathBuf>,
+
+        /// Open the folded stacks in flamelens once the sampling ends
+        #[clap(long)]
+        open: bool,
+
+        /// Samples per second (defaults to 100)
+        #[clap(long, value_name = "rate")]
+        rate: Option<u64>,
+
+        /// Stop sampling after this long (e.g. 30s or 5m)
+        #[clap(long, value_name = "duration", value_parser = parse_duration)]
+        duration: Option<std::time::Duration>,
+
+        /// Collect stack traces from native extensions written in Cython, C or C++
+        #[clap(long, conflicts_with = "nonblocking")]
+        native: bool,
+
+        /// Profile subprocesses of the original process
+        #[clap(long)]
+        subprocesses: bool,
+
+        /// Only include traces that are holding on to the GIL
+        #[clap(long)]
+        gil: bool,
+
+        /// Include stack traces for idle threads
+        #[clap(long)]
+        idle: bool,
+
+        /// Don't pause the python process when collecting samples
+        #[clap(long)]
+        nonblocking: bool,
+
+        /// Wait for the process to exist (or one with the --name to start) and run Python,
+        /// retrying until it can be sampled
+        #[clap(long)]
+        wait: bool,
+
+        /// Additional arguments to pass to "py-spy record" command
+        #[clap(long, value_name = "py-spy-args")]
+        py_spy_args: Option<String>,
+
+        /// Command to launch and sample, as in "flamelens record -- python script.py"
+        #[clap(last = true, value_name = "command", conflicts_with_all = ["pid", "name"])]
+        command: Vec<String>,
+    },
+    /// Take a guided tour of the key bindings on a demo profile
+    Tutorial,
+    /// Validate a folded stacks file and print a summary, exiting with a non-zero status if
+    /// problems were found
+    Check {
+        /// Folded stacks filename
+        file: std::path::PathBuf,
+    },
+}
+
+/// Parse a human readable size such as "512M" into bytes
+#[cfg(feature = "python")]
+fn parse_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let (number, multiplier) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    number
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size: {}", s))
+}
+
+fn parse_delimiter(s: &str) -> Result<String, String> {
+    if s.is_empty() || s.contains(char::is_whitespace) || s.contains('\\') {
+        return Err("the delimiter must not be empty or contain whitespace or backslashes".into());
+    }
+    Ok(s.to_string())
+}
+
+fn parse_annotation(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected key=value: {}", s)),
+    }
+}
+
+/// Parse a percent such as "0.5" or "0.5%"
+fn parse_percent(s: &str) -> Result<f64, String> {
+    match s.trim().trim_end_matches('%').parse::<f64>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err(format!("invalid percent: {}", s)),
+    }
+}
+
+/// Sample the process `pid`, or `command` launched for it, without the TUI and write the folded
+/// stacks to `output`. Returns the path of the file written.
+#[cfg(feature = "python")]
+fn record(
+    pid: Option<u64>,
+    name: Option<&str>,
+    command: &[String],
+    py_spy: PySpyOptions,
+    wait: bool,
+    py_spy_args: Option<&str>,
+    output: Option<std::path::PathBuf>,
+) -> AppResult<std::path::PathBuf> {
+    let mut spawned = if command.is_empty() {
+        None
+    } else {
+        let spawned = SpawnedCommand::spawn(command, OutputTarget::Inherited)
+            .map_err(|e| format!("Could not launch {}: {}", command.join(" "), e))?;
+        Some(spawned)
+    };
+    let pid = match (&spawned, pid, name) {
+        (Some(spawned), _, _) => spawned.pid() as u64,
+        (None, Some(pid), _) => pid,
+        (None, None, Some(name)) => flamelens::py_spy::find_process(name, wait)? as u64,
+        (None, None, None) => return Err("No pid or command to sample".into()),
+    };
+    let options = LiveOptions {
+        py_spy,
+        // The command has to load Python before it can be sampled
+        wait: wait || spawned.is_some(),
+        ..Default::default()
+    };
+    // The launched command is stopped below even if the py-spy arguments are invalid
+    let result = flamelens::py_spy::config_from_options(pid, &options.py_spy, py_spy_args)
+        .and_then(|config| {
+            flamelens::py_spy::record_headless(pid as remoteprocess::Pid, &config, &options)
+        });
+    if let Some(spawned) = spawned.as_mut() {
+        if spawned.stop() {
+            eprintln!("Stopped {}", command.join(" "));
+        }
+    }
+    let mut data = result?;
+    let path = output.unwrap_or_else(|| {
+        std::path::PathBuf::from(format!(
+            "flamelens-{}-{}.txt",
+            pid,
+            format_timestamp(std::time::SystemTime::now())
+        ))
+    });
+    data.push('\n');
+    std::fs::write(&path, data)
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+    eprintln!("Wrote the samples to {}", path.display());
+    Ok(path)
+}
+
+/// Parse a human readable interval such as "5m" into a duration
+#[cfg(feature = "python")]
+fn parse_duration(s: &str) -> Result<std::time::Duration, String> {
+    let s = s.trim();
+    let (number, multiplier) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1),
+        Some((i, 'm')) => (&s[..i], 60),
+        Some((i, 'h')) => (&s[..i], 60 * 60),
+        _ => (s, 1),
+    };
+    match number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+    {
+        Some(n) if n > 0 => Ok(std::time::Duration::from_secs(n)),
+        _ => Err(format!("invalid interval: {}", s)),
+    }
+}
+
+fn get_parse_options(args: &Args) -> ParseOptions {
+    ParseOptions {
+        sorted: args.sorted,
+        repair_frames: args.repair_frames,
+        format: args.format,
+        perf: PerfScriptOptions {
+            group_by: args.perf_group_by,
+            drop_kernel: args.perf_drop_kernel,
+        },
+        cpuprofile: CpuProfileOptions {
+            weight: args.cpuprofile_weight,
+        },
+        jfr: JfrOptions {
+            split_by_thread: args.jfr_split_by_thread,
+        },
+        ghc_prof: GhcProfOptions {
+            weight: args.ghc_prof_weight,
+        },
+        massif: MassifOptions {
+            snapshot: args.massif_snapshot,
+        },
+        count_unit: args.count_unit,
+        max_frame_name_length: (args.max_frame_name_length > 0)
+            .then_some(args.max_frame_name_length),
+        keep_full_frame_names: args.keep_full_frame_names,
+        delimiter: args.delimiter.clone(),
+        annotations: args.annotations || args.group_by.is_some() || args.lanes.is_some(),
+        annotation_filter: args.annotation_filter.clone(),
+        rewrite: args.rewrite.clone(),
+        demangle: args.demangle,
+    }
+}
+
+fn get_app_from_stream(args: &Args) -> App {
+    let filename = args.filename.as_deref().unwrap_or("stdin");
+    let mut app = App::with_flamegraph(
+        filename,
+        FlameGraph::from_string(String::new(), args.sorted),
+    );
+    app.stream_input(
+        args.filename.as_ref().map(|f| f.into()),
+        get_parse_options(args),
+    );
+    app
+}
+
+fn get_app_from_filename_or_stdin(args: &Args, echo: bool, save_prefs: bool) -> AppResult<App> {
+    if args.stream {
+        return Ok(get_app_from_stream(args));
+    }
+    let tutorial = matches!(args.command, Some(Command::Tutorial));
+    if args.demo || tutorial {
+        let flamegraph = FlameGraph::from_string_with_options(
+            DEMO_PROFILE.to_string(),
+            &get_parse_options(args),
+        );
+        let mut app = App::with_flamegraph(DEMO_NAME, flamegraph);
+        if tutorial {
+            app.tutorial = Some(Tutorial::default());
+        }
+        return Ok(app);
+    }
     let (filename, content) = if let Some(filename) = &args.filename {
         (
             filename.as_str(),
-            std::fs::read_to_string(filename).expect("Could not read file"),
+            std::fs::read(filename).expect("Could not read file"),
         )
     } else {
         let mut buf: Vec<u8> = Vec::new();
         io::stdin()
             .read_to_end(&mut buf)
             .expect("Could not read stdin");
-        let content = String::from_utf8(buf).expect("Could not parse stdin");
-        ("stdin", content)
+        ("stdin", buf)
     };
     if echo {
-        println!("{}", content);
+        let mut stdout = io::stdout();
+        stdout
+            .write_all(&content)
+            .expect("Could not write to stdout");
+        stdout.write_all(b"\n").expect("Could not write to stdout");
     }
     let tic = std::time::Instant::now();
-    let flamegraph = FlameGraph::from_string(content, args.sorted);
+    let options = get_parse_options(args);
+    // Stdin may give a different profile every time
+    let prefs_path = if save_prefs && args.filename.is_some() {
+        ProfilePrefs::path(&content)
+    } else {
+        None
+    };
+    let (mut flamegraph, samples) = FlameGraph::from_bytes_with_timed_samples(content, &options)?;
+    let flame_chart = samples
+        .and_then(|samples| FlameChart::from_samples(&samples))
+        .map(FlameChartView::new);
+    let diagnostics_summary = flamegraph.diagnostics().summary(options.repair_frames);
+    let mut sources = None;
+    if !args.merge.is_empty() {
+        let mut profiles = vec![(filename.to_string(), flamegraph)];
+        for filename in args.merge.iter() {
+            let content = std::fs::read(filename)
+                .map_err(|e| format!("Could not read file to merge {}: {}", filename, e))?;
+            let flamegraph = FlameGraph::from_bytes_with_options(content, &options)?;
+            profiles.push((filename.clone(), flamegraph));
+        }
+        flamegraph = FlameGraph::merge(&profiles.iter().map(|(_, fg)| fg).collect::<Vec<_>>());
+        sources = Some(Sources::new(&profiles));
+    }
     let mut app = App::with_flamegraph(filename, flamegraph);
+    app.prefs_path = prefs_path;
+    app.flamegraph_view.sources = sources;
+    // The timestamps of the main profile alone would not match the merged flamegraph
+    if app.flamegraph_view.sources.is_none() {
+        app.flame_chart = flame_chart;
+    }
     app.add_elapsed("flamegraph", tic.elapsed());
-    app
+    if let Some(summary) = diagnostics_summary {
+        app.set_transient_message(&summary);
+    }
+    Ok(app)
+}
+
+fn handle_event(event: Event, app: &mut App) -> AppResult<()> {
+    match event {
+        Event::Tick => app.tick(),
+        Event::Key(key_event) => handle_key_events(key_event, app)?,
+        Event::Mouse(mouse_event) => handle_mouse_events(mouse_event, app)?,
+        Event::Resize(_, _) => {}
+        #[cfg(unix)]
+        Event::Command(request) => {
+            let reply = app.handle_control_command(request.command);
+            let _ = request.reply.send(reply);
+        }
+    }
+    Ok(())
 }
 
 fn main() -> AppResult<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    #[allow(unused_mut)]
+    let mut recorded: Option<std::path::PathBuf> = None;
+    match &args.command {
+        #[cfg(unix)]
+        Some(Command::Remote { socket, command }) => {
+            match control::send(socket, &command.join(" ")) {
+                Ok(message) => {
+                    if !message.is_empty() {
+                        println!("{}", message);
+                    }
+                    return Ok(());
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Command::Batch {
+            apply,
+            export_dir,
+            files,
+        }) => {
+            let ops = BatchOps::load(apply)?;
+            let options = get_parse_options(&args);
+            if !batch::run(&ops, files, export_dir, &options, &mut io::stdout())? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Check { file }) => {
+            let report = check::check(std::fs::read(file)?);
+            print!("{}", report);
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        #[cfg(feature = "python")]
+        Some(Command::Record {
+            pid,
+            name,
+            output,
+            open,
+            rate,
+            duration,
+            native,
+            subprocesses,
+            gil,
+            idle,
+            nonblocking,
+            wait,
+            py_spy_args,
+            command,
+        }) => {
+            let py_spy = PySpyOptions {
+                rate: *rate,
+                duration: *duration,
+                native: *native,
+                subprocesses: *subprocesses,
+                gil: *gil,
+                idle: *idle,
+                nonblocking: *nonblocking,
+            };
+            let path = record(
+                *pid,
+                name.as_deref(),
+                command,
+                py_spy,
+                *wait,
+                py_spy_args.as_deref(),
+                output.clone(),
+            )?;
+            if !open {
+                return Ok(());
+            }
+            recorded = Some(path);
+        }
+        Some(Command::Tutorial) | None => {}
+    }
+    if let Some(path) = recorded {
+        args.filename = Some(path.display().to_string());
+    }
+    // A mistake in the config file shouldn't keep the profile from opening
+    let (config, config_error) = match Config::load() {
+        Ok(config) => (config, None),
+        Err(e) => (
+            Config::default(),
+            Some(format!("Using the default settings. {:#}", e)),
+        ),
+    };
+    let preset_rules = config
+        .rewrite_presets
+        .iter()
+        .chain(args.rewrite_preset.iter())
+        .flat_map(|preset| preset.rules())
+        .collect::<Vec<_>>();
+    args.rewrite = [
+        preset_rules.as_slice(),
+        config.rewrite.as_slice(),
+        args.rewrite.as_slice(),
+    ]
+    .concat();
 
     // Create an application.
     cfg_if::cfg_if! {
         if #[cfg(feature = "python")] {
-            let mut app = if let Some(_pid) = args.pid {
-                App::with_pid(
-                    _pid.parse().expect("Could not parse pid"),
+            let mut spawned_command = if args.target_command.is_empty() {
+                None
+            } else {
+                let target = match &args.command_output {
+                    Some(path) => OutputTarget::File(path),
+                    None => OutputTarget::Kept,
+                };
+                let command = SpawnedCommand::spawn(&args.target_command, target)
+                        .map_err(|e| {
+                            format!("Could not launch {}: {}", args.target_command.join(" "), e)
+                        })?;
+                Some(command)
+            };
+            let pid = match (&spawned_command, &args.pid, &args.process_name) {
+                (Some(command), _, _) => Some(command.pid() as u64),
+                (None, Some(pid), _) => {
+                    Some(pid.parse().map_err(|_| format!("Invalid pid: {}", pid))?)
+                }
+                (None, None, Some(name)) => {
+                    Some(flamelens::py_spy::find_process(name, args.wait)? as u64)
+                }
+                (None, None, None) => None,
+            };
+            let mut app = if let Some(pid) = pid {
+                let app = App::with_pid(
+                    pid,
                     args.py_spy_args.clone(),
-                )
+                    LiveOptions {
+                        max_memory: args.max_memory,
+                        snapshots: args.snapshot_every.map(|every| SnapshotOptions {
+                            every,
+                            dir: args.snapshot_dir.clone(),
+                        }),
+                        window: args.window,
+                        py_spy: PySpyOptions {
+                            rate: args.rate,
+                            duration: args.duration,
+                            native: args.native,
+                            subprocesses: args.subprocesses,
+                            gil: args.gil,
+                            idle: args.idle,
+                            nonblocking: args.nonblocking,
+                        },
+                        output: args.output.clone(),
+                        // The command has to load Python before it can be sampled
+                        wait: args.wait || spawned_command.is_some(),
+                    },
+                );
+                if let (Err(_), Some(command)) = (&app, spawned_command.as_mut()) {
+                    command.stop();
+                }
+                app?
             } else {
-                get_app_from_filename_or_stdin(&args, args.echo)
+                get_app_from_filename_or_stdin(&args, args.echo, config.save_profile_prefs)?
             };
+            app.spawned_command = spawned_command;
         } else {
-            let mut app = get_app_from_filename_or_stdin(&args, args.echo);
+            let mut app = get_app_from_filename_or_stdin(&args, args.echo, config.save_profile_prefs)?;
         }
     }
+    if let Some(filename) = &args.baseline {
+        let content = std::fs::read(filename)
+            .map_err(|e| format!("Could not read baseline file {}: {}", filename, e))?;
+        let baseline = FlameGraph::from_bytes_with_options(content, &get_parse_options(&args))?;
+        app.flamegraph_view
+            .set_baseline(Baseline::new(filename, &baseline));
+    }
     app.debug = args.debug;
+    app.flamegraph_view.state.palette = args.palette.or(config.palette).unwrap_or_default();
+    app.flamegraph_view.state.separators =
+        args.separators.or(config.separators).unwrap_or_default();
+    app.flamegraph_view.state.min_frame_width = args.min_frame_width.or(config.min_frame_width);
+    app.zoom_animation.enabled = args.zoom_animation || config.zoom_animation.unwrap_or(false);
+    app.color_depth = args
+        .colors
+        .or(config.colors)
+        .unwrap_or_else(ColorDepth::detect);
+    app.theme = Theme::new(args.theme.or(config.theme).unwrap_or_default())
+        .with_overrides(&config.theme_colors)?;
+    app.truncation = args.truncate.or(config.truncate).unwrap_or_default();
+    app.render_budget = RenderBudget::new(
+        args.render_budget
+            .or(config.render_budget)
+            .filter(|ms| *ms > 0)
+            .map(std::time::Duration::from_millis),
+    );
+    let min_frame_interval = args
+        .max_fps
+        .or(config.max_fps)
+        .filter(|fps| *fps > 0)
+        .map(|fps| std::time::Duration::from_secs(1) / fps);
+    app.top_columns = config.top_columns.clone();
+    app.sample_rate = args.rate;
+    app.keymap = Keymap::with_overrides(&config.keys);
+    app.macros = Macros::new(&config.macros);
+    app.show_ruler = args.ruler;
+    app.flamegraph_view.set_merge_lines(args.merge_lines);
+    app.flamegraph_view.set_group_by(args.group_by.clone());
+    if let Some(key) = &args.lanes {
+        app.lane_key = key.clone();
+        app.cycle_lane();
+    }
+    app.flamegraph_view.state.auto_skip = args.auto_skip;
+    if args.auto_skip {
+        app.flamegraph_view.skip_single_child_prefix();
+    }
+    if let (true, Some(filename)) = (args.watch_file, &args.filename) {
+        app.watch_file(filename.into(), get_parse_options(&args));
+    }
+    if let (true, Some(path)) = (config.save_search_history, SearchHistory::default_path()) {
+        app.search_history = SearchHistory::load(&path)?;
+    }
+    if let Some(path) = &app.prefs_path {
+        match ProfilePrefs::load(path) {
+            Ok(Some(prefs)) => prefs.apply(&mut app.flamegraph_view),
+            Ok(None) => {}
+            Err(e) => app.set_transient_message(&e.to_string()),
+        }
+    }
+    if let Some(message) = &config_error {
+        app.set_transient_message(message);
+    } else if !config.tutorial_seen {
+        app.tutorial.get_or_insert_with(Tutorial::default);
+        if let Err(e) = Config::persist("tutorial_seen", &true) {
+            app.set_transient_message(&format!("Could not save the config: {}", e));
+        }
+    }
+    let watch_patterns = [config.watch.as_slice(), args.watch.as_slice()].concat();
+    app.set_watch_list(WatchList::new(&watch_patterns)?);
 
     // Initialize the terminal user interface.
     let backend = CrosstermBackend::new(io::stderr());
     let terminal = Terminal::new(backend)?;
     let events = EventHandler::new(250);
+    #[cfg(unix)]
+    let _control_socket = args
+        .control_socket
+        .as_deref()
+        .map(|path| ControlSocket::bind(path, events.sender()))
+        .transpose()?;
     let mut tui = Tui::new(terminal, events);
     tui.init()?;
 
     // Start the main loop.
+    let mut last_draw = std::time::Instant::now();
     while app.running {
+        if let Some(request) = app.editor_request.take() {
+            tui.suspend()?;
+            let result = request.run();
+            tui.resume()?;
+            match result {
+                Ok(status) if !status.success() => {
+                    app.set_transient_message(&format!("Editor exited with {}", status))
+                }
+                Ok(_) => {}
+                Err(e) => app.set_transient_message(&format!("Could not open editor: {}", e)),
+            }
+        }
+        // Handle the events coming in too soon after the last frame before drawing the next one
+        if let Some(wait) =
+            min_frame_interval.and_then(|interval| interval.checked_sub(last_draw.elapsed()))
+        {
+            if let Some(event) = tui.events.next_timeout(wait)? {
+                handle_event(event, &mut app)?;
+                continue;
+            }
+        }
         // Render the user interface.
         tui.draw(&mut app)?;
-        // Handle events.
-        match tui.events.next()? {
-            Event::Tick => app.tick(),
-            Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
-            Event::Mouse(_) => {}
-            Event::Resize(_, _) => {}
-        }
+        last_draw = std::time::Instant::now();
+        // Handle events, drawing the next frame of a zoom transition when none comes
+        let event = if app.zoom_animation.is_running() {
+            match tui.events.next_timeout(ZOOM_TRANSITION_INTERVAL)? {
+                Some(event) => event,
+                None => continue,
+            }
+        } else {
+            tui.events.next()?
+        };
+        handle_event(event, &mut app)?;
     }
 
     // Exit the user interface.
     tui.exit()?;
+    if let Some(path) = &app.prefs_path {
+        if let Err(e) = ProfilePrefs::from_view(&app.flamegraph_view).save(path) {
+            eprintln!("Could not save the view preferences: {}", e);
+        }
+    }
+    #[cfg(feature = "python")]
+    if let Some(command) = app.spawned_command.as_mut() {
+        if command.stop() {
+            eprintln!("Stopped {}", args.target_command.join(" "));
+        }
+    }
+    #[cfg(feature = "python")]
+    if args.save_on_quit {
+        match app.save_live_data() {
+            Ok(path) => eprintln!("Saved all samples to {}", path.display()),
+            Err(e) => eprintln!("Could not save samples: {}", e),
+        }
+    }
     Ok(())
 }
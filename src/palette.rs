@@ -0,0 +1,454 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Color schemes for the flamegraph frames, roughly based on flamegraph.pl
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    /// Red, orange and yellow
+    #[default]
+    Hot,
+    /// Green for Java frames, yellow for C++ and red for native frames
+    Java,
+    /// Green for JavaScript frames, yellow for C++ and red for native frames
+    Js,
+    /// Green and blue
+    Memory,
+    /// Blue and cyan
+    Aqua,
+    /// Shades of gray
+    Grayscale,
+    /// One hue per module, package, crate or file
+    Module,
+    /// Green for Java and Kotlin frames, purple for JNI, yellow for the ART runtime and red for
+    /// native frames
+    Android,
+    /// Oranges and yellows told apart by their lightness, and orange/blue differences against a
+    /// baseline, for red-green color blindness (deuteranopia and protanopia)
+    Colorblind,
+}
+
+impl Palette {
+    pub fn all() -> &'static [Palette] {
+        &[
+            Palette::Hot,
+            Palette::Java,
+            Palette::Js,
+            Palette::Memory,
+            Palette::Aqua,
+            Palette::Grayscale,
+            Palette::Module,
+            Palette::Android,
+            Palette::Colorblind,
+        ]
+    }
+
+    pub fn next(&self) -> Palette {
+        let all = Self::all();
+        let idx = all.iter().position(|p| p == self).unwrap_or(0);
+        all[(idx + 1) % all.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Hot => "hot",
+            Palette::Java => "java",
+            Palette::Js => "js",
+            Palette::Memory => "memory",
+            Palette::Aqua => "aqua",
+            Palette::Grayscale => "grayscale",
+            Palette::Module => "module",
+            Palette::Android => "android",
+            Palette::Colorblind => "colorblind",
+        }
+    }
+
+    /// Color of a frame given its short name and the full name of its stack (used for hashing
+    /// so that the color is stable for a given stack).
+    pub fn color(&self, short_name: &str, full_name: &str) -> (u8, u8, u8) {
+        let v = hash_name(full_name);
+        let scale = |base: u8, range: f64| base.saturating_add((range * v) as u8);
+        match self {
+            Palette::Hot => (scale(205, 50.0), scale(0, 230.0), scale(0, 55.0)),
+            Palette::Java | Palette::Js => {
+                let is_managed = if *self == Palette::Java {
+                    // e.g. "java/util/HashMap.get" or "com.example.Foo.bar"
+                    short_name.contains('/')
+                        || (short_name.contains('.') && !short_name.contains("::"))
+                } else {
+                    short_name.contains(".js") || short_name.contains(".ts")
+                };
+                if is_managed {
+                    (scale(50, 60.0), scale(200, 55.0), scale(50, 60.0))
+                } else if short_name.contains("::") {
+                    (scale(185, 50.0), scale(185, 50.0), scale(0, 60.0))
+                } else if short_name.ends_with("_[k]") {
+                    (scale(205, 50.0), scale(100, 60.0), scale(0, 30.0))
+                } else {
+                    (scale(200, 55.0), scale(50, 80.0), scale(50, 80.0))
+                }
+            }
+            Palette::Memory => (0, scale(190, 50.0), scale(0, 210.0)),
+            Palette::Aqua => (scale(50, 60.0), scale(165, 55.0), scale(165, 55.0)),
+            Palette::Grayscale => {
+                let c = scale(70, 130.0);
+                (c, c, c)
+            }
+            Palette::Module => {
+                // Hue identifies the module, lightness varies slightly between frames
+                let hue = hash_name(module_of(short_name)) * 360.0;
+                hsl_to_rgb(hue, 0.65, 0.45 + 0.15 * v)
+            }
+            Palette::Colorblind => {
+                // From vermillion to yellow, which stay apart without telling red from green
+                let lerp = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * v) as u8;
+                (lerp(213, 240), lerp(94, 228), lerp(0, 66))
+            }
+            Palette::Android => match AndroidFrame::of(short_name) {
+                AndroidFrame::Managed => (scale(50, 60.0), scale(200, 55.0), scale(50, 60.0)),
+                AndroidFrame::Jni => (scale(150, 50.0), scale(90, 50.0), scale(200, 55.0)),
+                AndroidFrame::Runtime => (scale(185, 50.0), scale(185, 50.0), scale(0, 60.0)),
+                AndroidFrame::Kernel => (scale(205, 50.0), scale(100, 60.0), scale(0, 30.0)),
+                AndroidFrame::Native => (scale(200, 55.0), scale(50, 80.0), scale(50, 80.0)),
+            },
+        }
+    }
+}
+
+/// How adjacent sibling frames are told apart besides their colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Separators {
+    /// Only the colors of the frames
+    #[default]
+    None,
+    /// A blank cell at the start of every frame
+    Line,
+    /// Every other sibling frame is darker
+    Shade,
+}
+
+impl Separators {
+    pub fn next(&self) -> Separators {
+        match self {
+            Separators::None => Separators::Line,
+            Separators::Line => Separators::Shade,
+            Separators::Shade => Separators::None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Separators::None => "none",
+            Separators::Line => "line",
+            Separators::Shade => "shade",
+        }
+    }
+}
+
+/// Kinds of frames of Android profiles (simpleperf and ART method traces)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AndroidFrame {
+    /// Java or Kotlin method, interpreted or compiled
+    Managed,
+    /// Transition between Java and native code, or a native method called through JNI
+    Jni,
+    /// The ART runtime: interpreter, compiler, garbage collector
+    Runtime,
+    Kernel,
+    Native,
+}
+
+impl AndroidFrame {
+    fn of(name: &str) -> Self {
+        if name.starts_with("Java_") || name.contains("jni_trampoline") || name.contains("JNI") {
+            AndroidFrame::Jni
+        } else if name.starts_with("art::")
+            || name.starts_with("art_")
+            || name.starts_with("Nterp")
+            || name.starts_with("ExecuteNterp")
+            || name == "[libart.so]"
+        {
+            AndroidFrame::Runtime
+        } else if name.ends_with("_[k]") || name == "[kernel.kallsyms]" {
+            AndroidFrame::Kernel
+        } else if name.contains('.') && !name.contains("::") && !name.starts_with('[') {
+            // e.g. "com.example.Foo.bar" or "kotlinx.coroutines.Job.join (Job.kt:12)"
+            AndroidFrame::Managed
+        } else {
+            AndroidFrame::Native
+        }
+    }
+}
+
+/// Colors the terminal can show, the RGB colors being mapped to the nearest ones it has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorDepth {
+    /// 24-bit colors
+    Truecolor,
+    /// The 256 colors of xterm
+    #[value(name = "256")]
+    #[serde(rename = "256")]
+    Ansi256,
+    /// The 16 basic colors, as set by the terminal theme
+    #[value(name = "16")]
+    #[serde(rename = "16")]
+    Ansi16,
+}
+
+/// The 16 basic colors as xterm shows them by default
+const ANSI16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Levels of each component in the 6x6x6 color cube of the 256 colors
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorDepth {
+    /// Colors of the terminal going by `COLORTERM` and `TERM`: terminals announcing 24-bit colors
+    /// or without `TERM` (such as on Windows) have them, the others have 256 colors if their name
+    /// says so, 16 otherwise
+    pub fn detect() -> Self {
+        Self::detect_from(
+            std::env::var("COLORTERM").ok().as_deref(),
+            std::env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    fn detect_from(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        match (colorterm, term) {
+            (Some("truecolor" | "24bit"), _) => ColorDepth::Truecolor,
+            (_, None | Some("")) => ColorDepth::Truecolor,
+            (_, Some(term)) if term.contains("256color") => ColorDepth::Ansi256,
+            (_, Some(term)) if term.contains("truecolor") || term.contains("direct") => {
+                ColorDepth::Truecolor
+            }
+            _ => ColorDepth::Ansi16,
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+/// Index of the nearest of the 256 colors, from the color cube or the grayscale ramp
+pub fn nearest_ansi256(rgb: (u8, u8, u8)) -> u8 {
+    let cube_index = |c: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs())
+            .unwrap_or_default()
+    };
+    let (r, g, b) = (cube_index(rgb.0), cube_index(rgb.1), cube_index(rgb.2));
+    let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+    // 24 grays from 8 to 238
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray_index = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * gray_index;
+    if distance(rgb, (gray_level, gray_level, gray_level)) < distance(rgb, cube) {
+        232 + gray_index
+    } else {
+        16 + (36 * r + 6 * g + b) as u8
+    }
+}
+
+/// Index of the nearest of the 16 basic colors
+pub fn nearest_ansi16(rgb: (u8, u8, u8)) -> u8 {
+    (0..ANSI16.len())
+        .min_by_key(|&i| distance(rgb, ANSI16[i]))
+        .unwrap_or_default() as u8
+}
+
+/// Darker variant of a color for alternating sibling frames
+pub fn shade((r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+    let darken = |c: u8| (c as f64 * 0.7) as u8;
+    (darken(r), darken(g), darken(b))
+}
+
+/// Best effort extraction of the module a frame belongs to, e.g. the top level package of a
+/// Python file, the crate of a Rust path or the package of a Java method.
+pub fn module_of(name: &str) -> &str {
+    // Python (py-spy): "function (path/to/file.py:12)"
+    if let Some(start) = name.rfind(" (") {
+        if name.ends_with(')') {
+            let location = &name[start + 2..name.len() - 1];
+            let path = location.split(':').next().unwrap_or(location);
+            let module = match python_package_path(path) {
+                Some(path) => path.split('/').next().unwrap_or(path),
+                // Other absolute paths are the files of the project itself
+                None if path.starts_with('/') => path.rsplit('/').next().unwrap_or(path),
+                None => path.split('/').next().unwrap_or(path),
+            };
+            return module.strip_suffix(".py").unwrap_or(module);
+        }
+    }
+    // Rust / C++: "crate::module::function"
+    if let Some((module, _)) = name.split_once("::") {
+        // Strip generic or pointer prefixes such as "<" or "&"
+        return module.trim_start_matches(['<', '&', '*']);
+    }
+    // Java: "java/util/HashMap.get" or "com.example.Foo.bar"
+    for delimiter in ['/', '.'] {
+        let mut parts = name.splitn(3, delimiter);
+        if let (Some(first), Some(second), Some(_)) = (parts.next(), parts.next(), parts.next()) {
+            return &name[..first.len() + 1 + second.len()];
+        }
+    }
+    name
+}
+
+/// Path of a Python file relative to the installed packages or the standard library it's in, as
+/// in `json/decoder.py` for `/usr/lib/python3.12/json/decoder.py`
+fn python_package_path(path: &str) -> Option<&str> {
+    for packages in ["site-packages/", "dist-packages/"] {
+        if let Some(i) = path.rfind(packages) {
+            return Some(&path[i + packages.len()..]);
+        }
+    }
+    let i = path.rfind("lib/python")? + "lib/python".len();
+    if !path[i..].starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    path[i..].split_once('/').map(|(_, rest)| rest)
+}
+
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> (u8, u8, u8) {
+    let c = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = lightness - c / 2.0;
+    let to_u8 = |v: f64| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (to_u8(r), to_u8(g), to_u8(b))
+}
+
+fn hash_name(name: &str) -> f64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish() as f64 / u64::MAX as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_depth() {
+        assert_eq!(
+            ColorDepth::detect_from(Some("truecolor"), Some("xterm-256color")),
+            ColorDepth::Truecolor
+        );
+        assert_eq!(
+            ColorDepth::detect_from(None, Some("xterm-256color")),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(
+            ColorDepth::detect_from(None, Some("linux")),
+            ColorDepth::Ansi16
+        );
+        assert_eq!(ColorDepth::detect_from(None, None), ColorDepth::Truecolor);
+
+        assert_eq!(nearest_ansi256((255, 0, 0)), 196);
+        assert_eq!(nearest_ansi256((0, 0, 0)), 16);
+        assert_eq!(nearest_ansi256((128, 128, 128)), 244);
+        assert_eq!(nearest_ansi16((250, 10, 10)), 9);
+        assert_eq!(nearest_ansi16((20, 20, 20)), 0);
+    }
+
+    #[test]
+    fn test_module_of() {
+        assert_eq!(module_of("dot (numpy/core/multiarray.py:12)"), "numpy");
+        assert_eq!(
+            module_of("get (/venv/lib/python3.12/site-packages/requests/api.py:73)"),
+            "requests"
+        );
+        assert_eq!(module_of("<module> (long_running.py:25)"), "long_running");
+        assert_eq!(
+            module_of("decode (/usr/lib/python3.12/json/decoder.py:337)"),
+            "json"
+        );
+        assert_eq!(
+            module_of("run (/usr/lib/python3.12/threading.py:1010)"),
+            "threading"
+        );
+        assert_eq!(
+            module_of("get (/usr/lib/python3/dist-packages/requests/api.py:73)"),
+            "requests"
+        );
+        assert_eq!(
+            module_of("handle (/home/me/project/server.py:10)"),
+            "server"
+        );
+        assert_eq!(module_of("tokio::runtime::park::run"), "tokio");
+        assert_eq!(module_of("<alloc::vec::Vec<T> as Drop>::drop"), "alloc");
+        assert_eq!(module_of("java/util/HashMap.get"), "java/util");
+        assert_eq!(module_of("com.example.Foo.bar"), "com.example");
+        assert_eq!(module_of("main"), "main");
+    }
+
+    #[test]
+    fn test_android_frame() {
+        assert_eq!(
+            AndroidFrame::of("com.example.Main.run"),
+            AndroidFrame::Managed
+        );
+        assert_eq!(
+            AndroidFrame::of("art_quick_generic_jni_trampoline"),
+            AndroidFrame::Jni
+        );
+        assert_eq!(
+            AndroidFrame::of("Java_com_example_Native_compute"),
+            AndroidFrame::Jni
+        );
+        assert_eq!(
+            AndroidFrame::of("art::interpreter::Execute"),
+            AndroidFrame::Runtime
+        );
+        assert_eq!(AndroidFrame::of("[libart.so]"), AndroidFrame::Runtime);
+        assert_eq!(AndroidFrame::of("memcpy"), AndroidFrame::Native);
+        assert_eq!(AndroidFrame::of("[libc.so]"), AndroidFrame::Native);
+    }
+
+    #[test]
+    fn test_separators() {
+        assert_eq!(Separators::None.next(), Separators::Line);
+        assert_eq!(Separators::Shade.next(), Separators::None);
+        assert_eq!(shade((200, 100, 0)), (140, 70, 0));
+    }
+
+    #[test]
+    fn test_hsl_to_rgb() {
+        assert_eq!(hsl_to_rgb(0.0, 1.0, 0.5), (255, 0, 0));
+        assert_eq!(hsl_to_rgb(120.0, 1.0, 0.5), (0, 255, 0));
+        assert_eq!(hsl_to_rgb(240.0, 1.0, 0.5), (0, 0, 255));
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::flame::join_frames;
+
+use super::format_folded;
+
+/// Start of the text header of an ART method trace (`Debug.startMethodTracing`)
+const MAGIC: &[u8] = b"*version\n";
+/// End of the text header, followed by the binary records
+const HEADER_END: &[u8] = b"*end\n";
+/// Magic number of the binary part, "SLOW" in little endian
+const DATA_MAGIC: u32 = 0x574f4c53;
+
+pub fn looks_like_art_trace(buf: &[u8]) -> bool {
+    buf.starts_with(MAGIC)
+}
+
+/// What a record of the trace says happened to a method
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Enter,
+    Exit,
+}
+
+/// Threads and methods listed in the text header, by id
+#[derive(Default)]
+struct Header {
+    /// Whether records carry both the thread CPU time and the wall clock time
+    dual_clock: bool,
+    threads: HashMap<u64, String>,
+    methods: HashMap<u64, String>,
+}
+
+fn parse_header(text: &str) -> Header {
+    let mut header = Header::default();
+    let mut section = "";
+    for line in text.lines() {
+        if let Some(name) = line.strip_prefix('*') {
+            section = name;
+            continue;
+        }
+        match section {
+            "version" if line == "clock=dual" => header.dual_clock = true,
+            "threads" => {
+                if let Some((id, name)) = line.split_once('\t') {
+                    if let Ok(id) = id.parse() {
+                        header.threads.insert(id, name.to_string());
+                    }
+                }
+            }
+            "methods" => {
+                let fields = line.split('\t').collect::<Vec<_>>();
+                let Some(id) = fields
+                    .first()
+                    .and_then(|id| u64::from_str_radix(id.trim_start_matches("0x"), 16).ok())
+                else {
+                    continue;
+                };
+                let name = match fields.as_slice() {
+                    [_, class, method, _signature, file, line, ..] => {
+                        format!("{}.{} ({}:{})", class, method, file, line)
+                    }
+                    [_, class, method, ..] => format!("{}.{}", class, method),
+                    _ => continue,
+                };
+                header.methods.insert(id, name);
+            }
+            _ => {}
+        }
+    }
+    header
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        buf.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        buf.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Convert an ART method trace to folded stacks. Each stack counts the microseconds (of wall clock
+/// time when the trace has it) spent in its leaf method. The stacks of each thread start with the
+/// thread name when more than one thread was traced.
+pub fn to_folded(buf: &[u8]) -> Result<String> {
+    let header_len = buf
+        .windows(HEADER_END.len())
+        .position(|window| window == HEADER_END)
+        .ok_or_else(|| anyhow!("Invalid ART method trace: no end of header"))?;
+    let header = parse_header(&String::from_utf8_lossy(&buf[..header_len]));
+    let data = &buf[header_len + HEADER_END.len()..];
+    if read_u32(data, 0) != Some(DATA_MAGIC) {
+        bail!("Invalid ART method trace: bad magic");
+    }
+    let truncated = || anyhow!("Invalid ART method trace: truncated header");
+    let version = read_u16(data, 4).ok_or_else(truncated)?;
+    let offset = read_u16(data, 6).ok_or_else(truncated)? as usize;
+    let (thread_id_len, record_len) = match version {
+        1 => (1, 9),
+        2 => (2, 10),
+        _ => (2, read_u16(data, 16).ok_or_else(truncated)? as usize),
+    };
+    // The wall clock time is the last of the times of a record
+    let time_offset = if header.dual_clock && version >= 3 {
+        thread_id_len + 8
+    } else {
+        thread_id_len + 4
+    };
+    if record_len < time_offset + 4 {
+        bail!("Invalid ART method trace: records of {} bytes", record_len);
+    }
+
+    // Methods on the stack and time of the last record, by thread
+    let mut thread_stacks: HashMap<u64, (Vec<u64>, u32)> = HashMap::new();
+    let mut counts: HashMap<(u64, Vec<u64>), u64> = HashMap::new();
+    for record in data
+        .get(offset..)
+        .unwrap_or_default()
+        .chunks_exact(record_len)
+    {
+        let thread_id = match thread_id_len {
+            1 => record[0] as u64,
+            _ => read_u16(record, 0).unwrap_or_default() as u64,
+        };
+        let method_value = read_u32(record, thread_id_len).unwrap_or_default() as u64;
+        let time = read_u32(record, time_offset).unwrap_or_default();
+        let (method, action) = match method_value & 3 {
+            0 => (method_value & !3, Action::Enter),
+            // 1 is a regular exit and 2 an exit by an exception
+            _ => (method_value & !3, Action::Exit),
+        };
+        let (stack, last_time) = thread_stacks.entry(thread_id).or_insert((vec![], time));
+        let elapsed = time.saturating_sub(*last_time) as u64;
+        if !stack.is_empty() && elapsed > 0 {
+            *counts.entry((thread_id, stack.clone())).or_default() += elapsed;
+        }
+        *last_time = time;
+        match action {
+            Action::Enter => stack.push(method),
+            Action::Exit => {
+                if let Some(i) = stack.iter().rposition(|m| *m == method) {
+                    stack.truncate(i);
+                }
+            }
+        }
+    }
+
+    let split_by_thread = thread_stacks.len() > 1;
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    for ((thread_id, methods), count) in counts {
+        let mut frames = vec![];
+        if split_by_thread {
+            frames.push(
+                header
+                    .threads
+                    .get(&thread_id)
+                    .cloned()
+                    .unwrap_or_else(|| format!("thread {}", thread_id)),
+            );
+        }
+        frames.extend(methods.iter().map(|method| {
+            header
+                .methods
+                .get(method)
+                .cloned()
+                .unwrap_or_else(|| format!("[method {:#x}]", method))
+        }));
+        *stacks.entry(join_frames(&frames)).or_default() += count;
+    }
+    Ok(format_folded(&stacks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_folded() {
+        let mut buf = b"*version\n3\nclock=dual\nvm=art\n*threads\n1\tmain\n2\tworker\n\
+            *methods\n0x10\tcom.example.Main\trun\t()V\tMain.java\t10\n\
+            0x20\tcom.example.Main\twork\t(I)V\n*end\n"
+            .to_vec();
+        buf.extend_from_slice(&DATA_MAGIC.to_le_bytes());
+        buf.extend_from_slice(&3u16.to_le_bytes());
+        buf.extend_from_slice(&32u16.to_le_bytes());
+        buf.extend_from_slice(&0u64.to_le_bytes());
+        buf.extend_from_slice(&14u16.to_le_bytes());
+        buf.resize(buf.len() + 14, 0);
+        for (thread_id, method_value, wall_time) in [
+            (1u16, 0x10u32, 0u32),
+            (1, 0x20, 100),
+            (2, 0x20, 150),
+            (1, 0x21, 400),
+            (2, 0x22, 200),
+            (1, 0x11, 450),
+        ] {
+            buf.extend_from_slice(&thread_id.to_le_bytes());
+            buf.extend_from_slice(&method_value.to_le_bytes());
+            buf.extend_from_slice(&0u32.to_le_bytes());
+            buf.extend_from_slice(&wall_time.to_le_bytes());
+        }
+        assert!(looks_like_art_trace(&buf));
+        assert_eq!(
+            to_folded(&buf).unwrap(),
+            "\
+main;com.example.Main.run (Main.java:10) 150
+main;com.example.Main.run (Main.java:10);com.example.Main.work 300
+worker;com.example.Main.work 50"
+        );
+        assert!(to_folded(b"*version\n3\n").is_err());
+    }
+}
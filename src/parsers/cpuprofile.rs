@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::flame::join_frames;
+
+use super::{format_folded, TimedSample};
+
+/// What each sample of a `.cpuprofile` contributes to the counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum CpuProfileWeight {
+    /// Count every sample once
+    #[default]
+    Samples,
+    /// Weight samples by their duration in microseconds (from timeDeltas)
+    Time,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CpuProfileOptions {
+    pub weight: CpuProfileWeight,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CallFrame {
+    pub function_name: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default = "default_line_number")]
+    pub line_number: i64,
+}
+
+fn default_line_number() -> i64 {
+    -1
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Node {
+    pub id: u64,
+    pub call_frame: CallFrame,
+    #[serde(default)]
+    pub hit_count: u64,
+    #[serde(default)]
+    pub children: Vec<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CpuProfile {
+    pub nodes: Vec<Node>,
+    #[serde(default)]
+    pub samples: Vec<u64>,
+    #[serde(default)]
+    pub time_deltas: Vec<i64>,
+}
+
+/// A `.cpuprofile` is a JSON object with a `nodes` array of call frames.
+pub fn looks_like_cpuprofile(content: &str) -> bool {
+    let content = content.trim_start();
+    content.starts_with('{') && content.contains("\"nodes\"") && content.contains("\"callFrame\"")
+}
+
+fn frame_name(frame: &CallFrame) -> String {
+    let name = if frame.function_name.is_empty() {
+        "(anonymous)"
+    } else {
+        frame.function_name.as_str()
+    };
+    if frame.url.is_empty() {
+        name.to_string()
+    } else {
+        let url = frame.url.rsplit('/').next().unwrap_or(&frame.url);
+        if frame.line_number >= 0 {
+            // Line numbers are 0-based
+            format!("{} ({}:{})", name, url, frame.line_number + 1)
+        } else {
+            format!("{} ({})", name, url)
+        }
+    }
+}
+
+impl CpuProfile {
+    pub(crate) fn parse(content: &str) -> Result<Self> {
+        serde_json::from_str(content).map_err(|e| anyhow!("Invalid .cpuprofile: {}", e))
+    }
+
+    /// Folded stack of every node keyed by node id. The synthetic "(root)" node is omitted.
+    pub(crate) fn node_stacks(&self) -> Result<HashMap<u64, String>> {
+        let mut parents = HashMap::new();
+        for node in self.nodes.iter() {
+            for child in node.children.iter() {
+                parents.insert(*child, node.id);
+            }
+        }
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| (node.id, node))
+            .collect::<HashMap<_, _>>();
+        let mut stacks = HashMap::new();
+        for node in self.nodes.iter() {
+            let mut frames = vec![];
+            let mut current = Some(node.id);
+            let mut depth = 0;
+            while let Some(id) = current {
+                let Some(node) = nodes.get(&id) else {
+                    break;
+                };
+                // A stack deeper than the number of nodes goes around a cycle
+                depth += 1;
+                if depth > nodes.len() {
+                    return Err(anyhow!("Invalid .cpuprofile: cyclic node tree"));
+                }
+                if node.call_frame.function_name != "(root)" {
+                    frames.push(frame_name(&node.call_frame));
+                }
+                current = parents.get(&id).copied();
+            }
+            frames.reverse();
+            stacks.insert(node.id, join_frames(&frames));
+        }
+        Ok(stacks)
+    }
+}
+
+/// Convert a Chrome DevTools / Node `--cpu-prof` profile to folded stacks.
+pub fn to_folded(content: &str, options: &CpuProfileOptions) -> Result<String> {
+    let profile = CpuProfile::parse(content)?;
+    Ok(folded_stacks(&profile, &profile.node_stacks()?, options))
+}
+
+/// Convert a profile to folded stacks like [`to_folded`], along with its samples in the order they
+/// were taken. The samples are empty for older profiles without them.
+pub fn to_folded_with_timed_samples(
+    content: &str,
+    options: &CpuProfileOptions,
+) -> Result<(String, Vec<TimedSample>)> {
+    let profile = CpuProfile::parse(content)?;
+    let node_stacks = profile.node_stacks()?;
+    Ok((
+        folded_stacks(&profile, &node_stacks, options),
+        timed_samples(&profile, &node_stacks),
+    ))
+}
+
+fn folded_stacks(
+    profile: &CpuProfile,
+    node_stacks: &HashMap<u64, String>,
+    options: &CpuProfileOptions,
+) -> String {
+    let mut counts: HashMap<u64, u64> = HashMap::new();
+    if profile.samples.is_empty() {
+        // Older profiles only carry the aggregated hit counts
+        for node in profile.nodes.iter() {
+            counts.insert(node.id, node.hit_count);
+        }
+    } else {
+        for (i, node_id) in profile.samples.iter().enumerate() {
+            let weight = match options.weight {
+                CpuProfileWeight::Samples => 1,
+                // timeDeltas[i] is the time elapsed before sample i was taken, so the duration of
+                // sample i is the delta of the following sample. The last sample reuses its own.
+                CpuProfileWeight::Time => profile
+                    .time_deltas
+                    .get(i + 1)
+                    .or(profile.time_deltas.get(i))
+                    .copied()
+                    .unwrap_or(0)
+                    .max(0) as u64,
+            };
+            *counts.entry(*node_id).or_default() += weight;
+        }
+    }
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    for (node_id, count) in counts {
+        match node_stacks.get(&node_id) {
+            Some(stack) if count > 0 && !stack.is_empty() => {
+                *stacks.entry(stack.clone()).or_default() += count;
+            }
+            _ => {}
+        }
+    }
+    format_folded(&stacks)
+}
+
+fn timed_samples(profile: &CpuProfile, node_stacks: &HashMap<u64, String>) -> Vec<TimedSample> {
+    let mut time: u64 = 0;
+    profile
+        .samples
+        .iter()
+        .enumerate()
+        .map(|(i, node_id)| {
+            // Deltas can be slightly negative when samples are reordered; keep time monotonic
+            let delta = profile.time_deltas.get(i).copied().unwrap_or(0);
+            time = time.saturating_add(delta.max(0) as u64);
+            TimedSample {
+                time,
+                stack: node_stacks.get(node_id).cloned().unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_folded() {
+        let content = std::fs::read_to_string("tests/data/simple.cpuprofile").unwrap();
+        assert!(looks_like_cpuprofile(&content));
+        let folded = to_folded(&content, &CpuProfileOptions::default()).unwrap();
+        assert_eq!(
+            folded,
+            "\
+(garbage collector) 1
+(program) 1
+main (app.js:1);work (app.js:10) 2
+main (app.js:1);work (app.js:10);(anonymous) (lib.js:3) 1"
+        );
+
+        let options = CpuProfileOptions {
+            weight: CpuProfileWeight::Time,
+        };
+        let folded = to_folded(&content, &options).unwrap();
+        assert_eq!(
+            folded,
+            "\
+(garbage collector) 200
+(program) 100
+main (app.js:1);work (app.js:10) 300
+main (app.js:1);work (app.js:10);(anonymous) (lib.js:3) 400"
+        );
+    }
+
+    #[test]
+    fn test_cyclic_node_tree() {
+        let content = r#"{"nodes": [
+            {"id": 1, "callFrame": {"functionName": "a"}, "children": [2]},
+            {"id": 2, "callFrame": {"functionName": "b"}, "children": [1]}
+        ], "samples": [1, 2], "timeDeltas": [0, 100]}"#;
+        let error = to_folded(content, &CpuProfileOptions::default()).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid .cpuprofile: cyclic node tree");
+    }
+
+    #[test]
+    fn test_to_folded_with_timed_samples() {
+        let content = std::fs::read_to_string("tests/data/simple.cpuprofile").unwrap();
+        let options = CpuProfileOptions::default();
+        let (folded, samples) = to_folded_with_timed_samples(&content, &options).unwrap();
+        assert_eq!(folded, to_folded(&content, &options).unwrap());
+        let sample = |time, stack: &str| TimedSample {
+            time,
+            stack: stack.to_string(),
+        };
+        assert_eq!(
+            samples,
+            vec![
+                sample(0, "main (app.js:1);work (app.js:10)"),
+                sample(100, "(program)"),
+                sample(
+                    200,
+                    "main (app.js:1);work (app.js:10);(anonymous) (lib.js:3)"
+                ),
+                sample(600, "main (app.js:1);work (app.js:10)"),
+                sample(800, "(garbage collector)"),
+            ]
+        );
+    }
+}
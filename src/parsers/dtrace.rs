@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use crate::flame::join_frames;
+
+use super::format_folded;
+
+/// Header printed by `dtrace` before the probes firing, as in `CPU     ID    FUNCTION:NAME`
+fn is_header(line: &str) -> bool {
+    let mut columns = line.split_whitespace();
+    columns.next() == Some("CPU")
+        && columns.next() == Some("ID")
+        && columns.next() == Some("FUNCTION:NAME")
+}
+
+/// Count closing the stack of an aggregation, alone on its line
+fn parse_count(line: &str) -> Option<u64> {
+    let line = line.trim();
+    if line.is_empty() || !line.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    line.parse().ok()
+}
+
+/// Frame of a `ustack()` or `stack()`, as in `libc.so.1`malloc+0x1a` or an unresolved
+/// `0x7fff5fc01028`
+fn is_frame(line: &str) -> bool {
+    let line = line.trim();
+    line.contains('`') || line.starts_with("0x")
+}
+
+/// Name of a frame without its offset, as in `libc.so.1`malloc` for `libc.so.1`malloc+0x1a`
+fn frame_name(line: &str) -> String {
+    let line = line.trim();
+    let name = match line.rfind('+') {
+        Some(i) if line.contains('`') && line[i + 1..].starts_with("0x") => &line[..i],
+        _ => line,
+    };
+    name.to_string()
+}
+
+/// Output of a DTrace aggregation of `ustack()` or `stack()`, such as
+/// `dtrace -n 'profile-997 { @[ustack()] = count(); }'`: indented frames, the leaf first, followed
+/// by the count of the stack alone on its line.
+pub fn looks_like_dtrace(content: &str) -> bool {
+    let mut previous_is_frame = false;
+    for line in content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .take(200)
+    {
+        if is_header(line) || (previous_is_frame && parse_count(line).is_some()) {
+            return true;
+        }
+        previous_is_frame = line.starts_with(char::is_whitespace) && is_frame(line);
+    }
+    false
+}
+
+/// Convert the output of a DTrace aggregation of `ustack()` and/or `stack()` to folded stacks,
+/// dropping the offsets of the frames. The keys printed before the frames, as with
+/// `@[execname, ustack()]`, become the root frames of their stack.
+pub fn to_folded(content: &str) -> String {
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    let mut keys = vec![];
+    let mut frames = vec![];
+    let mut lines = content.lines().peekable();
+    // The probes that fired, such as the `tick-60s` ending the recording, are listed under the
+    // header until the first empty line
+    if lines.peek().is_some_and(|line| is_header(line)) {
+        lines
+            .by_ref()
+            .take_while(|line| !line.trim().is_empty())
+            .count();
+    }
+    for line in lines {
+        if line.trim().is_empty() || line.starts_with("dtrace:") {
+            continue;
+        }
+        if let Some(count) = parse_count(line) {
+            if count > 0 && !(keys.is_empty() && frames.is_empty()) {
+                let stack = join_frames(keys.drain(..).chain(frames.drain(..).rev()));
+                *stacks.entry(stack).or_default() += count;
+            }
+            keys.clear();
+            frames.clear();
+        } else if frames.is_empty() && !is_frame(line) {
+            keys.push(frame_name(line));
+        } else {
+            frames.push(frame_name(line));
+        }
+    }
+    format_folded(&stacks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_name() {
+        assert_eq!(frame_name("  libc.so.1`malloc+0x1a"), "libc.so.1`malloc");
+        assert_eq!(frame_name("  libc.so.1`_lwp_start"), "libc.so.1`_lwp_start");
+        assert_eq!(
+            frame_name("  mysqld`JOIN::exec()+0x9ab"),
+            "mysqld`JOIN::exec()"
+        );
+        assert_eq!(frame_name("  0x7fff5fc01028"), "0x7fff5fc01028");
+        assert_eq!(frame_name("  mysqld"), "mysqld");
+    }
+
+    #[test]
+    fn test_to_folded() {
+        let content = std::fs::read_to_string("tests/data/dtrace.txt").unwrap();
+        assert!(looks_like_dtrace(&content));
+        assert!(!looks_like_dtrace("main;a 3\nmain;b 1"));
+        assert_eq!(
+            to_folded(&content),
+            "\
+0x401000;mysqld`main;libc.so.1`malloc 3
+mysqld`main;mysqld`do_command;libc.so.1`read 17
+mysqld`main;mysqld`do_command;libc.so.1`write 5"
+        );
+        // Keys of the aggregation are the root frames
+        let content = "\n              mysqld\n              libc.so.1`read+0x8\n              mysqld`main+0x10\n               4\n";
+        assert!(looks_like_dtrace(content));
+        assert_eq!(to_folded(content), "mysqld;mysqld`main;libc.so.1`read 4");
+        // Semicolons in frame names are escaped
+        let content =
+            "\n              a.out`f;g+0x8\n              a.out`main+0x10\n               2\n";
+        assert_eq!(to_folded(content), "a.out`main;a.out`f\\;g 2");
+    }
+}
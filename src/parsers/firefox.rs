@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::flame::escape_frame;
+
+use super::format_folded;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct FuncTable {
+    name: Vec<usize>,
+    #[serde(rename = "fileName")]
+    file_name: Vec<Option<usize>>,
+    #[serde(rename = "lineNumber")]
+    line_number: Vec<Option<u32>>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct FrameTable {
+    func: Vec<usize>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct StackTable {
+    frame: Vec<usize>,
+    prefix: Vec<Option<usize>>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct SamplesTable {
+    stack: Vec<Option<usize>>,
+    /// Weight of each sample (a number of samples, milliseconds or bytes), all 1 when missing
+    weight: Option<Vec<Option<f64>>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Thread {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    process_name: Option<String>,
+    /// Strings of the thread, in profiles older than the shared string array
+    #[serde(default, alias = "stringTable")]
+    string_array: Option<Vec<String>>,
+    #[serde(default)]
+    func_table: FuncTable,
+    #[serde(default)]
+    frame_table: FrameTable,
+    #[serde(default)]
+    stack_table: StackTable,
+    #[serde(default)]
+    samples: SamplesTable,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Shared {
+    string_array: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Profile {
+    threads: Vec<Thread>,
+    #[serde(default)]
+    shared: Shared,
+}
+
+/// A processed Firefox Profiler profile is a JSON object with `threads` made of tables such as the
+/// `stackTable`.
+pub fn looks_like_firefox_profile(content: &str) -> bool {
+    let content = content.trim_start();
+    content.starts_with('{')
+        && content.contains("\"threads\"")
+        && content.contains("\"stackTable\"")
+}
+
+impl Thread {
+    fn root_name(&self) -> String {
+        match self.process_name.as_deref() {
+            Some(process_name) if !process_name.is_empty() => {
+                format!("{}/{}", process_name, self.name)
+            }
+            _ => self.name.clone(),
+        }
+    }
+
+    /// Name of every function, as in "name (file:line)"
+    fn func_names(&self, strings: &[String]) -> Vec<String> {
+        let string = |index: usize| strings.get(index).map_or("", |s| s.as_str());
+        let funcs = &self.func_table;
+        funcs
+            .name
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let name = string(*name);
+                let file = funcs.file_name.get(i).copied().flatten().map(string);
+                match file.filter(|file| !file.is_empty()) {
+                    Some(file) => {
+                        let file = file.rsplit('/').next().unwrap_or(file);
+                        match funcs.line_number.get(i).copied().flatten() {
+                            Some(line) => format!("{} ({}:{})", name, file, line),
+                            None => format!("{} ({})", name, file),
+                        }
+                    }
+                    None => name.to_string(),
+                }
+            })
+            .collect()
+    }
+
+    /// Folded stack of every stack index. The "(root)" function of native profiles is omitted.
+    fn stacks(&self, strings: &[String]) -> Vec<String> {
+        let func_names = self.func_names(strings);
+        let frame_name = |stack: usize| {
+            self.stack_table
+                .frame
+                .get(stack)
+                .and_then(|frame| self.frame_table.func.get(*frame))
+                .and_then(|func| func_names.get(*func))
+                .map_or("[unknown]", |name| name.as_str())
+        };
+        let mut stacks: Vec<String> = Vec::with_capacity(self.stack_table.frame.len());
+        for stack in 0..self.stack_table.frame.len() {
+            // Prefixes always come before the stacks using them
+            let prefix = self
+                .stack_table
+                .prefix
+                .get(stack)
+                .copied()
+                .flatten()
+                .and_then(|prefix| stacks.get(prefix))
+                .map_or("", |prefix| prefix.as_str());
+            let name = frame_name(stack);
+            let folded = if name == "(root)" {
+                prefix.to_string()
+            } else if prefix.is_empty() {
+                escape_frame(name).into_owned()
+            } else {
+                format!("{};{}", prefix, escape_frame(name))
+            };
+            stacks.push(folded);
+        }
+        stacks
+    }
+}
+
+/// Convert a processed Firefox Profiler profile to folded stacks, weighting the samples by their
+/// weight if they have one. The stacks of each thread start with the thread name when the profile
+/// has more than one thread with samples.
+pub fn to_folded(content: &str) -> Result<String> {
+    let profile: Profile = serde_json::from_str(content)
+        .map_err(|e| anyhow!("Invalid Firefox Profiler profile: {}", e))?;
+    let threads = profile
+        .threads
+        .iter()
+        .filter(|thread| thread.samples.stack.iter().any(|stack| stack.is_some()))
+        .collect::<Vec<_>>();
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    for thread in threads.iter() {
+        let strings = thread
+            .string_array
+            .as_deref()
+            .unwrap_or(&profile.shared.string_array);
+        let thread_stacks = thread.stacks(strings);
+        let root = (threads.len() > 1).then(|| thread.root_name());
+        for (i, stack) in thread.samples.stack.iter().enumerate() {
+            let Some(stack) = stack.and_then(|stack| thread_stacks.get(stack)) else {
+                continue;
+            };
+            let weight = match thread.samples.weight.as_ref() {
+                Some(weights) => weights.get(i).copied().flatten().unwrap_or(0.0).round() as u64,
+                None => 1,
+            };
+            if weight == 0 || stack.is_empty() {
+                continue;
+            }
+            let stack = match root.as_deref() {
+                Some(root) => format!("{};{}", escape_frame(root), stack),
+                None => stack.clone(),
+            };
+            *stacks.entry(stack).or_default() += weight;
+        }
+    }
+    Ok(format_folded(&stacks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_folded() {
+        let content = std::fs::read_to_string("tests/data/firefox-profile.json").unwrap();
+        assert!(looks_like_firefox_profile(&content));
+        assert_eq!(
+            to_folded(&content).unwrap(),
+            "\
+DOM Worker;work (lib.js:7) 3
+Web Content/GeckoMain;main (app.js:1) 1
+Web Content/GeckoMain;main (app.js:1);render (lib.js:3) 1
+Web Content/GeckoMain;main (app.js:1);work (app.js:10) 2"
+        );
+        assert!(to_folded("{\"threads\": 1}").is_err());
+    }
+}
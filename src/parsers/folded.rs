@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use crate::flame::{
+    escape_frame, split_frame_annotations, split_frames, FrameAnnotations, FRAME_DELIMITER,
+    FRAME_DELIMITER_ESCAPE,
+};
+
+/// Folded stacks of the extended format with the annotations of their frames taken out, as
+/// returned by [`strip_annotations`].
+#[derive(Debug, Clone, Default)]
+pub struct Annotated {
+    /// The folded stacks without the annotations
+    pub content: String,
+    /// Annotations of the frames, by full name of the stack without the annotations
+    pub frames: HashMap<String, FrameAnnotations>,
+    /// Stacks as written with their annotations and their counts, by stack without annotations
+    pub stacks: HashMap<String, Vec<(String, u64)>>,
+}
+
+/// Rewrite folded stacks whose frames are separated by `delimiter` into the standard
+/// `frame;frame count` form. A delimiter preceded by a backslash is part of the frame name, and
+/// semicolons in frame names are escaped so that they aren't taken as delimiters.
+pub fn with_delimiter(content: &str, delimiter: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        let (stack, rest) = match line.trim_end_matches(['\n', '\r']).rsplit_once(' ') {
+            Some((stack, _count)) if !line.starts_with('#') => (stack, &line[stack.len()..]),
+            _ => {
+                out.push_str(line);
+                continue;
+            }
+        };
+        let escaped_delimiter = format!("{}{}", FRAME_DELIMITER_ESCAPE, delimiter);
+        let mut frame = String::new();
+        let mut remaining = stack;
+        loop {
+            if let Some(after) = remaining.strip_prefix(escaped_delimiter.as_str()) {
+                frame.push_str(delimiter);
+                remaining = after;
+            } else if let Some(after) = remaining.strip_prefix(delimiter) {
+                out.push_str(&escape_frame(&frame));
+                out.push(FRAME_DELIMITER);
+                frame.clear();
+                remaining = after;
+            } else if let Some(c) = remaining.chars().next() {
+                frame.push(c);
+                remaining = &remaining[c.len_utf8()..];
+            } else {
+                break;
+            }
+        }
+        out.push_str(&escape_frame(&frame));
+        out.push_str(rest);
+    }
+    out
+}
+
+/// Take the `#key=value` annotations out of the frames of folded stacks (see
+/// [`split_frame_annotations`]), keeping them aside. With a `filter`, only the stacks with a frame
+/// annotated with that key and value are kept. Lines that aren't stacks are left as they are.
+pub fn strip_annotations(content: &str, filter: Option<&(String, String)>) -> Annotated {
+    let mut out = Annotated::default();
+    for line in content.split_inclusive('\n') {
+        let parsed = line
+            .trim_end_matches(['\n', '\r'])
+            .rsplit_once(' ')
+            .filter(|_| !line.starts_with('#'))
+            .and_then(|(stack, count)| Some((stack, count.parse::<u64>().ok()?)));
+        let Some((stack, count)) = parsed else {
+            out.content.push_str(line);
+            continue;
+        };
+        let frames = split_frames(stack)
+            .map(split_frame_annotations)
+            .collect::<Vec<_>>();
+        let matches_filter = filter.is_none_or(|(key, value)| {
+            frames
+                .iter()
+                .flat_map(|(_, annotations)| annotations)
+                .any(|(k, v)| k == key && v == value)
+        });
+        if !matches_filter {
+            continue;
+        }
+        let mut full_name = String::new();
+        for (i, (name, annotations)) in frames.iter().enumerate() {
+            if i > 0 {
+                full_name.push(FRAME_DELIMITER);
+            }
+            full_name.push_str(name);
+            if annotations.is_empty() {
+                continue;
+            }
+            let frame_annotations = out.frames.entry(full_name.clone()).or_default();
+            for (key, value) in annotations {
+                let samples = frame_annotations
+                    .entry(key.to_string())
+                    .or_default()
+                    .entry(value.to_string())
+                    .or_default();
+                *samples = samples.saturating_add(count);
+            }
+        }
+        out.stacks
+            .entry(full_name.clone())
+            .or_default()
+            .push((stack.to_string(), count));
+        out.content.push_str(&format!("{} {}\n", full_name, count));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_delimiter() {
+        let content = "# comment\nmain|parse|read 3\nmain|a;b|x\\|y 2\r\nno count\nmain 1";
+        assert_eq!(
+            with_delimiter(content, "|"),
+            "# comment\nmain;parse;read 3\nmain;a\\;b;x|y 2\r\nno count\nmain 1"
+        );
+        assert_eq!(with_delimiter("a::b::c 1\n", "::"), "a;b;c 1\n");
+    }
+
+    #[test]
+    fn test_strip_annotations() {
+        let content = "# comment\nmain;work#cpu=3#numa=1 2\nmain;work#cpu=4 1\nmain;Foo#bar 5\n";
+        let annotated = strip_annotations(content, None);
+        assert_eq!(
+            annotated.content,
+            "# comment\nmain;work 2\nmain;work 1\nmain;Foo#bar 5\n"
+        );
+        let work = &annotated.frames["main;work"];
+        assert_eq!(work["cpu"]["3"], 2);
+        assert_eq!(work["cpu"]["4"], 1);
+        assert_eq!(work["numa"]["1"], 2);
+        assert!(!annotated.frames.contains_key("main"));
+        assert_eq!(
+            annotated.stacks["main;work"],
+            vec![
+                ("main;work#cpu=3#numa=1".to_string(), 2),
+                ("main;work#cpu=4".to_string(), 1)
+            ]
+        );
+
+        let filter = ("cpu".to_string(), "4".to_string());
+        let annotated = strip_annotations(content, Some(&filter));
+        assert_eq!(annotated.content, "# comment\nmain;work 1\n");
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use crate::flame::join_frames;
+
+use super::{format_folded, meaningful_lines};
+
+/// What a line of a `function_graph` trace tells about the functions of its CPU
+#[derive(Debug, PartialEq)]
+enum Event<'a> {
+    /// `func() {`: the function was called and calls others
+    Entry(&'a str),
+    /// `func();`: the function was called and returned after the duration, in nanoseconds
+    Leaf(&'a str, u64),
+    /// `}`: the last entered function returned after the duration, in nanoseconds
+    Exit(u64),
+}
+
+/// A trace of the `function_graph` tracer, as read from `/sys/kernel/tracing/trace` or written by
+/// `trace-cmd report`, has a `# tracer: function_graph` header or lines such as
+/// ` 0)   0.529 us    |    kmem_cache_alloc();`.
+pub fn looks_like_function_graph(content: &str) -> bool {
+    content
+        .lines()
+        .take(5)
+        .any(|line| line.trim() == "# tracer: function_graph")
+        || meaningful_lines(content)
+            .take(10)
+            .any(|line| parse_line(line).is_some())
+}
+
+/// Number of the CPU column, as in ` 0) `, which can follow the absolute time column
+fn parse_cpu(line: &str) -> Option<u32> {
+    line.split('|').find_map(|column| {
+        let (cpu, _) = column.trim_start().split_once(')')?;
+        cpu.parse().ok()
+    })
+}
+
+/// Duration column, as in `+ 12.345 us`, in nanoseconds. The marker before the number flags long
+/// durations.
+fn parse_duration(column: &str) -> Option<u64> {
+    let duration = column
+        .trim()
+        .trim_start_matches(['+', '!', '#', '*', '@', '$'])
+        .trim_start()
+        .strip_suffix("us")?
+        .trim_end();
+    let micros = duration.parse::<f64>().ok()?;
+    Some((micros * 1000.0).round() as u64)
+}
+
+fn parse_line(line: &str) -> Option<(u32, Event<'_>)> {
+    let cpu = parse_cpu(line)?;
+    let (columns, function) = line.rsplit_once('|')?;
+    // The duration column follows the CPU (and task) columns
+    let duration_column = columns.rsplit('|').next()?;
+    let duration_column = duration_column
+        .split_once(')')
+        .map_or(duration_column, |(_, duration)| duration);
+    let duration = parse_duration(duration_column).unwrap_or(0);
+    // With the funcgraph-tail option, returns are followed by `/* func */`
+    let function = match function.split_once("/*") {
+        Some((function, _)) => function,
+        None => function,
+    }
+    .trim();
+    let event = if function == "}" {
+        Event::Exit(duration)
+    } else if let Some(name) = function.strip_suffix('{') {
+        Event::Entry(name.trim_end().trim_end_matches("()"))
+    } else if let Some(name) = function.strip_suffix(';') {
+        Event::Leaf(name.trim_end_matches("()"), duration)
+    } else {
+        return None;
+    };
+    Some((cpu, event))
+}
+
+/// Convert a `function_graph` trace to folded stacks. The calls of each CPU are followed to rebuild
+/// the stacks, which count the nanoseconds spent in their leaf function, not counting the time
+/// spent in the functions it calls. Returns before the first call of the trace and calls that
+/// didn't return before its end are ignored.
+pub fn to_folded(content: &str) -> String {
+    // Functions being called and the time spent in the functions they called, by CPU
+    let mut cpu_stacks: HashMap<u32, Vec<(&str, u64)>> = HashMap::new();
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    let mut add = |stack: &[(&str, u64)], name: &str, count: u64| {
+        if count == 0 {
+            return;
+        }
+        let mut frames = stack.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        frames.push(name);
+        *stacks.entry(join_frames(&frames)).or_default() += count;
+    };
+    for (cpu, event) in meaningful_lines(content).filter_map(parse_line) {
+        let stack = cpu_stacks.entry(cpu).or_default();
+        let duration = match event {
+            Event::Entry(name) => {
+                stack.push((name, 0));
+                continue;
+            }
+            Event::Leaf(name, duration) => {
+                add(stack, name, duration);
+                duration
+            }
+            Event::Exit(duration) => {
+                let Some((name, children)) = stack.pop() else {
+                    continue;
+                };
+                add(stack, name, duration.saturating_sub(children));
+                duration
+            }
+        };
+        if let Some((_, children)) = stack.last_mut() {
+            *children += duration;
+        }
+    }
+    format_folded(&stacks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(
+            parse_line(" 0)               |  do_sys_open() {"),
+            Some((0, Event::Entry("do_sys_open")))
+        );
+        assert_eq!(
+            parse_line(" 1)   0.529 us    |      kmem_cache_alloc();"),
+            Some((1, Event::Leaf("kmem_cache_alloc", 529)))
+        );
+        assert_eq!(
+            parse_line(" 0) + 12.345 us   |  } /* do_sys_open */"),
+            Some((0, Event::Exit(12345)))
+        );
+        assert_eq!(
+            parse_line(" 3)    sh-4802     |   0.616 us    |    rcu_process_gp_end();"),
+            Some((3, Event::Leaf("rcu_process_gp_end", 616)))
+        );
+        assert_eq!(
+            parse_line("  113.245678 |   2) ! 101.500 us  |  }"),
+            Some((2, Event::Exit(101500)))
+        );
+        assert_eq!(parse_line(" 1)  bash-1234    =>  sshd-567"), None);
+        assert_eq!(parse_line("main;do_sys_open 3"), None);
+    }
+
+    #[test]
+    fn test_to_folded() {
+        let content = std::fs::read_to_string("tests/data/function-graph.txt").unwrap();
+        assert!(looks_like_function_graph(&content));
+        assert_eq!(
+            to_folded(&content),
+            "\
+do_sys_open 2000
+do_sys_open;getname 500
+do_sys_open;getname;kmem_cache_alloc 1000
+do_sys_open;getname;strncpy_from_user 500
+do_sys_open;vfs_open 6000
+vfs_read 4000"
+        );
+    }
+}
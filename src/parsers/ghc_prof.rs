@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+
+use crate::flame::join_frames;
+
+use super::format_folded;
+
+/// What each cost centre of a GHC `.prof` report contributes to the counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum GhcProfWeight {
+    /// Time spent in the cost centre
+    #[default]
+    Time,
+    /// Memory allocated by the cost centre
+    Alloc,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GhcProfOptions {
+    pub weight: GhcProfWeight,
+}
+
+/// A GHC `.prof` report (from `+RTS -p` or `-P`) starts with its title and has a cost centre tree.
+pub fn looks_like_ghc_prof(content: &str) -> bool {
+    content
+        .lines()
+        .take(5)
+        .any(|line| line.contains("Profiling Report"))
+        && content.contains("COST CENTRE")
+}
+
+/// Columns of the cost centre tree, following the cost centre name, by their position in a line
+struct Columns {
+    module: usize,
+    src: Option<usize>,
+    /// Individual %time or %alloc, in tenths of a percent
+    percent: usize,
+    /// Individual ticks or bytes, only reported by `+RTS -P`
+    amount: Option<usize>,
+    count: usize,
+}
+
+impl Columns {
+    /// Read the columns from the header of the cost centre tree, which is the one with a `no.`
+    /// column (the flat summary before it has none).
+    fn parse(header: &str, weight: GhcProfWeight) -> Option<Self> {
+        let names = header
+            .trim_start()
+            .strip_prefix("COST CENTRE")?
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        if !names.contains(&"no.") {
+            return None;
+        }
+        let position = |name| names.iter().position(|n| *n == name);
+        let (percent, amount) = match weight {
+            GhcProfWeight::Time => ("%time", "ticks"),
+            GhcProfWeight::Alloc => ("%alloc", "bytes"),
+        };
+        Some(Self {
+            module: position("MODULE")?,
+            src: position("SRC"),
+            percent: position(percent)?,
+            amount: position(amount),
+            count: names.len(),
+        })
+    }
+}
+
+fn frame_name(cost_centre: &str, module: &str, src: Option<&str>) -> String {
+    match src {
+        Some(src) if !src.starts_with('<') => format!("{}.{} ({})", module, cost_centre, src),
+        _ => format!("{}.{}", module, cost_centre),
+    }
+}
+
+/// Convert the cost centre tree of a GHC `.prof` report to folded stacks. Every cost centre is a
+/// frame counting its individual ticks (or bytes), or its individual percentage in tenths of a
+/// percent when the report doesn't have them, so that the frames add up to the inherited values.
+/// The synthetic `MAIN` root is omitted.
+pub fn to_folded(content: &str, options: &GhcProfOptions) -> Result<String> {
+    let mut lines = content.lines();
+    let columns = lines
+        .by_ref()
+        .find_map(|line| Columns::parse(line, options.weight))
+        .ok_or_else(|| anyhow!("Invalid .prof report: no cost centre tree"))?;
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    // Indentation and frame of the cost centres on the path to the current one
+    let mut path: Vec<(usize, String)> = vec![];
+    for line in lines {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let Some((cost_centre, values)) = fields.split_first() else {
+            continue;
+        };
+        if values.len() != columns.count {
+            continue;
+        }
+        let module = values[columns.module];
+        let indent = line.len() - line.trim_start().len();
+        while path.last().is_some_and(|(i, _)| *i >= indent) {
+            path.pop();
+        }
+        if *cost_centre == "MAIN" && module == "MAIN" {
+            continue;
+        }
+        let src = columns.src.map(|src| values[src]);
+        path.push((indent, frame_name(cost_centre, module, src)));
+        let count = match columns.amount {
+            Some(amount) => values[amount].parse::<u64>().ok(),
+            None => values[columns.percent]
+                .parse::<f64>()
+                .ok()
+                .map(|percent| (percent * 10.0).round() as u64),
+        };
+        if let Some(count) = count.filter(|count| *count > 0) {
+            let stack = join_frames(path.iter().map(|(_, frame)| frame));
+            *stacks.entry(stack).or_default() += count;
+        }
+    }
+    Ok(format_folded(&stacks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_folded() {
+        let content = std::fs::read_to_string("tests/data/simple.prof").unwrap();
+        assert!(looks_like_ghc_prof(&content));
+        let folded = to_folded(&content, &GhcProfOptions::default()).unwrap();
+        assert_eq!(
+            folded,
+            "\
+Main.CAF;Main.main (Main.hs:8:1-30);Main.fib (Main.hs:5:1-30) 600
+Main.CAF;Main.main (Main.hs:8:1-30);Main.render (Main.hs:9:1-40) 100
+Main.CAF;Main.main (Main.hs:8:1-30);Main.render (Main.hs:9:1-40);Main.fib (Main.hs:5:1-30) 300"
+        );
+
+        // Older reports without sources, with ticks and bytes from +RTS -P
+        let content = "\
+\tTue Oct 15 12:00 2024 Time and Allocation Profiling Report  (Final)
+
+                                     individual    inherited
+COST CENTRE   MODULE  no.  entries  %time %alloc   %time %alloc  ticks  bytes
+
+MAIN          MAIN    1    0          0.0    0.0   100.0  100.0      0      0
+ main         Main    2    1         25.0   10.0   100.0  100.0      1    100
+  go          Main    3    9         75.0   90.0    75.0   90.0      3    900
+";
+        assert!(looks_like_ghc_prof(content));
+        let options = GhcProfOptions {
+            weight: GhcProfWeight::Alloc,
+        };
+        assert_eq!(
+            to_folded(content, &options).unwrap(),
+            "Main.main 100\nMain.main;Main.go 900"
+        );
+        assert!(to_folded("main;a 1", &options).is_err());
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use crate::flame::join_frames;
+
+use super::{format_folded, meaningful_lines};
+
+/// Columns of the header of a call tree copied from Instruments: `Weight`, `Self Weight`, an
+/// unnamed one and `Symbol Name` in recent versions, `Running Time`, `Self (ms)`, an unnamed one and
+/// `Symbol Name` in older ones
+fn parse_header(line: &str) -> Option<SelfUnit> {
+    let columns = line.split('\t').map(str::trim).collect::<Vec<_>>();
+    match columns.as_slice() {
+        ["Weight", "Self Weight", .., "Symbol Name"] => Some(SelfUnit::Count),
+        ["Running Time", "Self (ms)", .., "Symbol Name"] => Some(SelfUnit::Milliseconds),
+        _ => None,
+    }
+}
+
+/// What the self weights without a unit are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SelfUnit {
+    Count,
+    Milliseconds,
+}
+
+/// Self weight of a row in microseconds, as in `1.50 ms`, or as it is when it has no time unit
+/// (sample counts)
+fn parse_weight(weight: &str, unit: SelfUnit) -> Option<u64> {
+    let mut parts = weight.split_whitespace();
+    let value = parts.next()?.parse::<f64>().ok()?;
+    let micros = match (parts.next(), unit) {
+        (Some("s"), _) => value * 1_000_000.0,
+        (Some("ms"), _) | (None, SelfUnit::Milliseconds) => value * 1_000.0,
+        (Some("µs" | "us"), _) => value,
+        (Some("ns"), _) => value / 1_000.0,
+        (None, SelfUnit::Count) => value,
+        (Some(_), _) => return None,
+    };
+    Some(micros.round() as u64)
+}
+
+/// Depth, self weight and frame of a row, the depth being the indentation of the symbol
+fn parse_row(line: &str, unit: SelfUnit) -> Option<(usize, u64, String)> {
+    let columns = line.split('\t').collect::<Vec<_>>();
+    if columns.len() < 3 {
+        return None;
+    }
+    let weight = parse_weight(columns[1], unit)?;
+    let symbol = columns.last()?;
+    let depth = symbol.len() - symbol.trim_start().len();
+    let frame = symbol.split_whitespace().collect::<Vec<_>>().join(" ");
+    if frame.is_empty() {
+        return None;
+    }
+    Some((depth, weight, frame))
+}
+
+/// Call tree of a time profile copied from Instruments with "Deep Copy", starting with a
+/// `Weight  Self Weight  Symbol Name` header.
+pub fn looks_like_instruments(content: &str) -> bool {
+    meaningful_lines(content)
+        .next()
+        .is_some_and(|line| parse_header(line).is_some())
+}
+
+/// Convert the call tree copied from Instruments to folded stacks, each symbol counting its self
+/// weight: microseconds for weights in time units, samples otherwise.
+pub fn to_folded(content: &str) -> String {
+    let mut lines = meaningful_lines(content);
+    let Some(unit) = lines.next().and_then(parse_header) else {
+        return String::new();
+    };
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    // Frames leading to the current row, with their depth
+    let mut path: Vec<(usize, String)> = vec![];
+    for (depth, weight, frame) in lines.filter_map(|line| parse_row(line, unit)) {
+        while path.last().is_some_and(|(d, _)| *d >= depth) {
+            path.pop();
+        }
+        path.push((depth, frame));
+        if weight > 0 {
+            let stack = join_frames(path.iter().map(|(_, frame)| frame));
+            *stacks.entry(stack).or_default() += weight;
+        }
+    }
+    format_folded(&stacks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_weight() {
+        assert_eq!(parse_weight("1.50 ms", SelfUnit::Count), Some(1500));
+        assert_eq!(parse_weight("2 s", SelfUnit::Count), Some(2_000_000));
+        assert_eq!(parse_weight("0 s", SelfUnit::Count), Some(0));
+        assert_eq!(parse_weight("12", SelfUnit::Count), Some(12));
+        assert_eq!(parse_weight("1.5", SelfUnit::Milliseconds), Some(1500));
+        assert_eq!(parse_weight("3.0 Gc", SelfUnit::Count), None);
+    }
+
+    #[test]
+    fn test_to_folded() {
+        let content = std::fs::read_to_string("tests/data/instruments.txt").unwrap();
+        assert!(looks_like_instruments(&content));
+        assert!(!looks_like_instruments("main;a 3\nmain;b 1"));
+        assert_eq!(
+            to_folded(&content),
+            "\
+Finder (317);Main Thread 0x1c0e;start;main 1000
+Finder (317);Main Thread 0x1c0e;start;main;-[Finder refresh] 2500
+Finder (317);Main Thread 0x1c0e;start;main;NSApplicationMain;mach_msg_trap 5000
+Finder (317);Thread 0x1d2f;start_wqthread;__workq_kernreturn 1500"
+        );
+        let content = "Running Time\tSelf (ms)\t\tSymbol Name\n3.0ms  100.0%\t1.0\t \tmain\n2.0ms  66.6%\t2.0\t \t read\n";
+        assert!(looks_like_instruments(content));
+        assert_eq!(to_folded(content), "main 1000\nmain;read 2000");
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(feature = "jfr")]
+use std::collections::HashMap;
+
+#[cfg(feature = "jfr")]
+use anyhow::anyhow;
+use anyhow::Result;
+
+#[cfg(feature = "jfr")]
+use crate::flame::join_frames;
+
+/// Magic bytes at the start of every JFR chunk
+const MAGIC: &[u8] = b"FLR\0";
+
+#[derive(Debug, Clone, Default)]
+pub struct JfrOptions {
+    /// Add the sampled thread as the root frame of each stack
+    pub split_by_thread: bool,
+}
+
+pub fn looks_like_jfr(buf: &[u8]) -> bool {
+    buf.starts_with(MAGIC)
+}
+
+/// Convert the `jdk.ExecutionSample` events of a JFR recording to folded stacks.
+#[cfg(feature = "jfr")]
+pub fn to_folded(buf: &[u8], options: &JfrOptions) -> Result<String> {
+    use jfrs::reader::event::Accessor;
+    use jfrs::reader::JfrReader;
+
+    fn get_str<'a>(accessor: &Accessor<'a>, path: &[&str]) -> Option<&'a str> {
+        let mut current = accessor.get_field(path[0])?;
+        for name in &path[1..] {
+            current = current.get_field(name)?;
+        }
+        <&str>::try_from(current.value).ok()
+    }
+
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    let mut reader = JfrReader::new(std::io::Cursor::new(buf));
+    for chunk in reader.chunks() {
+        let (mut chunk_reader, chunk) = chunk.map_err(|e| anyhow!("Invalid JFR file: {}", e))?;
+        for event in chunk_reader.events(&chunk).flatten() {
+            if event.class.name() != "jdk.ExecutionSample" {
+                continue;
+            }
+            let value = event.value();
+            let Some(frames) = value
+                .get_field("stackTrace")
+                .and_then(|s| s.get_field("frames"))
+                .and_then(|f| f.as_iter())
+            else {
+                continue;
+            };
+            let mut names = frames
+                .filter_map(|frame| {
+                    let method = frame.get_field("method")?;
+                    let method_name = get_str(&method, &["name", "string"]).unwrap_or("[unknown]");
+                    Some(match get_str(&method, &["type", "name", "string"]) {
+                        Some(class_name) => {
+                            format!("{}.{}", class_name.replace('/', "."), method_name)
+                        }
+                        None => method_name.to_string(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            // JFR lists the leaf frame first
+            names.reverse();
+            if options.split_by_thread {
+                let thread = get_str(&value, &["sampledThread", "javaName"])
+                    .or_else(|| get_str(&value, &["sampledThread", "osName"]))
+                    .unwrap_or("[unknown thread]");
+                names.insert(0, thread.to_string());
+            }
+            if !names.is_empty() {
+                *stacks.entry(join_frames(&names)).or_default() += 1;
+            }
+        }
+    }
+    Ok(super::format_folded(&stacks))
+}
+
+#[cfg(not(feature = "jfr"))]
+pub fn to_folded(_buf: &[u8], _options: &JfrOptions) -> Result<String> {
+    anyhow::bail!("Reading JFR recordings requires flamelens to be built with the \"jfr\" feature")
+}
+
+#[cfg(all(test, feature = "jfr"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_folded() {
+        let buf = std::fs::read("tests/data/busy.jfr").unwrap();
+        assert!(looks_like_jfr(&buf));
+        let folded = to_folded(&buf, &JfrOptions::default()).unwrap();
+        assert_eq!(folded, "Busy.main;Busy.work 10");
+
+        let options = JfrOptions {
+            split_by_thread: true,
+        };
+        let folded = to_folded(&buf, &options).unwrap();
+        assert_eq!(folded, "main;Busy.main;Busy.work 10");
+    }
+}
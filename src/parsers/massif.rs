@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::flame::join_frames;
+
+use super::format_folded;
+
+#[derive(Debug, Clone, Default)]
+pub struct MassifOptions {
+    /// Number of the snapshot to show, the peak one when `None`
+    pub snapshot: Option<usize>,
+}
+
+/// A valgrind massif profile (`massif.out.<pid>`, also written by `heaptrack_print --print-massif`)
+/// starts with `desc:` and `cmd:` lines and is made of `snapshot=<n>` sections.
+pub fn looks_like_massif(content: &str) -> bool {
+    content.starts_with("desc:") && content.contains("\nsnapshot=")
+}
+
+/// A snapshot with a heap tree, as the lines of the tree
+struct Snapshot<'a> {
+    number: usize,
+    is_peak: bool,
+    tree: Vec<&'a str>,
+}
+
+fn detailed_snapshots(content: &str) -> Vec<Snapshot<'_>> {
+    let mut snapshots: Vec<Snapshot> = vec![];
+    let mut number = None;
+    for line in content.lines() {
+        if let Some(value) = line.strip_prefix("snapshot=") {
+            number = value.trim().parse().ok();
+        } else if let Some(kind) = line.strip_prefix("heap_tree=") {
+            if let (Some(number), "detailed" | "peak") = (number, kind.trim()) {
+                snapshots.push(Snapshot {
+                    number,
+                    is_peak: kind.trim() == "peak",
+                    tree: vec![],
+                });
+            }
+        } else if line.trim_start().starts_with('n') && line.contains(": ") {
+            if let Some(snapshot) = snapshots.last_mut().filter(|s| Some(s.number) == number) {
+                snapshot.tree.push(line);
+            }
+        }
+    }
+    snapshots
+}
+
+/// Depth, bytes and frame name of a node of a heap tree, as in `  n0: 600 0x4005A4: main (a.c:9)`
+fn parse_node(line: &str) -> Option<(usize, u64, String)> {
+    let depth = line.len() - line.trim_start().len();
+    let (_, rest) = line.trim_start().split_once(": ")?;
+    let (bytes, name) = rest.split_once(' ').unwrap_or((rest, ""));
+    let bytes = bytes.parse().ok()?;
+    let name = if name.contains("below massif's threshold") {
+        "(below threshold)".to_string()
+    } else {
+        // Drop the code address of the frame
+        match name.split_once(": ") {
+            Some((address, name)) if address.starts_with("0x") => name.to_string(),
+            _ => name.to_string(),
+        }
+    };
+    Some((depth, bytes, name))
+}
+
+/// Convert a snapshot of a massif profile to folded stacks counting the bytes allocated on the
+/// heap by each stack. The heap trees of massif start from the allocation functions, so the
+/// stacks are reversed to start from the outermost caller.
+pub fn to_folded(content: &str, options: &MassifOptions) -> Result<String> {
+    let snapshots = detailed_snapshots(content);
+    let snapshot = match options.snapshot {
+        Some(number) => snapshots
+            .iter()
+            .find(|snapshot| snapshot.number == number)
+            .ok_or_else(|| {
+                let numbers = snapshots
+                    .iter()
+                    .map(|snapshot| snapshot.number.to_string())
+                    .collect::<Vec<_>>();
+                anyhow!(
+                    "Snapshot {} of the massif profile has no heap tree; the detailed snapshots \
+                    are {}",
+                    number,
+                    numbers.join(", ")
+                )
+            })?,
+        None => match snapshots.iter().find(|snapshot| snapshot.is_peak) {
+            Some(snapshot) => snapshot,
+            None => snapshots
+                .last()
+                .ok_or_else(|| anyhow!("Invalid massif profile: no detailed snapshot"))?,
+        },
+    };
+
+    // Frames from the allocation functions to the current node, with their bytes and the bytes
+    // of their children
+    let mut path: Vec<(String, u64, u64)> = vec![];
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    let mut pop = |path: &mut Vec<(String, u64, u64)>| {
+        let Some((_, bytes, children)) = path.last() else {
+            return;
+        };
+        let count = bytes.saturating_sub(*children);
+        // The root stands for the allocation functions
+        if path.len() > 1 && count > 0 {
+            let stack = join_frames(path[1..].iter().rev().map(|(name, _, _)| name));
+            *stacks.entry(stack).or_default() += count;
+        }
+        path.pop();
+    };
+    for line in snapshot.tree.iter() {
+        let Some((depth, bytes, name)) = parse_node(line) else {
+            bail!("Invalid massif profile: bad heap tree line {:?}", line);
+        };
+        while path.len() > depth {
+            pop(&mut path);
+        }
+        if let Some((_, _, children)) = path.last_mut() {
+            *children += bytes;
+        }
+        path.push((name, bytes, 0));
+    }
+    while !path.is_empty() {
+        pop(&mut path);
+    }
+    Ok(format_folded(&stacks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_folded() {
+        let content = std::fs::read_to_string("tests/data/massif.out").unwrap();
+        assert!(looks_like_massif(&content));
+        assert_eq!(
+            to_folded(&content, &MassifOptions::default()).unwrap(),
+            "\
+(below threshold) 100
+main (prog.c:20);make_buffer (prog.c:10) 1500
+main (prog.c:20);make_list (prog.c:14) 400"
+        );
+        let options = MassifOptions { snapshot: Some(1) };
+        assert_eq!(
+            to_folded(&content, &options).unwrap(),
+            "main (prog.c:20);make_buffer (prog.c:10) 1000"
+        );
+        let options = MassifOptions { snapshot: Some(0) };
+        assert!(to_folded(&content, &options).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::Result;
+use clap::ValueEnum;
+
+use crate::flame::{CountUnit, ParseOptions, FRAME_DELIMITER};
+
+pub mod art_trace;
+pub mod cpuprofile;
+pub mod dtrace;
+pub mod firefox;
+pub mod folded;
+pub mod ftrace;
+pub mod ghc_prof;
+pub mod instruments;
+pub mod jfr;
+pub mod massif;
+pub mod perf_script;
+pub mod sample;
+pub mod simpleperf;
+pub mod strace;
+
+/// Supported input formats. All of them are converted to folded stacks before building the
+/// flamegraph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum InputFormat {
+    /// Detect the format from the content
+    #[default]
+    Auto,
+    /// Folded stacks, one `frame;frame;frame count` line per stack
+    Folded,
+    /// Output of `perf script`
+    Perf,
+    /// Chrome DevTools / Node `--cpu-prof` `.cpuprofile` JSON
+    Cpuprofile,
+    /// Java Flight Recorder recording (requires the "jfr" feature)
+    Jfr,
+    /// Firefox Profiler processed profile JSON
+    Firefox,
+    /// Output of Android's `simpleperf report-sample --protobuf`
+    Simpleperf,
+    /// Android ART method trace, from `Debug.startMethodTracing`
+    ArtTrace,
+    /// GHC `.prof` report, from a Haskell program run with `+RTS -p`
+    GhcProf,
+    /// Trace of the Linux ftrace `function_graph` tracer
+    FunctionGraph,
+    /// Output of `strace -T`, with the time spent in each syscall
+    Strace,
+    /// Valgrind massif profile, as written by `valgrind --tool=massif` or `heaptrack_print -M`
+    Massif,
+    /// Output of a DTrace aggregation of `ustack()` or `stack()`
+    Dtrace,
+    /// Report of macOS `sample <pid>`
+    Sample,
+    /// Call tree of a time profile copied from Instruments with "Deep Copy"
+    Instruments,
+}
+
+impl InputFormat {
+    /// What the counts of the stacks read from this format are
+    pub fn count_unit(&self) -> CountUnit {
+        match self {
+            InputFormat::Massif => CountUnit::Bytes,
+            _ => CountUnit::Samples,
+        }
+    }
+}
+
+/// Guess the input format by looking at the first few meaningful lines.
+pub fn detect_format(content: &str) -> InputFormat {
+    if massif::looks_like_massif(content) {
+        return InputFormat::Massif;
+    }
+    if cpuprofile::looks_like_cpuprofile(content) {
+        return InputFormat::Cpuprofile;
+    }
+    if firefox::looks_like_firefox_profile(content) {
+        return InputFormat::Firefox;
+    }
+    if ghc_prof::looks_like_ghc_prof(content) {
+        return InputFormat::GhcProf;
+    }
+    if ftrace::looks_like_function_graph(content) {
+        return InputFormat::FunctionGraph;
+    }
+    if strace::looks_like_strace(content) {
+        return InputFormat::Strace;
+    }
+    if sample::looks_like_sample(content) {
+        return InputFormat::Sample;
+    }
+    if instruments::looks_like_instruments(content) {
+        return InputFormat::Instruments;
+    }
+    if dtrace::looks_like_dtrace(content) {
+        return InputFormat::Dtrace;
+    }
+    if perf_script::looks_like_perf_script(content) {
+        return InputFormat::Perf;
+    }
+    InputFormat::Folded
+}
+
+/// Convert binary inputs to folded stacks. Returns `None` if the input is textual and should be
+/// decoded and passed to [`to_folded`] instead.
+pub fn binary_to_folded(buf: &[u8], options: &ParseOptions) -> Result<Option<String>> {
+    let format = match options.format {
+        InputFormat::Auto if jfr::looks_like_jfr(buf) => InputFormat::Jfr,
+        InputFormat::Auto if simpleperf::looks_like_simpleperf(buf) => InputFormat::Simpleperf,
+        InputFormat::Auto if art_trace::looks_like_art_trace(buf) => InputFormat::ArtTrace,
+        format => format,
+    };
+    match format {
+        InputFormat::Jfr => jfr::to_folded(buf, &options.jfr).map(Some),
+        InputFormat::Simpleperf => simpleperf::to_folded(buf).map(Some),
+        InputFormat::ArtTrace => art_trace::to_folded(buf).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Format of the content: the one in `options`, or the detected one
+pub fn resolve_format(content: &str, options: &ParseOptions) -> InputFormat {
+    match options.format {
+        InputFormat::Auto => detect_format(content),
+        format => format,
+    }
+}
+
+/// Convert the content to folded stacks according to its `format`, see [`resolve_format`].
+pub fn to_folded(content: String, format: InputFormat, options: &ParseOptions) -> Result<String> {
+    match format {
+        InputFormat::Auto | InputFormat::Folded => match options.delimiter.as_deref() {
+            Some(delimiter) if delimiter != FRAME_DELIMITER.to_string() => {
+                Ok(folded::with_delimiter(&content, delimiter))
+            }
+            _ => Ok(content),
+        },
+        InputFormat::Jfr | InputFormat::Simpleperf | InputFormat::ArtTrace => {
+            unreachable!("binary formats are handled by binary_to_folded")
+        }
+        InputFormat::Perf => Ok(perf_script::to_folded(&content, &options.perf)),
+        InputFormat::Cpuprofile => cpuprofile::to_folded(&content, &options.cpuprofile),
+        InputFormat::Firefox => firefox::to_folded(&content),
+        InputFormat::GhcProf => ghc_prof::to_folded(&content, &options.ghc_prof),
+        InputFormat::FunctionGraph => Ok(ftrace::to_folded(&content)),
+        InputFormat::Strace => Ok(strace::to_folded(&content)),
+        InputFormat::Massif => massif::to_folded(&content, &options.massif),
+        InputFormat::Dtrace => Ok(dtrace::to_folded(&content)),
+        InputFormat::Sample => Ok(sample::to_folded(&content)),
+        InputFormat::Instruments => Ok(instruments::to_folded(&content)),
+    }
+}
+
+/// A sample taken at a known time, as found in formats that keep the samples in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedSample {
+    /// Time of the sample in microseconds, from an arbitrary origin
+    pub time: u64,
+    /// Folded stack of the sample, empty when idle
+    pub stack: String,
+}
+
+/// Convert the content to folded stacks like [`to_folded`], along with its samples in the order
+/// they were taken for the formats that carry timestamps, read in the same pass. The samples are
+/// `None` for the other formats.
+pub fn to_folded_with_timed_samples(
+    content: String,
+    format: InputFormat,
+    options: &ParseOptions,
+) -> Result<(String, Option<Vec<TimedSample>>)> {
+    let (folded, samples) = match format {
+        InputFormat::Perf => perf_script::to_folded_with_timed_samples(&content, &options.perf),
+        InputFormat::Cpuprofile => {
+            cpuprofile::to_folded_with_timed_samples(&content, &options.cpuprofile)?
+        }
+        _ => return Ok((to_folded(content, format, options)?, None)),
+    };
+    Ok((folded, (!samples.is_empty()).then_some(samples)))
+}
+
+/// Iterate over lines that are neither empty nor comments.
+pub(crate) fn meaningful_lines(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+}
+
+/// Serialize aggregated stacks as folded lines, sorted for deterministic output.
+pub(crate) fn format_folded<'a, I>(stacks: I) -> String
+where
+    I: IntoIterator<Item = (&'a String, &'a u64)>,
+{
+    let mut lines = stacks
+        .into_iter()
+        .map(|(stack, count)| format!("{} {}", stack, count))
+        .collect::<Vec<_>>();
+    lines.sort();
+    lines.join("\n")
+}
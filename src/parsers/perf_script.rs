@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use regex::Regex;
+
+use crate::flame::join_frames;
+
+use super::{format_folded, meaningful_lines, TimedSample};
+
+/// How to group samples at the root of each stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum PerfGroupBy {
+    /// Do not add a root frame
+    None,
+    /// Add the process name as the root frame
+    #[default]
+    Process,
+    /// Add the process name and thread id as the root frame
+    Thread,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PerfScriptOptions {
+    pub group_by: PerfGroupBy,
+    pub drop_kernel: bool,
+}
+
+struct Sample<'a> {
+    comm: &'a str,
+    pid: &'a str,
+    tid: Option<&'a str>,
+    /// Timestamp in microseconds
+    time: Option<u64>,
+    frames: Vec<String>,
+}
+
+fn header_regex() -> Regex {
+    // e.g. "python3 12345/12346 [002] 1234.567890:     250000 cpu-clock:pppH:"
+    Regex::new(r"^(\S.*?)\s+(\d+)(?:/(\d+))?\s").unwrap()
+}
+
+fn time_regex() -> Regex {
+    // e.g. " 1234.567890:" after the cpu
+    Regex::new(r"\s(\d+)\.(\d{1,9}):").unwrap()
+}
+
+fn parse_time(header: &str, re: &Regex) -> Option<u64> {
+    let captures = re.captures(header)?;
+    let seconds = captures.get(1)?.as_str().parse::<u64>().ok()?;
+    let fraction = captures.get(2)?.as_str();
+    // Pad or cut the fraction to microseconds
+    let micros = format!("{:0<6}", fraction)[..6].parse::<u64>().ok()?;
+    Some(seconds * 1_000_000 + micros)
+}
+
+fn is_frame_line(line: &str) -> bool {
+    line.starts_with([' ', '\t'])
+}
+
+/// perf script output is made of a header line per sample followed by indented frame lines.
+pub fn looks_like_perf_script(content: &str) -> bool {
+    let re = header_regex();
+    let mut lines = meaningful_lines(content);
+    match (lines.next(), lines.next()) {
+        (Some(header), Some(frame)) => re.is_match(header) && is_frame_line(frame),
+        _ => false,
+    }
+}
+
+/// Parse a frame line such as `ffffffff8100 do_syscall_64+0x5b ([kernel.kallsyms])` into the
+/// symbol name and whether it is a kernel frame.
+fn parse_frame(line: &str) -> (String, bool) {
+    let line = line.trim();
+    let (rest, module) = match line.rfind(" (") {
+        Some(i) if line.ends_with(')') => (&line[..i], &line[i + 2..line.len() - 1]),
+        _ => (line, ""),
+    };
+    let (address, symbol) = match rest.split_once(char::is_whitespace) {
+        Some((address, symbol)) => (address, symbol.trim()),
+        None => (rest, ""),
+    };
+    let is_kernel = module.starts_with("[kernel.") || address.starts_with("ffffffff");
+    // Strip the offset, e.g. "main+0x1f"
+    let symbol = match symbol.rfind("+0x") {
+        Some(i) => &symbol[..i],
+        None => symbol,
+    };
+    let name = if !symbol.is_empty() && symbol != "[unknown]" {
+        symbol.to_string()
+    } else if !module.is_empty() && module != "unknown" {
+        // Use the basename of the module, e.g. "/usr/bin/python3" -> "[python3]"
+        let module = module.trim_start_matches('[').trim_end_matches(']');
+        format!("[{}]", module.rsplit('/').next().unwrap_or(module))
+    } else {
+        "[unknown]".to_string()
+    };
+    (name, is_kernel)
+}
+
+/// Call `f` with the timestamp (if any) and folded stack of every sample.
+fn for_each_sample<F>(content: &str, options: &PerfScriptOptions, mut f: F)
+where
+    F: FnMut(Option<u64>, String),
+{
+    let re = header_regex();
+    let time_re = time_regex();
+    let mut current: Option<Sample> = None;
+
+    let mut flush = |sample: Option<Sample>| {
+        let Some(sample) = sample else {
+            return;
+        };
+        // perf lists the leaf frame first
+        let mut frames = sample.frames.into_iter().rev().collect::<Vec<_>>();
+        let root = match options.group_by {
+            PerfGroupBy::None => None,
+            PerfGroupBy::Process => Some(sample.comm.to_string()),
+            PerfGroupBy::Thread => Some(format!(
+                "{}-{}/{}",
+                sample.comm,
+                sample.pid,
+                sample.tid.unwrap_or(sample.pid)
+            )),
+        };
+        if let Some(root) = root {
+            frames.insert(0, root);
+        }
+        if frames.is_empty() {
+            return;
+        }
+        f(sample.time, join_frames(&frames));
+    };
+
+    for line in content.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        if line.trim().is_empty() {
+            flush(current.take());
+        } else if is_frame_line(line) {
+            if let Some(sample) = current.as_mut() {
+                let (name, is_kernel) = parse_frame(line);
+                if !(options.drop_kernel && is_kernel) {
+                    sample.frames.push(name);
+                }
+            }
+        } else if let Some(captures) = re.captures(line) {
+            flush(current.take());
+            current = Some(Sample {
+                comm: captures.get(1).unwrap().as_str(),
+                pid: captures.get(2).unwrap().as_str(),
+                tid: captures.get(3).map(|m| m.as_str()),
+                time: parse_time(line, &time_re),
+                frames: vec![],
+            });
+        }
+    }
+    flush(current.take());
+}
+
+/// Convert `perf script` output to folded stacks. Each sample is counted once.
+pub fn to_folded(content: &str, options: &PerfScriptOptions) -> String {
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    for_each_sample(content, options, |_, stack| {
+        *stacks.entry(stack).or_default() += 1;
+    });
+    format_folded(&stacks)
+}
+
+/// Convert `perf script` output to folded stacks like [`to_folded`], along with the samples that
+/// have a timestamp sorted by time (perf doesn't strictly order samples across CPUs).
+pub fn to_folded_with_timed_samples(
+    content: &str,
+    options: &PerfScriptOptions,
+) -> (String, Vec<TimedSample>) {
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    let mut samples = vec![];
+    for_each_sample(content, options, |time, stack| {
+        if let Some(time) = time {
+            samples.push(TimedSample {
+                time,
+                stack: stack.clone(),
+            });
+        }
+        *stacks.entry(stack).or_default() += 1;
+    });
+    samples.sort_by_key(|sample| sample.time);
+    (format_folded(&stacks), samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "\
+python3 4242/4243 [002] 1234.567890:     250000 cpu-clock:pppH:
+\t    7f0000001234 compute+0x12 (/usr/lib/libfoo.so)
+\t    55aa00000001 main+0x1f (/usr/bin/python3)
+\t    55aa00000002 [unknown] (/usr/bin/python3)
+
+python3 4242/4243 [002] 1234.577890:     250000 cpu-clock:pppH:
+\tffffffff81000001 do_syscall_64+0x5b ([kernel.kallsyms])
+\t    55aa00000001 main+0x1f (/usr/bin/python3)
+\t    55aa00000002 [unknown] (/usr/bin/python3)
+
+my worker 17 [000] 1234.587890:     250000 cpu-clock:pppH:
+\t    55aa00000001 main+0x1f (/usr/bin/worker)
+";
+
+    #[test]
+    fn test_looks_like_perf_script() {
+        assert!(looks_like_perf_script(CONTENT));
+        assert!(!looks_like_perf_script("main;foo 1\nmain;bar 2\n"));
+    }
+
+    #[test]
+    fn test_to_folded() {
+        let folded = to_folded(CONTENT, &PerfScriptOptions::default());
+        assert_eq!(
+            folded,
+            "\
+my worker;main 1
+python3;[python3];main;compute 1
+python3;[python3];main;do_syscall_64 1"
+        );
+    }
+
+    #[test]
+    fn test_to_folded_by_thread_without_kernel() {
+        let options = PerfScriptOptions {
+            group_by: PerfGroupBy::Thread,
+            drop_kernel: true,
+        };
+        let folded = to_folded(CONTENT, &options);
+        assert_eq!(
+            folded,
+            "\
+my worker-17/17;main 1
+python3-4242/4243;[python3];main 1
+python3-4242/4243;[python3];main;compute 1"
+        );
+    }
+
+    #[test]
+    fn test_to_folded_with_timed_samples() {
+        let options = PerfScriptOptions::default();
+        let (folded, samples) = to_folded_with_timed_samples(CONTENT, &options);
+        assert_eq!(folded, to_folded(CONTENT, &options));
+        let sample = |time, stack: &str| TimedSample {
+            time,
+            stack: stack.to_string(),
+        };
+        assert_eq!(
+            samples,
+            vec![
+                sample(1234567890, "python3;[python3];main;compute"),
+                sample(1234577890, "python3;[python3];main;do_syscall_64"),
+                sample(1234587890, "my worker;main"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::flame::join_frames;
+
+use super::format_folded;
+
+/// Line starting the call trees of the threads in the report of `sample`
+const CALL_GRAPH_HEADER: &str = "Call graph:";
+
+/// Characters drawing the call tree before the count of a call, as in `+   ! : 2700 main`
+fn is_tree_char(c: char) -> bool {
+    matches!(c, ' ' | '+' | '!' | ':' | '|')
+}
+
+/// Column of the count of a call (telling its depth in the tree), the count and the frame of a
+/// line of the call graph
+fn parse_line(line: &str) -> Option<(usize, u64, String)> {
+    let column = line.find(|c: char| !is_tree_char(c))?;
+    let rest = &line[column..];
+    let (count, call) = rest.split_once(char::is_whitespace)?;
+    let count = count.parse().ok()?;
+    Some((column, count, frame_name(call)))
+}
+
+/// Frame of a call, as in `libsystem_kernel.dylib`__psynch_cvwait` for
+/// `__psynch_cvwait  (in libsystem_kernel.dylib) + 10  [0x7fff6f2f1882]`, written like the frames
+/// of DTrace. The threads, such as `Thread_1234   DispatchQueue_1: com.apple.main-thread  (serial)`,
+/// are kept as they are.
+fn frame_name(call: &str) -> String {
+    let name = match call.split_once("  (in ") {
+        Some((function, rest)) => match rest.split_once(')') {
+            Some((module, _)) => format!("{}`{}", module, function.trim()),
+            None => function.trim().to_string(),
+        },
+        None => match call.rfind("  [0x") {
+            Some(i) => call[..i].trim().to_string(),
+            None => call.trim().to_string(),
+        },
+    };
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Report written by macOS `sample <pid>`, with the call tree of each thread under
+/// `Call graph:`.
+pub fn looks_like_sample(content: &str) -> bool {
+    let mut lines = content.lines().take(100);
+    lines.any(|line| line.trim() == CALL_GRAPH_HEADER)
+        && lines
+            .find(|line| !line.trim().is_empty())
+            .is_some_and(|line| parse_line(line).is_some())
+}
+
+/// Convert the call graph of the report of `sample` to folded stacks, rooted at the threads.
+/// Each call counts the samples taken in it but not in the calls below it.
+pub fn to_folded(content: &str) -> String {
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    // Column, frame, samples and samples of the calls below of the calls leading to the current one
+    let mut path: Vec<(usize, String, u64, u64)> = vec![];
+    let pop = |path: &mut Vec<(usize, String, u64, u64)>, stacks: &mut HashMap<String, u64>| {
+        let stack = join_frames(path.iter().map(|(_, frame, _, _)| frame));
+        let (_, _, count, children_count) = path.pop().unwrap();
+        let own = count.saturating_sub(children_count);
+        if own > 0 {
+            *stacks.entry(stack).or_default() += own;
+        }
+    };
+    let lines = content
+        .lines()
+        .skip_while(|line| line.trim() != CALL_GRAPH_HEADER)
+        .skip(1)
+        .skip_while(|line| line.trim().is_empty())
+        .take_while(|line| !line.trim().is_empty());
+    for (column, count, frame) in lines.filter_map(parse_line) {
+        while path.last().is_some_and(|(c, _, _, _)| *c >= column) {
+            pop(&mut path, &mut stacks);
+        }
+        if let Some(parent) = path.last_mut() {
+            parent.3 += count;
+        }
+        path.push((column, frame, count, 0));
+    }
+    while !path.is_empty() {
+        pop(&mut path, &mut stacks);
+    }
+    format_folded(&stacks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(
+            parse_line("    +   ! 2700 -[NSApplication run]  (in AppKit) + 658  [0x7fff3]"),
+            Some((10, 2700, "AppKit`-[NSApplication run]".to_string()))
+        );
+        assert_eq!(
+            parse_line("    2713 Thread_1234   DispatchQueue_1: com.apple.main-thread  (serial)"),
+            Some((
+                4,
+                2713,
+                "Thread_1234 DispatchQueue_1: com.apple.main-thread (serial)".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_line("    +     1 ???  (in <unknown binary>)  [0x1234]"),
+            Some((10, 1, "<unknown binary>`???".to_string()))
+        );
+        assert_eq!(parse_line("    + 12"), None);
+        assert_eq!(parse_line("Binary Images:"), None);
+    }
+
+    #[test]
+    fn test_to_folded() {
+        let content = std::fs::read_to_string("tests/data/sample.txt").unwrap();
+        assert!(looks_like_sample(&content));
+        assert!(!looks_like_sample("main;a 3\nmain;b 1"));
+        assert_eq!(
+            to_folded(&content),
+            "\
+Thread_1234 DispatchQueue_1: com.apple.main-thread (serial);libdyld.dylib`start;Finder`main 3
+Thread_1234 DispatchQueue_1: com.apple.main-thread (serial);libdyld.dylib`start;Finder`main;AppKit`NSApplicationMain;libsystem_kernel.dylib`mach_msg_trap 5
+Thread_1234 DispatchQueue_1: com.apple.main-thread (serial);libdyld.dylib`start;Finder`main;Finder`-[Finder refresh] 2
+Thread_5678;libsystem_pthread.dylib`start_wqthread;libsystem_kernel.dylib`__workq_kernreturn 10"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::flame::join_frames;
+
+use super::format_folded;
+
+/// Magic bytes at the start of the output of `simpleperf report-sample --protobuf`
+const MAGIC: &[u8] = b"SIMPLEPERF";
+
+pub fn looks_like_simpleperf(buf: &[u8]) -> bool {
+    buf.starts_with(MAGIC)
+}
+
+/// Value of a protobuf field
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Reader of the protobuf wire format, only decoding what the report needs
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.buf.len() {
+            bail!("Invalid simpleperf report: truncated record");
+        }
+        let (taken, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("Invalid simpleperf report: varint too long")
+    }
+
+    /// Next field number and value, or `None` at the end of the message
+    fn field(&mut self) -> Result<Option<(u64, Value<'a>)>> {
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed
+            }
+            wire_type => bail!("Invalid simpleperf report: wire type {}", wire_type),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+/// Call `f` with every field of a message
+fn for_each_field<'a>(buf: &'a [u8], mut f: impl FnMut(u64, Value<'a>)) -> Result<()> {
+    let mut reader = Reader { buf };
+    while let Some((number, value)) = reader.field()? {
+        f(number, value);
+    }
+    Ok(())
+}
+
+/// Frame of a sample: the file it is in and its symbol in that file, if known
+#[derive(Default)]
+struct CallChainEntry {
+    file_id: u64,
+    symbol_id: Option<u64>,
+}
+
+#[derive(Default)]
+struct Sample {
+    thread_id: u64,
+    /// Leaf first
+    callchain: Vec<CallChainEntry>,
+}
+
+#[derive(Default)]
+struct File {
+    path: String,
+    symbols: Vec<String>,
+}
+
+#[derive(Default)]
+struct Thread {
+    process_id: u64,
+    name: String,
+}
+
+fn parse_sample(buf: &[u8]) -> Result<Sample> {
+    let mut sample = Sample::default();
+    let mut entries = vec![];
+    for_each_field(buf, |number, value| match (number, value) {
+        (2, Value::Varint(thread_id)) => sample.thread_id = thread_id,
+        (3, Value::Bytes(entry)) => entries.push(entry),
+        _ => {}
+    })?;
+    for buf in entries {
+        let mut entry = CallChainEntry::default();
+        for_each_field(buf, |number, value| match (number, value) {
+            (2, Value::Varint(file_id)) => entry.file_id = file_id,
+            // A negative (int32) symbol id means the symbol is unknown
+            (3, Value::Varint(symbol_id)) => {
+                entry.symbol_id = (symbol_id as i64 >= 0).then_some(symbol_id)
+            }
+            _ => {}
+        })?;
+        sample.callchain.push(entry);
+    }
+    Ok(sample)
+}
+
+fn parse_file(buf: &[u8]) -> Result<(u64, File)> {
+    let mut id = 0;
+    let mut file = File::default();
+    for_each_field(buf, |number, value| match (number, value) {
+        (1, Value::Varint(value)) => id = value,
+        (2, Value::Bytes(path)) => file.path = String::from_utf8_lossy(path).into_owned(),
+        (3, Value::Bytes(symbol)) => file
+            .symbols
+            .push(String::from_utf8_lossy(symbol).into_owned()),
+        _ => {}
+    })?;
+    Ok((id, file))
+}
+
+fn parse_thread(buf: &[u8]) -> Result<(u64, Thread)> {
+    let mut id = 0;
+    let mut thread = Thread::default();
+    for_each_field(buf, |number, value| match (number, value) {
+        (1, Value::Varint(value)) => id = value,
+        (2, Value::Varint(process_id)) => thread.process_id = process_id,
+        (3, Value::Bytes(name)) => thread.name = String::from_utf8_lossy(name).into_owned(),
+        _ => {}
+    })?;
+    Ok((id, thread))
+}
+
+/// Convert the output of `simpleperf report-sample --protobuf` to folded stacks. Each sample is
+/// counted once, under a root frame naming its process (the name of its main thread).
+pub fn to_folded(buf: &[u8]) -> Result<String> {
+    let mut reader = Reader {
+        buf: buf
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| anyhow!("Invalid simpleperf report: bad magic"))?,
+    };
+    // Version of the format
+    reader.take(2)?;
+    let mut samples = vec![];
+    let mut files = HashMap::new();
+    let mut threads = HashMap::new();
+    loop {
+        let size = u32::from_le_bytes(reader.take(4)?.try_into()?) as usize;
+        if size == 0 {
+            break;
+        }
+        let mut record = None;
+        for_each_field(reader.take(size)?, |number, value| {
+            if let Value::Bytes(buf) = value {
+                record = Some((number, buf));
+            }
+        })?;
+        match record {
+            Some((1, buf)) => samples.push(parse_sample(buf)?),
+            Some((3, buf)) => {
+                let (id, file) = parse_file(buf)?;
+                files.insert(id, file);
+            }
+            Some((4, buf)) => {
+                let (id, thread) = parse_thread(buf)?;
+                threads.insert(id, thread);
+            }
+            _ => {}
+        }
+    }
+
+    let frame_name = |entry: &CallChainEntry| {
+        let file = files.get(&entry.file_id);
+        let symbol = file.and_then(|file| file.symbols.get(entry.symbol_id? as usize));
+        match (symbol, file) {
+            (Some(symbol), _) => symbol.clone(),
+            (None, Some(file)) if !file.path.is_empty() => {
+                format!("[{}]", file.path.rsplit('/').next().unwrap_or(&file.path))
+            }
+            _ => "[unknown]".to_string(),
+        }
+    };
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    for sample in samples {
+        let process = threads
+            .get(&sample.thread_id)
+            .and_then(|thread| threads.get(&thread.process_id))
+            .map_or("[unknown]", |thread| thread.name.as_str());
+        let mut frames = vec![process.to_string()];
+        frames.extend(sample.callchain.iter().rev().map(frame_name));
+        *stacks.entry(join_frames(&frames)).or_default() += 1;
+    }
+    Ok(format_folded(&stacks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn field(number: u64, value: u64, out: &mut Vec<u8>) {
+        varint(number << 3, out);
+        varint(value, out);
+    }
+
+    fn message(number: u64, bytes: &[u8], out: &mut Vec<u8>) {
+        varint((number << 3) | 2, out);
+        varint(bytes.len() as u64, out);
+        out.extend_from_slice(bytes);
+    }
+
+    fn record(number: u64, bytes: &[u8], out: &mut Vec<u8>) {
+        let mut buf = vec![];
+        message(number, bytes, &mut buf);
+        out.extend_from_slice(&(buf.len() as u32).to_le_bytes());
+        out.extend_from_slice(&buf);
+    }
+
+    fn sample(thread_id: u64, callchain: &[(u64, i64)], out: &mut Vec<u8>) {
+        let mut buf = vec![];
+        field(2, thread_id, &mut buf);
+        for (file_id, symbol_id) in callchain {
+            let mut entry = vec![];
+            field(2, *file_id, &mut entry);
+            field(3, *symbol_id as u64, &mut entry);
+            message(3, &entry, &mut buf);
+        }
+        record(1, &buf, out);
+    }
+
+    #[test]
+    fn test_to_folded() {
+        let mut buf = MAGIC.to_vec();
+        buf.extend_from_slice(&1u16.to_le_bytes());
+        sample(11, &[(1, 0), (0, 1), (0, 0)], &mut buf);
+        sample(11, &[(1, 0), (0, 1), (0, 0)], &mut buf);
+        sample(10, &[(1, -1), (0, 0)], &mut buf);
+        for (id, path, symbols) in [
+            (
+                0,
+                "/data/app/base.apk",
+                &["com.example.Main.run", "com.example.Main.work"][..],
+            ),
+            (
+                1,
+                "/system/lib64/libart.so",
+                &["art_quick_generic_jni_trampoline"][..],
+            ),
+        ] {
+            let mut file = vec![];
+            field(1, id, &mut file);
+            message(2, path.as_bytes(), &mut file);
+            for symbol in symbols {
+                message(3, symbol.as_bytes(), &mut file);
+            }
+            record(3, &file, &mut buf);
+        }
+        for (id, name) in [(10, "com.example"), (11, "worker")] {
+            let mut thread = vec![];
+            field(1, id, &mut thread);
+            field(2, 10, &mut thread);
+            message(3, name.as_bytes(), &mut thread);
+            record(4, &thread, &mut buf);
+        }
+        buf.extend_from_slice(&0u32.to_le_bytes());
+
+        assert!(looks_like_simpleperf(&buf));
+        assert_eq!(
+            to_folded(&buf).unwrap(),
+            "\
+com.example;com.example.Main.run;[libart.so] 1
+com.example;com.example.Main.run;com.example.Main.work;art_quick_generic_jni_trampoline 2"
+        );
+        assert!(to_folded(&buf[..buf.len() - 10]).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::flame::join_frames;
+
+use super::{format_folded, meaningful_lines};
+
+fn syscall_regex() -> Regex {
+    // e.g. "[pid  4321] 12:34:56.789012 read(3, "", 4096) = 0 <0.000100>", where the pid (`-f`) and
+    // the timestamp (`-t`, `-tt`, `-ttt` or `-r`) are optional. A call interrupted by another
+    // process is written in two lines, and the second one ("<... read resumed>") has the time spent
+    // in the call.
+    Regex::new(
+        r"^\s*(?:\[pid\s+(\d+)\]\s+|(\d+)\s+)?(?:[\d:.]+\s+)?(?:<\.\.\.\s+(\w+)\s+resumed>|(\w+)\().*<(\d+\.\d+)>\s*$",
+    )
+    .unwrap()
+}
+
+/// Pid (if traced with `-f`), name and duration in microseconds of the syscall of a line
+fn parse_line<'a>(line: &'a str, re: &Regex) -> Option<(Option<&'a str>, &'a str, u64)> {
+    let captures = re.captures(line)?;
+    let pid = captures.get(1).or(captures.get(2)).map(|m| m.as_str());
+    let name = captures.get(3).or(captures.get(4))?.as_str();
+    let seconds = captures.get(5)?.as_str().parse::<f64>().ok()?;
+    Some((pid, name, (seconds * 1_000_000.0).round() as u64))
+}
+
+/// Output of `strace -T`, where each syscall line ends with the time spent in it, as in
+/// `openat(AT_FDCWD, "/etc/hosts", O_RDONLY) = 3 <0.000012>`.
+pub fn looks_like_strace(content: &str) -> bool {
+    let re = syscall_regex();
+    meaningful_lines(content)
+        .take(10)
+        .any(|line| parse_line(line, &re).is_some())
+}
+
+/// Convert the output of `strace -T` (and `-f`) to `pid;syscall` stacks counting the microseconds
+/// spent in each syscall, or `syscall` stacks without the pids. Calls still running at the end of
+/// the trace are ignored.
+pub fn to_folded(content: &str) -> String {
+    let re = syscall_regex();
+    let mut stacks: HashMap<String, u64> = HashMap::new();
+    for (pid, name, micros) in meaningful_lines(content).filter_map(|line| parse_line(line, &re)) {
+        if micros == 0 {
+            continue;
+        }
+        let stack = match pid {
+            Some(pid) => join_frames([pid, name]),
+            None => join_frames([name]),
+        };
+        *stacks.entry(stack).or_default() += micros;
+    }
+    format_folded(&stacks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let re = syscall_regex();
+        let parse_line = |line| parse_line(line, &re);
+        assert_eq!(
+            parse_line("openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = 3 <0.000012>"),
+            Some((None, "openat", 12))
+        );
+        assert_eq!(
+            parse_line("[pid  4321] 12:34:56.789012 read(3, \"\", 4096) = 0 <0.000100>"),
+            Some((Some("4321"), "read", 100))
+        );
+        assert_eq!(
+            parse_line("1700000000.123456 close(3) = 0 <0.000002>"),
+            Some((None, "close", 2))
+        );
+        assert_eq!(
+            parse_line("4321  <... wait4 resumed>[{WIFEXITED(s)}], 0, NULL) = 4322 <1.500000>"),
+            Some((Some("4321"), "wait4", 1500000))
+        );
+        assert_eq!(parse_line("4321  wait4(-1,  <unfinished ...>"), None);
+        assert_eq!(parse_line("4322  +++ exited with 0 +++"), None);
+        assert_eq!(parse_line("main;read 3"), None);
+    }
+
+    #[test]
+    fn test_to_folded() {
+        let content = std::fs::read_to_string("tests/data/strace.txt").unwrap();
+        assert!(looks_like_strace(&content));
+        assert_eq!(
+            to_folded(&content),
+            "\
+4321;clone 50
+4321;wait4 2000
+4322;execve 300
+4322;openat 30
+4322;read 1200"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::flame::SortColumn;
+use crate::palette::Palette;
+use crate::view::FlameGraphView;
+
+/// View preferences of a profile, saved on quit under a hash of the profile in the state directory
+/// and restored when the same profile is opened again.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfilePrefs {
+    /// Full names of the zoomed frames, the outermost zoom first
+    pub zoom: Vec<String>,
+    pub palette: Option<Palette>,
+    /// Functions removed from every stack, in the order they were pruned
+    pub pruned: Vec<String>,
+    /// Regex of the functions whose stacks are left out
+    pub excluded: Option<String>,
+    /// Column the Top view is sorted by, and whether smallest counts or first names come first
+    pub sort: Option<(SortColumn, bool)>,
+}
+
+impl ProfilePrefs {
+    /// Location of the preferences of the profile whose content is `content`, respecting
+    /// `XDG_STATE_HOME`
+    pub fn path(content: &[u8]) -> Option<PathBuf> {
+        let state_dir = match std::env::var_os("XDG_STATE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?)
+                .join(".local")
+                .join("state"),
+        };
+        Some(
+            state_dir
+                .join("flamelens")
+                .join("profiles")
+                .join(format!("{:016x}.json", content_hash(content))),
+        )
+    }
+
+    /// Preferences saved at `path`, if any
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read view preferences {}", path.display()))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .with_context(|| format!("Could not parse view preferences {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write view preferences {}", path.display()))
+    }
+
+    pub fn from_view(view: &FlameGraphView) -> Self {
+        let ordered = &view.flamegraph.ordered_stacks;
+        Self {
+            zoom: view
+                .state
+                .zoom_stack
+                .iter()
+                .filter_map(|zoom| view.flamegraph.get_stack_full_name(&zoom.stack_id))
+                .map(|name| name.to_string())
+                .collect(),
+            palette: Some(view.state.palette),
+            pruned: view.filters.pruned.clone(),
+            excluded: view.filters.excluded.clone(),
+            sort: Some((ordered.sorted_column, ordered.ascending)),
+        }
+    }
+
+    /// Restore the preferences in `view`, leaving out the zoomed frames it no longer has
+    pub fn apply(&self, view: &mut FlameGraphView) {
+        if let Some(palette) = self.palette {
+            view.state.palette = palette;
+        }
+        for name in self.pruned.iter() {
+            view.prune(name);
+        }
+        if let Some(excluded) = &self.excluded {
+            // An invalid pattern is left out, as it could not have been set
+            let _ = view.set_excluded(Some(excluded.clone()));
+        }
+        if let Some((column, ascending)) = self.sort {
+            view.flamegraph.ordered_stacks.sort(column, ascending);
+        }
+        let mut zoomed = false;
+        for name in self.zoom.iter() {
+            if let Some(stack_id) = view.flamegraph.get_stack_id_by_full_name(name) {
+                view.select_id(&stack_id);
+                view.set_zoom_for_id(stack_id);
+                zoomed = true;
+            }
+        }
+        if zoomed {
+            view.record_zoom();
+        }
+    }
+}
+
+/// FNV-1a hash of the content of a profile, stable across runs and versions
+fn content_hash(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flame::FlameGraph;
+
+    #[test]
+    fn test_restore() {
+        let content = "a;b;c 3\na;d 2\ne 1";
+        let mut view = FlameGraphView::new(FlameGraph::from_string(content.to_string(), true));
+        view.state.palette = Palette::Java;
+        view.prune("e");
+        view.flamegraph.ordered_stacks.sort(SortColumn::Name, false);
+        let stack_id = view.flamegraph.get_stack_id_by_full_name("a;b").unwrap();
+        view.select_id(&stack_id);
+        view.set_zoom();
+        let prefs = ProfilePrefs::from_view(&view);
+        assert_eq!(prefs.zoom, vec!["a;b"]);
+
+        let json = serde_json::to_string(&prefs).unwrap();
+        let prefs: ProfilePrefs = serde_json::from_str(&json).unwrap();
+        let mut view = FlameGraphView::new(FlameGraph::from_string(content.to_string(), true));
+        prefs.apply(&mut view);
+        assert_eq!(view.state.palette, Palette::Java);
+        assert_eq!(view.filters.pruned, vec!["e"]);
+        assert_eq!(
+            view.flamegraph.ordered_stacks.sorted_column,
+            SortColumn::Name
+        );
+        assert!(!view.flamegraph.ordered_stacks.ascending);
+        let zoomed = view.state.get_zoom().map(|zoom| zoom.stack_id);
+        assert_eq!(
+            zoomed.and_then(|id| view.flamegraph.get_stack_full_name(&id)),
+            Some("a;b")
+        );
+    }
+
+    #[test]
+    fn test_path() {
+        let path = ProfilePrefs::path(b"a;b 1").unwrap();
+        assert_eq!(path, ProfilePrefs::path(b"a;b 1").unwrap());
+        assert_ne!(path, ProfilePrefs::path(b"a;b 2").unwrap());
+    }
+}
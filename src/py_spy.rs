@@ -25,17 +25,22 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 use crate::py_spy_flamegraph::Flamegraph as PySpyFlamegraph;
+use crate::snapshot::SnapshotOptions;
 use anyhow::Error;
 use py_spy::config::RecordDuration;
 use py_spy::sampler;
 use py_spy::Config;
 use py_spy::Frame;
+use py_spy::PythonSpy;
 use remoteprocess;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Default)]
 pub enum SamplerStatus {
+    /// Waiting for the process to exist and run Python, see [`LiveOptions::wait`]
+    Waiting,
     #[default]
     Running,
     Error(String),
@@ -47,6 +52,212 @@ pub struct SamplerState {
     pub status: SamplerStatus,
     pub total_sampled_duration: Duration,
     pub late: Option<Duration>,
+    pub pruned_samples: usize,
+    /// Only show the samples of this last period instead of all the samples since attaching.
+    /// Set by the application, read by the sampler.
+    pub window: Option<Duration>,
+    /// Sampling options to use. Set by the application to restart the sampler with new options.
+    pub settings: Option<SamplerSettings>,
+    /// Message from the sampler to show to the user
+    pub message: Option<String>,
+    /// Discard the samples collected so far. Set by the application, cleared by the sampler.
+    pub clear_requested: bool,
+    /// Stop sampling, handing over the samples collected so far. Set by the application.
+    pub stop_requested: bool,
+    pub health: SamplerHealth,
+    /// How long to sample for, if not until the process exits
+    pub record_duration: Option<Duration>,
+}
+
+/// How well the sampler keeps up with its sampling rate. The errors count since attaching, the
+/// rest since the sampler last started with its current settings.
+#[derive(Debug, Clone, Default)]
+pub struct SamplerHealth {
+    /// Sampling rate asked for, in samples per second
+    pub requested_rate: u64,
+    /// Intervals sampled
+    pub intervals: u64,
+    /// Intervals sampled behind schedule
+    pub late_intervals: u64,
+    /// Time spent sampling
+    pub duration: Duration,
+    /// Processes that could not be sampled, once per interval
+    pub errors: u64,
+    /// Last sampling error, with the pid of the process it happened in
+    pub last_error: Option<String>,
+}
+
+impl SamplerHealth {
+    /// Intervals actually sampled per second
+    pub fn achieved_rate(&self) -> Option<f64> {
+        let seconds = self.duration.as_secs_f64();
+        (seconds > 0.0).then(|| self.intervals as f64 / seconds)
+    }
+
+    /// Intervals that should have been sampled by now but were not, as the sampler fell behind
+    pub fn missed_intervals(&self) -> u64 {
+        let expected = (self.duration.as_secs_f64() * self.requested_rate as f64) as u64;
+        expected.saturating_sub(self.intervals)
+    }
+
+    /// Share of the intervals sampled behind schedule
+    pub fn late_ratio(&self) -> f64 {
+        self.late_intervals as f64 / self.intervals.max(1) as f64
+    }
+}
+
+/// Sampling options that can be changed while attached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplerSettings {
+    /// Samples per second
+    pub sampling_rate: u64,
+    pub gil_only: bool,
+    pub include_idle: bool,
+    pub include_thread_ids: bool,
+    pub native: bool,
+}
+
+/// Sampling rates to step through, in samples per second
+const SAMPLING_RATES: &[u64] = &[10, 20, 50, 100, 200, 500, 1000];
+
+impl SamplerSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            sampling_rate: config.sampling_rate,
+            gil_only: config.gil_only,
+            include_idle: config.include_idle,
+            include_thread_ids: config.include_thread_ids,
+            native: config.native,
+        }
+    }
+
+    pub fn apply(&self, config: &mut Config) {
+        config.sampling_rate = self.sampling_rate;
+        config.gil_only = self.gil_only;
+        config.include_idle = self.include_idle;
+        config.include_thread_ids = self.include_thread_ids;
+        config.native = self.native;
+    }
+
+    /// Step to the next higher or lower sampling rate of [`SAMPLING_RATES`]
+    pub fn step_sampling_rate(&mut self, up: bool) {
+        let rate = if up {
+            SAMPLING_RATES.iter().find(|r| **r > self.sampling_rate)
+        } else {
+            SAMPLING_RATES
+                .iter()
+                .rev()
+                .find(|r| **r < self.sampling_rate)
+        };
+        if let Some(rate) = rate {
+            self.sampling_rate = *rate;
+        }
+    }
+}
+
+/// Options for live profiling sessions.
+#[derive(Debug, Clone, Default)]
+pub struct LiveOptions {
+    /// Budget in bytes for the accumulated stacks; smallest stacks are pruned when exceeded
+    pub max_memory: Option<usize>,
+    /// Periodically write the new samples to folded stacks files
+    pub snapshots: Option<SnapshotOptions>,
+    /// Initial sliding window, see [`SamplerState::window`]
+    pub window: Option<Duration>,
+    /// Options of the py-spy sampler
+    pub py_spy: PySpyOptions,
+    /// File to save all the samples to, instead of a timestamped file in the current directory
+    pub output: Option<PathBuf>,
+    /// Keep trying to attach to the process until it can be profiled, instead of failing
+    pub wait: bool,
+}
+
+/// Time between two attempts to attach to the process when waiting for it
+const WAIT_RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Options of "py-spy record" that have their own flamelens flags.
+#[derive(Debug, Clone, Default)]
+pub struct PySpyOptions {
+    /// Samples per second
+    pub rate: Option<u64>,
+    /// Stop sampling after this long
+    pub duration: Option<Duration>,
+    pub native: bool,
+    pub subprocesses: bool,
+    pub gil: bool,
+    pub idle: bool,
+    pub nonblocking: bool,
+}
+
+/// Configuration of py-spy to sample the process `pid` with `options`, and `extra_args` passed as
+/// is to "py-spy record". Fails with the error of py-spy's parser if `extra_args` are invalid.
+pub fn config_from_options(
+    pid: u64,
+    options: &PySpyOptions,
+    extra_args: Option<&str>,
+) -> Result<Config, Error> {
+    // Note: mimic a record command's invocation vs simply getting default Config as
+    // from_args does a lot of heavy lifting
+    let mut args = [
+        "py-spy",
+        "record",
+        "--pid",
+        pid.to_string().as_str(),
+        "--format",
+        "raw",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect::<Vec<String>>();
+    args.extend(options.to_args());
+    if let Some(extra_args) = extra_args {
+        args.extend(extra_args.split_whitespace().map(|s| s.to_string()));
+    }
+    Config::from_args(&args).map_err(|e| anyhow::anyhow!("Invalid py-spy arguments: {}", e))
+}
+
+impl PySpyOptions {
+    /// Arguments of "py-spy record" setting these options
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(rate) = self.rate {
+            args.extend(["--rate".to_string(), rate.to_string()]);
+        }
+        if let Some(duration) = self.duration {
+            args.extend(["--duration".to_string(), duration.as_secs().to_string()]);
+        }
+        let flags = [
+            (self.native, "--native"),
+            (self.subprocesses, "--subprocesses"),
+            (self.gil, "--gil"),
+            (self.idle, "--idle"),
+            (self.nonblocking, "--nonblocking"),
+        ];
+        for (enabled, flag) in flags {
+            if enabled {
+                args.push(flag.to_string());
+            }
+        }
+        args
+    }
+}
+
+/// Sliding windows to cycle through at runtime, `None` showing all the samples
+pub const WINDOW_CHOICES: &[Option<Duration>] = &[
+    None,
+    Some(Duration::from_secs(10)),
+    Some(Duration::from_secs(30)),
+    Some(Duration::from_secs(60)),
+];
+
+/// Window following `window` in [`WINDOW_CHOICES`]
+pub fn next_window(window: Option<Duration>) -> Option<Duration> {
+    let idx = WINDOW_CHOICES
+        .iter()
+        .position(|w| *w == window)
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+    WINDOW_CHOICES[idx % WINDOW_CHOICES.len()]
 }
 
 impl SamplerState {
@@ -65,21 +276,175 @@ impl SamplerState {
     pub fn unset_late(&mut self) {
         self.late = None;
     }
+
+    pub fn set_pruned_samples(&mut self, pruned_samples: usize) {
+        self.pruned_samples = pruned_samples;
+    }
+}
+
+/// Sample the process without the TUI until it exits, the record duration elapses or ctrl-c is
+/// pressed, reporting the progress on stderr. Returns the folded stacks of all the samples.
+pub fn record_headless(
+    pid: remoteprocess::Pid,
+    config: &Config,
+    options: &LiveOptions,
+) -> Result<String, Error> {
+    let output_data: Arc<Mutex<Option<ProfilerOutput>>> = Arc::new(Mutex::new(None));
+    let state = Arc::new(Mutex::new(SamplerState::default()));
+    {
+        let state = state.clone();
+        ctrlc::set_handler(move || state.lock().unwrap().stop_requested = true)?;
+    }
+    let handle = {
+        let (config, options) = (config.clone(), options.clone());
+        let (output_data, state) = (output_data.clone(), state.clone());
+        std::thread::spawn(move || record_samples(pid, &config, &options, output_data, state))
+    };
+    while !handle.is_finished() {
+        std::thread::sleep(Duration::from_millis(500));
+        let state = state.lock().unwrap();
+        if let SamplerStatus::Running = state.status {
+            eprint!(
+                "\rSampled pid {} for {}s ({} intervals), ctrl-c to stop",
+                pid,
+                state.health.duration.as_secs(),
+                state.health.intervals
+            );
+        }
+    }
+    eprintln!();
+    if let SamplerStatus::Error(e) = &state.lock().unwrap().status {
+        return Err(anyhow::anyhow!("{}", e));
+    }
+    let data = output_data
+        .lock()
+        .unwrap()
+        .take()
+        .map(|output| output.cumulative.unwrap_or(output.data))
+        .unwrap_or_default();
+    Ok(data)
+}
+
+/// Stack of a thread at the time of a dump
+#[derive(Debug, Clone)]
+pub struct ThreadStack {
+    /// Thread id, activity and name, as in `Thread 0x7F3A (active+gil): "MainThread"`
+    pub header: String,
+    /// Frames as `function (file:line)`, the innermost first
+    pub frames: Vec<String>,
+}
+
+/// Stacks of all the threads of the process right now, as printed by `py-spy dump`. Attaches a
+/// spy of its own, so the sampler keeps running meanwhile.
+pub fn dump_stacks(pid: remoteprocess::Pid, config: &Config) -> Result<Vec<ThreadStack>, Error> {
+    let mut spy = PythonSpy::new(pid, config)?;
+    let traces = spy.get_stack_traces()?;
+    Ok(traces
+        .iter()
+        .map(|trace| {
+            let mut header = format!(
+                "Thread {} ({})",
+                trace.format_threadid(),
+                trace.status_str()
+            );
+            if let Some(name) = &trace.thread_name {
+                header += format!(": \"{}\"", name).as_str();
+            }
+            let frames = trace
+                .frames
+                .iter()
+                .map(|frame| {
+                    let filename = frame.short_filename.as_ref().unwrap_or(&frame.filename);
+                    if frame.line != 0 {
+                        format!("{} ({}:{})", frame.name, filename, frame.line)
+                    } else {
+                        format!("{} ({})", frame.name, filename)
+                    }
+                })
+                .collect();
+            ThreadStack { header, frames }
+        })
+        .collect())
 }
 
 #[derive(Debug)]
 pub struct ProfilerOutput {
+    /// Folded stacks to display
     pub data: String,
+    /// Folded stacks of all the samples since attaching, if `data` only covers a window
+    pub cumulative: Option<String>,
+    /// Number of times the samples were cleared, so that consumers of counts that only grow
+    /// can start over
+    pub resets: usize,
+    /// No samples come after these ones, as the sampling is complete
+    pub last: bool,
 }
 
 pub fn record_samples(
     pid: remoteprocess::Pid,
     config: &Config,
+    options: &LiveOptions,
     output_data: Arc<Mutex<Option<ProfilerOutput>>>,
     state: Arc<Mutex<SamplerState>>,
 ) {
+    let mut attached = match attach(pid, config, options, &state) {
+        Ok(sampler) => Some(sampler),
+        Err(e) => {
+            let mut state = state.lock().unwrap();
+            state.set_status(SamplerStatus::Error(format!("{:?}", e)));
+            return;
+        }
+    };
     state.lock().unwrap().set_status(SamplerStatus::Running);
-    let result = run(pid, config, output_data, state.clone());
+    let mut config = config.clone();
+    let mut output = PySpyFlamegraph::new(config.show_line_numbers);
+    // Keep enough recent samples for any of the windows
+    output.retention = WINDOW_CHOICES
+        .iter()
+        .chain([&options.window])
+        .flatten()
+        .max()
+        .copied();
+    {
+        let mut state = state.lock().unwrap();
+        state.window = options.window;
+        state.record_duration = match &config.duration {
+            RecordDuration::Unlimited => None,
+            RecordDuration::Seconds(sec) => Some(Duration::from_secs(*sec)),
+        };
+        state.settings = Some(SamplerSettings::from_config(&config));
+    }
+    let mut start_tic = Instant::now();
+    // Settings to go back to if the sampler can't be restarted with new ones
+    let mut previous_settings: Option<SamplerSettings> = None;
+    let result = loop {
+        let run_result = run(
+            pid,
+            &config,
+            attached.take(),
+            options,
+            &mut output,
+            &mut start_tic,
+            output_data.clone(),
+            state.clone(),
+        );
+        match run_result {
+            Ok(Some(settings)) => {
+                previous_settings = Some(SamplerSettings::from_config(&config));
+                settings.apply(&mut config);
+            }
+            Ok(None) => break Ok(()),
+            Err(e) => match previous_settings.take() {
+                Some(settings) => {
+                    settings.apply(&mut config);
+                    let mut state = state.lock().unwrap();
+                    state.settings = Some(settings);
+                    state.message = Some(format!("Could not apply sampler settings: {}", e));
+                }
+                None => break Err(e),
+            },
+        }
+    };
     match result {
         Ok(_) => {
             state.lock().unwrap().set_status(SamplerStatus::Done);
@@ -93,30 +458,165 @@ pub fn record_samples(
     }
 }
 
+/// Start sampling the process. With [`LiveOptions::wait`], keep retrying while it doesn't exist,
+/// hasn't loaded Python or can't be attached to yet, until it exits, the error can't go away by
+/// itself (such as a lack of permissions) or the application asks to stop.
+fn attach(
+    pid: remoteprocess::Pid,
+    config: &Config,
+    options: &LiveOptions,
+    state: &Arc<Mutex<SamplerState>>,
+) -> Result<sampler::Sampler, Error> {
+    if !options.wait {
+        return sampler::Sampler::new(pid, config);
+    }
+    state.lock().unwrap().set_status(SamplerStatus::Waiting);
+    let mut seen = false;
+    loop {
+        let error = match sampler::Sampler::new(pid, config) {
+            Ok(sampler) => return Ok(sampler),
+            Err(e) if is_permanent(&e) => return Err(e),
+            Err(e) => e,
+        };
+        match remoteprocess::Process::new(pid).and_then(|p| p.cmdline()) {
+            // Exited processes that were not reaped yet have no command line
+            Ok(cmdline) if cmdline.is_empty() => {
+                return Err(error.context(format!("pid {} exited before it could be sampled", pid)))
+            }
+            Ok(_) => seen = true,
+            Err(_) if seen => {
+                return Err(error.context(format!("pid {} exited before it could be sampled", pid)))
+            }
+            Err(_) => {}
+        }
+        if state.lock().unwrap().stop_requested {
+            return Err(anyhow::anyhow!("Stopped waiting for pid {}", pid));
+        }
+        std::thread::sleep(WAIT_RETRY_INTERVAL);
+    }
+}
+
+/// Whether attaching failed for a reason that retrying won't fix, such as a lack of permissions
+fn is_permanent(error: &Error) -> bool {
+    error.chain().any(|cause| {
+        let io_error = match cause.downcast_ref::<remoteprocess::Error>() {
+            Some(remoteprocess::Error::IOError(e)) => Some(e),
+            _ => cause.downcast_ref::<std::io::Error>(),
+        };
+        match io_error {
+            Some(e) => e.kind() == std::io::ErrorKind::PermissionDenied,
+            // ptrace errors only come as text, as in "EPERM: Operation not permitted"
+            None => cause.to_string().starts_with("EPERM"),
+        }
+    })
+}
+
+/// Pid of the most recent process named `name`, as in `job.py` for `python -u /srv/job.py` or
+/// `worker` for `/usr/bin/worker --queue default`. With `wait`, wait for one to start. Only
+/// supported where processes are listed in `/proc`, such as on Linux.
+pub fn find_process(name: &str, wait: bool) -> Result<remoteprocess::Pid, Error> {
+    let mut waiting = false;
+    loop {
+        let own_pid = std::process::id() as remoteprocess::Pid;
+        let mut found = None;
+        let entries = std::fs::read_dir("/proc")
+            .map_err(|e| anyhow::anyhow!("Could not list the processes to find {}: {}", name, e))?;
+        for entry in entries.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|pid| pid.parse::<remoteprocess::Pid>().ok())
+            else {
+                continue;
+            };
+            let Ok(cmdline) = remoteprocess::Process::new(pid).and_then(|p| p.cmdline()) else {
+                continue;
+            };
+            if pid != own_pid && process_name_matches(&cmdline, name) {
+                found = found.max(Some(pid));
+            }
+        }
+        match found {
+            Some(pid) => return Ok(pid),
+            None if !wait => return Err(anyhow::anyhow!("No process named {}", name)),
+            None if !waiting => {
+                eprintln!(
+                    "Waiting for a process named {} to start, ctrl-c to stop",
+                    name
+                );
+                waiting = true;
+            }
+            None => {}
+        }
+        std::thread::sleep(WAIT_RETRY_INTERVAL);
+    }
+}
+
+/// Whether the program of a command line, or the first argument that is not an option (the script
+/// of an interpreter), has the file name `name`
+fn process_name_matches(cmdline: &[String], name: &str) -> bool {
+    let file_name = |arg: &String| arg.rsplit('/').next() == Some(name);
+    let script = cmdline.iter().skip(1).find(|arg| !arg.starts_with('-'));
+    cmdline.first().is_some_and(file_name) || script.is_some_and(file_name)
+}
+
+/// Sample the process until it exits, adding the samples to `output`. Returns the new settings
+/// if the application asked to restart with them. Samples with `sampler` if already attached.
+/// `start_tic` is moved when the samples are cleared.
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     pid: remoteprocess::Pid,
     config: &Config,
+    sampler: Option<sampler::Sampler>,
+    options: &LiveOptions,
+    output: &mut PySpyFlamegraph,
+    start_tic: &mut Instant,
     output_data: Arc<Mutex<Option<ProfilerOutput>>>,
     state: Arc<Mutex<SamplerState>>,
-) -> Result<(), Error> {
-    let mut output = PySpyFlamegraph::new(config.show_line_numbers);
-
-    let start_tic = std::time::Instant::now();
-    let sampler = sampler::Sampler::new(pid, config)?;
+) -> Result<Option<SamplerSettings>, Error> {
+    let settings = SamplerSettings::from_config(config);
+    let sampler = match sampler {
+        Some(sampler) => sampler,
+        None => sampler::Sampler::new(pid, config)?,
+    };
 
     let max_intervals = match &config.duration {
         RecordDuration::Unlimited => None,
         RecordDuration::Seconds(sec) => Some(sec * config.sampling_rate),
     };
 
-    let mut _errors = 0;
     let mut intervals = 0;
     let mut _samples = 0;
+    let sampling_tic = Instant::now();
+    {
+        let health = &mut state.lock().unwrap().health;
+        health.requested_rate = config.sampling_rate;
+        health.intervals = 0;
+        health.late_intervals = 0;
+        health.duration = Duration::ZERO;
+    }
 
     let mut last_late_message = std::time::Instant::now();
     let mut last_data_dump: Option<Instant> = None;
 
     for mut sample in sampler {
+        let requested_settings = state.lock().unwrap().settings;
+        if let Some(requested_settings) = requested_settings.filter(|s| *s != settings) {
+            return Ok(Some(requested_settings));
+        }
+        {
+            let mut state = state.lock().unwrap();
+            if state.stop_requested {
+                break;
+            }
+            if std::mem::take(&mut state.clear_requested) {
+                output.clear();
+                *start_tic = Instant::now();
+                state.set_pruned_samples(0);
+                last_data_dump = None;
+            }
+        }
+
         if let Some(delay) = sample.late {
             if delay > Duration::from_secs(1) {
                 let now = std::time::Instant::now();
@@ -131,6 +631,21 @@ pub fn run(
             state.lock().unwrap().unset_late();
         }
 
+        {
+            let health = &mut state.lock().unwrap().health;
+            health.intervals += 1;
+            if sample.late.is_some() {
+                health.late_intervals += 1;
+            }
+            health.duration = sampling_tic.elapsed();
+            if let Some(sampling_errors) = &sample.sampling_errors {
+                health.errors += sampling_errors.len() as u64;
+                if let Some((pid, e)) = sampling_errors.last() {
+                    health.last_error = Some(format!("pid {}: {}", pid, e));
+                }
+            }
+        }
+
         intervals += 1;
         if let Some(max_intervals) = max_intervals {
             if intervals >= max_intervals {
@@ -180,12 +695,6 @@ pub fn run(
             output.increment(trace)?;
         }
 
-        if let Some(sampling_errors) = sample.sampling_errors {
-            for (_pid, _e) in sampling_errors {
-                _errors += 1;
-            }
-        }
-
         let should_dump = match last_data_dump {
             Some(last_data_dump) => {
                 let elapsed = Instant::now() - last_data_dump;
@@ -195,17 +704,90 @@ pub fn run(
         };
         if should_dump {
             last_data_dump = Some(Instant::now());
-            let data = output.get_data();
-            // let mut file = std::fs::File::create("data.txt")?;
-            // std::io::Write::write_all(&mut file, data.as_bytes())?;
-            let profiler_output = ProfilerOutput { data };
-            output_data.lock().unwrap().replace(profiler_output);
+            dump(output, options, *start_tic, &output_data, &state, false);
+        }
+    }
+
+    // The samples since the last dump, so that the final flamegraph has all of them
+    dump(output, options, *start_tic, &output_data, &state, true);
+    Ok(None)
+}
+
+/// Hand the samples collected so far to the application
+fn dump(
+    output: &mut PySpyFlamegraph,
+    options: &LiveOptions,
+    start_tic: Instant,
+    output_data: &Arc<Mutex<Option<ProfilerOutput>>>,
+    state: &Arc<Mutex<SamplerState>>,
+    last: bool,
+) {
+    if let Some(max_memory) = options.max_memory {
+        if output.prune(max_memory) > 0 {
             state
                 .lock()
                 .unwrap()
-                .set_total_sampled_duration(start_tic.elapsed());
+                .set_pruned_samples(output.pruned_samples);
         }
     }
+    let window = state.lock().unwrap().window;
+    let profiler_output = match window {
+        Some(window) => ProfilerOutput {
+            data: output.get_window_data(window, Instant::now()),
+            cumulative: Some(output.get_data()),
+            resets: output.resets,
+            last,
+        },
+        None => ProfilerOutput {
+            data: output.get_data(),
+            cumulative: None,
+            resets: output.resets,
+            last,
+        },
+    };
+    output_data.lock().unwrap().replace(profiler_output);
+    state
+        .lock()
+        .unwrap()
+        .set_total_sampled_duration(start_tic.elapsed());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(())
+    #[test]
+    fn test_process_name_matches() {
+        let cmdline = |args: &str| args.split(' ').map(String::from).collect::<Vec<_>>();
+        assert!(process_name_matches(
+            &cmdline("python -u /srv/job.py --fast"),
+            "job.py"
+        ));
+        assert!(process_name_matches(
+            &cmdline("/usr/bin/worker --queue default"),
+            "worker"
+        ));
+        assert!(!process_name_matches(
+            &cmdline("python -u /srv/job.py --fast"),
+            "fast"
+        ));
+        // Not the arguments of a program that launches another one
+        assert!(!process_name_matches(
+            &cmdline("sudo flamelens record --name job.py"),
+            "job.py"
+        ));
+    }
+
+    #[test]
+    fn test_is_permanent() {
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(is_permanent(
+            &Error::from(denied).context("Failed to open process")
+        ));
+        let not_found = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert!(!is_permanent(&Error::from(not_found)));
+        assert!(!is_permanent(&anyhow::anyhow!(
+            "Failed to find python version"
+        )));
+    }
 }
@@ -53,16 +53,44 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io::Write;
+use std::time::{Duration, Instant};
 
 use anyhow::Error;
 
 use py_spy::StackTrace;
 
+use crate::flame::{frame_delimiters, join_frames};
+
+/// Name of the frame that replaces pruned stacks
+pub const PRUNED_FRAME: &str = "(pruned)";
+
+/// Rough per-entry overhead of the counts map on top of the stack string itself
+const ENTRY_OVERHEAD: usize = 64;
+
+/// Granularity of the recent samples kept for sliding windows
+const BUCKET_DURATION: Duration = Duration::from_secs(1);
+
+/// Counts of the samples taken during one bucket
+struct Bucket {
+    start: Instant,
+    counts: HashMap<String, usize>,
+}
+
 pub struct Flamegraph {
     pub counts: HashMap<String, usize>,
     pub show_linenumbers: bool,
+    /// Estimated memory used by `counts` in bytes
+    pub memory: usize,
+    /// Total number of samples folded into pruned frames
+    pub pruned_samples: usize,
+    /// How long to keep recent samples for sliding windows, if at all
+    pub retention: Option<Duration>,
+    /// Recent samples, oldest first
+    buckets: VecDeque<Bucket>,
+    /// Number of times the samples were cleared
+    pub resets: usize,
 }
 
 impl Flamegraph {
@@ -70,35 +98,135 @@ impl Flamegraph {
         Flamegraph {
             counts: HashMap::new(),
             show_linenumbers,
+            memory: 0,
+            pruned_samples: 0,
+            retention: None,
+            buckets: VecDeque::new(),
+            resets: 0,
         }
     }
 
+    /// Discard all the samples collected so far
+    pub fn clear(&mut self) {
+        self.counts.clear();
+        self.memory = 0;
+        self.pruned_samples = 0;
+        self.buckets.clear();
+        self.resets += 1;
+    }
+
     pub fn increment(&mut self, trace: &StackTrace) -> std::io::Result<()> {
         // convert the frame into a single ';' delimited String
-        let frame = trace
-            .frames
-            .iter()
-            .rev()
-            .map(|frame| {
-                let filename = match &frame.short_filename {
-                    Some(f) => f,
-                    None => &frame.filename,
-                };
-                if self.show_linenumbers && frame.line != 0 {
-                    format!("{} ({}:{})", frame.name, filename, frame.line)
-                } else if !filename.is_empty() {
-                    format!("{} ({})", frame.name, filename)
-                } else {
-                    frame.name.clone()
-                }
-            })
-            .collect::<Vec<String>>()
-            .join(";");
+        let frame = join_frames(trace.frames.iter().rev().map(|frame| {
+            let filename = match &frame.short_filename {
+                Some(f) => f,
+                None => &frame.filename,
+            };
+            if self.show_linenumbers && frame.line != 0 {
+                format!("{} ({}:{})", frame.name, filename, frame.line)
+            } else if !filename.is_empty() {
+                format!("{} ({})", frame.name, filename)
+            } else {
+                frame.name.clone()
+            }
+        }));
+        if self.retention.is_some() {
+            self.record_recent(&frame, Instant::now());
+        }
         // update counts for that frame
-        *self.counts.entry(frame).or_insert(0) += 1;
+        if let Some(count) = self.counts.get_mut(&frame) {
+            *count += 1;
+        } else {
+            self.memory += frame.len() + ENTRY_OVERHEAD;
+            self.counts.insert(frame, 1);
+        }
         Ok(())
     }
 
+    /// Fold the stacks with the smallest counts into a "(pruned)" frame under their root frame
+    /// until the estimated memory is below `max_memory`. Returns the number of samples folded.
+    pub fn prune(&mut self, max_memory: usize) -> usize {
+        if self.memory <= max_memory {
+            return 0;
+        }
+        // Prune a bit more than needed so that this does not run on every new stack
+        let target = max_memory / 10 * 9;
+        let mut candidates = self
+            .counts
+            .iter()
+            .filter(|(stack, _)| !stack.ends_with(PRUNED_FRAME))
+            .map(|(stack, count)| (*count, stack.clone()))
+            .collect::<Vec<_>>();
+        candidates.sort();
+        let mut pruned = 0;
+        for (count, stack) in candidates {
+            if self.memory <= target {
+                break;
+            }
+            self.counts.remove(&stack);
+            self.memory -= stack.len() + ENTRY_OVERHEAD;
+            let pruned_stack = match frame_delimiters(&stack).next() {
+                Some(i) => format!("{};{}", &stack[..i], PRUNED_FRAME),
+                None => PRUNED_FRAME.to_string(),
+            };
+            if let Some(pruned_count) = self.counts.get_mut(&pruned_stack) {
+                *pruned_count += count;
+            } else {
+                self.memory += pruned_stack.len() + ENTRY_OVERHEAD;
+                self.counts.insert(pruned_stack, count);
+            }
+            pruned += count;
+        }
+        self.pruned_samples += pruned;
+        pruned
+    }
+
+    fn record_recent(&mut self, frame: &str, now: Instant) {
+        let needs_bucket = self
+            .buckets
+            .back()
+            .is_none_or(|bucket| now.duration_since(bucket.start) >= BUCKET_DURATION);
+        if needs_bucket {
+            self.buckets.push_back(Bucket {
+                start: now,
+                counts: HashMap::new(),
+            });
+        }
+        if let Some(retention) = self.retention {
+            while self.buckets.front().is_some_and(|bucket| {
+                now.duration_since(bucket.start) > retention + BUCKET_DURATION
+            }) {
+                self.buckets.pop_front();
+            }
+        }
+        let bucket = self.buckets.back_mut().unwrap();
+        if let Some(count) = bucket.counts.get_mut(frame) {
+            *count += 1;
+        } else {
+            bucket.counts.insert(frame.to_string(), 1);
+        }
+    }
+
+    /// Folded stacks of the samples taken during the last `window`, to the granularity of the
+    /// buckets. Only covers the retention period.
+    pub fn get_window_data(&self, window: Duration, now: Instant) -> String {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for bucket in self
+            .buckets
+            .iter()
+            .filter(|bucket| now.saturating_duration_since(bucket.start) < window)
+        {
+            for (stack, count) in bucket.counts.iter() {
+                *counts.entry(stack.as_str()).or_default() += count;
+            }
+        }
+        counts
+            .iter()
+            .map(|(k, v)| format!("{} {}", k, v))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     fn get_lines(&self) -> Vec<String> {
         self.counts
             .iter()
@@ -118,3 +246,82 @@ impl Flamegraph {
         self.get_lines().join("\n")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_counts(counts: &[(&str, usize)]) -> Flamegraph {
+        let mut flamegraph = Flamegraph::new(true);
+        for (stack, count) in counts {
+            flamegraph.memory += stack.len() + ENTRY_OVERHEAD;
+            flamegraph.counts.insert(stack.to_string(), *count);
+        }
+        flamegraph
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut flamegraph =
+            with_counts(&[("main;a", 10), ("main;b", 1), ("main;c", 2), ("other;d", 3)]);
+        let memory = flamegraph.memory;
+        assert_eq!(memory, 4 * ENTRY_OVERHEAD + 25);
+
+        // Nothing to prune below the limit
+        assert_eq!(flamegraph.prune(memory), 0);
+        assert_eq!(flamegraph.counts.len(), 4);
+        assert_eq!(flamegraph.memory, memory);
+
+        // The smallest stacks go first, into the pruned frame under their root
+        assert_eq!(flamegraph.prune(memory - 30), 3);
+        assert_eq!(
+            flamegraph.counts,
+            HashMap::from([
+                ("main;a".to_string(), 10),
+                (format!("main;{}", PRUNED_FRAME), 3),
+                ("other;d".to_string(), 3),
+            ])
+        );
+        assert_eq!(flamegraph.memory, 3 * ENTRY_OVERHEAD + 26);
+        assert_eq!(flamegraph.pruned_samples, 3);
+
+        // Pruning a bit more than needed leaves room for new stacks
+        assert_eq!(flamegraph.prune(memory - 30), 0);
+    }
+
+    #[test]
+    fn test_window_data() {
+        let mut flamegraph = Flamegraph::new(true);
+        flamegraph.retention = Some(Duration::from_secs(3));
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let window = |flamegraph: &Flamegraph, millis, now| {
+            let data = flamegraph.get_window_data(Duration::from_millis(millis), at(now));
+            let mut lines = data.lines().map(String::from).collect::<Vec<_>>();
+            lines.sort();
+            lines
+        };
+
+        flamegraph.record_recent("main;a", at(0));
+        flamegraph.record_recent("main;a", at(500));
+        flamegraph.record_recent("main;b", at(1000));
+        flamegraph.record_recent("main;b", at(2500));
+        assert_eq!(flamegraph.buckets.len(), 3);
+        assert_eq!(window(&flamegraph, 1000, 2500), vec!["main;b 1"]);
+        // Buckets started exactly a window ago are left out
+        assert_eq!(window(&flamegraph, 1500, 2500), vec!["main;b 1"]);
+        assert_eq!(window(&flamegraph, 2000, 2500), vec!["main;b 2"]);
+        assert_eq!(
+            window(&flamegraph, 3000, 2500),
+            vec!["main;a 2", "main;b 2"]
+        );
+
+        // Buckets older than the retention expire as new samples come in
+        flamegraph.record_recent("main;c", at(5000));
+        assert_eq!(flamegraph.buckets.len(), 3);
+        assert_eq!(
+            window(&flamegraph, 10_000, 5000),
+            vec!["main;b 2", "main;c 1"]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::flame::{split_frames, FRAME_DELIMITER};
+
+/// A rule rewriting frame names, written like a sed substitution: `s/<regex>/<replacement>/`. Any
+/// character following the `s` can be the separator, and a separator preceded by a backslash is
+/// part of the regex or the replacement. The replacement can refer to groups as `$1` or `${name}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RewriteRule {
+    regex: Regex,
+    replacement: String,
+    /// The rule as written
+    source: String,
+}
+
+impl RewriteRule {
+    /// Replace all the matches of the regex in `frame`
+    pub fn apply<'a>(&self, frame: &'a str) -> Cow<'a, str> {
+        self.regex.replace_all(frame, self.replacement.as_str())
+    }
+}
+
+impl FromStr for RewriteRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected s/<regex>/<replacement>/: {}", s);
+        let rest = s.strip_prefix('s').ok_or_else(invalid)?;
+        let separator = rest
+            .chars()
+            .next()
+            .filter(|c| *c != '\\')
+            .ok_or_else(invalid)?;
+        let mut parts = vec![String::new()];
+        let mut chars = rest[separator.len_utf8()..].chars().peekable();
+        while let Some(c) = chars.next() {
+            let part = parts.last_mut().unwrap();
+            if c == '\\' && chars.peek() == Some(&separator) {
+                part.push(separator);
+                chars.next();
+            } else if c == separator {
+                parts.push(String::new());
+            } else {
+                part.push(c);
+            }
+        }
+        let (pattern, replacement) = match parts.as_slice() {
+            [pattern, replacement] => (pattern, replacement),
+            [pattern, replacement, end] if end.is_empty() => (pattern, replacement),
+            _ => return Err(invalid()),
+        };
+        let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+        Ok(Self {
+            regex,
+            replacement: replacement.clone(),
+            source: s.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for RewriteRule {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<RewriteRule> for String {
+    fn from(rule: RewriteRule) -> Self {
+        rule.source
+    }
+}
+
+impl fmt::Display for RewriteRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Rewrite rules for the frame names of a runtime, applied before the other rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RewritePreset {
+    /// Erlang and Elixir (eflame, eflambe and perf with the BEAM JIT): `$'Elixir.Enum':map/2`
+    /// becomes `Enum.map/2` and `$lists:foldl/3` becomes `lists:foldl/3`
+    Beam,
+}
+
+impl RewritePreset {
+    pub fn rules(&self) -> Vec<RewriteRule> {
+        let rules: &[&str] = match self {
+            RewritePreset::Beam => &[
+                // Prefix of the symbols of the JIT in perf maps
+                r"s/^\$//",
+                // Elixir modules are atoms prefixed with "Elixir."
+                r"s/^'?Elixir\.([^':]+)'?:/$1./",
+                // Anonymous functions, as in '-map/2-fun-0-'/1
+                r"s/'-([^']+)-'/$1/",
+            ],
+        };
+        rules
+            .iter()
+            .map(|rule| rule.parse().expect("valid preset rule"))
+            .collect()
+    }
+}
+
+/// Apply the rules in order to every frame of "frame;frame count" lines. Stacks that become equal
+/// are merged by the parser. Lines that aren't stacks are left as they are.
+pub fn rewrite_frames(content: &str, rules: &[RewriteRule]) -> String {
+    let mut out = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        let stack = line
+            .trim_end_matches(['\n', '\r'])
+            .rsplit_once(' ')
+            .filter(|_| !line.starts_with('#'))
+            .map(|(stack, _count)| stack);
+        let Some(stack) = stack else {
+            out.push_str(line);
+            continue;
+        };
+        for (i, frame) in split_frames(stack).enumerate() {
+            if i > 0 {
+                out.push(FRAME_DELIMITER);
+            }
+            let frame = rules.iter().fold(frame.to_string(), |frame, rule| {
+                rule.apply(&frame).into_owned()
+            });
+            out.push_str(&frame);
+        }
+        out.push_str(&line[stack.len()..]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_frames() {
+        let rules = [
+            "s/<.*>//".parse::<RewriteRule>().unwrap(),
+            "s|0x[0-9a-f]+|0x?|".parse().unwrap(),
+            r"s#/venv/lib/[^/]+/#\#site/#".parse().unwrap(),
+        ];
+        let content = "# comment\nmain;vector<int>::push 2\nmain;vector<long>::push 1\n\
+            f at 0xdeadbeef;/venv/lib/python3.12/json.py 3\nno count";
+        assert_eq!(
+            rewrite_frames(content, &rules),
+            "# comment\nmain;vector::push 2\nmain;vector::push 1\n\
+             f at 0x?;#site/json.py 3\nno count"
+        );
+        assert_eq!(rules[1].to_string(), "s|0x[0-9a-f]+|0x?|");
+        assert!("s/(/x/".parse::<RewriteRule>().is_err());
+        assert!("s/a/b/c".parse::<RewriteRule>().is_err());
+        assert!("x/a/b/".parse::<RewriteRule>().is_err());
+    }
+
+    #[test]
+    fn test_beam_preset() {
+        let rules = RewritePreset::Beam.rules();
+        let content = "<0.92.0>;$'Elixir.Enum':map/2;$'Elixir.Enum':'-map/2-fun-0-'/1 2\n\
+            <0.92.0>;lists:foldl/3;'Elixir.MyApp.Worker':run/1;process_main 1\n";
+        assert_eq!(
+            rewrite_frames(content, &rules),
+            "<0.92.0>;Enum.map/2;Enum.map/2-fun-0/1 2\n\
+             <0.92.0>;lists:foldl/3;MyApp.Worker.run/1;process_main 1\n"
+        );
+    }
+}
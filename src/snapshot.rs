@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::flame::{FlameGraph, ROOT_ID};
+
+/// Where and how often to write snapshots.
+#[derive(Debug, Clone)]
+pub struct SnapshotOptions {
+    pub every: Duration,
+    pub dir: PathBuf,
+}
+
+/// Writes the samples collected since the previous snapshot to timestamped folded stacks files,
+/// given successive flamegraphs whose counts only grow (such as the ones of a live session).
+#[derive(Debug)]
+pub struct Snapshotter {
+    options: SnapshotOptions,
+    prefix: String,
+    last_snapshot_at: Option<Instant>,
+    last_counts: HashMap<String, u64>,
+}
+
+impl Snapshotter {
+    pub fn new(options: SnapshotOptions, prefix: &str) -> Self {
+        Self {
+            options,
+            prefix: prefix.to_string(),
+            last_snapshot_at: None,
+            last_counts: HashMap::new(),
+        }
+    }
+
+    /// Write a snapshot if one is due at `now` and return its path.
+    pub fn maybe_write(
+        &mut self,
+        flamegraph: &FlameGraph,
+        now: Instant,
+    ) -> io::Result<Option<PathBuf>> {
+        let last_snapshot_at = *self.last_snapshot_at.get_or_insert(now);
+        if now.saturating_duration_since(last_snapshot_at) < self.options.every {
+            return Ok(None);
+        }
+        // Also on failure, so that a broken directory isn't retried on every update
+        self.last_snapshot_at = Some(now);
+        let folded = self.take_new_samples(flamegraph);
+        let path = self.options.dir.join(format!(
+            "{}-{}.txt",
+            self.prefix,
+            format_timestamp(SystemTime::now())
+        ));
+        std::fs::create_dir_all(&self.options.dir)?;
+        std::fs::write(&path, folded)?;
+        Ok(Some(path))
+    }
+
+    /// Take the next flamegraph as counting from zero, after the samples were cleared
+    pub fn rebase(&mut self) {
+        self.last_counts.clear();
+    }
+
+    /// Folded stacks of the samples added since the previous call.
+    fn take_new_samples(&mut self, flamegraph: &FlameGraph) -> String {
+        let counts = flamegraph.get_folded_counts(&ROOT_ID);
+        let mut lines = String::new();
+        for (name, count) in counts.iter() {
+            let previous = self.last_counts.get(name).copied().unwrap_or(0);
+            // Counts can shrink when stacks are pruned, in which case nothing is added
+            let delta = count.saturating_sub(previous);
+            if delta > 0 {
+                lines.push_str(&format!("{} {}\n", name, delta));
+            }
+        }
+        self.last_counts = counts.into_iter().collect();
+        lines
+    }
+}
+
+/// Format a time as a UTC timestamp usable in file names, e.g. "20240131T235959Z".
+pub fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, seconds_of_day) = (seconds / 86400, seconds % 86400);
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        let at = |seconds| UNIX_EPOCH + Duration::from_secs(seconds);
+        assert_eq!(format_timestamp(at(0)), "19700101T000000Z");
+        assert_eq!(format_timestamp(at(951782400)), "20000229T000000Z");
+        assert_eq!(format_timestamp(at(1706745599)), "20240131T235959Z");
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let dir = std::env::temp_dir().join(format!("flamelens-snapshots-{}", std::process::id()));
+        let options = SnapshotOptions {
+            every: Duration::from_secs(60),
+            dir: dir.clone(),
+        };
+        let mut snapshotter = Snapshotter::new(options, "test");
+        let start = Instant::now();
+        let snapshot = |content: &str| FlameGraph::from_string(content.to_string(), true);
+
+        let first = snapshot("main;a 2\nmain;b 1");
+        assert_eq!(snapshotter.maybe_write(&first, start).unwrap(), None);
+        let second = snapshot("main;a 3\nmain;b 1\nmain;c 4");
+        let at = start + Duration::from_secs(30);
+        assert_eq!(snapshotter.maybe_write(&second, at).unwrap(), None);
+
+        let at = start + Duration::from_secs(60);
+        let path = snapshotter.maybe_write(&second, at).unwrap().unwrap();
+        assert!(path.starts_with(&dir));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "main;a 3\nmain;b 1\nmain;c 4\n"
+        );
+
+        // Only the samples added since the previous snapshot
+        let third = snapshot("main;a 5\nmain;b 1\nmain;c 4");
+        let at = start + Duration::from_secs(120);
+        let path = snapshotter.maybe_write(&third, at).unwrap().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "main;a 2\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
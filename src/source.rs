@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Largest source file read for the preview
+const MAX_SOURCE_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Source files kept in memory before the cache is emptied
+const MAX_CACHED_FILES: usize = 32;
+
+/// Lines of the source files shown in the preview panel, read once by path. Files that can't be
+/// read are kept with the reason, so that they aren't tried again on every render.
+#[derive(Debug, Default)]
+pub struct SourceCache {
+    files: HashMap<String, Result<Vec<String>, String>>,
+}
+
+impl SourceCache {
+    /// Read `file` unless it was already
+    pub fn load(&mut self, file: &str) {
+        if self.files.contains_key(file) {
+            return;
+        }
+        if self.files.len() >= MAX_CACHED_FILES {
+            self.files.clear();
+        }
+        self.files.insert(file.to_string(), read_source(file));
+    }
+
+    /// Lines of `file`, or why they couldn't be read. `None` if it wasn't loaded.
+    pub fn get(&self, file: &str) -> Option<Result<&[String], &str>> {
+        self.files
+            .get(file)
+            .map(|lines| lines.as_deref().map_err(|e| e.as_str()))
+    }
+}
+
+/// Lines of a local source file. Remote locations (URLs) and large or binary files are refused.
+pub fn read_source(file: &str) -> Result<Vec<String>, String> {
+    if file.contains("://") {
+        return Err("remote file".to_string());
+    }
+    let path = Path::new(file);
+    let metadata = std::fs::metadata(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => "not found".to_string(),
+        _ => e.to_string(),
+    })?;
+    if !metadata.is_file() {
+        return Err("not a file".to_string());
+    }
+    if metadata.len() > MAX_SOURCE_FILE_SIZE {
+        return Err("file too large".to_string());
+    }
+    let content = std::fs::read(path).map_err(|e| e.to_string())?;
+    if content.contains(&0) {
+        return Err("binary file".to_string());
+    }
+    Ok(String::from_utf8_lossy(&content)
+        .lines()
+        .map(|line| line.replace('\t', "    "))
+        .collect())
+}
+
+/// Range of the lines (0-based) to show in `height` rows so that `line` (1-based) is in the middle
+/// when possible, or the first lines if there is no line
+pub fn visible_lines(num_lines: usize, line: Option<usize>, height: usize) -> (usize, usize) {
+    let start = match line {
+        Some(line) => line
+            .saturating_sub(1)
+            .saturating_sub(height / 2)
+            .min(num_lines.saturating_sub(height)),
+        None => 0,
+    };
+    (start, (start + height).min(num_lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_source() {
+        let lines = read_source("tests/data/py-spy-simple.txt").unwrap();
+        assert!(!lines.is_empty());
+        assert_eq!(
+            read_source("tests/data/missing.py"),
+            Err("not found".to_string())
+        );
+        assert_eq!(read_source("tests/data"), Err("not a file".to_string()));
+        assert_eq!(
+            read_source("tests/data/busy.jfr"),
+            Err("binary file".to_string())
+        );
+        assert_eq!(
+            read_source("https://example.com/app.js"),
+            Err("remote file".to_string())
+        );
+
+        let mut cache = SourceCache::default();
+        assert!(cache.get("tests/data/missing.py").is_none());
+        cache.load("tests/data/missing.py");
+        assert_eq!(cache.get("tests/data/missing.py"), Some(Err("not found")));
+    }
+
+    #[test]
+    fn test_visible_lines() {
+        assert_eq!(visible_lines(100, Some(50), 7), (46, 53));
+        assert_eq!(visible_lines(100, Some(2), 7), (0, 7));
+        assert_eq!(visible_lines(100, Some(99), 7), (93, 100));
+        assert_eq!(visible_lines(3, Some(2), 7), (0, 3));
+        assert_eq!(visible_lines(100, None, 7), (0, 7));
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use crate::flame::{FlameGraph, ROOT};
+
+/// Profiles merged into one flamegraph, such as those of the hosts of a fleet, with the number of
+/// them having each stack, keyed by full name.
+#[derive(Debug, Clone)]
+pub struct Sources {
+    pub names: Vec<String>,
+    counts: HashMap<String, usize>,
+}
+
+impl Sources {
+    pub fn new(profiles: &[(String, FlameGraph)]) -> Self {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for (_, flamegraph) in profiles {
+            for stack in flamegraph.stacks() {
+                if stack.total_count > 0 {
+                    let full_name = flamegraph.get_stack_full_name_from_info(stack);
+                    *counts.entry(full_name.to_string()).or_default() += 1;
+                }
+            }
+        }
+        counts.insert(ROOT.to_string(), profiles.len());
+        Self {
+            names: profiles.iter().map(|(name, _)| name.clone()).collect(),
+            counts,
+        }
+    }
+
+    /// Number of profiles with samples in a stack
+    pub fn count(&self, full_name: &str) -> usize {
+        self.counts.get(full_name).copied().unwrap_or(0)
+    }
+
+    /// Color of a stack by how consistently it shows up: gray when it is in every profile, up to
+    /// orange when it is in a single one. Stacks not found in any profile, such as those renamed
+    /// by pruning, are white.
+    pub fn color(&self, full_name: &str) -> (u8, u8, u8) {
+        let count = self.count(full_name);
+        if count == 0 || self.names.len() < 2 {
+            return (255, 255, 255);
+        }
+        // 0 for the stacks of a single profile, 1 for the stacks of all of them
+        let share = (count - 1) as f64 / (self.names.len() - 1) as f64;
+        let mix =
+            |rare: u8, common: u8| (rare as f64 + (common as f64 - rare as f64) * share) as u8;
+        (mix(255, 130), mix(120, 140), mix(0, 160))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sources() {
+        let profiles = ["main;a 2\nmain;b 1", "main;a 1", "main;a 3\nmain;c 1"]
+            .iter()
+            .enumerate()
+            .map(|(i, content)| {
+                (
+                    format!("host{}.txt", i),
+                    FlameGraph::from_string(content.to_string(), true),
+                )
+            })
+            .collect::<Vec<_>>();
+        let merged = FlameGraph::merge(&profiles.iter().map(|(_, fg)| fg).collect::<Vec<_>>());
+        assert_eq!(merged.total_count(), 8);
+        assert_eq!(
+            merged.get_stack_by_full_name("main;a").unwrap().total_count,
+            6
+        );
+
+        let sources = Sources::new(&profiles);
+        assert_eq!(sources.count("all"), 3);
+        assert_eq!(sources.count("main;a"), 3);
+        assert_eq!(sources.count("main;b"), 1);
+        assert_eq!(sources.count("main;d"), 0);
+
+        assert_eq!(sources.color("main;a"), (130, 140, 160));
+        assert_eq!(sources.color("main;c"), (255, 120, 0));
+        assert_eq!(sources.color("main;d"), (255, 255, 255));
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Lines kept from the output of the profiled command, the oldest ones being dropped first
+const MAX_OUTPUT_LINES: usize = 1000;
+
+/// Last lines written by the profiled command to its stdout and stderr
+#[derive(Debug, Default)]
+pub struct CommandOutput {
+    lines: VecDeque<String>,
+    /// Lines dropped to keep at most [`MAX_OUTPUT_LINES`]
+    pub num_dropped: usize,
+}
+
+impl CommandOutput {
+    pub fn push(&mut self, line: String) {
+        if self.lines.len() == MAX_OUTPUT_LINES {
+            self.lines.pop_front();
+            self.num_dropped += 1;
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Lines kept, the oldest first
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator {
+        self.lines.iter().map(|line| line.as_str())
+    }
+}
+
+/// Where the stdout and stderr of a launched command go
+#[derive(Debug, Clone, Copy)]
+pub enum OutputTarget<'a> {
+    /// Kept in [`SpawnedCommand::output`], so that they don't mess with the TUI
+    Kept,
+    File(&'a Path),
+    /// The terminal of flamelens, stdin included, when there is no TUI
+    Inherited,
+}
+
+/// A command launched by flamelens to profile it from its very start
+#[derive(Debug)]
+pub struct SpawnedCommand {
+    child: Child,
+    /// Output of the command, unless it is written to a file
+    pub output: Option<Arc<Mutex<CommandOutput>>>,
+    /// Exit status, once the command exited
+    pub status: Option<ExitStatus>,
+}
+
+impl SpawnedCommand {
+    /// Launch `command`, its stdout and stderr going to `target`
+    pub fn spawn(command: &[String], target: OutputTarget) -> std::io::Result<Self> {
+        let Some((program, args)) = command.split_first() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "No command to run",
+            ));
+        };
+        let mut process = Command::new(program);
+        process.args(args);
+        if !matches!(target, OutputTarget::Inherited) {
+            // The keys are for the TUI
+            process.stdin(Stdio::null());
+        }
+        let output = match target {
+            OutputTarget::Kept => {
+                process.stdout(Stdio::piped()).stderr(Stdio::piped());
+                Some(Arc::new(Mutex::new(CommandOutput::default())))
+            }
+            OutputTarget::File(path) => {
+                let file = File::create(path)?;
+                process.stdout(file.try_clone()?).stderr(file);
+                None
+            }
+            OutputTarget::Inherited => None,
+        };
+        let mut child = process.spawn()?;
+        if let Some(output) = &output {
+            if let Some(stdout) = child.stdout.take() {
+                read_lines(stdout, output.clone());
+            }
+            if let Some(stderr) = child.stderr.take() {
+                read_lines(stderr, output.clone());
+            }
+        }
+        Ok(Self {
+            child,
+            output,
+            status: None,
+        })
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Exit status of the command if it just exited, `None` if it is still running or its exit
+    /// was already reported
+    pub fn check_exit(&mut self) -> Option<ExitStatus> {
+        if self.status.is_some() {
+            return None;
+        }
+        self.status = self.child.try_wait().ok().flatten();
+        self.status
+    }
+
+    /// Stop the command if it is still running. Returns whether it was.
+    pub fn stop(&mut self) -> bool {
+        if self.status.is_some() || self.check_exit().is_some() {
+            return false;
+        }
+        let stopped = self.child.kill().is_ok();
+        self.status = self.child.wait().ok();
+        stopped
+    }
+}
+
+/// Keep reading the lines of `stream` into `output` in the background until it is closed
+fn read_lines(stream: impl Read + Send + 'static, output: Arc<Mutex<CommandOutput>>) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            output.lock().unwrap().push(line);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output() {
+        let mut output = CommandOutput::default();
+        for i in 0..MAX_OUTPUT_LINES + 2 {
+            output.push(i.to_string());
+        }
+        assert_eq!(output.num_dropped, 2);
+        assert_eq!(output.lines().len(), MAX_OUTPUT_LINES);
+        assert_eq!(output.lines().next(), Some("2"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawn() {
+        let command = ["sh", "-c", "echo out; echo err >&2"].map(String::from);
+        let mut spawned = SpawnedCommand::spawn(&command, OutputTarget::Kept).unwrap();
+        let status = spawned.child.wait().unwrap();
+        assert!(status.success());
+        // The lines are read in the background
+        let output = spawned.output.clone().unwrap();
+        for _ in 0..100 {
+            if output.lock().unwrap().lines().len() == 2 {
+                break;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let mut lines = output
+            .lock()
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(lines, vec!["err", "out"]);
+        assert!(SpawnedCommand::spawn(&[], OutputTarget::Inherited).is_err());
+    }
+}
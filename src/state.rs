@@ -1,4 +1,21 @@
-use crate::flame::{FlameGraph, SearchPattern, StackIdentifier, ROOT_ID};
+use std::collections::HashSet;
+
+use crate::flame::{FlameGraph, FunctionCalls, SearchPattern, StackIdentifier, ROOT_ID};
+use crate::palette::{Palette, Separators};
+
+/// Most zoom targets kept in the zoom history
+const MAX_ZOOM_HISTORY: usize = 100;
+
+/// Most search patterns kept highlighted along with the current one
+pub const MAX_KEPT_SEARCHES: usize = 3;
+
+/// A search pattern kept highlighted in its own color while searching for another one
+#[derive(Debug, Clone)]
+pub struct KeptSearch {
+    pub pattern: SearchPattern,
+    /// Samples in the frames matching the pattern, not counting nested matches twice
+    pub coverage_count: u64,
+}
 
 #[derive(Debug, Clone)]
 pub struct ZoomState {
@@ -18,6 +35,8 @@ impl ZoomState {
 pub enum ViewKind {
     FlameGraph,
     Table,
+    CallTree,
+    FlameChart,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -33,6 +52,25 @@ impl TableState {
     }
 }
 
+/// Frames unfolded in the call tree view, by full name so that they stay unfolded across reloads
+#[derive(Default, Debug, Clone)]
+pub struct CallTreeState {
+    pub expanded: HashSet<String>,
+    pub offset: usize,
+}
+
+/// Breakdown of the callers and callees of a function opened from the table view
+#[derive(Debug, Clone)]
+pub struct DrillDownState {
+    /// Short name of the function
+    pub name: String,
+    /// Index of the selected caller or callee, callers first
+    pub selected: usize,
+    /// Callers and callees of the function, computed again whenever the flamegraph is replaced
+    /// since its stack ids change
+    pub calls: FunctionCalls,
+}
+
 #[derive(Debug, Clone)]
 pub struct FlameGraphState {
     pub selected: StackIdentifier,
@@ -41,9 +79,28 @@ pub struct FlameGraphState {
     pub frame_width: Option<u16>,
     pub zoom_stack: Vec<ZoomState>,
     pub search_pattern: Option<SearchPattern>,
+    /// Previous search patterns still highlighted, in their own colors
+    pub kept_searches: Vec<KeptSearch>,
     pub freeze: bool,
     pub view_kind: ViewKind,
     pub table_state: TableState,
+    pub call_tree: CallTreeState,
+    pub drill_down: Option<DrillDownState>,
+    pub palette: Palette,
+    pub separators: Separators,
+    /// Percent of the flamegraph width under which sibling frames are collapsed into a placeholder
+    pub min_frame_width: Option<f64>,
+    /// Start past the chain of frames with a single child at the top of the flamegraph
+    pub auto_skip: bool,
+    /// Frames zoomed on in order, the root standing for no zoom
+    pub zoom_history: Vec<StackIdentifier>,
+    /// Position of the current zoom in `zoom_history`
+    pub zoom_history_index: usize,
+    /// Frame pinned to compare the selected frame with
+    pub pinned: Option<StackIdentifier>,
+    /// Depth grows from left to right, siblings being stacked vertically with heights proportional
+    /// to their samples
+    pub sideways: bool,
 }
 
 impl Default for FlameGraphState {
@@ -55,9 +112,20 @@ impl Default for FlameGraphState {
             frame_width: None,
             zoom_stack: Vec::new(),
             search_pattern: None,
+            kept_searches: Vec::new(),
             freeze: false,
             view_kind: ViewKind::FlameGraph,
             table_state: TableState::default(),
+            call_tree: CallTreeState::default(),
+            drill_down: None,
+            palette: Palette::default(),
+            separators: Separators::default(),
+            min_frame_width: None,
+            auto_skip: false,
+            zoom_history: vec![ROOT_ID],
+            zoom_history_index: 0,
+            pinned: None,
+            sideways: false,
         }
     }
 }
@@ -77,9 +145,7 @@ impl FlameGraphState {
                 Some(prev_zoom) if !prev_zoom.descendants.contains(&zoom.stack_id) => {
                     self.zoom_stack.pop();
                 }
-                _ => {
-                    break
-                }
+                _ => break,
             }
         }
         self.zoom_stack.push(zoom);
@@ -89,7 +155,6 @@ impl FlameGraphState {
         self.zoom_stack.pop();
     }
 
-
     pub fn unset_zoom(&mut self) {
         self.zoom_stack.clear();
     }
@@ -98,6 +163,31 @@ impl FlameGraphState {
         self.zoom_stack.last()
     }
 
+    /// Add a zoom target to the history, dropping the ones that were gone back from
+    pub fn record_zoom(&mut self, stack_id: StackIdentifier) {
+        if self.zoom_history.get(self.zoom_history_index) == Some(&stack_id) {
+            return;
+        }
+        self.zoom_history.truncate(self.zoom_history_index + 1);
+        self.zoom_history.push(stack_id);
+        if self.zoom_history.len() > MAX_ZOOM_HISTORY {
+            self.zoom_history.remove(0);
+        }
+        self.zoom_history_index = self.zoom_history.len() - 1;
+    }
+
+    /// Move back (or forward) in the zoom history and return the zoom target there
+    pub fn step_zoom_history(&mut self, forward: bool) -> Option<StackIdentifier> {
+        let index = if forward {
+            self.zoom_history_index + 1
+        } else {
+            self.zoom_history_index.checked_sub(1)?
+        };
+        let stack_id = *self.zoom_history.get(index)?;
+        self.zoom_history_index = index;
+        Some(stack_id)
+    }
+
     pub fn set_search_pattern(&mut self, search_pattern: SearchPattern) {
         self.search_pattern = Some(search_pattern);
     }
@@ -106,14 +196,30 @@ impl FlameGraphState {
         self.search_pattern = None;
     }
 
+    /// Regexes of the kept search patterns
+    pub fn kept_search_res(&self) -> Vec<&regex::Regex> {
+        self.kept_searches
+            .iter()
+            .map(|kept| &kept.pattern.re)
+            .collect()
+    }
+
     pub fn toggle_freeze(&mut self) {
         self.freeze = !self.freeze;
     }
 
+    pub fn cycle_palette(&mut self) {
+        self.palette = self.palette.next();
+    }
+
+    pub fn cycle_separators(&mut self) {
+        self.separators = self.separators.next();
+    }
+
     pub fn toggle_view_kind(&mut self) {
         self.view_kind = match self.view_kind {
             ViewKind::FlameGraph => ViewKind::Table,
-            ViewKind::Table => ViewKind::FlameGraph,
+            ViewKind::Table | ViewKind::CallTree | ViewKind::FlameChart => ViewKind::FlameGraph,
         };
     }
 
@@ -127,11 +233,51 @@ impl FlameGraphState {
             }
         }
 
-        self.unset_zoom();
+        self.pinned = self.pinned.and_then(|pinned| {
+            if pinned == ROOT_ID {
+                Some(ROOT_ID)
+            } else {
+                Self::get_new_stack_id(&pinned, old, new)
+            }
+        });
+
+        // Keep the zooms on the frames that are still there, up to the first one that isn't. The
+        // rest of the zoom state is recomputed by the caller.
+        let mut zoom_stack = std::mem::take(&mut self.zoom_stack);
+        for zoom in zoom_stack.iter_mut() {
+            match Self::get_new_stack_id(&zoom.stack_id, old, new) {
+                Some(new_stack_id) => zoom.stack_id = new_stack_id,
+                None => break,
+            }
+            self.zoom_stack.push(zoom.clone());
+        }
+        // Same for the zoom history, dropping the frames that are gone
+        let mut zoom_history_index = 0;
+        let zoom_history = std::mem::take(&mut self.zoom_history);
+        for (i, stack_id) in zoom_history.iter().enumerate() {
+            let new_stack_id = if *stack_id == ROOT_ID {
+                Some(ROOT_ID)
+            } else {
+                Self::get_new_stack_id(stack_id, old, new)
+            };
+            if let Some(new_stack_id) = new_stack_id {
+                self.zoom_history.push(new_stack_id);
+            }
+            if i == self.zoom_history_index {
+                zoom_history_index = self.zoom_history.len().saturating_sub(1);
+            }
+        }
+        if self.zoom_history.is_empty() {
+            self.zoom_history.push(ROOT_ID);
+        }
+        self.zoom_history_index = zoom_history_index;
         // Preserve search pattern. If expensive, can move this to next flamegraph construction
         // thread and share SearchPattern via Arc but let's keep it simple for now.
+        for kept in self.kept_searches.iter_mut() {
+            kept.coverage_count = new.count_coverage(&kept.pattern.re);
+        }
         if let Some(p) = &self.search_pattern {
-            new.set_hits(p);
+            new.set_hits(p, &self.kept_search_res());
         }
     }
 
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+/// Counts of the folded stacks read so far from a stream, merging repeated stacks.
+#[derive(Debug, Clone, Default)]
+pub struct StreamAggregator {
+    counts: HashMap<String, u64>,
+    /// Whether stacks were added since the last call to [`StreamAggregator::take_folded`]
+    dirty: bool,
+    pub invalid_lines: u64,
+}
+
+impl StreamAggregator {
+    /// Add a "frame;frame count" line. Comments and blank lines are skipped, other lines without a
+    /// count are counted as invalid.
+    pub fn add_line(&mut self, line: &str) {
+        let line = line.trim_end_matches(['\n', '\r']);
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+        let parsed = line
+            .rsplit_once(' ')
+            .and_then(|(stack, count)| Some((stack, count.parse::<u64>().ok()?)))
+            .filter(|(stack, _)| !stack.is_empty());
+        let Some((stack, count)) = parsed else {
+            self.invalid_lines += 1;
+            return;
+        };
+        match self.counts.get_mut(stack) {
+            Some(total) => *total = total.saturating_add(count),
+            None => {
+                self.counts.insert(stack.to_string(), count);
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Add all the lines of `reader` until the end of the stream
+    pub fn read_lines(
+        aggregator: &std::sync::Mutex<Self>,
+        mut reader: impl BufRead,
+    ) -> std::io::Result<()> {
+        let mut buf = vec![];
+        loop {
+            buf.clear();
+            if reader.read_until(b'\n', &mut buf)? == 0 {
+                return Ok(());
+            }
+            aggregator
+                .lock()
+                .unwrap()
+                .add_line(&String::from_utf8_lossy(&buf));
+        }
+    }
+
+    /// Folded stacks of all the lines so far, if stacks were added since the previous call
+    pub fn take_folded(&mut self) -> Option<String> {
+        if !std::mem::take(&mut self.dirty) {
+            return None;
+        }
+        Some(
+            self.counts
+                .iter()
+                .map(|(stack, count)| format!("{} {}", stack, count))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_aggregator() {
+        let aggregator = std::sync::Mutex::new(StreamAggregator::default());
+        let input = "main;a 1\n# comment\nmain;b 2\r\n\nmain;a 3\nbroken\nmain;c";
+        StreamAggregator::read_lines(&aggregator, input.as_bytes()).unwrap();
+        let mut aggregator = aggregator.into_inner().unwrap();
+        assert_eq!(aggregator.invalid_lines, 2);
+        let folded = aggregator.take_folded().unwrap();
+        let mut lines = folded.lines().collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(lines, vec!["main;a 4", "main;b 2"]);
+        assert_eq!(aggregator.take_folded(), None);
+
+        aggregator.add_line("main;b 1\n");
+        assert_eq!(aggregator.take_folded().unwrap().lines().count(), 2);
+    }
+}
@@ -0,0 +1,127 @@
+use std::borrow::Cow;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Marks the place where a name was cut
+pub const ELLIPSIS: &str = "…";
+
+/// Which part of a name too long for its frame is left out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Truncation {
+    /// Keep the start, e.g. `pkg.module.Class…`
+    #[default]
+    End,
+    /// Keep the start and the end, e.g. `pkg.module…function_name`
+    Middle,
+}
+
+impl Truncation {
+    /// `text` cut to fit in `max_width` columns, with an ellipsis where it is cut
+    pub fn apply<'a>(&self, text: &'a str, max_width: usize) -> Cow<'a, str> {
+        match self {
+            Truncation::End => truncate_end(text, max_width),
+            Truncation::Middle => truncate_middle(text, max_width),
+        }
+    }
+}
+
+/// Columns taken by `text` on screen, wide characters such as CJK ones taking two
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+/// `text` cut to fit in `max_width` columns, ending with an ellipsis when it is cut. Graphemes are
+/// kept whole, so the result can be a column narrower than `max_width` when a wide character
+/// doesn't fit.
+pub fn truncate_end(text: &str, max_width: usize) -> Cow<'_, str> {
+    if display_width(text) <= max_width {
+        return Cow::Borrowed(text);
+    }
+    // No room for the ellipsis and something before it
+    if max_width < 2 {
+        return Cow::Borrowed(take_width(text, max_width));
+    }
+    let kept = take_width(text, max_width - display_width(ELLIPSIS));
+    Cow::Owned(format!("{}{}", kept, ELLIPSIS))
+}
+
+/// `text` cut to fit in `max_width` columns by leaving out its middle, the end getting the extra
+/// column if any since it is usually the most telling part of deep paths
+pub fn truncate_middle(text: &str, max_width: usize) -> Cow<'_, str> {
+    if display_width(text) <= max_width {
+        return Cow::Borrowed(text);
+    }
+    if max_width < 3 {
+        return truncate_end(text, max_width);
+    }
+    let available = max_width - display_width(ELLIPSIS);
+    let head = take_width(text, available / 2);
+    let tail = take_width_from_end(text, available - display_width(head));
+    Cow::Owned(format!("{}{}{}", head, ELLIPSIS, tail))
+}
+
+/// Longest suffix of whole graphemes of `text` fitting in `max_width` columns
+fn take_width_from_end(text: &str, max_width: usize) -> &str {
+    let mut width = 0;
+    let mut start = text.len();
+    for (index, grapheme) in text.grapheme_indices(true).rev() {
+        width += grapheme.width();
+        if width > max_width {
+            break;
+        }
+        start = index;
+    }
+    &text[start..]
+}
+
+/// Longest prefix of whole graphemes of `text` fitting in `max_width` columns
+fn take_width(text: &str, max_width: usize) -> &str {
+    let mut width = 0;
+    for (index, grapheme) in text.grapheme_indices(true) {
+        width += grapheme.width();
+        if width > max_width {
+            return &text[..index];
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_end() {
+        assert_eq!(truncate_end("main", 10), "main");
+        assert_eq!(truncate_end("main", 4), "main");
+        assert_eq!(truncate_end("main", 3), "ma…");
+        assert_eq!(truncate_end("main", 1), "m");
+        assert_eq!(truncate_end("main", 0), "");
+
+        // Two columns per character
+        assert_eq!(display_width("処理関数"), 8);
+        assert_eq!(truncate_end("処理関数", 8), "処理関数");
+        assert_eq!(truncate_end("処理関数", 7), "処理関…");
+        assert_eq!(truncate_end("処理関数", 6), "処理…");
+        assert_eq!(display_width(&truncate_end("処理関数", 6)), 5);
+
+        // Emoji and combining characters stay whole
+        assert_eq!(truncate_end("🔥🔥🔥", 4), "🔥…");
+        assert_eq!(truncate_end("cafe\u{301}st", 5), "cafe\u{301}…");
+    }
+
+    #[test]
+    fn test_truncate_middle() {
+        let name = "pkg.module.compute_total";
+        assert_eq!(truncate_middle(name, 30), name);
+        assert_eq!(truncate_middle(name, 12), "pkg.m…_total");
+        assert_eq!(truncate_middle(name, 2), "p…");
+        assert_eq!(truncate_middle("処理関数", 6), "処…数");
+        assert_eq!(truncate_middle("処理関数", 7), "処…関数");
+        assert_eq!(Truncation::End.apply(name, 12), "pkg.module.…");
+    }
+}
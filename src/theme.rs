@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::state::MAX_KEPT_SEARCHES;
+
+/// Built-in sets of interface colors, for the background of the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// Colors of the interface around the frames, as written in the config file: `#rrggbb` or names
+/// such as `yellow`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeColors {
+    pub selected_frame: Option<String>,
+    pub matched_frame: Option<String>,
+    pub match_text: Option<String>,
+    pub selected_row: Option<String>,
+    pub frame_text_dark: Option<String>,
+    pub frame_text_light: Option<String>,
+    pub accent: Option<String>,
+    pub hint: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Colors of the interface: selections, search matches, titles and messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub selected_frame: Color,
+    /// Background of the frames matching the search
+    pub matched_frame: Color,
+    /// Backgrounds of the frames matching the kept search patterns, in the order they were kept
+    pub kept_matched_frames: [Color; MAX_KEPT_SEARCHES],
+    /// Matched part of names, in the frames and the Top view
+    pub match_text: Color,
+    /// Background of the selected row of the tables and lists
+    pub selected_row: Color,
+    /// Text of light frames
+    pub frame_text_dark: Color,
+    /// Text of dark frames
+    pub frame_text_light: Color,
+    /// Titles, key bindings and the current view
+    pub accent: Color,
+    /// Secondary text such as placeholders
+    pub hint: Color,
+    pub error: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new(ThemeName::Dark)
+    }
+}
+
+impl Theme {
+    pub fn new(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Dark => Self {
+                selected_frame: Color::Rgb(250, 250, 250),
+                matched_frame: Color::Rgb(10, 35, 150),
+                kept_matched_frames: [
+                    Color::Rgb(140, 20, 110),
+                    Color::Rgb(20, 110, 50),
+                    Color::Rgb(150, 80, 0),
+                ],
+                match_text: Color::Rgb(225, 10, 10),
+                selected_row: Color::Rgb(65, 65, 65),
+                frame_text_dark: Color::Rgb(10, 10, 10),
+                frame_text_light: Color::Rgb(225, 225, 225),
+                accent: Color::Yellow,
+                hint: Color::DarkGray,
+                error: Color::Rgb(255, 90, 90),
+            },
+            ThemeName::Light => Self {
+                selected_frame: Color::Rgb(40, 40, 40),
+                matched_frame: Color::Rgb(110, 150, 250),
+                kept_matched_frames: [
+                    Color::Rgb(230, 130, 210),
+                    Color::Rgb(120, 210, 140),
+                    Color::Rgb(250, 180, 90),
+                ],
+                match_text: Color::Rgb(180, 0, 0),
+                selected_row: Color::Rgb(205, 205, 205),
+                frame_text_dark: Color::Rgb(10, 10, 10),
+                frame_text_light: Color::Rgb(245, 245, 245),
+                accent: Color::Rgb(20, 80, 190),
+                hint: Color::Rgb(120, 120, 120),
+                error: Color::Rgb(200, 0, 0),
+            },
+        }
+    }
+
+    /// The theme with the colors set in `colors` instead of its own
+    pub fn with_overrides(mut self, colors: &ThemeColors) -> Result<Self> {
+        let overrides = [
+            (&colors.selected_frame, &mut self.selected_frame),
+            (&colors.matched_frame, &mut self.matched_frame),
+            (&colors.match_text, &mut self.match_text),
+            (&colors.selected_row, &mut self.selected_row),
+            (&colors.frame_text_dark, &mut self.frame_text_dark),
+            (&colors.frame_text_light, &mut self.frame_text_light),
+            (&colors.accent, &mut self.accent),
+            (&colors.hint, &mut self.hint),
+            (&colors.error, &mut self.error),
+        ];
+        for (value, color) in overrides {
+            if let Some(value) = value {
+                *color = Color::from_str(value)
+                    .map_err(|_| anyhow!("Invalid color in the theme: {}", value))?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Text color readable on a frame of color `background`
+    pub fn frame_text(&self, background: Color) -> Color {
+        match background {
+            Color::Rgb(r, g, b) => {
+                let luma = 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64;
+                if luma > 128.0 {
+                    self.frame_text_dark
+                } else {
+                    self.frame_text_light
+                }
+            }
+            _ => Color::Black,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides() {
+        let colors = ThemeColors {
+            selected_row: Some("#102030".to_string()),
+            accent: Some("blue".to_string()),
+            ..Default::default()
+        };
+        let theme = Theme::new(ThemeName::Light)
+            .with_overrides(&colors)
+            .unwrap();
+        assert_eq!(theme.selected_row, Color::Rgb(16, 32, 48));
+        assert_eq!(theme.accent, Color::Blue);
+        assert_eq!(
+            theme.selected_frame,
+            Theme::new(ThemeName::Light).selected_frame
+        );
+
+        let colors = ThemeColors {
+            error: Some("not a color".to_string()),
+            ..Default::default()
+        };
+        assert!(Theme::default().with_overrides(&colors).is_err());
+    }
+}
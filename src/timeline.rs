@@ -0,0 +1,205 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::flame::FlameGraph;
+
+pub const DEFAULT_BUCKET_DURATION: Duration = Duration::from_secs(1);
+pub const DEFAULT_MAX_BUCKETS: usize = 600;
+/// Snapshots whose new samples are kept to tell the recent shares of a function
+pub const MAX_REFRESHES: usize = 30;
+
+/// Samples collected during one time bucket.
+#[derive(Debug, Clone, Default)]
+pub struct Bucket {
+    pub total: u64,
+    pub counts: HashMap<String, u64>,
+}
+
+/// Samples per function over time, built from successive snapshots of a flamegraph whose counts
+/// only grow (such as the one of a live session).
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    bucket_duration: Duration,
+    max_buckets: usize,
+    buckets: VecDeque<Bucket>,
+    /// Samples added by each of the last snapshots, oldest first
+    refreshes: VecDeque<Bucket>,
+    current_bucket_start: Option<Instant>,
+    last_totals: HashMap<String, u64>,
+    last_total: u64,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUCKET_DURATION, DEFAULT_MAX_BUCKETS)
+    }
+}
+
+impl Timeline {
+    pub fn new(bucket_duration: Duration, max_buckets: usize) -> Self {
+        Self {
+            bucket_duration,
+            max_buckets: max_buckets.max(1),
+            buckets: VecDeque::new(),
+            refreshes: VecDeque::new(),
+            current_bucket_start: None,
+            last_totals: HashMap::new(),
+            last_total: 0,
+        }
+    }
+
+    pub fn bucket_duration(&self) -> Duration {
+        self.bucket_duration
+    }
+
+    pub fn num_buckets(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Record the samples added since the previous snapshot into the bucket of `now`.
+    pub fn record(&mut self, flamegraph: &FlameGraph, now: Instant) {
+        self.advance(now);
+        let totals = flamegraph
+            .ordered_stacks
+            .entries
+            .iter()
+            .map(|entry| (entry.name.clone(), entry.count.total))
+            .collect::<HashMap<_, _>>();
+        let total = flamegraph.total_count();
+        // Counts can shrink when stacks are pruned, in which case nothing is added
+        let mut refresh = Bucket {
+            total: total.saturating_sub(self.last_total),
+            counts: HashMap::new(),
+        };
+        for (name, count) in totals.iter() {
+            let previous = self.last_totals.get(name).copied().unwrap_or(0);
+            let delta = count.saturating_sub(previous);
+            if delta > 0 {
+                refresh.counts.insert(name.clone(), delta);
+            }
+        }
+        let bucket = self.buckets.back_mut().unwrap();
+        bucket.total += refresh.total;
+        for (name, delta) in refresh.counts.iter() {
+            *bucket.counts.entry(name.clone()).or_default() += delta;
+        }
+        self.refreshes.push_back(refresh);
+        if self.refreshes.len() > MAX_REFRESHES {
+            self.refreshes.pop_front();
+        }
+        self.last_totals = totals;
+        self.last_total = total;
+    }
+
+    /// Take the next snapshot as counting from zero, after the samples were cleared
+    pub fn rebase(&mut self) {
+        self.last_totals.clear();
+        self.last_total = 0;
+    }
+
+    /// Start new (possibly empty) buckets until the current one contains `now`.
+    fn advance(&mut self, now: Instant) {
+        let start = *self.current_bucket_start.get_or_insert(now);
+        if self.buckets.is_empty() {
+            self.buckets.push_back(Bucket::default());
+        }
+        let elapsed_buckets = (now.saturating_duration_since(start).as_nanos()
+            / self.bucket_duration.as_nanos()) as u32;
+        if elapsed_buckets == 0 {
+            return;
+        }
+        for _ in 0..(elapsed_buckets as usize).min(self.max_buckets) {
+            self.buckets.push_back(Bucket::default());
+        }
+        while self.buckets.len() > self.max_buckets {
+            self.buckets.pop_front();
+        }
+        self.current_bucket_start = Some(start + self.bucket_duration * elapsed_buckets);
+    }
+
+    /// Samples of a function per bucket, oldest first.
+    pub fn function_counts(&self, name: &str) -> Vec<u64> {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.counts.get(name).copied().unwrap_or(0))
+            .collect()
+    }
+
+    /// Share of a function in the samples added by each of the last snapshots, oldest first.
+    /// Snapshots without new samples are left out.
+    pub fn function_shares(&self, name: &str) -> Vec<f64> {
+        self.refreshes
+            .iter()
+            .filter(|refresh| refresh.total > 0)
+            .map(|refresh| {
+                refresh.counts.get(name).copied().unwrap_or(0) as f64 / refresh.total as f64
+            })
+            .collect()
+    }
+
+    /// Samples of all functions per bucket, oldest first.
+    pub fn total_counts(&self) -> Vec<u64> {
+        self.buckets.iter().map(|bucket| bucket.total).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut timeline = Timeline::new(Duration::from_secs(1), 3);
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let snapshot = |content: &str| FlameGraph::from_string(content.to_string(), true);
+
+        timeline.record(&snapshot("main;a 2\nmain;b 1"), at(0));
+        timeline.record(&snapshot("main;a 3\nmain;b 1"), at(500));
+        // One empty bucket in between
+        timeline.record(&snapshot("main;a 3\nmain;b 5"), at(2100));
+        assert_eq!(timeline.function_counts("a"), vec![3, 0, 0]);
+        assert_eq!(timeline.function_counts("b"), vec![1, 0, 4]);
+        assert_eq!(timeline.function_counts("main"), vec![4, 0, 4]);
+        assert_eq!(timeline.total_counts(), vec![4, 0, 4]);
+
+        assert_eq!(timeline.function_shares("a"), vec![2.0 / 3.0, 1.0, 0.0]);
+        assert_eq!(timeline.function_shares("b"), vec![1.0 / 3.0, 0.0, 1.0]);
+        assert_eq!(timeline.function_shares("c"), vec![0.0, 0.0, 0.0]);
+
+        // Oldest buckets are dropped
+        timeline.record(&snapshot("main;a 4\nmain;b 5"), at(3000));
+        assert_eq!(timeline.num_buckets(), 3);
+        assert_eq!(timeline.function_counts("a"), vec![0, 0, 1]);
+
+        // After the samples are cleared, counts start from zero again
+        timeline.rebase();
+        timeline.record(&snapshot("main;a 2"), at(3500));
+        assert_eq!(timeline.function_counts("a"), vec![0, 0, 3]);
+    }
+
+    #[test]
+    fn test_bucket_boundaries() {
+        let mut timeline = Timeline::new(Duration::from_secs(1), 3);
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let snapshot = |count: u64| FlameGraph::from_string(format!("main;a {}", count), true);
+
+        timeline.record(&snapshot(1), at(0));
+        timeline.record(&snapshot(2), at(999));
+        assert_eq!(timeline.function_counts("a"), vec![2]);
+        timeline.record(&snapshot(3), at(1000));
+        assert_eq!(timeline.function_counts("a"), vec![2, 1]);
+        // Buckets stay aligned on the first one
+        timeline.record(&snapshot(4), at(2500));
+        timeline.record(&snapshot(5), at(2999));
+        assert_eq!(timeline.function_counts("a"), vec![2, 1, 2]);
+
+        // A gap longer than the timeline expires every bucket
+        timeline.record(&snapshot(6), at(10_000));
+        timeline.record(&snapshot(7), at(10_999));
+        assert_eq!(timeline.num_buckets(), 3);
+        assert_eq!(timeline.function_counts("a"), vec![0, 0, 2]);
+        assert_eq!(timeline.total_counts(), vec![0, 0, 2]);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::keys::{Action, Keymap};
+
+/// A step of the guided tour, done once one of its actions is run
+#[derive(Debug)]
+pub struct TutorialStep {
+    pub title: &'static str,
+    /// What to do, with `{keys}` standing for the keys of the actions
+    text: &'static str,
+    /// Actions completing the step
+    actions: &'static [Action],
+}
+
+pub const STEPS: &[TutorialStep] = &[
+    TutorialStep {
+        title: "Moving around",
+        text: "Each frame is a function, drawn below its caller and as wide as its share of the \
+            samples. Move the cursor between frames with {keys}.",
+        actions: &[
+            Action::MoveLeft,
+            Action::MoveDown,
+            Action::MoveUp,
+            Action::MoveRight,
+        ],
+    },
+    TutorialStep {
+        title: "Zooming in",
+        text: "Press {keys} to zoom in on the selected frame, so that it takes the whole width.",
+        actions: &[Action::Zoom],
+    },
+    TutorialStep {
+        title: "Zooming out",
+        text: "Press {keys} to zoom back out to the whole flamegraph.",
+        actions: &[Action::Unzoom],
+    },
+    TutorialStep {
+        title: "Searching",
+        text: "Press {keys}, type a regex such as \"json\" and press Enter to highlight the \
+            matching frames. The status bar tells their share of the samples.",
+        actions: &[Action::Search],
+    },
+    TutorialStep {
+        title: "Jumping between matches",
+        text: "Press {keys} to select the next matching frame.",
+        actions: &[Action::NextMatch],
+    },
+    TutorialStep {
+        title: "The Top view",
+        text: "Press {keys} to switch to the Top view, which ranks the functions by their samples \
+            wherever they are called from.",
+        actions: &[Action::SwitchView],
+    },
+    TutorialStep {
+        title: "Back to the flamegraph",
+        text: "Press {keys} again to go back to the flamegraph.",
+        actions: &[Action::SwitchView],
+    },
+    TutorialStep {
+        title: "Live profiling",
+        text: "Run \"flamelens --pid <pid>\" to watch a running Python program being sampled, or \
+            open the output of perf, py-spy, Chrome, Java and many more profilers. Press {keys} \
+            to end the tour.",
+        actions: &[Action::Quit],
+    },
+];
+
+/// Progress through the guided tour, driven by the actions run with the usual keys
+#[derive(Debug, Default)]
+pub struct Tutorial {
+    pub step: usize,
+}
+
+impl Tutorial {
+    pub fn current(&self) -> Option<&'static TutorialStep> {
+        STEPS.get(self.step)
+    }
+
+    /// Text of the current step with the keys bound to its actions
+    pub fn text(&self, keymap: &Keymap) -> Option<String> {
+        let step = self.current()?;
+        Some(step.text.replace("{keys}", &keymap.label(step.actions, "")))
+    }
+
+    /// Go to the next step if `action` completes the current one. Returns whether the tour is over.
+    pub fn advance(&mut self, action: Action) -> bool {
+        if let Some(step) = self.current() {
+            if step.actions.contains(&action) {
+                self.step += 1;
+            }
+        }
+        self.step >= STEPS.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demo::DEMO_PROFILE;
+    use crate::flame::FlameGraph;
+
+    #[test]
+    fn test_advance() {
+        let fg = FlameGraph::from_string(DEMO_PROFILE.to_string(), true);
+        assert!(fg.total_count() > 0);
+
+        let keymap = Keymap::default();
+        let mut tutorial = Tutorial::default();
+        assert!(tutorial.text(&keymap).unwrap().contains("with hjkl."));
+        assert!(!tutorial.advance(Action::Zoom));
+        assert_eq!(tutorial.step, 0);
+        assert!(!tutorial.advance(Action::MoveDown));
+        assert_eq!(tutorial.current().unwrap().title, "Zooming in");
+        for action in [
+            Action::Zoom,
+            Action::Unzoom,
+            Action::Search,
+            Action::NextMatch,
+            Action::SwitchView,
+            Action::SwitchView,
+        ] {
+            assert!(!tutorial.advance(action));
+        }
+        assert!(tutorial.advance(Action::Quit));
+        assert!(tutorial.current().is_none());
+    }
+}
@@ -1,31 +1,54 @@
 #[cfg(feature = "python")]
+use crate::app::{CompletionBanner, SettingsPopup, ThreadDump};
+#[cfg(feature = "python")]
 use crate::py_spy::SamplerStatus;
+#[cfg(feature = "python")]
+use crate::spawn::CommandOutput;
 use crate::{
-    app::{App, FlameGraphInput},
-    flame::{SortColumn, StackIdentifier, StackInfo},
+    animation::ZoomTransition,
+    app::{
+        App, FlameGraphInput, FrameLabels, MatchList, ProcessList, Prompt, ThreadList,
+        EXCLUDE_PREFIX,
+    },
+    budget::{DEGRADED_MIN_FRAME_WIDTH, DEGRADED_MIN_LABEL_WIDTH},
+    flame::{format_bytes, SortColumn, StackIdentifier, StackInfo, TopColumn, ROOT_ID},
+    flamechart::{format_duration, FlameChartView},
+    keys::{Action, Keymap},
+    palette::{nearest_ansi16, nearest_ansi256, shade, ColorDepth, Palette, Separators},
+    source::visible_lines,
     state::ViewKind,
+    text::{display_width, ELLIPSIS},
+    tutorial::{Tutorial, STEPS as TUTORIAL_STEPS},
+    view::{layout_children, ChildSpan},
+    watch::Trend,
 };
 use ratatui::{
     buffer::Buffer,
-    layout::{Alignment, Constraint, Direction, Layout, Offset, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Offset, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Span, Text},
     widgets::{
-        block::Position, Block, Borders, Paragraph, Row, StatefulWidget, Table, TableState, Widget,
-        Wrap,
+        block::Position, Block, Borders, Clear, Paragraph, Row, Sparkline, StatefulWidget, Table,
+        TableState, Widget, Wrap,
     },
     Frame,
 };
 use std::time::Duration;
-use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
-};
 
 const SEARCH_PREFIX: &str = "";
-const COLOR_SELECTED_STACK: Color = Color::Rgb(250, 250, 250);
-const COLOR_MATCHED_BACKGROUND: Color = Color::Rgb(10, 35, 150);
-const COLOR_TABLE_SELECTED_ROW: Color = Color::Rgb(65, 65, 65);
+/// Longest frame name shown in the zoom breadcrumbs
+const BREADCRUMB_MAX_WIDTH: usize = 40;
+const DETAILS_PANEL_HEIGHT: u16 = 12;
+const DETAILS_PANEL_SIDE_MIN_WIDTH: u16 = 160;
+const DETAILS_TREND_HEIGHT: u16 = 3;
+const SOURCE_PANEL_HEIGHT: u16 = 10;
+const TUTORIAL_WIDTH: u16 = 60;
+/// Columns taken by each level of the flamegraph when drawn sideways
+const SIDEWAYS_LEVEL_WIDTH: u16 = 24;
+/// Width of the optional columns of the Top view
+const TOP_COLUMN_WIDTH: u16 = 8;
+/// Below this width, the header is abbreviated and the Top view only shows the sorted count
+const NARROW_WIDTH: u16 = 40;
 
 #[derive(Debug, Clone, Default)]
 pub struct FlamelensWidgetState {
@@ -33,6 +56,14 @@ pub struct FlamelensWidgetState {
     frame_width: u16,
     render_time: Duration,
     cursor_position: Option<(u16, u16)>,
+    breadcrumbs: Vec<(Rect, StackIdentifier)>,
+}
+
+/// Frames narrower than `min_x_budget` columns are collapsed, except for the selected frame and
+/// its ancestors
+pub struct TinyFrames {
+    pub min_x_budget: f64,
+    pub keep: Vec<StackIdentifier>,
 }
 
 pub struct ZoomState {
@@ -60,18 +91,22 @@ impl StatefulWidget for FlamelensWidget<'_> {
 
 impl<'a> FlamelensWidget<'a> {
     fn render_all(self, area: Rect, buf: &mut Buffer, state: &mut FlamelensWidgetState) {
-        let view_kind_indicator = self.get_view_kind_indicator();
-        let version_indicator = self.get_version_indicator();
+        let narrow = area.width < NARROW_WIDTH;
+        let view_kind_indicator = self.get_view_kind_indicator(narrow);
+        let version_indicator = if narrow {
+            Line::default()
+        } else {
+            self.get_version_indicator()
+        };
 
         let header_text = Text::from(self.get_header_text(area.width));
         let header = Paragraph::new(header_text)
             .wrap(Wrap { trim: false })
             .alignment(Alignment::Center);
-        let indicator_width = std::cmp::max(view_kind_indicator.width(), version_indicator.width());
         let filename_width = area
             .width
-            .saturating_sub(indicator_width as u16)
-            .saturating_sub(indicator_width as u16);
+            .saturating_sub(view_kind_indicator.width() as u16)
+            .saturating_sub(version_indicator.width() as u16);
         let header_line_count_with_borders = header.line_count(filename_width) as u16 + 2;
 
         // Context such as search, selected stack, etc.
@@ -85,7 +120,11 @@ impl<'a> FlamelensWidget<'a> {
                         Block::new()
                             .borders(Borders::TOP)
                             .title(format!("{} ", title))
-                            .title_style(Style::default().add_modifier(Modifier::BOLD).yellow())
+                            .title_style(
+                                Style::default()
+                                    .add_modifier(Modifier::BOLD)
+                                    .fg(self.app.theme.accent),
+                            )
                             .title_position(Position::Top),
                     )
             })
@@ -137,17 +176,122 @@ impl<'a> FlamelensWidget<'a> {
         header.render(header_layout[1].offset(header_offset), buf);
         version_indicator.render(header_layout[2].offset(header_offset), buf);
 
-        // Main area for flamegraph / top view
-        let tic = std::time::Instant::now();
-        let main_area = layout[1];
-        if self.is_flamegraph_view() {
-            self.render_flamegraph(main_area, buf)
+        // Main area for flamegraph / top view, with the details panel below or on the side
+        let (main_area, details_area) = if self.app.show_details && self.is_flamegraph_view() {
+            let (direction, size) = if layout[1].width >= DETAILS_PANEL_SIDE_MIN_WIDTH {
+                (Direction::Horizontal, layout[1].width / 3)
+            } else {
+                (
+                    Direction::Vertical,
+                    DETAILS_PANEL_HEIGHT.min(layout[1].height / 2),
+                )
+            };
+            let details_layout = Layout::default()
+                .direction(direction)
+                .constraints([Constraint::Fill(1), Constraint::Length(size)])
+                .split(layout[1]);
+            let borders = if direction == Direction::Horizontal {
+                Borders::LEFT
+            } else {
+                Borders::TOP
+            };
+            (details_layout[0], Some((details_layout[1], borders)))
+        } else {
+            (layout[1], None)
+        };
+        // Source of the selected frame below the flamegraph
+        let (main_area, source_area) = if self.app.show_source && self.is_flamegraph_view() {
+            let [main_area, source_area] = Layout::vertical([
+                Constraint::Fill(1),
+                Constraint::Length(SOURCE_PANEL_HEIGHT.min(main_area.height / 2)),
+            ])
+            .areas(main_area);
+            (main_area, Some(source_area))
         } else {
-            self.render_table(main_area, buf);
-            false
+            (main_area, None)
         };
+        // Ruler and zoom breadcrumbs above the flamegraph
+        let show_ruler = self.app.show_ruler && !self.app.flamegraph_state().sideways;
+        let main_area = if self.is_flamegraph_view() && show_ruler {
+            let [ruler_area, main_area] =
+                Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(main_area);
+            self.render_ruler(ruler_area, buf);
+            main_area
+        } else {
+            main_area
+        };
+        let zoom_breadcrumbs = self.app.flamegraph_view.get_zoom_breadcrumbs();
+        let main_area = if self.is_flamegraph_view() && !zoom_breadcrumbs.is_empty() {
+            let [breadcrumbs_area, main_area] =
+                Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(main_area);
+            state.breadcrumbs =
+                self.render_zoom_breadcrumbs(&zoom_breadcrumbs, breadcrumbs_area, buf);
+            main_area
+        } else {
+            main_area
+        };
+        let tic = std::time::Instant::now();
+        match self.view_kind() {
+            ViewKind::FlameGraph | ViewKind::Table | ViewKind::CallTree
+                if self.app.flamegraph().total_count() == 0 =>
+            {
+                self.render_empty_state(main_area, buf);
+            }
+            ViewKind::FlameGraph => {
+                self.render_flamegraph(main_area, buf);
+            }
+            ViewKind::Table => self.render_table(main_area, buf),
+            ViewKind::CallTree => self.render_call_tree(main_area, buf),
+            ViewKind::FlameChart => self.render_flame_chart(main_area, buf),
+        }
         let flamegraph_render_time = tic.elapsed();
 
+        if let Some((details_area, borders)) = details_area {
+            self.render_details_panel(details_area, borders, buf);
+        }
+        if let Some(source_area) = source_area {
+            self.render_source_panel(source_area, buf);
+        }
+
+        // Overlays on top of the main area
+        if self.app.show_stats {
+            self.render_stats_overlay(main_area, buf);
+        }
+        #[cfg(feature = "python")]
+        if let Some(output) = self
+            .app
+            .spawned_command
+            .as_ref()
+            .and_then(|command| command.output.as_ref())
+            .filter(|_| self.app.show_command_output)
+        {
+            self.render_command_output(&output.lock().unwrap(), main_area, buf);
+        }
+        if let Some(match_list) = &self.app.match_list {
+            self.render_match_list(match_list, main_area, buf);
+        }
+        if let Some(thread_list) = &self.app.thread_list {
+            self.render_thread_list(thread_list, main_area, buf);
+        }
+        if let Some(process_list) = &self.app.process_list {
+            self.render_process_list(process_list, main_area, buf);
+        }
+        if let Some(tutorial) = &self.app.tutorial {
+            self.render_tutorial(tutorial, main_area, buf);
+        }
+        #[cfg(feature = "python")]
+        if let Some(popup) = &self.app.settings_popup {
+            self.render_settings_popup(popup, main_area, buf);
+        }
+        #[cfg(feature = "python")]
+        if let Some(thread_dump) = &self.app.thread_dump {
+            self.render_thread_dump(thread_dump, main_area, buf);
+        }
+        #[cfg(feature = "python")]
+        if let Some(banner) = &self.app.completion_banner {
+            self.render_completion_banner(banner, main_area, buf);
+        }
+
         // Context bars
         for (i, bar) in context_bars.iter().enumerate() {
             bar.render(layout[context_bar_index_start + i], buf);
@@ -157,39 +301,135 @@ impl<'a> FlamelensWidget<'a> {
         help_bar.render(layout[help_bar_index], buf);
 
         // Update widget state
-        state.frame_height = main_area.height;
-        state.frame_width = main_area.width;
+        // Sideways, the levels are columns and the frames share the height
+        if self.app.flamegraph_state().sideways {
+            state.frame_height = (main_area.width / SIDEWAYS_LEVEL_WIDTH).max(1);
+            state.frame_width = main_area.height;
+        } else {
+            state.frame_height = main_area.height;
+            state.frame_width = main_area.width;
+        }
         state.render_time = flamegraph_render_time;
         state.cursor_position = self.get_cursor_position(layout[help_bar_index - 1]);
     }
 
     fn get_help_tags(&self) -> HelpTags {
-        let mut help_tags = HelpTags::new();
+        let keymap = &self.app.keymap;
+        let mut help_tags = HelpTags::new(keymap, self.app.theme.accent);
         if self.is_flamegraph_view() {
-            help_tags.add("hjkl", "move cursor");
-            help_tags.add("f/b", "scroll");
-            help_tags.add("enter/esc", "zoom");
-            help_tags.add("/", "search");
-            help_tags.add("#", "search like cursor");
+            help_tags.add(
+                keymap.label(
+                    &[
+                        Action::MoveLeft,
+                        Action::MoveDown,
+                        Action::MoveUp,
+                        Action::MoveRight,
+                    ],
+                    "",
+                ),
+                "move cursor",
+            );
+            help_tags.add(
+                keymap.label(&[Action::ScrollDown, Action::ScrollUp], "/"),
+                "scroll",
+            );
+            help_tags.add(keymap.label(&[Action::Zoom, Action::Unzoom], "/"), "zoom");
+            if !self.app.flamegraph_state().zoom_stack.is_empty() {
+                help_tags.add(keymap.label(&[Action::ZoomParent], "/"), "zoom out");
+            }
+            help_tags.add(keymap.label(&[Action::Search], "/"), "search");
+            help_tags.add(
+                keymap.label(&[Action::SearchSelected], "/"),
+                "search like cursor",
+            );
+            help_tags.add(keymap.label(&[Action::CyclePalette], "/"), "palette");
+            help_tags.add(keymap.label(&[Action::ToggleDetails], "/"), "details");
+            help_tags.add(keymap.label(&[Action::ToggleWatch], "/"), "watch");
             if let Some(p) = &self.app.flamegraph_state().search_pattern {
                 if p.is_manual {
-                    help_tags.add("n/N", "next/prev search");
+                    help_tags.add(
+                        keymap.label(&[Action::NextMatch, Action::PreviousMatch], "/"),
+                        "next/prev search",
+                    );
                 }
             }
             #[cfg(feature = "python")]
             if let FlameGraphInput::Pid(_, _) = self.app.flamegraph_input {
+                let freeze = keymap.label(&[Action::ToggleFreeze], "/");
                 if self.app.flamegraph_state().freeze {
-                    help_tags.add("z", "unfreeze");
+                    help_tags.add(freeze, "unfreeze");
                 } else {
-                    help_tags.add("z", "freeze");
+                    help_tags.add(freeze, "freeze");
+                }
+                help_tags.add(keymap.label(&[Action::CycleWindow], "/"), "window");
+                help_tags.add(keymap.label(&[Action::SamplerSettings], "/"), "sampler");
+            }
+        } else if self.view_kind() == ViewKind::FlameChart {
+            help_tags.add(
+                keymap.label(&[Action::MoveLeft, Action::MoveRight], "/"),
+                "pan",
+            );
+            help_tags.add(
+                keymap.label(&[Action::MoveDown, Action::MoveUp], "/"),
+                "move cursor",
+            );
+            help_tags.add(
+                keymap.label(&[Action::ZoomIn, Action::ZoomOut], "/"),
+                "zoom time",
+            );
+            help_tags.add(
+                keymap.label(&[Action::Zoom, Action::Unzoom], "/"),
+                "zoom to frame/reset",
+            );
+            help_tags.add(keymap.label(&[Action::Search], "/"), "search");
+        } else if self.is_call_tree_view() {
+            help_tags.add(
+                keymap.label(&[Action::MoveDown, Action::MoveUp], "/"),
+                "move cursor",
+            );
+            help_tags.add(
+                keymap.label(&[Action::MoveRight, Action::MoveLeft], "/"),
+                "expand/collapse",
+            );
+            help_tags.add(keymap.label(&[Action::Zoom], "/"), "toggle");
+            help_tags.add(
+                keymap.label(&[Action::ScrollDown, Action::ScrollUp], "/"),
+                "scroll",
+            );
+            help_tags.add(keymap.label(&[Action::Search], "/"), "search");
+            if let Some(p) = &self.app.flamegraph_state().search_pattern {
+                if p.is_manual {
+                    help_tags.add(
+                        keymap.label(&[Action::NextMatch, Action::PreviousMatch], "/"),
+                        "next/prev search",
+                    );
                 }
             }
+        } else if self.app.flamegraph_state().drill_down.is_some() {
+            help_tags.add(
+                keymap.label(&[Action::MoveDown, Action::MoveUp], "/"),
+                "move cursor",
+            );
+            help_tags.add(keymap.label(&[Action::Zoom], "/"), "show in flamegraph");
+            help_tags.add(keymap.label(&[Action::Unzoom], "/"), "back");
         } else {
-            help_tags.add("j/k", "move cursor");
-            help_tags.add("f/b", "scroll");
-            help_tags.add("1", "sort by total");
-            help_tags.add("2", "sort by own");
-            help_tags.add("/", "filter");
+            help_tags.add(
+                keymap.label(&[Action::MoveDown, Action::MoveUp], "/"),
+                "move cursor",
+            );
+            help_tags.add(keymap.label(&[Action::Zoom], "/"), "callers/callees");
+            help_tags.add(
+                keymap.label(&[Action::ZoomSelected], "/"),
+                "zoom in flamegraph",
+            );
+            help_tags.add(
+                keymap.label(&[Action::ScrollDown, Action::ScrollUp], "/"),
+                "scroll",
+            );
+            help_tags.add(keymap.label(&[Action::SortByTotal], "/"), "sort by total");
+            help_tags.add(keymap.label(&[Action::SortByOwn], "/"), "sort by own");
+            help_tags.add(keymap.label(&[Action::SortByName], "/"), "sort by name");
+            help_tags.add(keymap.label(&[Action::Search], "/"), "filter");
         }
         help_tags
     }
@@ -204,11 +444,13 @@ impl<'a> FlamelensWidget<'a> {
                 zoom_stack: zoom.stack_id,
                 ancestors: self.app.flamegraph().get_ancestors(&zoom.stack_id),
             });
+        let degraded = self.app.render_budget.degraded;
         let re = self
             .app
             .flamegraph_state()
             .search_pattern
             .as_ref()
+            .filter(|_| !degraded)
             .and_then(|p| {
                 if p.is_manual {
                     Some(&p.re)
@@ -218,6 +460,40 @@ impl<'a> FlamelensWidget<'a> {
                     None
                 }
             });
+        let tiny_frames = self
+            .app
+            .flamegraph_state()
+            .min_frame_width
+            .map(|percent| match degraded {
+                true => percent.max(DEGRADED_MIN_FRAME_WIDTH),
+                false => percent,
+            })
+            .or(degraded.then_some(DEGRADED_MIN_FRAME_WIDTH))
+            .filter(|percent| *percent > 0.0)
+            .map(|percent| TinyFrames {
+                min_x_budget: area.width as f64 * percent / 100.0,
+                keep: self
+                    .app
+                    .flamegraph()
+                    .get_ancestors(&self.app.flamegraph_state().selected),
+            });
+        if self.app.flamegraph_state().sideways {
+            self.render_stacks_sideways(
+                self.app.flamegraph().root(),
+                buf,
+                area,
+                area.y,
+                area.height as f64,
+                &zoom_state,
+                &re,
+                false,
+            );
+            return false;
+        }
+        if let Some(transition) = self.app.zoom_animation.get_transition() {
+            self.render_zoom_transition(transition, area, buf, &zoom_state);
+            return false;
+        }
         let has_more_rows_to_render = self.render_stacks(
             self.app.flamegraph().root(),
             buf,
@@ -226,19 +502,1055 @@ impl<'a> FlamelensWidget<'a> {
             area.width as f64,
             area.bottom(),
             &zoom_state,
+            &tiny_frames,
             &re,
+            false,
         );
         has_more_rows_to_render
     }
 
+    /// Render ticks at every quarter of the width with the share and the number of samples of the
+    /// zoomed frame they stand for. The counts, then the labels are left out if they don't fit.
+    fn render_ruler(&self, area: Rect, buf: &mut Buffer) {
+        let width = area.width as usize;
+        if width < 2 {
+            return;
+        }
+        let zoom_total = self
+            .app
+            .flamegraph_state()
+            .get_zoom()
+            .and_then(|zoom| self.app.flamegraph().get_stack(&zoom.stack_id))
+            .unwrap_or_else(|| self.app.flamegraph().root())
+            .total_count;
+        let count_format = self.app.count_format();
+        let ticks = (0..=4)
+            .map(|i| (i * (width - 1) / 4, i as u64 * 25))
+            .collect::<Vec<_>>();
+        let layout_labels = |label_of: &dyn Fn(u64) -> String| {
+            let mut row = vec!['─'; width];
+            let mut end = 0;
+            for (i, (x, percent)) in ticks.iter().enumerate() {
+                if *x < end {
+                    return None;
+                }
+                row[*x] = match i {
+                    0 => '┌',
+                    4 => '┐',
+                    _ => '┬',
+                };
+                let label = label_of(*percent).chars().collect::<Vec<_>>();
+                // Labels follow their tick, except the last one which precedes it
+                let start = if i == 4 {
+                    x.checked_sub(label.len())?
+                } else {
+                    x + 1
+                };
+                if start < end || start + label.len() > width {
+                    return None;
+                }
+                row[start..start + label.len()].copy_from_slice(&label);
+                end = start + label.len();
+            }
+            Some(row.into_iter().collect::<String>())
+        };
+        let with_counts = |percent| match percent {
+            0 => " 0 ".to_string(),
+            _ => format!(
+                " {}% ({}) ",
+                percent,
+                count_format.format(zoom_total * percent / 100)
+            ),
+        };
+        let with_percents = |percent| match percent {
+            0 => " 0 ".to_string(),
+            _ => format!(" {}% ", percent),
+        };
+        let row = layout_labels(&with_counts)
+            .or_else(|| layout_labels(&with_percents))
+            .or_else(|| layout_labels(&|_| String::new()));
+        if let Some(row) = row {
+            buf.set_string(area.x, area.y, row, Style::default().dim());
+        }
+    }
+
+    /// Render the chain of zoomed frames, eliding the ones after the root if it doesn't fit, and
+    /// return the area of each segment.
+    fn render_zoom_breadcrumbs(
+        &self,
+        breadcrumbs: &[StackIdentifier],
+        area: Rect,
+        buf: &mut Buffer,
+    ) -> Vec<(Rect, StackIdentifier)> {
+        const SEPARATOR: &str = " › ";
+        let flamegraph = self.app.flamegraph();
+        let mut segments = breadcrumbs
+            .iter()
+            .filter_map(|id| {
+                let name = flamegraph.get_stack_short_name(id)?;
+                let name = self
+                    .app
+                    .truncation
+                    .apply(name, BREADCRUMB_MAX_WIDTH)
+                    .into_owned();
+                Some((name, *id))
+            })
+            .collect::<Vec<_>>();
+        let total_width = |segments: &[(String, StackIdentifier)]| {
+            segments
+                .iter()
+                .map(|(name, _)| display_width(name) + display_width(SEPARATOR))
+                .sum::<usize>()
+        };
+        let mut elided = false;
+        let ellipsis_width = display_width(ELLIPSIS) + display_width(SEPARATOR);
+        while segments.len() > 2
+            && 1 + total_width(&segments) + if elided { ellipsis_width } else { 0 }
+                > area.width as usize
+        {
+            segments.remove(1);
+            elided = true;
+        }
+
+        let mut areas = vec![];
+        let mut x = area.x + 1;
+        for (i, (name, id)) in segments.iter().enumerate() {
+            if i > 0 {
+                let separator = if i == 1 && elided {
+                    format!("{}{}{}", SEPARATOR, ELLIPSIS, SEPARATOR)
+                } else {
+                    SEPARATOR.to_string()
+                };
+                let (next_x, _) = buf.set_stringn(
+                    x,
+                    area.y,
+                    &separator,
+                    area.right().saturating_sub(x) as usize,
+                    Style::default(),
+                );
+                x = next_x;
+            }
+            let style = if i == segments.len() - 1 {
+                Style::default().bold()
+            } else {
+                Style::default().underlined()
+            };
+            let (next_x, _) = buf.set_stringn(
+                x,
+                area.y,
+                name,
+                area.right().saturating_sub(x) as usize,
+                style,
+            );
+            areas.push((Rect::new(x, area.y, next_x - x, 1), *id));
+            x = next_x;
+        }
+        areas
+    }
+
+    fn render_details_panel(&self, area: Rect, borders: Borders, buf: &mut Buffer) {
+        let flamegraph = self.app.flamegraph();
+        let Some(stack) = self.app.flamegraph_view.get_selected_stack() else {
+            return;
+        };
+        let short_name = flamegraph.get_stack_short_name_from_info(stack);
+        let name = flamegraph
+            .get_full_frame_name(short_name)
+            .unwrap_or(short_name);
+        let percent_of =
+            |count: u64, total: u64| format!("{:.2}%", count as f64 / total.max(1) as f64 * 100.0);
+        let mut total = format!(
+            "{}, {} of all",
+            self.app.count_format().format_with_unit(stack.total_count),
+            percent_of(stack.total_count, flamegraph.total_count())
+        );
+        if let Some(parent) = stack.parent.and_then(|id| flamegraph.get_stack(&id)) {
+            total += &format!(
+                ", {} of parent",
+                percent_of(stack.total_count, parent.total_count)
+            );
+        }
+        if let Some(zoom) = self.app.flamegraph_state().get_zoom() {
+            if let Some(zoom_stack) = flamegraph.get_stack(&zoom.stack_id) {
+                total += &format!(
+                    ", {} of zoom",
+                    percent_of(stack.total_count, zoom_stack.total_count)
+                );
+            }
+        }
+        let mut rows = vec![("Name", name.to_string())];
+        if let Some(mangled) = flamegraph.get_mangled_name(short_name) {
+            rows.push(("Mangled", mangled.to_string()));
+        }
+        if let Some((file, line)) = flamegraph.get_location(stack) {
+            let location = match line {
+                Some(line) => format!("{}:{}", file, line),
+                None => file.to_string(),
+            };
+            rows.push(("Location", location));
+        }
+        rows.push(("Total", total));
+        rows.push((
+            "Own",
+            format!(
+                "{}, {} of all",
+                self.app.count_format().format_with_unit(stack.self_count),
+                percent_of(stack.self_count, flamegraph.total_count())
+            ),
+        ));
+        for (key, values) in flamegraph.get_annotations(&stack.id).into_iter().flatten() {
+            let values = values
+                .iter()
+                .map(|(value, samples)| {
+                    format!("{} ({})", value, percent_of(*samples, stack.total_count))
+                })
+                .collect::<Vec<_>>();
+            rows.push((key.as_str(), values.join(", ")));
+        }
+
+        let mut lines = vec![];
+        let label_width = 10;
+        let label = |label: &str| {
+            Span::styled(
+                format!(" {:width$} ", label, width = label_width),
+                Style::default().bold(),
+            )
+        };
+        for (name, value) in rows {
+            lines.push(Line::from(vec![label(name), Span::from(value)]));
+        }
+        if let Some(calls) = self
+            .app
+            .flamegraph_view
+            .get_details_calls()
+            .filter(|_| stack.id != ROOT_ID)
+        {
+            lines.push(Line::from(vec![
+                label("Call sites"),
+                Span::from(calls.stack_ids.len().to_string()),
+            ]));
+            for (title, entries) in [("Callers", &calls.callers), ("Callees", &calls.callees)] {
+                if entries.is_empty() {
+                    continue;
+                }
+                lines.push(Line::from(label(title)));
+                for entry in entries.iter() {
+                    lines.push(Line::from(vec![
+                        Span::from(format!(
+                            "   {:>8} {:>7}  ",
+                            entry.count,
+                            percent_of(entry.count, calls.total)
+                        )),
+                        Span::from(entry.name.clone()),
+                    ]));
+                }
+            }
+        }
+
+        Clear.render(area, buf);
+        let block = Block::new().borders(borders).title("Details ").title_style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .fg(self.app.theme.accent),
+        );
+        let inner_area = block.inner(area);
+        block.render(area, buf);
+
+        // Samples of the function over the session, newest on the right
+        let trend = self
+            .app
+            .timeline
+            .as_ref()
+            .map(|timeline| timeline.lock().unwrap())
+            .filter(|_| stack.id != ROOT_ID && inner_area.height > DETAILS_TREND_HEIGHT + 2)
+            .map(|timeline| {
+                let width = inner_area.width.saturating_sub(2) as usize;
+                let counts = timeline.function_counts(short_name);
+                let counts = counts[counts.len().saturating_sub(width)..].to_vec();
+                (counts, timeline.bucket_duration())
+            });
+        let text_area = if let Some((counts, bucket_duration)) = trend {
+            let layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Fill(1),
+                    Constraint::Length(1),
+                    Constraint::Length(DETAILS_TREND_HEIGHT),
+                ])
+                .split(inner_area);
+            let max = counts.iter().max().copied().unwrap_or(0);
+            Line::from(vec![
+                label("Trend"),
+                Span::from(format!(
+                    "last {}s, max {} samples per {}s",
+                    counts.len() as u64 * bucket_duration.as_secs(),
+                    max,
+                    bucket_duration.as_secs()
+                )),
+            ])
+            .render(layout[1], buf);
+            Sparkline::default()
+                .data(&counts)
+                .max(max.max(1))
+                .style(Style::default().fg(self.app.theme.accent))
+                .render(layout[2].inner(Margin::new(1, 0)), buf);
+            layout[0]
+        } else {
+            inner_area
+        };
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .render(text_area, buf);
+    }
+
+    /// Lines of the source file of the selected frame around its line, which is highlighted
+    fn render_source_panel(&self, area: Rect, buf: &mut Buffer) {
+        let flamegraph = self.app.flamegraph();
+        let location = self
+            .app
+            .flamegraph_view
+            .get_selected_stack()
+            .and_then(|stack| flamegraph.get_location(stack));
+        let title = match location {
+            Some((file, Some(line))) => format!("Source: {}:{} ", file, line),
+            Some((file, None)) => format!("Source: {} ", file),
+            None => "Source ".to_string(),
+        };
+        Clear.render(area, buf);
+        let block = Block::new().borders(Borders::TOP).title(title).title_style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .fg(self.app.theme.accent),
+        );
+        let inner_area = block.inner(area);
+        block.render(area, buf);
+
+        let Some((file, line)) = location else {
+            Line::from("No source location for this frame".dim()).render(inner_area, buf);
+            return;
+        };
+        let lines = match self.app.source_cache.get(file) {
+            Some(Ok(lines)) => lines,
+            Some(Err(reason)) => {
+                Line::from(format!("Cannot show {}: {}", file, reason).dim())
+                    .render(inner_area, buf);
+                return;
+            }
+            None => return,
+        };
+        let (start, end) = visible_lines(lines.len(), line, inner_area.height as usize);
+        let number_width = end.to_string().len();
+        let text = (start..end)
+            .map(|i| {
+                let is_hot = line == Some(i + 1);
+                let number = Span::from(format!("{:>width$} ", i + 1, width = number_width));
+                let style = if is_hot {
+                    Style::default()
+                        .bg(self.app.theme.selected_row)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                Line::from(vec![
+                    if is_hot {
+                        number.fg(self.app.theme.accent).bold()
+                    } else {
+                        number.dim()
+                    },
+                    Span::from(lines[i].as_str()),
+                ])
+                .style(style)
+            })
+            .collect::<Vec<_>>();
+        Paragraph::new(text).render(inner_area, buf);
+    }
+
+    fn render_stats_overlay(&self, area: Rect, buf: &mut Buffer) {
+        let stats = self.app.flamegraph().stats();
+        let format_elapsed = |name: &str| {
+            self.app
+                .elapsed
+                .get(name)
+                .map(|d| format!("{:.2}ms", d.as_micros() as f64 / 1000.0))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let mut rows = vec![
+            ("Stacks", stats.num_stacks.to_string()),
+            ("Levels", stats.num_levels.to_string()),
+            ("Distinct symbols", stats.num_symbols.to_string()),
+            ("Input size", format_bytes(stats.num_input_bytes as u64)),
+            ("Memory estimate", format_bytes(stats.memory_bytes as u64)),
+            ("Parse time", format_elapsed("flamegraph")),
+        ];
+        if let FlameGraphInput::Pid(_, _) = self.app.flamegraph_input {
+            rows.push(("Last rebuild", format_elapsed("replacement")));
+            rows.push((
+                "Last update",
+                format!(
+                    "{:.1}s ago",
+                    self.app.flamegraph_view.updated_at.elapsed().as_secs_f64()
+                ),
+            ));
+        }
+        rows.push(("Render time", format_elapsed("render")));
+        #[cfg(feature = "python")]
+        if let Some(state) = self.app.sampler_state() {
+            let health = state.health;
+            rows.push((
+                "Sampling rate",
+                match health.achieved_rate() {
+                    Some(rate) => format!("{:.1}/s of {}/s", rate, health.requested_rate),
+                    None => "-".to_string(),
+                },
+            ));
+            rows.push((
+                "Late samples",
+                format!(
+                    "{} ({:.1}%)",
+                    health.late_intervals,
+                    100.0 * health.late_ratio()
+                ),
+            ));
+            if let Some(late) = state.late {
+                rows.push(("Behind by", format!("{:.1}s", late.as_secs_f64())));
+            }
+            rows.push(("Missed intervals", health.missed_intervals().to_string()));
+            rows.push(("Sampling errors", health.errors.to_string()));
+            if let Some(last_error) = health.last_error {
+                rows.push((
+                    "Last error",
+                    crate::text::truncate_end(&last_error, 60).into_owned(),
+                ));
+            }
+        }
+        let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+        let lines = rows
+            .into_iter()
+            .map(|(label, value)| {
+                Line::from(vec![
+                    Span::styled(
+                        format!(" {:width$}  ", label, width = label_width),
+                        Style::default().bold(),
+                    ),
+                    Span::from(value),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let width = lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 3;
+        let popup_area = centered_rect(width, lines.len() as u16 + 2, area);
+        Clear.render(popup_area, buf);
+        Paragraph::new(lines)
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .title(" Stats ")
+                    .title_style(
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .fg(self.app.theme.accent),
+                    ),
+            )
+            .render(popup_area, buf);
+    }
+
+    /// Frames matching the search with their counts, the selected one highlighted
+    fn render_match_list(&self, match_list: &MatchList, area: Rect, buf: &mut Buffer) {
+        let flamegraph = self.app.flamegraph();
+        let root_total_count = flamegraph.root().total_count;
+        let keymap = &self.app.keymap;
+        let hint = format!(
+            " {}: move, {}: jump, {}: close ",
+            keymap.label(&[Action::MoveDown, Action::MoveUp], "/"),
+            keymap.label(&[Action::Zoom], "/"),
+            keymap.label(&[Action::Unzoom], "/"),
+        );
+        let popup_area = centered_rect(
+            (area.width * 4 / 5).max(hint.chars().count() as u16 + 2),
+            match_list.ids.len() as u16 + 2,
+            area,
+        );
+        let num_rows = popup_area.height.saturating_sub(2) as usize;
+        // Keep the selected row in the middle of the list when scrolling
+        let offset = match_list
+            .selected
+            .saturating_sub(num_rows / 2)
+            .min(match_list.ids.len().saturating_sub(num_rows));
+        let count_width = match_list
+            .ids
+            .iter()
+            .filter_map(|id| flamegraph.get_stack(id))
+            .map(|stack| self.app.count_format().format(stack.total_count).len())
+            .max()
+            .unwrap_or(0);
+        let lines = match_list
+            .ids
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(num_rows)
+            .filter_map(|(i, id)| Some((i, flamegraph.get_stack(id)?)))
+            .map(|(i, stack)| {
+                let mut spans = vec![
+                    Span::from(format!(
+                        " {:>width$} {:>6.2}% ",
+                        self.app.count_format().format(stack.total_count),
+                        100.0 * stack.total_count as f64 / root_total_count.max(1) as f64,
+                        width = count_width,
+                    ))
+                    .dim(),
+                    Span::from(flamegraph.get_stack_short_name_from_info(stack).to_string()).bold(),
+                ];
+                if let Some(parent) = stack.parent.filter(|parent| *parent != ROOT_ID) {
+                    if let Some(name) = flamegraph.get_stack_short_name(&parent) {
+                        spans.push(format!(" ← {}", name).dim());
+                    }
+                }
+                let line = Line::from(spans);
+                if i == match_list.selected {
+                    line.style(Style::default().bg(self.app.theme.selected_row))
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>();
+        Clear.render(popup_area, buf);
+        Paragraph::new(lines)
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        " Matches {}/{} ",
+                        match_list.selected + 1,
+                        match_list.ids.len()
+                    ))
+                    .title_style(
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .fg(self.app.theme.accent),
+                    )
+                    .title_bottom(hint),
+            )
+            .render(popup_area, buf);
+    }
+
+    /// Threads with their samples among all the samples, the hidden ones unchecked
+    fn render_thread_list(&self, thread_list: &ThreadList, area: Rect, buf: &mut Buffer) {
+        // Counts are taken again on every frame as live samples keep coming
+        let threads = self.app.flamegraph_view.get_threads();
+        let root_total_count = self
+            .app
+            .flamegraph_view
+            .unfiltered_flamegraph()
+            .root()
+            .total_count;
+        let hidden_threads = &self.app.flamegraph_view.filters.hidden_threads;
+        let keymap = &self.app.keymap;
+        let hint = format!(
+            " {}: move, {}: show/hide, {}: close ",
+            keymap.label(&[Action::MoveDown, Action::MoveUp], "/"),
+            keymap.label(&[Action::Zoom], "/"),
+            keymap.label(&[Action::Unzoom], "/"),
+        );
+        let popup_area = centered_rect(
+            (area.width * 3 / 5).max(hint.chars().count() as u16 + 2),
+            thread_list.names.len() as u16 + 2,
+            area,
+        );
+        let num_rows = popup_area.height.saturating_sub(2) as usize;
+        let offset = thread_list
+            .selected
+            .saturating_sub(num_rows / 2)
+            .min(thread_list.names.len().saturating_sub(num_rows));
+        let counts = thread_list
+            .names
+            .iter()
+            .map(|name| {
+                threads
+                    .iter()
+                    .find(|(thread, _)| thread == name)
+                    .map_or(0, |(_, count)| *count)
+            })
+            .collect::<Vec<_>>();
+        let count_width = counts
+            .iter()
+            .map(|count| self.app.count_format().format(*count).len())
+            .max()
+            .unwrap_or(0);
+        let lines = thread_list
+            .names
+            .iter()
+            .zip(counts.iter())
+            .enumerate()
+            .skip(offset)
+            .take(num_rows)
+            .map(|(i, (name, count))| {
+                let hidden = hidden_threads.contains(name);
+                let line = Line::from(vec![
+                    Span::from(if hidden { " [ ] " } else { " [x] " }),
+                    Span::from(format!(
+                        "{:>width$} {:>6.2}% ",
+                        self.app.count_format().format(*count),
+                        100.0 * *count as f64 / root_total_count.max(1) as f64,
+                        width = count_width,
+                    ))
+                    .dim(),
+                    if hidden {
+                        Span::from(name.clone()).dim()
+                    } else {
+                        Span::from(name.clone()).bold()
+                    },
+                ]);
+                if i == thread_list.selected {
+                    line.style(Style::default().bg(self.app.theme.selected_row))
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>();
+        Clear.render(popup_area, buf);
+        Paragraph::new(lines)
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        " Threads {}/{} shown ",
+                        thread_list.names.len()
+                            - thread_list
+                                .names
+                                .iter()
+                                .filter(|name| hidden_threads.contains(name))
+                                .count(),
+                        thread_list.names.len()
+                    ))
+                    .title_style(
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .fg(self.app.theme.accent),
+                    )
+                    .title_bottom(hint),
+            )
+            .render(popup_area, buf);
+    }
+
+    /// Processes indented under their parent process with their pid, samples and command line, the
+    /// hidden ones unchecked and their child processes dimmed
+    fn render_process_list(&self, process_list: &ProcessList, area: Rect, buf: &mut Buffer) {
+        let root_total_count = self
+            .app
+            .flamegraph_view
+            .unfiltered_flamegraph()
+            .root()
+            .total_count;
+        let hidden_processes = &self.app.flamegraph_view.filters.hidden_processes;
+        let keymap = &self.app.keymap;
+        let hint = format!(
+            " {}: move, {}: show/hide, {}: zoom, {}: close ",
+            keymap.label(&[Action::MoveDown, Action::MoveUp], "/"),
+            keymap.label(&[Action::Zoom], "/"),
+            keymap.label(&[Action::ZoomSelected], "/"),
+            keymap.label(&[Action::Unzoom], "/"),
+        );
+        let popup_area = centered_rect(
+            (area.width * 4 / 5).max(hint.chars().count() as u16 + 2),
+            process_list.processes.len() as u16 + 2,
+            area,
+        );
+        let num_rows = popup_area.height.saturating_sub(2) as usize;
+        let offset = process_list
+            .selected
+            .saturating_sub(num_rows / 2)
+            .min(process_list.processes.len().saturating_sub(num_rows));
+        let count_width = process_list
+            .processes
+            .iter()
+            .map(|process| self.app.count_format().format(process.total_count).len())
+            .max()
+            .unwrap_or(0);
+        // Depth of the closest hidden process above each row, whose child processes are hidden too
+        let mut hidden_depth: Option<usize> = None;
+        let mut lines = vec![];
+        for (i, process) in process_list.processes.iter().enumerate() {
+            if hidden_depth.is_some_and(|depth| process.depth <= depth) {
+                hidden_depth = None;
+            }
+            let hidden = hidden_processes.contains(&process.name);
+            let under_hidden = hidden_depth.is_some();
+            if hidden && !under_hidden {
+                hidden_depth = Some(process.depth);
+            }
+            if i < offset || i >= offset + num_rows {
+                continue;
+            }
+            let mut spans = vec![
+                Span::from(if hidden { " [ ] " } else { " [x] " }),
+                Span::from(format!(
+                    "{:>width$} {:>6.2}% ",
+                    self.app.count_format().format(process.total_count),
+                    100.0 * process.total_count as f64 / root_total_count.max(1) as f64,
+                    width = count_width,
+                ))
+                .dim(),
+                Span::from("  ".repeat(process.depth)),
+                Span::from(process.pid.clone()).bold(),
+                Span::from(format!(" {}", process.cmdline)),
+            ];
+            if hidden || under_hidden {
+                spans.iter_mut().skip(2).for_each(|span| {
+                    span.style = span.style.remove_modifier(Modifier::BOLD).dim();
+                });
+            }
+            let line = Line::from(spans);
+            lines.push(if i == process_list.selected {
+                line.style(Style::default().bg(self.app.theme.selected_row))
+            } else {
+                line
+            });
+        }
+        Clear.render(popup_area, buf);
+        Paragraph::new(lines)
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .title(format!(" Processes ({}) ", process_list.processes.len()))
+                    .title_style(
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .fg(self.app.theme.accent),
+                    )
+                    .title_bottom(hint),
+            )
+            .render(popup_area, buf);
+    }
+
+    /// Box at the bottom right of the flamegraph telling what to do in the current step of the
+    /// guided tour
+    fn render_tutorial(&self, tutorial: &Tutorial, area: Rect, buf: &mut Buffer) {
+        let (Some(step), Some(text)) = (tutorial.current(), tutorial.text(&self.app.keymap)) else {
+            return;
+        };
+        let hint = format!(
+            " {}: end the tour ",
+            self.app.keymap.label(&[Action::Quit], "/")
+        );
+        let paragraph = Paragraph::new(text).wrap(Wrap { trim: true }).block(
+            Block::new()
+                .borders(Borders::ALL)
+                .padding(ratatui::widgets::Padding::horizontal(1))
+                .title(format!(
+                    " Tour {}/{}: {} ",
+                    tutorial.step + 1,
+                    TUTORIAL_STEPS.len(),
+                    step.title
+                ))
+                .title_style(
+                    Style::default()
+                        .add_modifier(Modifier::BOLD)
+                        .fg(self.app.theme.accent),
+                )
+                .title_bottom(hint),
+        );
+        let width = area.width.min(TUTORIAL_WIDTH);
+        let height = (paragraph.line_count(width) as u16).min(area.height);
+        let popup_area = Rect::new(
+            area.right().saturating_sub(width + 1),
+            area.bottom().saturating_sub(height),
+            width,
+            height,
+        )
+        .intersection(area);
+        Clear.render(popup_area, buf);
+        paragraph.render(popup_area, buf);
+    }
+
+    #[cfg(feature = "python")]
+    fn render_settings_popup(&self, popup: &SettingsPopup, area: Rect, buf: &mut Buffer) {
+        let settings = &popup.settings;
+        let checkbox = |checked: bool| if checked { "[x]" } else { "[ ]" }.to_string();
+        let rows = [
+            ("Sampling rate", format!("{}/s", settings.sampling_rate)),
+            ("GIL only", checkbox(settings.gil_only)),
+            ("Include idle", checkbox(settings.include_idle)),
+            ("Thread ids", checkbox(settings.include_thread_ids)),
+            ("Native frames", checkbox(settings.native)),
+        ];
+        let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+        let lines = rows
+            .into_iter()
+            .enumerate()
+            .map(|(i, (label, value))| {
+                let line = Line::from(vec![
+                    Span::styled(
+                        format!(" {:width$}  ", label, width = label_width),
+                        Style::default().bold(),
+                    ),
+                    Span::from(format!("{:<7}", value)),
+                ]);
+                if i == popup.selected {
+                    line.style(Style::default().bg(self.app.theme.selected_row))
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>();
+        let keymap = &self.app.keymap;
+        let hint = format!(
+            " {}: change, {}: apply, {}: cancel ",
+            keymap.label(&[Action::MoveLeft, Action::MoveRight], "/"),
+            keymap.label(&[Action::Zoom], "/"),
+            keymap.label(&[Action::Unzoom], "/"),
+        );
+        let width = lines
+            .iter()
+            .map(|l| l.width())
+            .chain([hint.chars().count()])
+            .max()
+            .unwrap_or(0) as u16
+            + 3;
+        let popup_area = centered_rect(width, lines.len() as u16 + 2, area);
+        Clear.render(popup_area, buf);
+        Paragraph::new(lines)
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .title(" Sampler settings ")
+                    .title_style(
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .fg(self.app.theme.accent),
+                    )
+                    .title_bottom(hint),
+            )
+            .render(popup_area, buf);
+    }
+
+    /// Last lines written by the profiled command, at the bottom of the main area
+    #[cfg(feature = "python")]
+    fn render_command_output(&self, output: &CommandOutput, area: Rect, buf: &mut Buffer) {
+        let height = (output.lines().len() as u16 + 2).clamp(3, (area.height / 3).max(3));
+        let output_area = Rect {
+            y: area.bottom().saturating_sub(height),
+            height: height.min(area.height),
+            ..area
+        };
+        let num_rows = output_area.height.saturating_sub(2) as usize;
+        let lines = if output.lines().len() == 0 {
+            vec![Line::from("No output yet").fg(self.app.theme.hint)]
+        } else {
+            let skip = output.lines().len().saturating_sub(num_rows);
+            output.lines().skip(skip).map(Line::from).collect()
+        };
+        let title = if output.num_dropped > 0 {
+            format!(
+                " Command output ({} older lines dropped) ",
+                output.num_dropped
+            )
+        } else {
+            " Command output ".to_string()
+        };
+        Clear.render(output_area, buf);
+        Paragraph::new(lines)
+            .block(
+                Block::new().borders(Borders::ALL).title(title).title_style(
+                    Style::default()
+                        .add_modifier(Modifier::BOLD)
+                        .fg(self.app.theme.accent),
+                ),
+            )
+            .render(output_area, buf);
+    }
+
+    /// Stacks of the threads dumped on demand, each thread header followed by its frames
+    #[cfg(feature = "python")]
+    fn render_thread_dump(&self, thread_dump: &ThreadDump, area: Rect, buf: &mut Buffer) {
+        let mut lines = vec![];
+        for (i, thread) in thread_dump.threads.iter().enumerate() {
+            if i > 0 {
+                lines.push(Line::from(""));
+            }
+            lines.push(
+                Line::from(thread.header.clone()).style(
+                    Style::default()
+                        .add_modifier(Modifier::BOLD)
+                        .fg(self.app.theme.accent),
+                ),
+            );
+            lines.extend(
+                thread
+                    .frames
+                    .iter()
+                    .map(|frame| Line::from(format!("    {}", frame))),
+            );
+        }
+        let keymap = &self.app.keymap;
+        let hint = format!(
+            " {}: scroll, {}: dump again, {}: close ",
+            keymap.label(&[Action::MoveDown, Action::MoveUp], "/"),
+            keymap.label(&[Action::DumpThreads], "/"),
+            keymap.label(&[Action::Unzoom], "/"),
+        );
+        let popup_area = centered_rect(
+            (area.width * 4 / 5).max(hint.chars().count() as u16 + 2),
+            (area.height * 4 / 5).min(lines.len() as u16 + 2),
+            area,
+        );
+        Clear.render(popup_area, buf);
+        Paragraph::new(lines)
+            .scroll((thread_dump.offset.min(u16::MAX as usize) as u16, 0))
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        " Thread dump ({} threads) ",
+                        thread_dump.threads.len()
+                    ))
+                    .title_style(
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .fg(self.app.theme.accent),
+                    )
+                    .title_bottom(hint),
+            )
+            .render(popup_area, buf);
+    }
+
+    /// Summary of the session once the sampling is complete, offering to write the samples
+    #[cfg(feature = "python")]
+    fn render_completion_banner(&self, banner: &CompletionBanner, area: Rect, buf: &mut Buffer) {
+        let keymap = &self.app.keymap;
+        let lines = vec![
+            Line::from(format!(
+                " {} samples over {} ",
+                self.app.count_format().format(banner.num_samples),
+                format_clock(banner.duration)
+            )),
+            Line::from(format!(
+                " The view is frozen, {} to unfreeze it ",
+                keymap.label(&[Action::ToggleFreeze], "/")
+            ))
+            .dim(),
+        ];
+        let hint = format!(
+            " {}: write the samples to a file, {}: close ",
+            keymap.label(&[Action::Save], "/"),
+            keymap.label(&[Action::Unzoom], "/"),
+        );
+        let width = lines
+            .iter()
+            .map(|l| l.width())
+            .chain([hint.chars().count()])
+            .max()
+            .unwrap_or(0) as u16
+            + 2;
+        let popup_area = centered_rect(width, lines.len() as u16 + 2, area);
+        Clear.render(popup_area, buf);
+        Paragraph::new(lines)
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .title(" Sampling complete ")
+                    .title_style(
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .fg(self.app.theme.accent),
+                    )
+                    .title_bottom(hint),
+            )
+            .render(popup_area, buf);
+    }
+
     fn render_table(&self, area: Rect, buf: &mut Buffer) {
-        let ordered_stacks_table = self.get_ordered_stacks_table();
+        if let Some(drill_down) = &self.app.flamegraph_state().drill_down {
+            self.render_drill_down(drill_down.selected, area, buf);
+            return;
+        }
+        let ordered_stacks_table = self.get_ordered_stacks_table(area.width < NARROW_WIDTH);
         let mut table_state = TableState::default()
             .with_selected(self.app.flamegraph_state().table_state.selected)
             .with_offset(self.app.flamegraph_state().table_state.offset);
         StatefulWidget::render(ordered_stacks_table, area, buf, &mut table_state);
     }
 
+    /// Unfolded frames as an indented outline with their total and own counts, the selected frame
+    /// highlighted
+    fn render_call_tree(&self, area: Rect, buf: &mut Buffer) {
+        let flamegraph = self.app.flamegraph();
+        let state = self.app.flamegraph_state();
+        let total_count = flamegraph.total_count();
+        let count_format = self.app.count_format();
+        let format_count = |count: u64| {
+            format!(
+                "{} ({:.2}%)  ",
+                count_format.format(count),
+                100.0 * count as f64 / total_count.max(1) as f64
+            )
+        };
+        let manual_re = state
+            .search_pattern
+            .as_ref()
+            .filter(|p| p.is_manual)
+            .map(|p| &p.re);
+        let stack_ids = self.app.flamegraph_view.get_call_tree_rows();
+        let mut rows = vec![];
+        let mut total_max_width: u16 = 0;
+        let mut own_max_width: u16 = 0;
+        let mut selected = None;
+        for (i, stack) in stack_ids
+            .iter()
+            .filter_map(|x| flamegraph.get_stack(x))
+            .enumerate()
+        {
+            if stack.id == state.selected {
+                selected = Some(i);
+            }
+            let total_formatted = Line::from(format_count(stack.total_count));
+            let own_formatted = Line::from(format_count(stack.self_count));
+            total_max_width = total_max_width.max(total_formatted.width() as u16);
+            own_max_width = own_max_width.max(own_formatted.width() as u16);
+            let marker = match (
+                stack.children.is_empty(),
+                self.app.flamegraph_view.is_expanded(stack),
+            ) {
+                (true, _) => "  ",
+                (false, true) => "▾ ",
+                (false, false) => "▸ ",
+            };
+            let mut name_spans = vec![Span::from(format!(
+                "{}{}",
+                " ".repeat(2 * stack.level),
+                marker
+            ))];
+            let name = flamegraph.get_stack_short_name_from_info(stack);
+            match manual_re {
+                Some(re) if re.is_match(name) => {
+                    name_spans.extend(self.get_highlighted_spans(name, re, Style::default()));
+                }
+                _ => name_spans.push(Span::from(name)),
+            }
+            rows.push(Row::new(vec![
+                total_formatted,
+                own_formatted,
+                Line::from(name_spans),
+            ]));
+        }
+        let header = Row::new(vec!["Total", "Own", "Call tree"]).style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::REVERSED),
+        );
+        let widths = [
+            Constraint::Max(total_max_width),
+            Constraint::Max(own_max_width),
+            Constraint::Fill(1),
+        ];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .row_highlight_style(Style::default().bg(self.app.theme.selected_row));
+        let mut table_state = TableState::default()
+            .with_selected(selected)
+            .with_offset(state.call_tree.offset);
+        StatefulWidget::render(table, area, buf, &mut table_state);
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn render_stacks(
         &self,
@@ -249,7 +1561,9 @@ impl<'a> FlamelensWidget<'a> {
         x_budget: f64,
         y_max: u16,
         zoom_state: &Option<ZoomState>,
+        tiny_frames: &Option<TinyFrames>,
         re: &Option<&regex::Regex>,
+        shaded: bool,
     ) -> bool {
         let after_level_offset = stack.level >= self.app.flamegraph_state().level_offset;
 
@@ -257,11 +1571,19 @@ impl<'a> FlamelensWidget<'a> {
         let effective_x_budget = x_budget as u16;
         if y < y_max && effective_x_budget > 0 {
             if after_level_offset {
-                let stack_color = self.get_stack_color(stack, zoom_state);
-                let text_color = FlamelensWidget::<'a>::get_text_color(stack_color);
-                let style = Style::default().fg(text_color).bg(stack_color);
-                let line = self.get_line_for_stack(stack, effective_x_budget, style, re);
-                buf.set_line(x, y, &line, effective_x_budget);
+                let stack_color = self.get_stack_color(stack, zoom_state, shaded);
+                let text_color = self.app.theme.frame_text(stack_color);
+                let mut style = Style::default().fg(text_color).bg(stack_color);
+                if self.app.flamegraph_state().pinned == Some(stack.id) {
+                    style = style.add_modifier(Modifier::UNDERLINED | Modifier::BOLD);
+                }
+                if self.app.render_budget.degraded && effective_x_budget < DEGRADED_MIN_LABEL_WIDTH
+                {
+                    buf.set_style(Rect::new(x, y, effective_x_budget, 1), style);
+                } else {
+                    let line = self.get_line_for_stack(stack, effective_x_budget, style, re);
+                    buf.set_line(x, y, &line, effective_x_budget);
+                }
             }
         } else {
             // Can skip rendering children if the stack is already not visible
@@ -284,49 +1606,241 @@ impl<'a> FlamelensWidget<'a> {
             .map(|idx| stack.children[idx]);
 
         let mut has_more_rows_to_render = false;
-        for child in &stack.children {
-            let child_stack = self.app.flamegraph().get_stack(child).unwrap();
-            let child_x_budget = if let Some(zoomed_child_id) = zoomed_child {
+        let shade_siblings = self.app.flamegraph_state().separators == Separators::Shade;
+        let child_y = y + if after_level_offset { 1 } else { 0 };
+        let children_visible = stack.level + 1 >= self.app.flamegraph_state().level_offset;
+        let x_budgets = stack
+            .children
+            .iter()
+            .map(|child| match zoomed_child {
                 // Zoomer takes all
-                if zoomed_child_id == *child {
-                    x_budget
-                } else {
-                    0.0
+                Some(zoomed_child_id) if zoomed_child_id == *child => x_budget,
+                Some(_) => 0.0,
+                None => {
+                    let child_stack = self.app.flamegraph().get_stack(child).unwrap();
+                    x_budget * (child_stack.total_count as f64 / stack.total_count.max(1) as f64)
+                }
+            })
+            .collect::<Vec<_>>();
+        let min_x_budget = tiny_frames.as_ref().map_or(0.0, |t| t.min_x_budget);
+        let keep = |i: usize| {
+            tiny_frames
+                .as_ref()
+                .is_some_and(|t| t.keep.contains(&stack.children[i]))
+        };
+        for span in layout_children(&x_budgets, min_x_budget, keep) {
+            match span {
+                ChildSpan::TinyRun { count, x_budget } => {
+                    if children_visible && child_y < y_max {
+                        self.render_tiny_frames_placeholder(
+                            buf,
+                            x + x_offset,
+                            child_y,
+                            x_budget as u16,
+                            count,
+                        );
+                    }
+                    x_offset += x_budget as u16;
+                }
+                ChildSpan::Frame { index, x_budget } => {
+                    let child_stack = self
+                        .app
+                        .flamegraph()
+                        .get_stack(&stack.children[index])
+                        .unwrap();
+                    has_more_rows_to_render |= self.render_stacks(
+                        child_stack,
+                        buf,
+                        x + x_offset,
+                        child_y,
+                        x_budget,
+                        y_max,
+                        zoom_state,
+                        tiny_frames,
+                        re,
+                        shade_siblings && index % 2 == 1,
+                    );
+                    x_offset += x_budget as u16;
+                }
+            }
+        }
+
+        has_more_rows_to_render
+    }
+
+    /// Render a frame and its children sideways: each level is a column of
+    /// [`SIDEWAYS_LEVEL_WIDTH`] cells and the frames of a level are stacked from the top, taking
+    /// rows in proportion to their samples. Frames shorter than a row are left out.
+    #[allow(clippy::too_many_arguments)]
+    fn render_stacks_sideways(
+        &self,
+        stack: &'a StackInfo,
+        buf: &mut Buffer,
+        area: Rect,
+        y: u16,
+        y_budget: f64,
+        zoom_state: &Option<ZoomState>,
+        re: &Option<&regex::Regex>,
+        shaded: bool,
+    ) {
+        let height = y_budget as u16;
+        if height == 0 || y >= area.bottom() {
+            return;
+        }
+        let level_offset = self.app.flamegraph_state().level_offset;
+        if let Some(column) = stack.level.checked_sub(level_offset) {
+            let x = area.x as usize + column * SIDEWAYS_LEVEL_WIDTH as usize;
+            if x >= area.right() as usize {
+                return;
+            }
+            let x = x as u16;
+            let width = SIDEWAYS_LEVEL_WIDTH.min(area.right() - x);
+            let height = height.min(area.bottom() - y);
+            let stack_color = self.get_stack_color(stack, zoom_state, shaded);
+            let text_color = self.app.theme.frame_text(stack_color);
+            let mut style = Style::default().fg(text_color).bg(stack_color);
+            if self.app.flamegraph_state().pinned == Some(stack.id) {
+                style = style.add_modifier(Modifier::UNDERLINED | Modifier::BOLD);
+            }
+            let line = self.get_line_for_stack(stack, width, style, re);
+            buf.set_line(x, y, &line, width);
+            if height > 1 {
+                // Keep the blank column telling the levels apart below the label
+                let fill_x = match self.app.flamegraph_state().separators {
+                    Separators::Line if width > 1 => x + 1,
+                    _ => x,
+                };
+                let fill_width = width - (fill_x - x);
+                buf.set_style(Rect::new(fill_x, y + 1, fill_width, height - 1), style);
+            }
+        }
+
+        let zoomed_child = stack.children.iter().find(|child_id| {
+            zoom_state.as_ref().is_some_and(|zoom_state| {
+                **child_id == zoom_state.zoom_stack || zoom_state.ancestors.contains(child_id)
+            })
+        });
+        let shade_siblings = self.app.flamegraph_state().separators == Separators::Shade;
+        let mut y_offset = 0;
+        for (i, child) in stack.children.iter().enumerate() {
+            let child_stack = self.app.flamegraph().get_stack(child).unwrap();
+            let child_y_budget = match zoomed_child {
+                Some(zoomed_child) if zoomed_child == child => y_budget,
+                Some(_) => 0.0,
+                None => {
+                    y_budget * (child_stack.total_count as f64 / stack.total_count.max(1) as f64)
                 }
-            } else {
-                x_budget * (child_stack.total_count as f64 / stack.total_count as f64)
             };
-            has_more_rows_to_render |= self.render_stacks(
+            self.render_stacks_sideways(
                 child_stack,
                 buf,
-                x + x_offset,
-                y + if after_level_offset { 1 } else { 0 },
-                child_x_budget,
-                y_max,
+                area,
+                y + y_offset,
+                child_y_budget,
                 zoom_state,
                 re,
+                shade_siblings && i % 2 == 1,
             );
-            x_offset += child_x_budget as u16;
+            y_offset += child_y_budget as u16;
         }
+    }
 
-        has_more_rows_to_render
+    /// Frames at their interpolated positions during a zoom transition
+    fn render_zoom_transition(
+        &self,
+        transition: &ZoomTransition,
+        area: Rect,
+        buf: &mut Buffer,
+        zoom_state: &Option<ZoomState>,
+    ) {
+        let flamegraph = self.app.flamegraph();
+        let level_offset = self.app.flamegraph_state().level_offset;
+        for (stack_id, x, width) in transition.get_frames(flamegraph) {
+            let Some(stack) = flamegraph.get_stack(&stack_id) else {
+                continue;
+            };
+            let Some(row) = stack.level.checked_sub(level_offset) else {
+                continue;
+            };
+            if row >= area.height as usize {
+                continue;
+            }
+            let start = (x.round() as u16).min(area.width);
+            let end = ((x + width).round() as u16).min(area.width);
+            if end <= start {
+                continue;
+            }
+            let stack_color = self.get_stack_color(stack, zoom_state, false);
+            let text_color = self.app.theme.frame_text(stack_color);
+            let style = Style::default().fg(text_color).bg(stack_color);
+            let line = self.get_line_for_stack(stack, end - start, style, &None);
+            buf.set_line(area.x + start, area.y + row as u16, &line, end - start);
+        }
+    }
+
+    /// Dimmed cells standing for `count` collapsed frames
+    fn render_tiny_frames_placeholder(
+        &self,
+        buf: &mut Buffer,
+        x: u16,
+        y: u16,
+        width: u16,
+        count: usize,
+    ) {
+        if width == 0 {
+            return;
+        }
+        let text = format!("{:width$}", format!("+{}", count), width = width as usize);
+        buf.set_stringn(
+            x,
+            y,
+            text,
+            width as usize,
+            Style::default()
+                .fg(self.app.theme.hint)
+                .add_modifier(Modifier::DIM),
+        );
     }
 
-    fn get_ordered_stacks_table(&self) -> Table {
+    /// Table of the functions with their total and own counts and the optional columns, or only
+    /// the count they are sorted by when `narrow`
+    fn get_ordered_stacks_table(&self, narrow: bool) -> Table<'_> {
+        let ordered = &self.app.flamegraph().ordered_stacks;
         let add_sorted_indicator = |label: &str, sort_column: SortColumn| {
-            let suffix = if sort_column == self.app.flamegraph().ordered_stacks.sorted_column {
-                " [▼]"
-            } else {
-                ""
+            let suffix = match (sort_column == ordered.sorted_column, ordered.ascending) {
+                (false, _) => "",
+                (true, false) => " [▼]",
+                (true, true) => " [▲]",
             };
             format!("{}{}", label, suffix)
         };
-        let header = Row::new(vec![
+        let sorted_column = ordered.sorted_column;
+        let top_columns: &[TopColumn] = if narrow { &[] } else { &self.app.top_columns };
+        let mut header = vec![
             add_sorted_indicator("Total", SortColumn::Total),
             add_sorted_indicator("Own", SortColumn::Own),
-            "Name".to_string(),
-        ])
-        .style(
+        ];
+        header.extend(top_columns.iter().map(|column| {
+            match column {
+                TopColumn::Stacks => "Stacks",
+                TopColumn::Depth => "Depth",
+            }
+            .to_string()
+        }));
+        header.push(add_sorted_indicator(
+            ordered.aggregation.label(),
+            SortColumn::Name,
+        ));
+        // Which of the total and own columns to drop
+        let hidden_column = match (narrow, sorted_column) {
+            (false, _) => None,
+            (true, SortColumn::Total | SortColumn::Name) => Some(1),
+            (true, SortColumn::Own) => Some(0),
+        };
+        if let Some(column) = hidden_column {
+            header.remove(column);
+        }
+        let header = Row::new(header).style(
             Style::default()
                 .add_modifier(Modifier::BOLD)
                 .add_modifier(Modifier::REVERSED),
@@ -337,46 +1851,292 @@ impl<'a> FlamelensWidget<'a> {
         let mut total_max_width: u16 = 0;
         let mut own_max_width: u16 = 0;
 
-        fn format_count(count: u64, total_count: u64) -> String {
+        let count_format = self.app.count_format();
+        let format_count = |count: u64, total_count: u64| {
             format!(
                 "{} ({:.2}%)  ",
-                count,
-                100.0 * count as f64 / total_count as f64
+                count_format.format(count),
+                100.0 * count as f64 / total_count.max(1) as f64
             )
-        }
+        };
 
         for entry in counts.iter().filter(|entry| entry.visible) {
             let total_formatted = Line::from(format_count(entry.count.total, total_count));
             let own_formatted = Line::from(format_count(entry.count.own, total_count));
             total_max_width = total_max_width.max(total_formatted.width() as u16);
             own_max_width = own_max_width.max(own_formatted.width() as u16);
-            let name_formatted = if let Some(p) = &self.app.flamegraph_state().search_pattern {
-                if p.is_manual {
-                    Line::from(self.get_highlighted_spans(
-                        entry.name.as_str(),
-                        &p.re,
-                        Style::default(),
-                    ))
-                } else {
-                    Line::from(entry.name.as_str())
+            let name = entry.name.as_str();
+            let name_formatted = match &self.app.flamegraph_state().search_pattern {
+                Some(p) if p.is_manual && p.re.is_match(name) => {
+                    Line::from(self.get_highlighted_spans(name, &p.re, Style::default()))
                 }
-            } else {
-                Line::from(entry.name.as_str())
+                _ => match self.get_kept_search_color(name) {
+                    Some(color) => Line::from(Span::styled(
+                        name,
+                        Style::default()
+                            .fg(self.app.theme.frame_text(color))
+                            .bg(color),
+                    )),
+                    None => Line::from(name),
+                },
             };
-            rows.push(Row::new(vec![
-                total_formatted,
-                own_formatted,
-                name_formatted,
-            ]));
+            let mut cells = vec![total_formatted, own_formatted];
+            cells.extend(top_columns.iter().map(|column| match column {
+                TopColumn::Stacks => Line::from(entry.count.frames.to_string()),
+                TopColumn::Depth => Line::from(format!("{:.1}", entry.count.average_depth())),
+            }));
+            cells.push(name_formatted);
+            if let Some(column) = hidden_column {
+                cells.remove(column);
+            }
+            rows.push(Row::new(cells));
         }
-        let widths = [
+        let mut widths = vec![
             Constraint::Max(total_max_width),
             Constraint::Max(own_max_width),
-            Constraint::Fill(1),
         ];
+        widths.extend(
+            top_columns
+                .iter()
+                .map(|_| Constraint::Length(TOP_COLUMN_WIDTH)),
+        );
+        widths.push(Constraint::Fill(1));
+        if let Some(column) = hidden_column {
+            widths.remove(column);
+        }
         Table::new(rows, widths)
             .header(header)
-            .row_highlight_style(Style::default().bg(COLOR_TABLE_SELECTED_ROW))
+            .row_highlight_style(Style::default().bg(self.app.theme.selected_row))
+    }
+
+    /// Explain why there is nothing to show instead of an empty flamegraph or table: samples yet
+    /// to come in live mode, filters leaving out every stack or an input without samples
+    fn render_empty_state(&self, area: Rect, buf: &mut Buffer) {
+        let keymap = &self.app.keymap;
+        let filters = &self.app.flamegraph_view.filters;
+        let lines = if self
+            .app
+            .flamegraph_view
+            .unfiltered_flamegraph()
+            .total_count()
+            > 0
+        {
+            let mut hints = vec![];
+            if filters.focus.is_some() {
+                hints.push(format!(
+                    "{} to show all stacks",
+                    keymap.label(&[Action::ToggleFocus], "/")
+                ));
+            }
+            if filters.excluded.is_some() {
+                hints.push(format!(
+                    "{}{} to bring back the excluded stacks",
+                    keymap.label(&[Action::Search], "/"),
+                    EXCLUDE_PREFIX
+                ));
+            }
+            if !filters.pruned.is_empty() {
+                hints.push(format!(
+                    "{} to undo the last hide",
+                    keymap.label(&[Action::UndoPrune], "/")
+                ));
+            }
+            if filters.lane.is_some() {
+                hints.push(format!(
+                    "{} to show the next lane",
+                    keymap.label(&[Action::CycleLane], "/")
+                ));
+            }
+            if !filters.hidden_threads.is_empty() {
+                hints.push(format!(
+                    "{} to show the hidden threads",
+                    keymap.label(&[Action::ListThreads], "/")
+                ));
+            }
+            if !filters.hidden_processes.is_empty() {
+                hints.push(format!(
+                    "{} to show the hidden processes",
+                    keymap.label(&[Action::ListProcesses], "/")
+                ));
+            }
+            let mut lines = vec![Line::from("No samples left once filtered").bold()];
+            lines.extend(hints.into_iter().map(|hint| Line::from(hint).dim()));
+            lines
+        } else if self.app.live {
+            vec![
+                Line::from("Waiting for the first samples...").bold(),
+                Line::from("The flamegraph shows up as soon as some are collected").dim(),
+            ]
+        } else {
+            vec![
+                Line::from("No samples").bold(),
+                Line::from("The input has no stacks with a count above zero").dim(),
+            ]
+        };
+        let popup_area = centered_rect(area.width, lines.len() as u16, area);
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .render(popup_area, buf);
+    }
+
+    fn render_flame_chart(&self, area: Rect, buf: &mut Buffer) {
+        let Some(view) = &self.app.flame_chart else {
+            return;
+        };
+        if area.height == 0 || area.width == 0 {
+            return;
+        }
+        buf.set_line(
+            area.x,
+            area.y,
+            &self.get_time_axis_line(view, area.width),
+            area.width,
+        );
+
+        let selected = view.get_selected_frame();
+        let rows = area.height - 1;
+        // Keep the selected frame in view
+        let level_offset = selected
+            .map(|f| (f.level + 1).saturating_sub(rows as usize))
+            .unwrap_or(0);
+        let re = self
+            .app
+            .flamegraph_state()
+            .search_pattern
+            .as_ref()
+            .filter(|p| p.is_manual)
+            .map(|p| &p.re);
+        let (start, end) = (view.window_start, view.window_end);
+        let scale = area.width as f64 / (end - start).max(1) as f64;
+        for row in 0..rows {
+            let level = level_offset + row as usize;
+            if level >= view.chart.num_levels() {
+                break;
+            }
+            // Frames narrower than a column may round to the same one; the first one wins
+            let mut next_x = 0;
+            for frame in view.chart.frames_in(level, start, end) {
+                let x0 = ((frame.start.max(start) - start) as f64 * scale).round() as u16;
+                let x1 = ((frame.end.min(end) - start) as f64 * scale).round() as u16;
+                let x0 = x0.max(next_x);
+                if x1 <= x0 {
+                    continue;
+                }
+                next_x = x1;
+                let color = if selected.is_some_and(|s| std::ptr::eq(s, frame)) {
+                    self.app.theme.selected_frame
+                } else if re.is_some_and(|re| re.is_match(&frame.name)) {
+                    self.app.theme.matched_frame
+                } else if let Some(color) = self.get_kept_search_color(&frame.name) {
+                    color
+                } else {
+                    let (r, g, b) = self
+                        .app
+                        .flamegraph_state()
+                        .palette
+                        .color(&frame.name, &frame.name);
+                    Color::Rgb(r, g, b)
+                };
+                let style = Style::default()
+                    .fg(self.app.theme.frame_text(color))
+                    .bg(color);
+                let width = (x1 - x0) as usize;
+                let text = if width > 1 {
+                    format!(" {:width$}", frame.name, width = width - 1)
+                } else {
+                    ".".to_string()
+                };
+                buf.set_stringn(area.x + x0, area.y + 1 + row, text, width, style);
+            }
+        }
+    }
+
+    /// Start and end of the time window relative to the start of the profile, with a marker at
+    /// the cursor in the middle.
+    fn get_time_axis_line(&self, view: &FlameChartView, width: u16) -> Line<'_> {
+        let origin = view.chart.start;
+        let left = format!("+{}", format_duration(view.window_start - origin));
+        let right = format!("+{}", format_duration(view.window_end - origin));
+        let center = width as usize / 2;
+        let left_width = left.chars().count();
+        let right_width = right.chars().count();
+        let mut text = format!("{:center$}", left, center = center);
+        if left_width < center && center + 1 + right_width < width as usize {
+            text.truncate(
+                text.char_indices()
+                    .nth(center)
+                    .map_or(text.len(), |(i, _)| i),
+            );
+            text.push('▼');
+            text += &format!("{:>w$}", right, w = width as usize - center - 1);
+        }
+        Line::from(text).style(Style::default().dim())
+    }
+
+    fn render_drill_down(&self, selected: usize, area: Rect, buf: &mut Buffer) {
+        let Some(calls) = self.app.flamegraph_view.get_drill_down_calls() else {
+            return;
+        };
+        let name = &self
+            .app
+            .flamegraph_state()
+            .drill_down
+            .as_ref()
+            .unwrap()
+            .name;
+        let count_header = self.app.count_format().label();
+        let header = Row::new(vec![count_header, "Share", "Name"]).style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::REVERSED),
+        );
+        let section_style = Style::default().add_modifier(Modifier::BOLD);
+        let mut rows = vec![];
+        let mut count_max_width = format!("{}  ", calls.total).len().max("Samples  ".len()) as u16;
+        let mut selected_row = None;
+        let mut index = 0;
+        for (title, entries) in [
+            ("Callers of", &calls.callers),
+            ("Callees of", &calls.callees),
+        ] {
+            rows.push(
+                Row::new(vec![
+                    Line::from(self.app.count_format().format(calls.total)),
+                    Line::from(""),
+                    Line::from(format!("{} {}", title, name)),
+                ])
+                .style(section_style),
+            );
+            if entries.is_empty() {
+                rows.push(Row::new(vec!["", "", "  (none)"]).style(Style::default().dim()));
+            }
+            for entry in entries.iter() {
+                if index == selected {
+                    selected_row = Some(rows.len());
+                }
+                index += 1;
+                let count_formatted = format!("{}  ", self.app.count_format().format(entry.count));
+                count_max_width = count_max_width.max(count_formatted.len() as u16);
+                rows.push(Row::new(vec![
+                    Line::from(count_formatted),
+                    Line::from(format!(
+                        "{:.2}%  ",
+                        100.0 * entry.count as f64 / calls.total.max(1) as f64
+                    )),
+                    Line::from(format!("  {}", entry.name)),
+                ]));
+            }
+        }
+        let widths = [
+            Constraint::Length(count_max_width),
+            Constraint::Length(9),
+            Constraint::Fill(1),
+        ];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .row_highlight_style(Style::default().bg(self.app.theme.selected_row));
+        let mut table_state = TableState::default().with_selected(selected_row);
+        StatefulWidget::render(table, area, buf, &mut table_state);
     }
 
     fn get_highlighted_spans<'b>(
@@ -395,7 +2155,7 @@ impl<'a> FlamelensWidget<'a> {
                 spans.push(Span::styled(
                     matched.as_str(),
                     style
-                        .fg(Color::Rgb(225, 10, 10))
+                        .fg(self.app.theme.match_text)
                         .add_modifier(Modifier::BOLD),
                 ));
             }
@@ -409,58 +2169,106 @@ impl<'a> FlamelensWidget<'a> {
         width: u16,
         style: Style,
         re: &Option<&regex::Regex>,
-    ) -> Line {
+    ) -> Line<'_> {
         let short_name = self.app.flamegraph().get_stack_short_name_from_info(stack);
 
-        // Empty space separator at the beginning
-        let mut spans = vec![Span::styled(if width > 1 { " " } else { "." }, style)];
+        // Empty space separator at the beginning, left blank to tell siblings apart if enabled
+        let separator_style = match self.app.flamegraph_state().separators {
+            Separators::Line if width > 1 => Style::default(),
+            _ => style,
+        };
+        let mut spans = vec![Span::styled(
+            if width > 1 { " " } else { "." },
+            separator_style,
+        )];
 
-        // Stack name with highlighted search terms if needed
-        let short_name_spans = if let (true, &Some(re)) = (stack.hit, re) {
-            self.get_highlighted_spans(short_name, re, style)
-        } else {
-            vec![Span::styled(short_name, style)]
+        // Stack name cut to the width of the frame, with highlighted search terms if needed
+        let name = self
+            .app
+            .truncation
+            .apply(short_name, (width as usize).saturating_sub(1));
+        let name_width = display_width(&name);
+        let short_name_spans = match (stack.hit, re) {
+            (true, &Some(re)) => self
+                .get_highlighted_spans(&name, re, style)
+                .into_iter()
+                .map(|span| Span::styled(span.content.into_owned(), span.style))
+                .collect(),
+            _ => vec![Span::styled(name, style)],
         };
         spans.extend(short_name_spans);
 
+        // Count or share at the end of the frame if enabled and there is room for it
+        let count = match self.app.flamegraph().ordered_stacks.sorted_column {
+            SortColumn::Total | SortColumn::Name => stack.total_count,
+            SortColumn::Own => stack.self_count,
+        };
+        let count = match self.app.frame_labels {
+            FrameLabels::None => String::new(),
+            FrameLabels::Count => format!(" {} ", self.app.count_format().format(count)),
+            FrameLabels::Percent => {
+                let base_count = self
+                    .get_parent_total_count(stack)
+                    .unwrap_or_else(|| self.app.flamegraph().total_count());
+                format!(" {:.1}% ", 100.0 * count as f64 / base_count.max(1) as f64)
+            }
+        };
+        let count_fits = (display_width(short_name) + count.len() + 1) as u16 <= width;
+        let count_length = if count_fits { count.len() } else { 0 };
+
         // Padding to fill the rest of the width
         let pad_length = width
-            .saturating_sub(short_name.len() as u16)
+            .saturating_sub(name_width as u16)
+            .saturating_sub(count_length as u16)
             .saturating_sub(1) as usize;
         spans.push(Span::styled(
             format!("{:width$}", "", width = pad_length),
             style,
         ));
+        if count_length > 0 {
+            spans.push(Span::styled(count, style.add_modifier(Modifier::DIM)));
+        }
 
         Line::from(spans)
     }
 
-    fn get_stack_color(&self, stack: &StackInfo, zoom_state: &Option<ZoomState>) -> Color {
+    fn get_stack_color(
+        &self,
+        stack: &StackInfo,
+        zoom_state: &Option<ZoomState>,
+        shaded: bool,
+    ) -> Color {
         if self.app.flamegraph_state().selected == stack.id {
-            return COLOR_SELECTED_STACK;
-        }
-        // Roughly based on flamegraph.pl
-        fn hash_name(name: &str) -> f64 {
-            let mut hasher = DefaultHasher::new();
-            name.hash(&mut hasher);
-            hasher.finish() as f64 / u64::MAX as f64
-        }
-        let full_name = self.app.flamegraph().get_stack_full_name_from_info(stack);
-        let v1 = hash_name(full_name);
-        let v2 = hash_name(full_name);
-        let mut r;
-        let mut g;
-        let mut b;
-        if !stack.hit {
-            r = 205 + (50.0 * v2) as u8;
-            g = (230.0 * v1) as u8;
-            b = (55.0 * v2) as u8;
-        } else if let Color::Rgb(r_, g_, b_) = COLOR_MATCHED_BACKGROUND {
-            r = r_;
-            g = g_;
-            b = b_;
+            return self.app.theme.selected_frame;
+        }
+        let kept_search_color = || {
+            self.get_kept_search_color(self.app.flamegraph().get_stack_short_name_from_info(stack))
+        };
+        let (mut r, mut g, mut b) = if stack.hit {
+            match self.app.theme.matched_frame {
+                Color::Rgb(r, g, b) => (r, g, b),
+                // Named colors can't be shaded
+                color => return color,
+            }
+        } else if let Some(Color::Rgb(r, g, b)) = kept_search_color() {
+            (r, g, b)
+        } else if let Some(diff) = &self.app.flamegraph_view.diff {
+            diff.color(
+                &stack.id,
+                self.app.flamegraph_state().palette == Palette::Colorblind,
+            )
+        } else if let Some(sources) = &self.app.flamegraph_view.sources {
+            sources.color(self.app.flamegraph().get_stack_full_name_from_info(stack))
         } else {
-            unreachable!();
+            let short_name = self.app.flamegraph().get_stack_short_name_from_info(stack);
+            let full_name = self.app.flamegraph().get_stack_full_name_from_info(stack);
+            self.app
+                .flamegraph_state()
+                .palette
+                .color(short_name, full_name)
+        };
+        if shaded {
+            (r, g, b) = shade((r, g, b));
         }
         if let Some(zoom_state) = zoom_state {
             if zoom_state.ancestors.contains(&stack.id) {
@@ -472,58 +2280,100 @@ impl<'a> FlamelensWidget<'a> {
         Color::Rgb(r, g, b)
     }
 
-    fn get_text_color(c: Color) -> Color {
-        match c {
-            Color::Rgb(r, g, b) => {
-                let luma = 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64;
-                if luma > 128.0 {
-                    Color::Rgb(10, 10, 10)
-                } else {
-                    Color::Rgb(225, 225, 225)
-                }
+    /// Background of the first kept search pattern matching the name of a frame
+    fn get_kept_search_color(&self, short_name: &str) -> Option<Color> {
+        let flamegraph = self.app.flamegraph();
+        self.app
+            .flamegraph_state()
+            .kept_searches
+            .iter()
+            .zip(self.app.theme.kept_matched_frames)
+            .find(|(kept, _)| flamegraph.is_name_match(&kept.pattern.re, short_name))
+            .map(|(_, color)| color)
+    }
+
+    /// Kept search patterns in their colors, with the share of the samples they cover
+    fn get_kept_searches_line(&self, root_total_count: u64) -> Line<'_> {
+        let mut spans = vec![];
+        for (kept, color) in self
+            .app
+            .flamegraph_state()
+            .kept_searches
+            .iter()
+            .zip(self.app.theme.kept_matched_frames)
+        {
+            if !spans.is_empty() {
+                spans.push(Span::from("  "));
             }
-            _ => Color::Black,
+            spans.push(Span::styled(
+                format!("\"{}\"", kept.pattern.pattern),
+                Style::default()
+                    .fg(self.app.theme.frame_text(color))
+                    .bg(color),
+            ));
+            spans.push(Span::from(format!(
+                " {:.2}%",
+                100.0 * kept.coverage_count as f64 / root_total_count.max(1) as f64
+            )));
         }
+        Line::from(spans)
     }
 
-    fn get_view_kind_indicator(&self) -> Line {
+    /// Names of the views with the current one highlighted, or only the current one abbreviated when
+    /// `narrow`
+    fn get_view_kind_indicator(&self, narrow: bool) -> Line<'_> {
+        let current_view_kind = self.app.flamegraph_state().view_kind;
+        if narrow {
+            let label = match current_view_kind {
+                ViewKind::FlameGraph => "Flame",
+                ViewKind::Table => "Top",
+                ViewKind::CallTree => "Tree",
+                ViewKind::FlameChart => "Chart",
+            };
+            return Line::from(Span::styled(
+                format!(" [{}] ", label),
+                Style::default().bold().fg(self.app.theme.accent),
+            ));
+        }
         let mut header_bottom_title_spans = vec![Span::from(" ")];
 
-        fn _get_view_kind_span(
-            label: &str,
-            view_kind: ViewKind,
-            current_view_kind: ViewKind,
-        ) -> Span {
+        let accent = self.app.theme.accent;
+        let get_view_kind_span = |label: &str, view_kind: ViewKind| {
             let (content, style) = if view_kind == current_view_kind {
-                (format!("[{}]", label), Style::default().bold().yellow())
+                (format!("[{}]", label), Style::default().bold().fg(accent))
             } else {
                 (label.to_string(), Style::default().bold())
             };
             Span::styled(content, style)
-        }
+        };
 
-        header_bottom_title_spans.push(_get_view_kind_span(
-            "Flamegraph",
-            ViewKind::FlameGraph,
-            self.app.flamegraph_state().view_kind,
-        ));
+        header_bottom_title_spans.push(get_view_kind_span("Flamegraph", ViewKind::FlameGraph));
         header_bottom_title_spans.push(Span::from(" | "));
-        header_bottom_title_spans.push(_get_view_kind_span(
-            "Top",
-            ViewKind::Table,
-            self.app.flamegraph_state().view_kind,
-        ));
+        header_bottom_title_spans.push(get_view_kind_span("Top", ViewKind::Table));
+        header_bottom_title_spans.push(Span::from(" | "));
+        header_bottom_title_spans.push(get_view_kind_span("Tree", ViewKind::CallTree));
+        if self.app.flame_chart.is_some() {
+            header_bottom_title_spans.push(Span::from(" | "));
+            header_bottom_title_spans.push(get_view_kind_span("Chart", ViewKind::FlameChart));
+        }
         header_bottom_title_spans.push(Span::from(" "));
         Line::from(header_bottom_title_spans)
     }
 
-    fn get_version_indicator(&self) -> Line {
+    fn get_version_indicator(&self) -> Line<'_> {
         Line::from(format!("flamelens v{}", env!("CARGO_PKG_VERSION")))
             .style(Style::default().bold())
     }
 
-    fn get_header_text(&self, _width: u16) -> Line {
-        let header_text = match &self.app.flamegraph_input {
+    fn get_header_text(&self, width: u16) -> Line<'_> {
+        let mut header_text = match &self.app.flamegraph_input {
+            // Only the file name on narrow terminals
+            FlameGraphInput::File(path) if width < NARROW_WIDTH => {
+                std::path::Path::new(path).file_name().map_or_else(
+                    || path.to_string(),
+                    |name| name.to_string_lossy().to_string(),
+                )
+            }
             FlameGraphInput::File(path) => path.to_string(),
             FlameGraphInput::Pid(pid, info) => {
                 let mut out = format!("Process: {}", pid);
@@ -533,16 +2383,29 @@ impl<'a> FlamelensWidget<'a> {
                 #[cfg(feature = "python")]
                 if let Some(state) = &self.app.sampler_state() {
                     out += match state.status {
+                        SamplerStatus::Waiting => " [Waiting for the process]".to_string(),
                         SamplerStatus::Running => " [Running]".to_string(),
                         _ => " [Exited]".to_string(),
                     }
                     .as_str();
-                    let duration = state.total_sampled_duration;
-                    let seconds = duration.as_secs() % 60;
-                    let minutes = (duration.as_secs() / 60) % 60;
-                    let hours = (duration.as_secs() / 60) / 60;
-                    out += format!(" [Duration: {:0>2}:{:0>2}:{:0>2}]", hours, minutes, seconds)
-                        .as_str();
+                    out += format!(
+                        " [Duration: {}]",
+                        format_clock(state.total_sampled_duration)
+                    )
+                    .as_str();
+                    if let (SamplerStatus::Running, Some(record_duration)) =
+                        (&state.status, state.record_duration)
+                    {
+                        let remaining =
+                            record_duration.saturating_sub(state.total_sampled_duration);
+                        out += format!(" [Remaining: {}]", format_clock(remaining)).as_str();
+                    }
+                    if let Some(window) = state.window {
+                        out += format!(" [Window: last {}s]", window.as_secs()).as_str();
+                    }
+                    if state.pruned_samples > 0 {
+                        out += format!(" [Pruned: {} samples]", state.pruned_samples).as_str();
+                    }
                     if self.app.flamegraph_state().freeze {
                         out += " [Frozen; press 'z' again to unfreeze]";
                     }
@@ -550,10 +2413,41 @@ impl<'a> FlamelensWidget<'a> {
                 out
             }
         };
+        if let Some(baseline) = &self.app.flamegraph_view.baseline {
+            header_text += format!(" [Diff vs {}]", baseline.name).as_str();
+        }
+        if let Some(sources) = &self.app.flamegraph_view.sources {
+            header_text += format!(" [Merged {} profiles]", sources.names.len()).as_str();
+        }
+        let filters = &self.app.flamegraph_view.filters;
+        if let Some(focus) = &filters.focus {
+            header_text += format!(" [Focus: {}]", focus).as_str();
+        }
+        if filters.merge_lines {
+            header_text += " [Lines merged]";
+        }
+        if let Some(lane) = &filters.lane {
+            header_text += format!(" [Lane: {}]", lane.label()).as_str();
+        }
+        if let Some(key) = &filters.group_by {
+            header_text += format!(" [Group by: {}]", key).as_str();
+        }
+        if let Some(pattern) = &filters.excluded {
+            header_text += format!(" [Excluded: {}]", pattern).as_str();
+        }
+        if !filters.pruned.is_empty() {
+            header_text += format!(" [Pruned: {}]", filters.pruned.join(", ")).as_str();
+        }
+        if self.app.render_budget.degraded {
+            header_text += " [Degraded: slow render]";
+        }
+        if let Some(register) = self.app.macros.recording() {
+            header_text += format!(" [Recording @{}]", register).as_str();
+        }
         Line::from(header_text).style(Style::default().bold())
     }
 
-    fn get_status_text(&self, width: u16) -> Vec<(&'static str, Line)> {
+    fn get_status_text(&self, width: u16) -> Vec<(&'static str, Line<'_>)> {
         if self.app.input_buffer.is_some() {
             self.get_status_text_buffer()
         } else {
@@ -561,10 +2455,35 @@ impl<'a> FlamelensWidget<'a> {
         }
     }
 
-    fn get_status_text_buffer(&self) -> Vec<(&'static str, Line)> {
+    fn get_status_text_buffer(&self) -> Vec<(&'static str, Line<'_>)> {
         let input_buffer = self.app.input_buffer.as_ref().unwrap();
         let status_text = format!("{}{}", SEARCH_PREFIX, input_buffer.buffer);
-        vec![("Search", Line::from(status_text))]
+        let title = match input_buffer.prompt {
+            Prompt::Search if input_buffer.buffer.value().starts_with(EXCLUDE_PREFIX) => "Exclude",
+            Prompt::Search => "Search",
+            Prompt::Goto => "Go to",
+        };
+        let mut line = Line::from(status_text);
+        if input_buffer.prompt == Prompt::Search {
+            let modes = match self.app.search_modes.label() {
+                Some(label) => format!("  [{}]", label),
+                None => String::new(),
+            };
+            if let Some(error) = &input_buffer.error {
+                line.push_span(Span::styled(
+                    format!("  Invalid regex: {}", error),
+                    Style::default().fg(self.app.theme.error),
+                ));
+            }
+            line.push_span(Span::styled(
+                format!(
+                    "{}  (alt-c: ignore case, alt-r: literal, up/down: history)",
+                    modes
+                ),
+                Style::default().dim(),
+            ));
+        }
+        vec![(title, line)]
     }
 
     fn get_cursor_position(&self, status_area: Rect) -> Option<(u16, u16)> {
@@ -576,22 +2495,29 @@ impl<'a> FlamelensWidget<'a> {
         })
     }
 
-    fn get_status_text_command(&self, width: u16) -> Vec<(&'static str, Line)> {
+    fn get_status_text_command(&self, width: u16) -> Vec<(&'static str, Line<'_>)> {
         let stack = self
             .app
             .flamegraph()
             .get_stack(&self.app.flamegraph_state().selected);
         let root_total_count = self.app.flamegraph().root().total_count;
         let mut lines = vec![];
+        if !self.app.watch_list.is_empty() {
+            lines.push(("Watch", self.get_watch_line(root_total_count)));
+        }
+        if !self.app.flamegraph_state().kept_searches.is_empty() {
+            lines.push(("Kept", self.get_kept_searches_line(root_total_count)));
+        }
         match stack {
             Some(stack) => {
-                let zoom_total_count = self.app.flamegraph_state().get_zoom().as_ref().map(|zoom| {
-                    self.app
-                        .flamegraph()
-                        .get_stack(&zoom.stack_id)
-                        .unwrap()
-                        .total_count
-                });
+                let zoom_total_count =
+                    self.app.flamegraph_state().get_zoom().as_ref().map(|zoom| {
+                        self.app
+                            .flamegraph()
+                            .get_stack(&zoom.stack_id)
+                            .unwrap()
+                            .total_count
+                    });
                 if let Some(p) = &self.app.flamegraph_state().search_pattern {
                     if let (true, Some(hit_coverage_count)) =
                         (p.is_manual, self.app.flamegraph().hit_coverage_count())
@@ -599,9 +2525,11 @@ impl<'a> FlamelensWidget<'a> {
                         let mut match_text = format!(
                             "\"{}\" {}",
                             p.re.as_str(),
-                            FlamelensWidget::get_count_stats_str(
+                            self.get_count_stats_str(
                                 None,
                                 hit_coverage_count,
+                                None,
+                                None,
                                 root_total_count,
                                 zoom_total_count,
                             )
@@ -615,23 +2543,61 @@ impl<'a> FlamelensWidget<'a> {
                         {
                             match_text += " (no match; showing all)";
                         }
+                        if let (true, Some((position, num_hits))) = (
+                            self.is_flamegraph_view() || self.is_call_tree_view(),
+                            self.app.flamegraph_view.get_search_position(),
+                        ) {
+                            match_text += &match position {
+                                Some(position) => {
+                                    format!(" match {}/{} frames", position + 1, num_hits)
+                                }
+                                None => format!(" {} frames", num_hits),
+                            };
+                        }
                         let match_text = format!("{:width$}", match_text, width = width as usize,);
                         lines.push(("Match", Line::from(match_text)));
                     }
                 }
-                let selected_text = format!(
+                let mut selected_text = format!(
                     "{} {}",
                     self.app.flamegraph().get_stack_short_name_from_info(stack),
-                    FlamelensWidget::get_count_stats_str(
+                    self.get_count_stats_str(
                         None,
                         stack.total_count,
+                        Some(stack.self_count),
+                        self.get_parent_total_count(stack),
                         root_total_count,
                         zoom_total_count
                     ),
                 );
+                if let Some(baseline) = &self.app.flamegraph_view.baseline {
+                    let full_name = self.app.flamegraph().get_stack_full_name_from_info(stack);
+                    selected_text += &format!(
+                        " [baseline: {:.2}% of all]",
+                        baseline.share(full_name) * 100.0
+                    );
+                }
+                if let Some(sources) = &self.app.flamegraph_view.sources {
+                    let full_name = self.app.flamegraph().get_stack_full_name_from_info(stack);
+                    selected_text += &format!(
+                        " [in {}/{} profiles]",
+                        sources.count(full_name),
+                        sources.names.len()
+                    );
+                }
                 let status_text = format!("{:width$}", selected_text, width = width as usize,);
-                if self.is_flamegraph_view() {
+                if self.is_flamegraph_view() || self.is_call_tree_view() {
                     lines.push(("Selected", Line::from(status_text)));
+                    if let Some(compare_line) = self.get_compare_line() {
+                        lines.push(("Compare", compare_line));
+                    }
+                    if let Some(trend_line) = self.get_trend_line() {
+                        lines.push(("Trend", trend_line));
+                    }
+                } else if let (ViewKind::FlameChart, Some(view)) =
+                    (self.view_kind(), &self.app.flame_chart)
+                {
+                    lines.push(("Selected", Line::from(self.get_flame_chart_status(view))));
                 }
                 if self.app.debug {
                     let elapsed_str = format!(
@@ -654,21 +2620,155 @@ impl<'a> FlamelensWidget<'a> {
         }
     }
 
+    fn get_watch_line(&self, total_count: u64) -> Line<'_> {
+        let mut spans = vec![];
+        for (i, entry) in self.app.watch_list.entries().iter().enumerate() {
+            if i > 0 {
+                spans.push(Span::from(" │ "));
+            }
+            let trend = entry.trend();
+            let color = match trend {
+                Trend::Up => Color::Red,
+                Trend::Down => Color::Green,
+                Trend::Steady => Color::Reset,
+            };
+            spans.push(Span::styled(trend.arrow(), Style::default().fg(color)));
+            spans.push(Span::from(format!(
+                " {} {:.2}% ({}/{}), own {:.2}%",
+                entry.pattern.pattern,
+                entry.share * 100.0,
+                entry.count,
+                total_count,
+                entry.own_share * 100.0
+            )));
+        }
+        Line::from(spans)
+    }
+
+    /// How the selected frame measures up against the pinned frame
+    fn get_compare_line(&self) -> Option<Line<'_>> {
+        let flamegraph = self.app.flamegraph();
+        let pinned = self.app.flamegraph_state().pinned?;
+        let pinned_name = flamegraph.get_stack_short_name(&pinned)?;
+        let Some(comparison) = self.app.flamegraph_view.get_pin_comparison() else {
+            return Some(Line::from(format!(
+                "{} is pinned; select another frame to compare",
+                pinned_name
+            )));
+        };
+        let count = |id| {
+            self.app
+                .count_format()
+                .format(flamegraph.get_stack(id).map_or(0, |s| s.total_count))
+        };
+        Some(Line::from(format!(
+            "{} is {:.2}x {} ({} vs {}) │ common ancestor: {} │ depth {:+}",
+            flamegraph.get_stack_short_name(&comparison.selected)?,
+            comparison.ratio,
+            pinned_name,
+            count(&comparison.selected),
+            count(&comparison.pinned),
+            flamegraph.get_stack_short_name(&comparison.common_ancestor)?,
+            comparison.depth_difference,
+        )))
+    }
+
+    /// Share of the pinned function, or else the selected one, in the samples of each of the last
+    /// refreshes of the live flamegraph
+    fn get_trend_line(&self) -> Option<Line<'_>> {
+        let timeline = self.app.timeline.as_ref()?.lock().unwrap();
+        let stack_id = self
+            .app
+            .flamegraph_state()
+            .pinned
+            .unwrap_or(self.app.flamegraph_state().selected);
+        if stack_id == ROOT_ID {
+            return None;
+        }
+        let short_name = self.app.flamegraph().get_stack_short_name(&stack_id)?;
+        let shares = timeline.function_shares(short_name);
+        let (&last, _) = shares.split_last()?;
+        let max = shares.iter().fold(0.0f64, |max, share| max.max(*share));
+        let min = shares.iter().fold(1.0f64, |min, share| min.min(*share));
+        Some(Line::from(vec![
+            Span::from(format!("{} ", short_name)),
+            Span::styled(
+                sparkline_text(&shares, max),
+                Style::default().fg(self.app.theme.accent),
+            ),
+            Span::from(format!(
+                " {:.2}% of the last refresh, {:.2}% to {:.2}% over the last {} refreshes",
+                last * 100.0,
+                min * 100.0,
+                max * 100.0,
+                shares.len()
+            )),
+        ]))
+    }
+
+    fn get_flame_chart_status(&self, view: &FlameChartView) -> String {
+        let window = format!(
+            "[window: {} of {}]",
+            format_duration(view.window_duration()),
+            format_duration(view.chart.duration())
+        );
+        match view.get_selected_frame() {
+            Some(frame) => format!(
+                "{} [{} at +{}] {}",
+                frame.name,
+                format_duration(frame.duration()),
+                format_duration(frame.start - view.chart.start),
+                window
+            ),
+            None => format!("No frame at the cursor {}", window),
+        }
+    }
+
+    /// Samples of the parent of the frame, when shares are shown relative to the parent
+    fn get_parent_total_count(&self, stack: &StackInfo) -> Option<u64> {
+        stack
+            .parent
+            .filter(|_| self.app.show_parent_share)
+            .and_then(|parent| self.app.flamegraph().get_stack(&parent))
+            .map(|parent| parent.total_count)
+    }
+
+    /// Count and shares of a frame, in its parent if `parent_total_count` is given, followed when
+    /// zoomed by the share of its own count (`own`) in the zoomed frame
     fn get_count_stats_str(
+        &self,
         name: Option<&str>,
         count: u64,
+        own: Option<u64>,
+        parent_total_count: Option<u64>,
         total_count: u64,
         zoomed_total_count: Option<u64>,
     ) -> String {
+        let count_format = self.app.count_format();
         format!(
-            "[{}{} samples, {:.2}% of all{}]",
+            "[{}{}, {:.2}% of all{}{}]",
             name.map(|n| format!("{}: ", n)).unwrap_or_default(),
-            count,
-            (count as f64 / total_count as f64) * 100.0,
+            count_format.format_with_unit(count),
+            (count as f64 / total_count.max(1) as f64) * 100.0,
+            parent_total_count
+                .map(|parent_total_count| format!(
+                    ", {:.2}% of parent",
+                    (count as f64 / parent_total_count.max(1) as f64) * 100.0
+                ))
+                .unwrap_or_default(),
             if let Some(zoomed_total_count) = zoomed_total_count {
+                let zoomed_share = |count: u64| count as f64 / zoomed_total_count.max(1) as f64;
+                let own = own.map(|own| {
+                    format!(
+                        "; own {}, {:.2}% of zoomed",
+                        count_format.format_with_unit(own),
+                        zoomed_share(own) * 100.0
+                    )
+                });
                 format!(
-                    ", {:.2}% of zoomed",
-                    (count as f64 / zoomed_total_count as f64) * 100.0
+                    ", {:.2}% of zoomed{}",
+                    zoomed_share(count) * 100.0,
+                    own.unwrap_or_default()
                 )
             } else {
                 "".to_string()
@@ -684,25 +2784,72 @@ impl<'a> FlamelensWidget<'a> {
         self.view_kind() == ViewKind::Table
     }
 
+    fn is_call_tree_view(&self) -> bool {
+        self.view_kind() == ViewKind::CallTree
+    }
+
     fn is_flamegraph_view(&self) -> bool {
         self.view_kind() == ViewKind::FlameGraph
     }
 }
 
+/// A duration as hours, minutes and seconds, as in "00:01:30"
+#[cfg(feature = "python")]
+fn format_clock(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{:0>2}:{:0>2}:{:0>2}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    )
+}
+
+/// Values as a line of block characters as high as their fraction of `max`, as in "▁▃█▂"
+fn sparkline_text(values: &[f64], max: f64) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    values
+        .iter()
+        .map(|value| {
+            let level = if max > 0.0 { value / max } else { 0.0 };
+            BARS[((level * (BARS.len() - 1) as f64).round() as usize).min(BARS.len() - 1)]
+        })
+        .collect()
+}
+
+/// Rect of the given size centered in `area`, clamped to fit inside it
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
 struct HelpTags {
-    tags: Vec<(&'static str, &'static str)>,
-    default: Vec<(&'static str, &'static str)>,
+    tags: Vec<(String, &'static str)>,
+    default: Vec<(String, &'static str)>,
+    accent: Color,
 }
 
 impl HelpTags {
-    fn new() -> Self {
+    fn new(keymap: &Keymap, accent: Color) -> Self {
         Self {
             tags: vec![],
-            default: vec![("r", "reset"), ("tab", "switch view"), ("q", "quit")],
+            default: vec![
+                (keymap.label(&[Action::Reset], "/"), "reset"),
+                (keymap.label(&[Action::SwitchView], "/"), "switch view"),
+                (keymap.label(&[Action::ToggleStats], "/"), "stats"),
+                (keymap.label(&[Action::Quit], "/"), "quit"),
+            ],
+            accent,
         }
     }
 
-    fn add(&mut self, tag: &'static str, description: &'static str) {
+    fn add(&mut self, tag: String, description: &'static str) {
         self.tags.push((tag, description));
     }
 
@@ -711,8 +2858,10 @@ impl HelpTags {
         for (tag, description) in self.tags.iter().chain(self.default.iter()) {
             spans.push(Span::from("["));
             spans.push(Span::styled(
-                *tag,
-                Style::default().add_modifier(Modifier::BOLD).yellow(),
+                tag.clone(),
+                Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .fg(self.accent),
             ));
             spans.push(Span::from(format!(": {}", description)));
             spans.push(Span::from("] "));
@@ -721,21 +2870,81 @@ impl HelpTags {
     }
 }
 
+/// Map the RGB colors of the rendered cells to the nearest ones the terminal has
+fn reduce_colors(buf: &mut Buffer, color_depth: ColorDepth) {
+    let reduce = |color: Color| match (color, color_depth) {
+        (Color::Rgb(r, g, b), ColorDepth::Ansi256) => Color::Indexed(nearest_ansi256((r, g, b))),
+        (Color::Rgb(r, g, b), ColorDepth::Ansi16) => match nearest_ansi16((r, g, b)) {
+            0 => Color::Black,
+            1 => Color::Red,
+            2 => Color::Green,
+            3 => Color::Yellow,
+            4 => Color::Blue,
+            5 => Color::Magenta,
+            6 => Color::Cyan,
+            7 => Color::Gray,
+            8 => Color::DarkGray,
+            9 => Color::LightRed,
+            10 => Color::LightGreen,
+            11 => Color::LightYellow,
+            12 => Color::LightBlue,
+            13 => Color::LightMagenta,
+            14 => Color::LightCyan,
+            _ => Color::White,
+        },
+        _ => color,
+    };
+    for cell in buf.content.iter_mut() {
+        cell.fg = reduce(cell.fg);
+        cell.bg = reduce(cell.bg);
+    }
+}
+
 /// Renders the user interface widgets.
 pub fn render(app: &mut App, frame: &mut Frame) {
     // This is where you add new widgets.
     // See the following resources:
     // - https://docs.rs/ratatui/latest/ratatui/widgets/index.html
     // - https://github.com/ratatui-org/ratatui/tree/master/examples
+    let zoom = app
+        .flamegraph_state()
+        .get_zoom()
+        .as_ref()
+        .map(|zoom| zoom.stack_id);
+    app.zoom_animation.update(
+        &app.flamegraph_view.flamegraph,
+        zoom,
+        app.flamegraph_view.updated_at,
+        app.flamegraph_view.state.frame_width.unwrap_or_default(),
+    );
+    if app.show_details {
+        app.flamegraph_view.update_details_calls();
+    }
+    if app.show_source {
+        let file = app.flamegraph_view.get_selected_stack().and_then(|stack| {
+            app.flamegraph()
+                .get_location(stack)
+                .map(|(file, _)| file.to_string())
+        });
+        if let Some(file) = file {
+            app.source_cache.load(&file);
+        }
+    }
     let flamelens_widget = FlamelensWidget::new(app);
     let mut flamelens_state = FlamelensWidgetState::default();
     frame.render_stateful_widget(flamelens_widget, frame.area(), &mut flamelens_state);
+    if app.color_depth != ColorDepth::Truecolor {
+        reduce_colors(frame.buffer_mut(), app.color_depth);
+    }
     app.flamegraph_view
         .set_frame_height(flamelens_state.frame_height);
     app.flamegraph_view
         .set_frame_width(flamelens_state.frame_width);
     app.add_elapsed("render", flamelens_state.render_time);
+    app.render_budget.update(flamelens_state.render_time);
     if let Some(input_buffer) = &mut app.input_buffer {
         input_buffer.cursor = flamelens_state.cursor_position;
     }
+    app.breadcrumbs = flamelens_state.breadcrumbs;
+    app.zoom_animation.advance();
 }
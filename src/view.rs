@@ -1,15 +1,162 @@
-use std::cmp::min;
+use std::cmp::{min, Reverse};
 
 use crate::{
-    flame::{FlameGraph, SearchPattern, SortColumn, StackIdentifier, StackInfo, ROOT_ID},
-    state::{FlameGraphState, ZoomState},
+    baseline::{Baseline, Diff},
+    flame::{
+        strip_line_number, Aggregation, CallEntry, FlameGraph, FunctionCalls, SearchPattern,
+        SortColumn, StackIdentifier, StackInfo, ROOT_ID,
+    },
+    sources::Sources,
+    state::{DrillDownState, FlameGraphState, KeptSearch, ViewKind, ZoomState, MAX_KEPT_SEARCHES},
 };
 
+/// The selected frame measured against the pinned one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PinComparison {
+    pub pinned: StackIdentifier,
+    pub selected: StackIdentifier,
+    /// Total count of the selected frame divided by the one of the pinned frame
+    pub ratio: f64,
+    pub common_ancestor: StackIdentifier,
+    /// Level of the selected frame minus the level of the pinned frame
+    pub depth_difference: isize,
+}
+
+/// Stacks of one device, told apart by an annotation such as `#device=gpu0`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lane {
+    /// Annotation key whose value is the device of a stack
+    pub key: String,
+    /// Device of the stacks kept, `None` for the stacks without the annotation (the CPU)
+    pub value: Option<String>,
+}
+
+impl Lane {
+    pub fn label(&self) -> &str {
+        self.value.as_deref().unwrap_or("cpu")
+    }
+}
+
+/// Start of the names of the frames standing for threads, such as `thread (0x7f3a): MainThread`,
+/// added by py-spy when recording thread ids
+pub const THREAD_FRAME_PREFIX: &str = "thread (";
+
+/// Start of the names of the frames standing for processes, such as `process 1234:"python app.py"`,
+/// added by py-spy when profiling subprocesses
+pub const PROCESS_FRAME_PREFIX: &str = "process ";
+
+/// A process of the profile, found from its frame under the frames of its parent processes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessNode {
+    /// Name of the frame of the process
+    pub name: String,
+    /// Full name of the frame, going through the frames of the parent processes
+    pub full_name: String,
+    pub pid: String,
+    pub cmdline: String,
+    /// Number of parent processes in the profile
+    pub depth: usize,
+    pub total_count: u64,
+}
+
+/// Changes to the stacks of the flamegraph, applied again to every new flamegraph
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filters {
+    /// Function whose stacks are the only ones kept
+    pub focus: Option<String>,
+    /// Functions removed from every stack, in the order they were pruned
+    pub pruned: Vec<String>,
+    /// Annotation key whose values are the top-level frames
+    pub group_by: Option<String>,
+    /// Merge the frames of a function that only differ by the line of their source location
+    pub merge_lines: bool,
+    /// Device whose stacks are the only ones kept
+    pub lane: Option<Lane>,
+    /// Regex of the functions whose stacks are left out
+    pub excluded: Option<String>,
+    /// Thread frames whose stacks are left out
+    pub hidden_threads: Vec<String>,
+    /// Process frames whose stacks, including the ones of their child processes, are left out
+    pub hidden_processes: Vec<String>,
+}
+
+impl Filters {
+    pub fn is_empty(&self) -> bool {
+        self.focus.is_none()
+            && self.pruned.is_empty()
+            && self.group_by.is_none()
+            && !self.merge_lines
+            && self.lane.is_none()
+            && self.excluded.is_none()
+            && self.hidden_threads.is_empty()
+            && self.hidden_processes.is_empty()
+    }
+
+    fn apply(&self, flamegraph: &FlameGraph) -> FlameGraph {
+        let mut out = None;
+        if self.merge_lines {
+            out = Some(flamegraph.merge_line_numbers());
+        }
+        if let Some(lane) = &self.lane {
+            out = Some(
+                out.as_ref()
+                    .unwrap_or(flamegraph)
+                    .filter_by_annotation(&lane.key, lane.value.as_deref()),
+            );
+        }
+        if let Some(key) = &self.group_by {
+            out = Some(out.as_ref().unwrap_or(flamegraph).group_by_annotation(key));
+        }
+        if !self.pruned.is_empty() {
+            out = Some(
+                out.as_ref()
+                    .unwrap_or(flamegraph)
+                    .prune_functions(&self.pruned),
+            );
+        }
+        // The pattern was checked when it was set
+        if let Some(re) = self
+            .excluded
+            .as_deref()
+            .and_then(|p| regex::Regex::new(p).ok())
+        {
+            out = Some(out.as_ref().unwrap_or(flamegraph).exclude_by_function(&re));
+        }
+        if !self.hidden_threads.is_empty() || !self.hidden_processes.is_empty() {
+            let names = self
+                .hidden_threads
+                .iter()
+                .chain(self.hidden_processes.iter())
+                .map(|name| regex::escape(name))
+                .collect::<Vec<_>>();
+            // Escaped names always make a valid pattern
+            if let Ok(re) = regex::Regex::new(&format!("^(?:{})$", names.join("|"))) {
+                out = Some(out.as_ref().unwrap_or(flamegraph).exclude_by_function(&re));
+            }
+        }
+        if let Some(name) = &self.focus {
+            out = Some(out.as_ref().unwrap_or(flamegraph).filter_by_function(name));
+        }
+        out.unwrap_or_else(|| flamegraph.clone())
+    }
+}
+
 #[derive(Debug)]
 pub struct FlameGraphView {
     pub flamegraph: FlameGraph,
     pub state: FlameGraphState,
     pub updated_at: std::time::Instant,
+    pub baseline: Option<Baseline>,
+    /// Comparison of the flamegraph against the baseline
+    pub diff: Option<Diff>,
+    pub filters: Filters,
+    /// Profiles merged into the flamegraph, to color the frames by how many of them have them
+    pub sources: Option<Sources>,
+    /// All the stacks, when some are filtered out of `flamegraph`
+    unfiltered: Option<FlameGraph>,
+    /// Callers and callees of the function of the selected frame for the details panel, by name
+    /// of the function, computed again whenever the flamegraph is replaced
+    details_calls: Option<(String, FunctionCalls)>,
 }
 
 impl FlameGraphView {
@@ -18,9 +165,20 @@ impl FlameGraphView {
             flamegraph,
             state: FlameGraphState::default(),
             updated_at: std::time::Instant::now(),
+            baseline: None,
+            diff: None,
+            filters: Filters::default(),
+            sources: None,
+            unfiltered: None,
+            details_calls: None,
         }
     }
 
+    pub fn set_baseline(&mut self, baseline: Baseline) {
+        self.diff = Some(baseline.compare(&self.flamegraph));
+        self.baseline = Some(baseline);
+    }
+
     pub fn select_id(&mut self, stack_id: &StackIdentifier) {
         self.state.select_id(stack_id);
         if let Some(p) = self.state.search_pattern.as_ref() {
@@ -35,25 +193,282 @@ impl FlameGraphView {
         }
     }
 
-    pub fn replace_flamegraph(&mut self, mut new_flamegraph: FlameGraph) {
+    pub fn replace_flamegraph(&mut self, new_flamegraph: FlameGraph) {
+        if self.filters.is_empty() {
+            self.set_flamegraph(new_flamegraph);
+            return;
+        }
+        let filtered = self.filters.apply(&new_flamegraph);
+        self.unfiltered = Some(new_flamegraph);
+        self.set_flamegraph(filtered);
+    }
+
+    /// Rebuild the flamegraph from all the stacks after the filters changed
+    fn apply_filters(&mut self) {
+        match self.unfiltered.take() {
+            Some(unfiltered) if self.filters.is_empty() => {
+                self.set_flamegraph(unfiltered);
+            }
+            Some(unfiltered) => {
+                let filtered = self.filters.apply(&unfiltered);
+                self.set_flamegraph(filtered);
+                self.unfiltered = Some(unfiltered);
+            }
+            None if self.filters.is_empty() => {}
+            None => {
+                let filtered = self.filters.apply(&self.flamegraph);
+                self.unfiltered = Some(self.set_flamegraph(filtered));
+            }
+        }
+    }
+
+    /// Only keep the stacks going through a frame of the function `name`
+    pub fn set_focus(&mut self, name: &str) {
+        self.filters.focus = Some(name.to_string());
+        self.apply_filters();
+    }
+
+    /// Bring back the stacks hidden by [`FlameGraphView::set_focus`]
+    pub fn unset_focus(&mut self) {
+        if self.filters.focus.take().is_some() {
+            self.apply_filters();
+        }
+    }
+
+    /// Leave out the stacks going through a function matching the regex `pattern`, or bring them
+    /// back
+    pub fn set_excluded(&mut self, pattern: Option<String>) -> Result<(), regex::Error> {
+        if let Some(pattern) = &pattern {
+            regex::Regex::new(pattern)?;
+        }
+        if self.filters.excluded != pattern {
+            self.filters.excluded = pattern;
+            self.apply_filters();
+        }
+        Ok(())
+    }
+
+    /// Thread frames of all the stacks, filtered out or not, with their samples, most samples first
+    pub fn get_threads(&self) -> Vec<(String, u64)> {
+        let flamegraph = self.unfiltered_flamegraph();
+        let mut threads: Vec<(String, u64)> = vec![];
+        for stack in flamegraph.stacks() {
+            let name = flamegraph.get_stack_short_name_from_info(stack);
+            if !name.starts_with(THREAD_FRAME_PREFIX) {
+                continue;
+            }
+            // The same thread can be under several process frames
+            match threads.iter_mut().find(|(thread, _)| thread == name) {
+                Some((_, count)) => *count += stack.total_count,
+                None => threads.push((name.to_string(), stack.total_count)),
+            }
+        }
+        threads.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        threads
+    }
+
+    /// Leave out the stacks of the thread frame `name`, or bring them back
+    pub fn toggle_thread(&mut self, name: &str) {
+        toggle_name(&mut self.filters.hidden_threads, name);
+        self.apply_filters();
+    }
+
+    /// Process frames of all the stacks, filtered out or not, each process followed by its child
+    /// processes, most samples first
+    pub fn get_processes(&self) -> Vec<ProcessNode> {
+        let flamegraph = self.unfiltered_flamegraph();
+        let mut processes = vec![];
+        let mut pending = vec![(ROOT_ID, 0)];
+        while let Some((stack_id, depth)) = pending.pop() {
+            let Some(stack) = flamegraph.get_stack(&stack_id) else {
+                continue;
+            };
+            if stack_id != ROOT_ID {
+                let name = flamegraph.get_stack_short_name_from_info(stack);
+                let (pid, cmdline) = parse_process_frame(name).unwrap_or_default();
+                processes.push(ProcessNode {
+                    name: name.to_string(),
+                    full_name: flamegraph.get_stack_full_name_from_info(stack).to_string(),
+                    pid: pid.to_string(),
+                    cmdline: cmdline.to_string(),
+                    depth,
+                    total_count: stack.total_count,
+                });
+            }
+            let mut children = stack
+                .children
+                .iter()
+                .filter_map(|id| flamegraph.get_stack(id))
+                .filter(|child| {
+                    parse_process_frame(flamegraph.get_stack_short_name_from_info(child)).is_some()
+                })
+                .collect::<Vec<_>>();
+            // Popped last, so that the child with the most samples comes first
+            children.sort_by_key(|child| child.total_count);
+            let child_depth = if stack_id == ROOT_ID { 0 } else { depth + 1 };
+            pending.extend(children.into_iter().map(|child| (child.id, child_depth)));
+        }
+        processes
+    }
+
+    /// Leave out the stacks of the process frame `name` and of its child processes, or bring them
+    /// back
+    pub fn toggle_process(&mut self, name: &str) {
+        toggle_name(&mut self.filters.hidden_processes, name);
+        self.apply_filters();
+    }
+
+    /// Zoom in on the frame of the process `process`. Returns false if the filtered flamegraph
+    /// doesn't have it.
+    pub fn zoom_to_process(&mut self, process: &ProcessNode) -> bool {
+        let Some(stack_id) = self
+            .flamegraph
+            .get_stack_id_by_full_name(&process.full_name)
+        else {
+            return false;
+        };
+        self.jump_to(stack_id);
+        self.set_zoom();
+        true
+    }
+
+    /// Merge the frames that only differ by their line, or bring them back. The names of the
+    /// focused and pruned functions lose their line when merging.
+    pub fn set_merge_lines(&mut self, merge_lines: bool) {
+        if self.filters.merge_lines == merge_lines {
+            return;
+        }
+        self.filters.merge_lines = merge_lines;
+        if merge_lines {
+            if let Some(focus) = self.filters.focus.as_mut() {
+                *focus = strip_line_number(focus).into_owned();
+            }
+            for name in self.filters.pruned.iter_mut() {
+                *name = strip_line_number(name).into_owned();
+            }
+        }
+        self.apply_filters();
+    }
+
+    /// Pivot the flamegraph by the values of the annotation `key`, or stop grouping
+    pub fn set_group_by(&mut self, key: Option<String>) {
+        if self.filters.group_by != key {
+            self.filters.group_by = key;
+            self.apply_filters();
+        }
+    }
+
+    /// Only keep the stacks of a device, or all of them
+    pub fn set_lane(&mut self, lane: Option<Lane>) {
+        if self.filters.lane != lane {
+            self.filters.lane = lane;
+            self.apply_filters();
+        }
+    }
+
+    /// Remove the frames of the function `name` from every stack, splicing their children up to
+    /// their parents. The selection moves up to the closest remaining ancestor.
+    pub fn prune(&mut self, name: &str) {
+        let keep_selected = self
+            .flamegraph
+            .get_ancestors(&self.state.selected)
+            .into_iter()
+            .find(|id| self.flamegraph.get_stack_short_name(id) != Some(name));
+        if let Some(stack_id) = keep_selected {
+            self.select_id(&stack_id);
+        }
+        let selected = self.get_selected_full_name();
+        self.filters.pruned.push(name.to_string());
+        self.apply_filters();
+        self.reselect(selected, name);
+    }
+
+    /// Bring back the frames of the last function pruned, returning its name
+    pub fn undo_prune(&mut self) -> Option<String> {
+        let name = self.filters.pruned.pop()?;
+        let selected = self.get_selected_full_name();
+        self.apply_filters();
+        self.reselect(selected, &name);
+        Some(name)
+    }
+
+    fn get_selected_full_name(&self) -> Option<String> {
+        if self.is_root_selected() {
+            return None;
+        }
+        self.flamegraph
+            .get_stack_full_name(&self.state.selected)
+            .map(|s| s.to_string())
+    }
+
+    /// Select the first frame with the same stack as `full_name` once the frames of the function
+    /// `pruned` are left out of both, since the stacks changed with the pruning
+    fn reselect(&mut self, full_name: Option<String>, pruned: &str) {
+        let Some(full_name) = full_name else {
+            return;
+        };
+        let stack_id = self
+            .flamegraph
+            .find_stack_ignoring_function(&full_name, pruned);
+        if let Some(stack_id) = stack_id {
+            self.select_id(&stack_id);
+            self.scroll_to_selected();
+        }
+    }
+
+    /// The flamegraph with all the stacks, whether filtered or not
+    pub fn unfiltered_flamegraph(&self) -> &FlameGraph {
+        self.unfiltered.as_ref().unwrap_or(&self.flamegraph)
+    }
+
+    /// Replace the flamegraph, keeping the selection and zoom on the frames that are still there.
+    /// Returns the previous flamegraph.
+    fn set_flamegraph(&mut self, mut new_flamegraph: FlameGraph) -> FlameGraph {
         self.state
             .handle_flamegraph_replacement(&self.flamegraph, &mut new_flamegraph);
-        // Preserve the sort column
+        // Preserve the sort and the aggregation of the Top view
+        let ordered = &self.flamegraph.ordered_stacks;
         new_flamegraph
             .ordered_stacks
-            .set_sort_column(self.flamegraph.ordered_stacks.sorted_column);
-        self.flamegraph = new_flamegraph;
-        // Now the id in ZoomState points to the one in new flamegraph, but the ancestors and
-        // descendants are not. Set the zoom again to update them.
-        if let Some(zoom) = &self.state.get_zoom() {
-            self.set_zoom_for_id(zoom.stack_id);
+            .sort(ordered.sorted_column, ordered.ascending);
+        new_flamegraph.set_aggregation(self.flamegraph.ordered_stacks.aggregation);
+        let was_empty = self.flamegraph.total_count() == 0;
+        let previous = std::mem::replace(&mut self.flamegraph, new_flamegraph);
+        // In live mode, skip once the first samples come in
+        if self.state.auto_skip && was_empty && self.flamegraph.total_count() > 0 {
+            self.skip_single_child_prefix();
+        }
+        if let Some(baseline) = &self.baseline {
+            self.diff = Some(baseline.compare(&self.flamegraph));
+        }
+        if let Some(drill_down) = self.state.drill_down.as_mut() {
+            drill_down.calls = self.flamegraph.get_function_calls(&drill_down.name);
+        }
+        self.details_calls = None;
+        // Now the ids in ZoomState point to the ones in new flamegraph, but the ancestors and
+        // descendants are not. Set the zooms again to update them.
+        let zoom_ids = self
+            .state
+            .zoom_stack
+            .iter()
+            .map(|z| z.stack_id)
+            .collect::<Vec<_>>();
+        self.state.unset_zoom();
+        for stack_id in zoom_ids {
+            self.set_zoom_for_id(stack_id);
         }
         self.updated_at = std::time::Instant::now();
+        previous
     }
 
     pub fn set_frame_height(&mut self, frame_height: u16) {
+        let was_unknown = self.state.frame_height.is_none();
         self.state.frame_height = Some(frame_height);
-        self.keep_selected_stack_in_view_port();
+        if was_unknown {
+            self.scroll_to_selected();
+        } else {
+            self.keep_selected_stack_in_view_port();
+        }
     }
 
     pub fn set_frame_width(&mut self, frame_width: u16) {
@@ -105,7 +520,7 @@ impl FlameGraphView {
             if let Some(parent) = parent {
                 if let Some(parent_stack) = self.flamegraph.get_stack(&parent) {
                     if !self.is_stack_in_view_port(parent_stack) {
-                        self.state.level_offset -= 1;
+                        self.state.level_offset = self.state.level_offset.saturating_sub(1);
                     }
                 }
                 self.select_id(&parent);
@@ -118,8 +533,8 @@ impl FlameGraphView {
     fn is_stack_in_view_port(&self, stack: &StackInfo) -> bool {
         if let Some(frame_height) = self.state.frame_height {
             let min_level = self.state.level_offset;
-            let max_level = min_level + frame_height as usize - 1;
-            min_level <= stack.level && stack.level <= max_level
+            // Nothing is in view when the terminal leaves no room for the flamegraph
+            min_level <= stack.level && stack.level < min_level + frame_height as usize
         } else {
             true
         }
@@ -254,16 +669,88 @@ impl FlameGraphView {
         }
     }
 
-    pub fn to_previous_search_result(&mut self) {
-        if let Some(previous_id) = self.get_previous_hit() {
-            self.select_id(&previous_id);
-            self.scroll_to_selected();
+    /// Select the previous frame matching the search pattern, wrapping around to the last one
+    /// before the first. Returns whether it wrapped around.
+    pub fn to_previous_search_result(&mut self) -> bool {
+        self.select_search_result(self.get_previous_hit(), false)
+    }
+
+    /// Select the next frame matching the search pattern, wrapping around to the first one after
+    /// the last. Returns whether it wrapped around.
+    pub fn to_next_search_result(&mut self) -> bool {
+        self.select_search_result(self.get_next_hit(), true)
+    }
+
+    fn select_search_result(&mut self, hit: Option<StackIdentifier>, forward: bool) -> bool {
+        let (hit, wrapped) = match hit {
+            Some(hit) => (Some(hit), false),
+            None => (self.get_wrapped_hit(forward), true),
+        };
+        let Some(hit) = hit else {
+            return false;
+        };
+        self.select_id(&hit);
+        self.scroll_to_selected();
+        wrapped
+    }
+
+    /// Position of the selected frame among the frames matching the search pattern, if it is one
+    /// of them, and the number of matching frames
+    pub fn get_search_position(&self) -> Option<(Option<usize>, usize)> {
+        let hit_ids = self.flamegraph.hit_ids()?;
+        let position = hit_ids.iter().position(|id| *id == self.state.selected);
+        Some((position, hit_ids.len()))
+    }
+
+    /// Select the next (or previous) frame of the same function as the selected frame, wrapping
+    /// around, and zoom out if it is outside of the zoom. Returns the position of the newly
+    /// selected frame among all the frames of the function and their number.
+    pub fn to_next_occurrence(&mut self, forward: bool) -> Option<(usize, usize)> {
+        if self.is_root_selected() {
+            return None;
+        }
+        let short_name = self.flamegraph.get_stack_short_name(&self.state.selected)?;
+        let occurrences = self.flamegraph.get_stacks_with_short_name(short_name);
+        let len = occurrences.len();
+        let index = occurrences
+            .iter()
+            .position(|id| *id == self.state.selected)?;
+        let index = if forward {
+            (index + 1) % len
+        } else {
+            (index + len - 1) % len
+        };
+        self.jump_to(occurrences[index]);
+        Some((index, len))
+    }
+
+    /// Select a frame and scroll to it, zooming out if it is outside of the zoom
+    pub fn jump_to(&mut self, stack_id: StackIdentifier) {
+        if self
+            .state
+            .get_zoom()
+            .is_some_and(|z| !z.is_ancestor_or_descendant(&stack_id))
+        {
+            self.state.unset_zoom();
+            self.record_zoom();
         }
+        self.select_id(&stack_id);
+        self.scroll_to_selected();
     }
 
-    pub fn to_next_search_result(&mut self) {
-        if let Some(next_id) = self.get_next_hit() {
-            self.select_id(&next_id);
+    /// Select the widest frame matching the search pattern within the current zoom
+    pub fn to_heaviest_search_result(&mut self) {
+        let zoom = self.state.get_zoom();
+        let heaviest = self.flamegraph.hit_ids().and_then(|hit_ids| {
+            hit_ids
+                .iter()
+                .filter(|id| zoom.is_none_or(|z| z.is_ancestor_or_descendant(id)))
+                .filter_map(|id| self.flamegraph.get_stack(id))
+                .max_by_key(|stack| stack.total_count)
+                .map(|stack| stack.id)
+        });
+        if let Some(stack_id) = heaviest {
+            self.select_id(&stack_id);
             self.scroll_to_selected();
         }
     }
@@ -315,6 +802,22 @@ impl FlameGraphView {
         })
     }
 
+    /// First (or last) visible frame matching the search pattern
+    fn get_wrapped_hit(&self, forward: bool) -> Option<StackIdentifier> {
+        let _ = self.state.search_pattern.as_ref()?;
+        let hit_ids = self.flamegraph.hit_ids()?;
+        let is_visible = |id: &&StackIdentifier| {
+            self.flamegraph
+                .get_stack(id)
+                .is_some_and(|stack| self.is_stack_visibly_wide(stack, None))
+        };
+        if forward {
+            hit_ids.iter().find(is_visible).copied()
+        } else {
+            hit_ids.iter().rev().find(is_visible).copied()
+        }
+    }
+
     fn get_next_hit_same_level<'a, I>(&self, level_stacks: I) -> Option<StackIdentifier>
     where
         I: Iterator<Item = &'a StackIdentifier>,
@@ -373,7 +876,12 @@ impl FlameGraphView {
     }
 
     pub fn set_zoom_for_id(&mut self, stack_id: StackIdentifier) {
-        if let Some(selected_stack) = self.flamegraph.get_stack(&stack_id) {
+        // Frames without samples have no width to zoom to
+        let selected_stack = self
+            .flamegraph
+            .get_stack(&stack_id)
+            .filter(|stack| stack.total_count > 0);
+        if let Some(selected_stack) = selected_stack {
             let zoom_factor =
                 self.flamegraph.total_count() as f64 / selected_stack.total_count as f64;
             let ancestors = self.flamegraph.get_ancestors(&stack_id);
@@ -390,7 +898,7 @@ impl FlameGraphView {
                 match self.state.get_zoom().as_ref() {
                     Some(cur_zoom) if cur_zoom.stack_id == zoom.stack_id => {
                         self.state.pop_zoom();
-                    },
+                    }
                     _ => {
                         self.state.set_zoom(zoom);
                     }
@@ -401,6 +909,7 @@ impl FlameGraphView {
 
     pub fn set_zoom(&mut self) {
         self.set_zoom_for_id(self.state.selected);
+        self.record_zoom();
     }
 
     pub fn unset_zoom(&mut self) {
@@ -409,13 +918,135 @@ impl FlameGraphView {
             self.select_id(&zoom_stack_id);
         }
         self.state.unset_zoom();
+        self.record_zoom();
+    }
+
+    /// Add the current zoom to the zoom history
+    pub fn record_zoom(&mut self) {
+        let stack_id = self.state.get_zoom().map_or(ROOT_ID, |z| z.stack_id);
+        self.state.record_zoom(stack_id);
+    }
+
+    /// Go back (or forward) to the previous (or next) zoom of the zoom history
+    pub fn step_zoom_history(&mut self, forward: bool) {
+        let Some(stack_id) = self.state.step_zoom_history(forward) else {
+            return;
+        };
+        let zoomed_id = self.state.get_zoom().map_or(ROOT_ID, |z| z.stack_id);
+        if stack_id == ROOT_ID {
+            self.state.unset_zoom();
+        } else if stack_id != zoomed_id {
+            self.set_zoom_for_id(stack_id);
+        }
+        // Keep the previously zoomed frame selected when it is still in view, like unzooming
+        let keep_selected = self
+            .state
+            .get_zoom()
+            .is_none_or(|z| z.is_ancestor_or_descendant(&zoomed_id));
+        if keep_selected && zoomed_id != ROOT_ID {
+            self.select_id(&zoomed_id);
+        } else {
+            self.select_id(&stack_id);
+        }
+        self.scroll_to_selected();
+    }
+
+    /// Pin the selected frame to compare other frames with, or unpin it if it is already pinned.
+    /// Returns whether a frame is pinned.
+    pub fn toggle_pin(&mut self) -> bool {
+        if self.state.pinned == Some(self.state.selected) {
+            self.state.pinned = None;
+        } else {
+            self.state.pinned = Some(self.state.selected);
+        }
+        self.state.pinned.is_some()
+    }
+
+    /// Comparison of the selected frame with the pinned frame, when they differ
+    pub fn get_pin_comparison(&self) -> Option<PinComparison> {
+        let pinned = self.state.pinned?;
+        let selected = self.state.selected;
+        if pinned == selected {
+            return None;
+        }
+        let pinned_stack = self.flamegraph.get_stack(&pinned)?;
+        let selected_stack = self.flamegraph.get_stack(&selected)?;
+        Some(PinComparison {
+            pinned,
+            selected,
+            ratio: selected_stack.total_count as f64 / pinned_stack.total_count.max(1) as f64,
+            common_ancestor: self.flamegraph.get_common_ancestor(&pinned, &selected),
+            depth_difference: selected_stack.level as isize - pinned_stack.level as isize,
+        })
+    }
+
+    /// The root and the frames down to the zoomed one, empty when not zoomed
+    pub fn get_zoom_breadcrumbs(&self) -> Vec<StackIdentifier> {
+        match self.state.get_zoom() {
+            Some(zoom) => zoom.ancestors.iter().rev().copied().collect(),
+            None => vec![],
+        }
+    }
+
+    /// Zoom out to an ancestor of the zoomed frame, all the way out for the root, and select the
+    /// previously zoomed frame
+    pub fn zoom_out_to(&mut self, stack_id: StackIdentifier) {
+        let Some(zoomed_id) = self.state.get_zoom().map(|z| z.stack_id) else {
+            return;
+        };
+        // Drop the zooms below the ancestor so that unzooming goes back to the ones above it
+        while self
+            .state
+            .get_zoom()
+            .is_some_and(|z| z.stack_id != stack_id && z.ancestors.contains(&stack_id))
+        {
+            self.state.pop_zoom();
+        }
+        if stack_id != ROOT_ID && self.state.get_zoom().map(|z| z.stack_id) != Some(stack_id) {
+            self.set_zoom_for_id(stack_id);
+        }
+        self.select_id(&zoomed_id);
+        self.scroll_to_selected();
+        self.record_zoom();
+    }
+
+    /// Zoom out to the parent of the zoomed frame
+    pub fn zoom_to_parent(&mut self) {
+        let parent_id = self
+            .state
+            .get_zoom()
+            .and_then(|z| self.flamegraph.get_stack(&z.stack_id))
+            .and_then(|s| s.parent);
+        if let Some(parent_id) = parent_id {
+            self.zoom_out_to(parent_id);
+        }
     }
 
     pub fn set_search_pattern(&mut self, search_pattern: SearchPattern) {
-        self.flamegraph.set_hits(&search_pattern);
+        self.flamegraph
+            .set_hits(&search_pattern, &self.state.kept_search_res());
         self.state.set_search_pattern(search_pattern);
     }
 
+    /// Keep highlighting the manual search pattern in its own color and clear the search, so that
+    /// another pattern can be searched. Returns false if there is no manual search pattern or
+    /// [`MAX_KEPT_SEARCHES`] patterns are already kept.
+    pub fn keep_search_pattern(&mut self) -> bool {
+        let pattern = match &self.state.search_pattern {
+            Some(p) if p.is_manual && self.state.kept_searches.len() < MAX_KEPT_SEARCHES => {
+                p.clone()
+            }
+            _ => return false,
+        };
+        let coverage_count = self.flamegraph.count_coverage(&pattern.re);
+        self.state.kept_searches.push(KeptSearch {
+            pattern,
+            coverage_count,
+        });
+        self.unset_search_pattern();
+        true
+    }
+
     pub fn unset_search_pattern(&mut self) {
         self.flamegraph.clear_hits();
         self.state.unset_search_pattern();
@@ -430,11 +1061,43 @@ impl FlameGraphView {
     }
 
     pub fn reset(&mut self) {
+        self.unset_focus();
         self.state.select_root();
         self.state.level_offset = 0;
         self.state.unset_zoom();
+        self.record_zoom();
         self.state.table_state.reset();
+        self.state.drill_down = None;
+        self.state.pinned = None;
+        self.state.kept_searches.clear();
         self.unset_search_pattern();
+        if self.state.auto_skip {
+            self.skip_single_child_prefix();
+        }
+    }
+
+    /// Scroll past the chain of frames with a single child at the top of the flamegraph, such as
+    /// interpreter startup and main wrappers, and select the first frame that branches.
+    pub fn skip_single_child_prefix(&mut self) {
+        let stack_id = self.flamegraph.get_single_child_chain_end();
+        if let Some(level) = self.flamegraph.get_stack(&stack_id).map(|s| s.level) {
+            self.set_level_offset(level);
+            if stack_id != ROOT_ID {
+                self.select_id(&stack_id);
+            }
+        }
+    }
+
+    /// Reveal the frames skipped by [`Self::skip_single_child_prefix`], or skip them again.
+    /// Returns whether they are skipped now.
+    pub fn toggle_single_child_prefix(&mut self) -> bool {
+        if self.state.level_offset > 0 {
+            self.scroll_top();
+            false
+        } else {
+            self.skip_single_child_prefix();
+            self.state.level_offset > 0
+        }
     }
 
     pub fn to_next_row(&mut self) {
@@ -467,25 +1130,337 @@ impl FlameGraphView {
         self.state.table_state.offset = new_value;
     }
 
-    pub fn set_sort_by_own(&mut self) {
-        self.flamegraph
-            .ordered_stacks
-            .set_sort_column(SortColumn::Own);
-    }
-
-    pub fn set_sort_by_total(&mut self) {
-        self.flamegraph
-            .ordered_stacks
-            .set_sort_column(SortColumn::Total);
+    /// Sort the Top view by `column`, or the other way around if it is already sorted by it
+    pub fn set_sort_column(&mut self, column: SortColumn) {
+        let ordered = &mut self.flamegraph.ordered_stacks;
+        if ordered.sorted_column == column {
+            ordered.toggle_sort_direction();
+        } else {
+            ordered.set_sort_column(column);
+        }
     }
 
     pub fn get_selected_row_name(&mut self) -> Option<&str> {
         self.flamegraph
             .ordered_stacks
             .entries
-            .get(self.state.table_state.selected)
+            .iter()
+            .filter(|entry| entry.visible)
+            .nth(self.state.table_state.selected)
             .map(|x| x.name.as_str())
     }
+
+    /// Count the samples of the Top view by function, file or module, in turn
+    pub fn cycle_aggregation(&mut self) -> Aggregation {
+        let aggregation = self.flamegraph.ordered_stacks.aggregation.next();
+        self.flamegraph.set_aggregation(aggregation);
+        self.state.table_state.selected = 0;
+        self.state.table_state.offset = 0;
+        aggregation
+    }
+
+    /// Rows of the call tree view: each unfolded frame is followed by its children, heaviest first
+    pub fn get_call_tree_rows(&self) -> Vec<StackIdentifier> {
+        let mut rows = vec![];
+        let mut pending = vec![ROOT_ID];
+        while let Some(stack_id) = pending.pop() {
+            let Some(stack) = self.flamegraph.get_stack(&stack_id) else {
+                continue;
+            };
+            rows.push(stack_id);
+            if self.is_expanded(stack) {
+                let mut children = stack
+                    .children
+                    .iter()
+                    .filter_map(|x| self.flamegraph.get_stack(x))
+                    .collect::<Vec<_>>();
+                // Popped from the end, so the heaviest child comes last and ties keep their order
+                children.sort_by_key(|x| (x.total_count, Reverse(x.id)));
+                pending.extend(children.iter().map(|x| x.id));
+            }
+        }
+        rows
+    }
+
+    /// Whether the children of the frame are listed in the call tree view. The root always is.
+    pub fn is_expanded(&self, stack: &StackInfo) -> bool {
+        stack.id == ROOT_ID
+            || self
+                .state
+                .call_tree
+                .expanded
+                .contains(self.flamegraph.get_stack_full_name_from_info(stack))
+    }
+
+    fn set_expanded(&mut self, stack_id: &StackIdentifier, expanded: bool) {
+        if *stack_id == ROOT_ID {
+            return;
+        }
+        if let Some(full_name) = self.flamegraph.get_stack_full_name(stack_id) {
+            if expanded {
+                self.state.call_tree.expanded.insert(full_name.to_string());
+            } else {
+                self.state.call_tree.expanded.remove(full_name);
+            }
+        }
+    }
+
+    /// Unfold the ancestors of the selected frame so that it has a row in the call tree view
+    pub fn reveal_selected_in_call_tree(&mut self) {
+        let ancestors = self.flamegraph.get_ancestors(&self.state.selected);
+        for ancestor in ancestors.iter().skip(1) {
+            self.set_expanded(ancestor, true);
+        }
+    }
+
+    fn select_call_tree_row(&mut self, stack_id: &StackIdentifier) {
+        self.select_id(stack_id);
+        self.scroll_to_selected();
+    }
+
+    /// Row of the selected frame in the call tree view, unfolding its ancestors if needed
+    fn get_call_tree_selected_row(&mut self) -> (Vec<StackIdentifier>, usize) {
+        self.reveal_selected_in_call_tree();
+        let rows = self.get_call_tree_rows();
+        let index = rows
+            .iter()
+            .position(|x| *x == self.state.selected)
+            .unwrap_or_default();
+        (rows, index)
+    }
+
+    pub fn to_next_call_tree_row(&mut self, delta: usize) {
+        let (rows, index) = self.get_call_tree_selected_row();
+        let new_index = min(index.saturating_add(delta), rows.len().saturating_sub(1));
+        if let Some(stack_id) = rows.get(new_index) {
+            self.select_call_tree_row(stack_id);
+        }
+        if delta > 1 {
+            self.state.call_tree.offset = new_index;
+        }
+    }
+
+    pub fn to_previous_call_tree_row(&mut self, delta: usize) {
+        let (rows, index) = self.get_call_tree_selected_row();
+        let new_index = index.saturating_sub(delta);
+        if let Some(stack_id) = rows.get(new_index) {
+            self.select_call_tree_row(stack_id);
+        }
+        if delta > 1 {
+            self.state.call_tree.offset = new_index;
+        }
+    }
+
+    pub fn scroll_call_tree_rows(&mut self, forward: bool) {
+        let delta = self.state.frame_height.unwrap_or(10) as usize;
+        if forward {
+            self.to_next_call_tree_row(delta);
+        } else {
+            self.to_previous_call_tree_row(delta);
+        }
+    }
+
+    pub fn to_call_tree_top(&mut self) {
+        self.state.call_tree.offset = 0;
+        self.select_call_tree_row(&ROOT_ID);
+    }
+
+    pub fn to_call_tree_bottom(&mut self) {
+        if let Some(stack_id) = self.get_call_tree_rows().last() {
+            self.select_call_tree_row(stack_id);
+        }
+    }
+
+    /// Unfold the selected frame, or move to its heaviest child if it already is
+    pub fn expand_or_enter_call_tree_row(&mut self) {
+        let Some(stack) = self.get_selected_stack() else {
+            return;
+        };
+        if stack.children.is_empty() {
+            return;
+        }
+        if !self.is_expanded(stack) {
+            let stack_id = stack.id;
+            self.set_expanded(&stack_id, true);
+        } else if let Some(child) = stack
+            .children
+            .iter()
+            .filter_map(|x| self.flamegraph.get_stack(x))
+            .max_by_key(|x| (x.total_count, Reverse(x.id)))
+            .map(|x| x.id)
+        {
+            self.select_call_tree_row(&child);
+        }
+    }
+
+    /// Fold the selected frame, or move to its parent if it already is
+    pub fn collapse_or_leave_call_tree_row(&mut self) {
+        let Some(stack) = self.get_selected_stack() else {
+            return;
+        };
+        if stack.id != ROOT_ID && self.is_expanded(stack) && !stack.children.is_empty() {
+            let stack_id = stack.id;
+            self.set_expanded(&stack_id, false);
+        } else if let Some(parent) = stack.parent {
+            self.select_call_tree_row(&parent);
+        }
+    }
+
+    /// Fold or unfold the selected frame
+    pub fn toggle_call_tree_row(&mut self) {
+        if let Some(stack) = self.get_selected_stack() {
+            let (stack_id, expanded) = (stack.id, self.is_expanded(stack));
+            self.set_expanded(&stack_id, !expanded);
+        }
+    }
+
+    /// Open the callers/callees breakdown of the function in the selected row
+    pub fn open_drill_down(&mut self) {
+        if self.flamegraph.ordered_stacks.aggregation != Aggregation::Function {
+            return;
+        }
+        if let Some(name) = self.get_selected_row_name().map(|s| s.to_string()) {
+            let calls = self.flamegraph.get_function_calls(&name);
+            self.state.drill_down = Some(DrillDownState {
+                name,
+                selected: 0,
+                calls,
+            });
+        }
+    }
+
+    pub fn close_drill_down(&mut self) {
+        self.state.drill_down = None;
+    }
+
+    /// Callers and callees of the function in the breakdown
+    /// Compute the callers and callees of the function of the selected frame for the details
+    /// panel, unless they are known already
+    pub fn update_details_calls(&mut self) {
+        if self.is_root_selected() {
+            return;
+        }
+        let Some(name) = self.flamegraph.get_stack_short_name(&self.state.selected) else {
+            return;
+        };
+        if self.details_calls.as_ref().is_some_and(|(n, _)| n == name) {
+            return;
+        }
+        let calls = self.flamegraph.get_function_calls(name);
+        self.details_calls = Some((name.to_string(), calls));
+    }
+
+    /// Callers and callees of the function of the selected frame, once computed by
+    /// [`Self::update_details_calls`]
+    pub fn get_details_calls(&self) -> Option<&FunctionCalls> {
+        let name = self.flamegraph.get_stack_short_name(&self.state.selected)?;
+        self.details_calls
+            .as_ref()
+            .filter(|(n, _)| n == name)
+            .map(|(_, calls)| calls)
+    }
+
+    pub fn get_drill_down_calls(&self) -> Option<&FunctionCalls> {
+        self.state.drill_down.as_ref().map(|d| &d.calls)
+    }
+
+    pub fn get_drill_down_selected_entry(&self) -> Option<CallEntry> {
+        let drill_down = self.state.drill_down.as_ref()?;
+        let calls = &drill_down.calls;
+        calls
+            .callers
+            .iter()
+            .chain(calls.callees.iter())
+            .nth(drill_down.selected)
+            .cloned()
+    }
+
+    pub fn to_next_drill_down_entry(&mut self) {
+        let num_entries = self
+            .get_drill_down_calls()
+            .map(|calls| calls.callers.len() + calls.callees.len())
+            .unwrap_or(0);
+        if let Some(drill_down) = self.state.drill_down.as_mut() {
+            drill_down.selected = min(drill_down.selected + 1, num_entries.saturating_sub(1));
+        }
+    }
+
+    pub fn to_previous_drill_down_entry(&mut self) {
+        if let Some(drill_down) = self.state.drill_down.as_mut() {
+            drill_down.selected = drill_down.selected.saturating_sub(1);
+        }
+    }
+
+    /// Switch to the flamegraph view with the selected caller or callee occurrence selected
+    pub fn jump_to_drill_down_entry(&mut self) {
+        let Some(entry) = self.get_drill_down_selected_entry() else {
+            return;
+        };
+        let in_zoom = self.state.get_zoom().is_none_or(|zoom| {
+            zoom.stack_id == entry.stack_id || zoom.is_ancestor_or_descendant(&entry.stack_id)
+        });
+        if !in_zoom {
+            self.state.unset_zoom();
+        }
+        self.select_id(&entry.stack_id);
+        self.scroll_to_selected();
+        self.state.view_kind = ViewKind::FlameGraph;
+    }
+}
+
+/// Pid and command line of a frame named like `process 1234:"python app.py"`
+pub fn parse_process_frame(name: &str) -> Option<(&str, &str)> {
+    let (pid, cmdline) = name.strip_prefix(PROCESS_FRAME_PREFIX)?.split_once(':')?;
+    if pid.is_empty() || !pid.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    Some((pid, cmdline.trim_matches('"')))
+}
+
+/// Part of the row below a frame: one of its children, or a run of adjacent children narrower
+/// than the minimum frame width, drawn as a single `+N` placeholder
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChildSpan {
+    Frame { index: usize, x_budget: f64 },
+    TinyRun { count: usize, x_budget: f64 },
+}
+
+/// Lay out the children of a frame from their widths, collapsing the runs of visible children
+/// narrower than `min_x_budget` unless `keep` holds for their index (such as the ancestors of the
+/// selected frame). The spans take the same total width as the children.
+pub fn layout_children(
+    x_budgets: &[f64],
+    min_x_budget: f64,
+    keep: impl Fn(usize) -> bool,
+) -> Vec<ChildSpan> {
+    let mut spans = vec![];
+    let mut tiny_run = (0, 0.0);
+    for (index, x_budget) in x_budgets.iter().copied().enumerate() {
+        if x_budget > 0.0 && x_budget < min_x_budget && !keep(index) {
+            tiny_run = (tiny_run.0 + 1, tiny_run.1 + x_budget);
+            continue;
+        }
+        if tiny_run.0 > 0 {
+            let (count, x_budget) = std::mem::take(&mut tiny_run);
+            spans.push(ChildSpan::TinyRun { count, x_budget });
+        }
+        spans.push(ChildSpan::Frame { index, x_budget });
+    }
+    if tiny_run.0 > 0 {
+        let (count, x_budget) = tiny_run;
+        spans.push(ChildSpan::TinyRun { count, x_budget });
+    }
+    spans
+}
+
+/// Remove `name` from `names` if it is there, add it otherwise
+fn toggle_name(names: &mut Vec<String>, name: &str) {
+    match names.iter().position(|other| other == name) {
+        Some(index) => {
+            names.remove(index);
+        }
+        None => names.push(name.to_string()),
+    }
 }
 
 #[cfg(test)]
@@ -563,6 +1538,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_keep_search_pattern() {
+        let content = std::fs::read_to_string("tests/data/readable.txt").unwrap();
+        let mut view = FlameGraphView::new(FlameGraph::from_string(content, false));
+        assert!(!view.keep_search_pattern());
+
+        view.set_search_pattern(SearchPattern::new("level2-b", true, true).unwrap());
+        assert!(view.keep_search_pattern());
+        assert!(view.state.search_pattern.is_none());
+        assert_eq!(view.state.kept_searches.len(), 1);
+        assert_eq!(view.state.kept_searches[0].coverage_count, 20);
+
+        // The rows of both patterns stay in the Top view
+        view.set_search_pattern(SearchPattern::new("level1-b", true, true).unwrap());
+        let visible = view
+            .flamegraph
+            .ordered_stacks
+            .entries
+            .iter()
+            .filter(|entry| entry.visible)
+            .map(|entry| entry.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(visible, ["level2-b", "level1-b"]);
+
+        view.reset();
+        assert!(view.state.kept_searches.is_empty());
+    }
+
     #[test]
     fn test_get_next_and_previous_search_result() {
         let content = std::fs::read_to_string("tests/data/readable.txt").unwrap();
@@ -593,12 +1596,16 @@ mod tests {
 
         view.to_next_search_result();
         assert_eq!(get_selected_short_name(&view), "level2-e");
+        assert_eq!(view.get_search_position(), Some((Some(3), 4)));
 
-        view.to_next_search_result();
+        // Wrap around to the first result
+        assert!(view.to_next_search_result());
+        assert_eq!(get_selected_short_name(&view), "level1-b");
+        assert!(view.to_previous_search_result());
         assert_eq!(get_selected_short_name(&view), "level2-e");
 
         // Check going to the previous search result
-        view.to_previous_search_result();
+        assert!(!view.to_previous_search_result());
         assert_eq!(get_selected_short_name(&view), "level2-c");
 
         view.to_previous_search_result();
@@ -606,8 +1613,556 @@ mod tests {
 
         view.to_previous_search_result();
         assert_eq!(get_selected_short_name(&view), "level1-b");
+        assert_eq!(view.get_search_position(), Some((Some(0), 4)));
 
-        view.to_previous_search_result();
-        assert_eq!(get_selected_short_name(&view), "level1-b");
+        view.select_id(&ROOT_ID);
+        assert_eq!(view.get_search_position(), Some((None, 4)));
+    }
+
+    #[test]
+    fn test_drill_down() {
+        let content = std::fs::read_to_string("tests/data/py-spy-simple.txt").unwrap();
+        let fg = FlameGraph::from_string(content, true);
+        let mut view = FlameGraphView::new(fg);
+        view.state.toggle_view_kind();
+
+        // The heaviest function by total comes first
+        view.set_sort_column(SortColumn::Total);
+        view.open_drill_down();
+        assert_eq!(
+            view.state.drill_down.as_ref().unwrap().name,
+            "<module> (long_running.py:25)"
+        );
+        // Callers first, then callees; the cursor stops at the last entry
+        view.to_next_drill_down_entry();
+        view.to_next_drill_down_entry();
+        view.to_next_drill_down_entry();
+        assert_eq!(
+            view.get_drill_down_selected_entry().unwrap().name,
+            "work (long_running.py:7)"
+        );
+
+        view.jump_to_drill_down_entry();
+        assert_eq!(view.state.view_kind, ViewKind::FlameGraph);
+        assert_eq!(
+            view.state.selected,
+            get_id(
+                &view,
+                "<module> (long_running.py:25);work (long_running.py:7)"
+            )
+        );
+
+        // The breakdown follows the flamegraph when it's replaced
+        let total = view.get_drill_down_calls().unwrap().total;
+        let content = std::fs::read_to_string("tests/data/py-spy-simple.txt").unwrap();
+        let content = format!(
+            "{}\n<module> (long_running.py:25);other 5",
+            content.trim_end()
+        );
+        view.replace_flamegraph(FlameGraph::from_string(content, true));
+        let calls = view.get_drill_down_calls().unwrap();
+        assert_eq!(calls.total, total + 5);
+        let other = calls.callees.iter().find(|e| e.name == "other").unwrap();
+        assert_eq!(
+            other.stack_id,
+            get_id(&view, "<module> (long_running.py:25);other")
+        );
+
+        view.close_drill_down();
+        assert!(view.get_drill_down_calls().is_none());
+    }
+
+    #[test]
+    fn test_details_calls() {
+        let content = std::fs::read_to_string("tests/data/py-spy-simple.txt").unwrap();
+        let mut view = FlameGraphView::new(FlameGraph::from_string(content.clone(), true));
+        view.select_id(&get_id(&view, "<module> (long_running.py:25)"));
+        assert!(view.get_details_calls().is_none());
+        view.update_details_calls();
+        let total = view.get_details_calls().unwrap().total;
+
+        // Computed again for another function or a new flamegraph
+        view.select_id(&get_id(
+            &view,
+            "<module> (long_running.py:25);work (long_running.py:8)",
+        ));
+        assert!(view.get_details_calls().is_none());
+        view.select_id(&get_id(&view, "<module> (long_running.py:25)"));
+        let content = format!("{}\n<module> (long_running.py:25) 5", content.trim_end());
+        view.replace_flamegraph(FlameGraph::from_string(content, true));
+        assert!(view.get_details_calls().is_none());
+        view.update_details_calls();
+        assert_eq!(view.get_details_calls().unwrap().total, total + 5);
+    }
+
+    #[test]
+    fn test_skip_single_child_prefix() {
+        let fg =
+            FlameGraph::from_string("python;main;run;a 1\npython;main;run;b 2".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.skip_single_child_prefix();
+        assert_eq!(view.state.level_offset, 3);
+        assert_eq!(get_selected_short_name(&view), "run");
+
+        assert!(!view.toggle_single_child_prefix());
+        assert_eq!(view.state.level_offset, 0);
+        assert!(view.toggle_single_child_prefix());
+        assert_eq!(view.state.level_offset, 3);
+
+        // Nothing to skip when the root branches
+        let fg = FlameGraph::from_string("a 1\nb 2".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        assert!(!view.toggle_single_child_prefix());
+        assert_eq!(view.state.selected, ROOT_ID);
+    }
+
+    #[test]
+    fn test_replace_flamegraph_keeps_zoom() {
+        let fg = FlameGraph::from_string("a;b;c 1\na;b;d 1\ne 1".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.set_zoom_for_id(get_id(&view, "a"));
+        view.set_zoom_for_id(get_id(&view, "a;b;c"));
+
+        view.replace_flamegraph(FlameGraph::from_string(
+            "e 3\na;b;c 2\na;b;d 1".to_string(),
+            true,
+        ));
+        let zoomed = view.state.get_zoom().unwrap().stack_id;
+        assert_eq!(zoomed, get_id(&view, "a;b;c"));
+        assert_eq!(view.state.zoom_stack.len(), 2);
+        assert!(view
+            .state
+            .get_zoom()
+            .unwrap()
+            .ancestors
+            .contains(&get_id(&view, "a")));
+
+        // Zooms on frames that are gone are dropped
+        view.replace_flamegraph(FlameGraph::from_string("a;x 1".to_string(), true));
+        assert_eq!(view.state.zoom_stack.len(), 1);
+        assert_eq!(view.state.get_zoom().unwrap().stack_id, get_id(&view, "a"));
+    }
+
+    #[test]
+    fn test_zoom_history() {
+        let fg = FlameGraph::from_string("a;b;c 1\na;b;d 1\ne 1".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        let zoomed = |view: &FlameGraphView| view.state.get_zoom().map(|z| z.stack_id);
+        let (a, c, e) = (
+            get_id(&view, "a"),
+            get_id(&view, "a;b;c"),
+            get_id(&view, "e"),
+        );
+
+        view.select_id(&a);
+        view.set_zoom();
+        view.select_id(&c);
+        view.set_zoom();
+        view.unset_zoom();
+        view.select_id(&e);
+        view.set_zoom();
+        assert_eq!(view.state.zoom_history, vec![ROOT_ID, a, c, ROOT_ID, e]);
+
+        view.step_zoom_history(false);
+        assert_eq!(zoomed(&view), None);
+        assert_eq!(get_selected_short_name(&view), "e");
+        view.step_zoom_history(false);
+        assert_eq!(zoomed(&view), Some(c));
+        view.step_zoom_history(false);
+        assert_eq!(zoomed(&view), Some(a));
+        assert_eq!(get_selected_short_name(&view), "c");
+        view.step_zoom_history(true);
+        assert_eq!(zoomed(&view), Some(c));
+
+        // Zooming after going back drops the forward history
+        view.zoom_to_parent();
+        assert_eq!(zoomed(&view), Some(get_id(&view, "a;b")));
+        assert_eq!(view.state.zoom_history.len(), 4);
+        view.step_zoom_history(true);
+        assert_eq!(zoomed(&view), Some(get_id(&view, "a;b")));
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        let fg = FlameGraph::from_string("a;f;g 1\nb;c;f 1\nb;f 1\ng 1".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        let (a_f, b_c_f, b_f) = (
+            get_id(&view, "a;f"),
+            get_id(&view, "b;c;f"),
+            get_id(&view, "b;f"),
+        );
+
+        // Frames of the widest parent come first, then the deeper ones
+        view.select_id(&b_f);
+        assert_eq!(view.to_next_occurrence(true), Some((1, 3)));
+        assert_eq!(view.state.selected, a_f);
+        assert_eq!(view.to_next_occurrence(true), Some((2, 3)));
+        assert_eq!(view.state.selected, b_c_f);
+        assert_eq!(view.to_next_occurrence(true), Some((0, 3)));
+        assert_eq!(view.state.selected, b_f);
+        assert_eq!(view.to_next_occurrence(false), Some((2, 3)));
+        assert_eq!(view.state.selected, b_c_f);
+
+        // Zoom out to reach a frame outside of the zoom
+        view.select_id(&get_id(&view, "b"));
+        view.set_zoom();
+        view.select_id(&b_c_f);
+        view.to_next_occurrence(true);
+        assert_eq!(view.state.selected, b_f);
+        assert!(view.state.get_zoom().is_some());
+        view.to_next_occurrence(true);
+        assert_eq!(view.state.selected, a_f);
+        assert!(view.state.get_zoom().is_none());
+    }
+
+    #[test]
+    fn test_focus() {
+        let fg = FlameGraph::from_string("a;log 2\nb;c;log 3\nb;d 4".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.select_id(&get_id(&view, "b;c;log"));
+        view.set_focus("log");
+        assert_eq!(view.flamegraph.total_count(), 5);
+        assert_eq!(get_selected_short_name(&view), "log");
+        assert!(view.flamegraph.get_stack_by_full_name("b;d").is_none());
+
+        // New flamegraphs are filtered too
+        view.replace_flamegraph(FlameGraph::from_string(
+            "a;log 2\nb;c;log 3\nb;d 4\ne;log 1".to_string(),
+            true,
+        ));
+        assert_eq!(view.flamegraph.total_count(), 6);
+        assert_eq!(view.unfiltered_flamegraph().total_count(), 10);
+
+        view.unset_focus();
+        assert!(view.filters.is_empty());
+        assert_eq!(view.flamegraph.total_count(), 10);
+        assert_eq!(get_selected_short_name(&view), "log");
+    }
+
+    #[test]
+    fn test_exclude() {
+        let fg = FlameGraph::from_string("a;gc 2\nb;c;gc_mark 3\nb;d 4".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.select_id(&get_id(&view, "b;d"));
+        assert!(view.set_excluded(Some("(".to_string())).is_err());
+        view.set_excluded(Some("^gc".to_string())).unwrap();
+        assert_eq!(view.flamegraph.total_count(), 4);
+        assert!(view.flamegraph.get_stack_by_full_name("a").is_none());
+        assert_eq!(get_selected_short_name(&view), "d");
+
+        view.set_focus("b");
+        view.replace_flamegraph(FlameGraph::from_string(
+            "a;gc 2\nb;c;gc_mark 3\nb;d 4\nb;gc 1".to_string(),
+            true,
+        ));
+        assert_eq!(view.flamegraph.total_count(), 4);
+        view.set_excluded(None).unwrap();
+        assert_eq!(view.flamegraph.total_count(), 8);
+        view.unset_focus();
+        assert!(view.filters.is_empty());
+    }
+
+    #[test]
+    fn test_hide_threads() {
+        let content =
+            "proc;thread (0x1): main;a 2\nthread (0x2): worker;b 3\nthread (0x2): worker;c 1\nd 1";
+        let mut view = FlameGraphView::new(FlameGraph::from_string(content.to_string(), true));
+        assert_eq!(
+            view.get_threads(),
+            vec![
+                ("thread (0x2): worker".to_string(), 4),
+                ("thread (0x1): main".to_string(), 2),
+            ]
+        );
+        view.toggle_thread("thread (0x2): worker");
+        assert_eq!(view.flamegraph.total_count(), 3);
+        // Hidden threads are still listed with their samples
+        assert_eq!(view.get_threads().len(), 2);
+
+        view.replace_flamegraph(FlameGraph::from_string(
+            format!("{}\nthread (0x2): worker;b 5", content),
+            true,
+        ));
+        assert_eq!(view.flamegraph.total_count(), 3);
+        view.toggle_thread("thread (0x2): worker");
+        assert_eq!(view.flamegraph.total_count(), 12);
+        assert!(view.filters.is_empty());
+    }
+
+    #[test]
+    fn test_processes() {
+        let content = r#"process 1:"python main.py";main 2
+process 1:"python main.py";process 3:"python job.py";job 1
+process 1:"python main.py";process 2:"python worker.py";work 4
+process 1:"python main.py";process 2:"python worker.py";process 4:"sh -c ls";ls 1
+other 1"#;
+        let mut view = FlameGraphView::new(FlameGraph::from_string(content.to_string(), true));
+        let processes = view.get_processes();
+        let pids = processes
+            .iter()
+            .map(|process| (process.pid.as_str(), process.depth))
+            .collect::<Vec<_>>();
+        assert_eq!(pids, vec![("1", 0), ("2", 1), ("4", 2), ("3", 1)]);
+        assert_eq!(processes[1].cmdline, "python worker.py");
+        assert_eq!(processes[1].total_count, 5);
+
+        // Hiding a process hides its child processes as well
+        view.toggle_process(&processes[1].name);
+        assert_eq!(view.flamegraph.total_count(), 4);
+        assert_eq!(view.get_processes().len(), 4);
+        assert!(!view.zoom_to_process(&processes[2]));
+        view.toggle_process(&processes[1].name);
+
+        assert!(view.zoom_to_process(&processes[1]));
+        assert_eq!(
+            view.state.get_zoom().map(|zoom| zoom.stack_id),
+            view.flamegraph
+                .get_stack_id_by_full_name(&processes[1].full_name)
+        );
+        assert_eq!(parse_process_frame("process x:\"a\""), None);
+    }
+
+    #[test]
+    fn test_prune() {
+        let fg = FlameGraph::from_string("a;wrap;b 2\na;wrap;c;wrap;d 3\ne 1".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.select_id(&get_id(&view, "a;wrap;c;wrap"));
+        view.prune("wrap");
+        assert_eq!(view.flamegraph.total_count(), 6);
+        assert!(view.flamegraph.get_stack_by_full_name("a;c;d").is_some());
+        // The selection moves up to the closest remaining ancestor
+        assert_eq!(get_selected_short_name(&view), "c");
+        assert_eq!(view.undo_prune(), Some("wrap".to_string()));
+        assert_eq!(view.state.selected, get_id(&view, "a;wrap;c"));
+        view.prune("wrap");
+
+        view.set_focus("b");
+        view.prune("e");
+        assert_eq!(view.flamegraph.total_count(), 2);
+        assert_eq!(view.undo_prune(), Some("e".to_string()));
+        assert_eq!(view.undo_prune(), Some("wrap".to_string()));
+        assert_eq!(view.undo_prune(), None);
+        assert!(view.flamegraph.get_stack_by_full_name("a;wrap;b").is_some());
+        view.unset_focus();
+        assert_eq!(view.flamegraph.total_count(), 6);
+    }
+
+    #[test]
+    fn test_pin_comparison() {
+        let fg = FlameGraph::from_string("a;b;c 1\na;b;d 3\ne 2".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        let (b, c, d) = (
+            get_id(&view, "a;b"),
+            get_id(&view, "a;b;c"),
+            get_id(&view, "a;b;d"),
+        );
+
+        view.select_id(&c);
+        assert!(view.toggle_pin());
+        assert_eq!(view.get_pin_comparison(), None);
+        view.select_id(&d);
+        assert_eq!(
+            view.get_pin_comparison(),
+            Some(PinComparison {
+                pinned: c,
+                selected: d,
+                ratio: 3.0,
+                common_ancestor: b,
+                depth_difference: 0,
+            })
+        );
+        view.select_id(&b);
+        let comparison = view.get_pin_comparison().unwrap();
+        assert_eq!(comparison.common_ancestor, b);
+        assert_eq!(comparison.depth_difference, -1);
+
+        // Pinning another frame moves the pin, pinning it again removes it
+        assert!(view.toggle_pin());
+        assert_eq!(view.state.pinned, Some(b));
+        assert!(!view.toggle_pin());
+        assert_eq!(view.get_pin_comparison(), None);
+    }
+
+    #[test]
+    fn test_zoom_breadcrumbs() {
+        let fg = FlameGraph::from_string("a;b;c;d 1\na;b;e 1\nf 1".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        assert!(view.get_zoom_breadcrumbs().is_empty());
+
+        view.set_zoom_for_id(get_id(&view, "a;b"));
+        view.set_zoom_for_id(get_id(&view, "a;b;c;d"));
+        let names = |view: &FlameGraphView| {
+            view.get_zoom_breadcrumbs()
+                .iter()
+                .map(|id| {
+                    view.flamegraph
+                        .get_stack_short_name(id)
+                        .unwrap()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&view), vec!["all", "a", "b", "c", "d"]);
+
+        view.zoom_to_parent();
+        assert_eq!(names(&view), vec!["all", "a", "b", "c"]);
+        assert_eq!(get_selected_short_name(&view), "d");
+
+        view.zoom_out_to(get_id(&view, "a"));
+        assert_eq!(names(&view), vec!["all", "a"]);
+        assert_eq!(get_selected_short_name(&view), "c");
+
+        // Going back out from the earlier zoom on b
+        view.set_zoom_for_id(get_id(&view, "a;b"));
+        view.zoom_out_to(ROOT_ID);
+        assert!(view.get_zoom_breadcrumbs().is_empty());
+        assert_eq!(get_selected_short_name(&view), "b");
+    }
+
+    #[test]
+    fn test_without_samples() {
+        let fg = FlameGraph::from_string("main;a 0\nmain;b 0".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        assert_eq!(view.flamegraph.total_count(), 0);
+        assert!(view
+            .flamegraph
+            .stacks()
+            .iter()
+            .all(|stack| !stack.width_factor.is_nan()));
+        view.select_id(&get_id(&view, "main;a"));
+        view.set_zoom();
+        assert!(view.state.get_zoom().is_none());
+
+        let fg = FlameGraph::from_string("main;a 2".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.set_excluded(Some("^a$".to_string())).unwrap();
+        assert_eq!(view.flamegraph.total_count(), 0);
+        assert_eq!(view.unfiltered_flamegraph().total_count(), 2);
+    }
+
+    #[test]
+    fn test_frame_height_change() {
+        let fg = FlameGraph::from_string("a;b;c;d;e;f 1".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.set_frame_height(10);
+        view.select_id(&get_id(&view, "a;b;c;d;e"));
+        // Fewer levels fit, as when drawing sideways: the selected frame is scrolled into view
+        view.state.frame_height = None;
+        view.set_frame_height(2);
+        assert_eq!(get_selected_short_name(&view), "e");
+        assert_eq!(view.state.level_offset, 5);
+    }
+
+    #[test]
+    fn test_call_tree() {
+        let fg = FlameGraph::from_string("a;b 3\na;c;d 5\ne 1".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.set_frame_height(10);
+        let names = |view: &FlameGraphView| {
+            view.get_call_tree_rows()
+                .iter()
+                .map(|x| view.flamegraph.get_stack_short_name(x).unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&view), vec!["all", "a", "e"]);
+
+        // Unfold the selected frame, then move to its heaviest child
+        view.to_next_call_tree_row(1);
+        view.expand_or_enter_call_tree_row();
+        assert_eq!(names(&view), vec!["all", "a", "c", "b", "e"]);
+        view.expand_or_enter_call_tree_row();
+        assert_eq!(get_selected_short_name(&view), "c");
+
+        // Fold the selected frame, or move to its parent
+        view.toggle_call_tree_row();
+        assert_eq!(names(&view), vec!["all", "a", "c", "d", "b", "e"]);
+        view.collapse_or_leave_call_tree_row();
+        assert_eq!(names(&view), vec!["all", "a", "c", "b", "e"]);
+        view.collapse_or_leave_call_tree_row();
+        assert_eq!(get_selected_short_name(&view), "a");
+
+        // A frame selected elsewhere gets its ancestors unfolded
+        view.select_id(&get_id(&view, "a;c;d"));
+        view.state.call_tree.expanded.clear();
+        view.reveal_selected_in_call_tree();
+        assert_eq!(names(&view), vec!["all", "a", "c", "d", "b", "e"]);
+        view.to_call_tree_bottom();
+        assert_eq!(get_selected_short_name(&view), "e");
+    }
+
+    #[test]
+    fn test_layout_children() {
+        use ChildSpan::*;
+        let x_budgets = [40.0, 0.5, 1.5, 20.0, 0.5, 0.0, 1.0, 0.5];
+        let spans = layout_children(&x_budgets, 2.0, |_| false);
+        assert_eq!(
+            spans,
+            vec![
+                Frame {
+                    index: 0,
+                    x_budget: 40.0
+                },
+                TinyRun {
+                    count: 2,
+                    x_budget: 2.0
+                },
+                Frame {
+                    index: 3,
+                    x_budget: 20.0
+                },
+                TinyRun {
+                    count: 1,
+                    x_budget: 0.5
+                },
+                // Hidden frames, such as the siblings of a zoomed one, end a run
+                Frame {
+                    index: 5,
+                    x_budget: 0.0
+                },
+                TinyRun {
+                    count: 2,
+                    x_budget: 1.5
+                },
+            ]
+        );
+        let total = |spans: &[ChildSpan]| {
+            spans
+                .iter()
+                .map(|span| match span {
+                    Frame { x_budget, .. } | TinyRun { x_budget, .. } => x_budget,
+                })
+                .sum::<f64>()
+        };
+        assert_eq!(total(&spans), x_budgets.iter().sum::<f64>());
+
+        // A single tiny frame is collapsed too, unless it is kept
+        assert_eq!(
+            layout_children(&[10.0, 1.0], 2.0, |_| false),
+            vec![
+                Frame {
+                    index: 0,
+                    x_budget: 10.0
+                },
+                TinyRun {
+                    count: 1,
+                    x_budget: 1.0
+                },
+            ]
+        );
+        assert_eq!(
+            layout_children(&[10.0, 1.0], 2.0, |i| i == 1),
+            vec![
+                Frame {
+                    index: 0,
+                    x_budget: 10.0
+                },
+                Frame {
+                    index: 1,
+                    x_budget: 1.0
+                },
+            ]
+        );
+        // Nothing is collapsed without a minimum width
+        assert_eq!(layout_children(&x_budgets, 0.0, |_| false).len(), 8);
     }
 }
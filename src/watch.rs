@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::flame::{FlameGraph, SearchPattern};
+
+/// Period over which the trend of a watched pattern is computed
+const TREND_PERIOD: Duration = Duration::from_secs(5);
+
+/// Smallest change of the share of all samples that counts as a trend
+const TREND_THRESHOLD: f64 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    Up,
+    Down,
+    Steady,
+}
+
+impl Trend {
+    pub fn arrow(&self) -> &'static str {
+        match self {
+            Trend::Up => "▲",
+            Trend::Down => "▼",
+            Trend::Steady => "=",
+        }
+    }
+}
+
+/// A watched pattern with the samples of the frames matching it.
+#[derive(Debug, Clone)]
+pub struct WatchEntry {
+    pub pattern: SearchPattern,
+    /// Samples in frames matching the pattern, not counting nested matches twice
+    pub count: u64,
+    /// Share of all samples
+    pub share: f64,
+    /// Samples in the frames matching the pattern themselves, rather than in their callees
+    pub own_count: u64,
+    /// Share of all samples of `own_count`
+    pub own_share: f64,
+    /// Shares of previous updates, oldest first, going back at least `TREND_PERIOD` when possible
+    history: VecDeque<(Instant, f64)>,
+}
+
+impl WatchEntry {
+    fn new(pattern: SearchPattern) -> Self {
+        Self {
+            pattern,
+            count: 0,
+            share: 0.0,
+            own_count: 0,
+            own_share: 0.0,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn update(&mut self, flamegraph: &FlameGraph, now: Instant) {
+        self.count = flamegraph.count_coverage(&self.pattern.re);
+        self.own_count = flamegraph.count_own(&self.pattern.re);
+        let total = flamegraph.total_count().max(1) as f64;
+        self.share = self.count as f64 / total;
+        self.own_share = self.own_count as f64 / total;
+        self.history.push_back((now, self.share));
+        while self
+            .history
+            .get(1)
+            .is_some_and(|(time, _)| now.duration_since(*time) >= TREND_PERIOD)
+        {
+            self.history.pop_front();
+        }
+    }
+
+    /// Direction of the share of all samples compared to `TREND_PERIOD` ago
+    pub fn trend(&self) -> Trend {
+        let Some((_, previous)) = self.history.front() else {
+            return Trend::Steady;
+        };
+        let change = self.share - previous;
+        if change > TREND_THRESHOLD {
+            Trend::Up
+        } else if change < -TREND_THRESHOLD {
+            Trend::Down
+        } else {
+            Trend::Steady
+        }
+    }
+}
+
+/// Function patterns whose counts are always shown, regardless of the selection.
+#[derive(Debug, Clone, Default)]
+pub struct WatchList {
+    entries: Vec<WatchEntry>,
+}
+
+impl WatchList {
+    pub fn new(patterns: &[String]) -> Result<Self, regex::Error> {
+        let entries = patterns
+            .iter()
+            .map(|p| SearchPattern::new(p, true, false).map(WatchEntry::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[WatchEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Watch a pattern, or stop watching it if it is already watched. Returns whether it is
+    /// watched now.
+    pub fn toggle(&mut self, pattern: SearchPattern) -> bool {
+        let idx = self
+            .entries
+            .iter()
+            .position(|e| e.pattern.re.as_str() == pattern.re.as_str());
+        match idx {
+            Some(idx) => {
+                self.entries.remove(idx);
+                false
+            }
+            None => {
+                self.entries.push(WatchEntry::new(pattern));
+                true
+            }
+        }
+    }
+
+    pub fn update(&mut self, flamegraph: &FlameGraph, now: Instant) {
+        for entry in self.entries.iter_mut() {
+            entry.update(flamegraph, now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_list() {
+        let mut watch_list = WatchList::new(&["^a$".to_string(), "x".to_string()]).unwrap();
+        let fg = FlameGraph::from_string("main;a;b 2\nmain;b;a 2\nmain;c 4".to_string(), true);
+        let now = Instant::now();
+        watch_list.update(&fg, now);
+        let counts = |w: &WatchList| {
+            w.entries()
+                .iter()
+                .map(|e| (e.count, e.trend()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            counts(&watch_list),
+            vec![(4, Trend::Steady), (0, Trend::Steady)]
+        );
+        // a is a leaf once and calls b once
+        assert_eq!(watch_list.entries()[0].own_count, 2);
+        assert_eq!(watch_list.entries()[0].own_share, 0.25);
+
+        // a takes a larger share, compared to the first update until it is older than the period
+        let fg = FlameGraph::from_string("main;a 8\nmain;c 2".to_string(), true);
+        watch_list.update(&fg, now + Duration::from_secs(1));
+        assert_eq!(
+            counts(&watch_list),
+            vec![(8, Trend::Up), (0, Trend::Steady)]
+        );
+        watch_list.update(&fg, now + Duration::from_secs(4));
+        assert_eq!(
+            counts(&watch_list),
+            vec![(8, Trend::Up), (0, Trend::Steady)]
+        );
+        watch_list.update(&fg, now + Duration::from_secs(7));
+        assert_eq!(
+            counts(&watch_list),
+            vec![(8, Trend::Steady), (0, Trend::Steady)]
+        );
+
+        let pattern = SearchPattern::new("c", false, true).unwrap();
+        assert!(watch_list.toggle(pattern.clone()));
+        assert_eq!(watch_list.entries().len(), 3);
+        assert!(!watch_list.toggle(pattern));
+        assert_eq!(watch_list.entries().len(), 2);
+    }
+}